@@ -1,11 +1,13 @@
 use std::{env, fs, io, panic, path::Path, time::Duration};
 
 use crate::{
-    client::ClientManager,
+    client::{ClientHandle, ClientManager},
     command::CommandManager,
     editor::{Editor, EditorContext, EditorFlow},
     editor_utils::{LogKind, REGISTER_READLINE_INPUT},
     events::{ClientEvent, ClientEventReceiver, ServerEvent, TargetClient},
+    format,
+    mode::insert,
     platform::{Key, Platform, PlatformEvent, PlatformRequest, ProcessTag},
     plugin::{PluginCollection, PluginDefinition},
     serialization::{DeserializeError, Serialize},
@@ -93,6 +95,53 @@ impl ServerApplication {
         })
     }
 
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        let pending_timeout = self.pending_timeout();
+        if self.ctx.editor.needs_idle_polling() {
+            Some(pending_timeout.map_or(SERVER_IDLE_DURATION, |t| t.min(SERVER_IDLE_DURATION)))
+        } else {
+            pending_timeout
+        }
+    }
+
+    // how long to wait for the first idle event after some activity
+    pub fn activity_idle_timeout(&self) -> Duration {
+        match self.pending_timeout() {
+            Some(timeout) => timeout.min(SERVER_IDLE_DURATION),
+            None => SERVER_IDLE_DURATION,
+        }
+    }
+
+    // time left until either pending keys are executed or pending completions are updated
+    fn pending_timeout(&self) -> Option<Duration> {
+        let timeout = match (
+            self.ctx.editor.pending_keys_timeout(),
+            insert::pending_completion_timeout(&self.ctx.editor),
+        ) {
+            (Some(a), Some(b)) => a.min(b),
+            (a, b) => a.or(b)?,
+        };
+        Some(timeout.max(Duration::from_millis(1)))
+    }
+
+    fn on_editor_flow(ctx: &mut EditorContext, handle: ClientHandle, flow: EditorFlow) {
+        match flow {
+            EditorFlow::Continue => (),
+            EditorFlow::Suspend => {
+                let mut buf = ctx.platform.buf_pool.acquire();
+                ServerEvent::Suspend.serialize(buf.write());
+                ctx.platform
+                    .requests
+                    .enqueue(PlatformRequest::WriteToClient { handle, buf });
+            }
+            EditorFlow::Quit => ctx
+                .platform
+                .requests
+                .enqueue(PlatformRequest::CloseClient { handle }),
+            EditorFlow::QuitAll => ctx.platform.requests.enqueue(PlatformRequest::Quit),
+        }
+    }
+
     pub fn update<I>(&mut self, events: I)
     where
         I: Iterator<Item = PlatformEvent>,
@@ -102,11 +151,17 @@ impl ServerApplication {
                 PlatformEvent::Idle => {
                     self.ctx.editor.on_idle();
                     self.ctx.trigger_event_handlers();
+                    if let Some((handle, flow)) = Editor::on_pending_keys_timeout(&mut self.ctx) {
+                        Self::on_editor_flow(&mut self.ctx, handle, flow);
+                    }
+                    insert::on_pending_completion_timeout(&mut self.ctx);
+                    self.ctx.trigger_event_handlers();
                 }
                 PlatformEvent::ConnectionOpen { handle } => {
                     self.ctx.clients.on_client_joined(handle)
                 }
                 PlatformEvent::ConnectionClose { handle } => {
+                    self.ctx.editor.on_client_left(handle);
                     self.ctx
                         .editor
                         .buffer_views
@@ -123,25 +178,8 @@ impl ServerApplication {
                     self.ctx.platform.buf_pool.release(buf);
 
                     while let Some(event) = events.next(&self.client_event_receiver) {
-                        match Editor::on_client_event(&mut self.ctx, handle, event) {
-                            EditorFlow::Continue => (),
-                            EditorFlow::Suspend => {
-                                let mut buf = self.ctx.platform.buf_pool.acquire();
-                                ServerEvent::Suspend.serialize(buf.write());
-                                self.ctx
-                                    .platform
-                                    .requests
-                                    .enqueue(PlatformRequest::WriteToClient { handle, buf });
-                            }
-                            EditorFlow::Quit => self
-                                .ctx
-                                .platform
-                                .requests
-                                .enqueue(PlatformRequest::CloseClient { handle }),
-                            EditorFlow::QuitAll => {
-                                self.ctx.platform.requests.enqueue(PlatformRequest::Quit)
-                            }
-                        }
+                        let flow = Editor::on_client_event(&mut self.ctx, handle, event);
+                        Self::on_editor_flow(&mut self.ctx, handle, flow);
                     }
                     events.finish(&mut self.client_event_receiver);
                 }
@@ -158,6 +196,16 @@ impl ServerApplication {
                             .editor
                             .picker_entries_process_buf
                             .on_process_spawned(),
+                        ProcessTag::GitBlame(_)
+                        | ProcessTag::GitDiff(_)
+                        | ProcessTag::GitIndex(..) => (),
+                        ProcessTag::Formatter(buffer_handle) => self
+                            .ctx
+                            .editor
+                            .buffers
+                            .get_mut(buffer_handle)
+                            .formatter
+                            .on_process_spawned(&mut self.ctx.platform, handle),
                         ProcessTag::Plugin { plugin_handle, id } => {
                             PluginCollection::on_process_spawned(
                                 &mut self.ctx,
@@ -188,6 +236,28 @@ impl ServerApplication {
                                 self.ctx.editor.registers.get(REGISTER_READLINE_INPUT),
                                 bytes,
                             ),
+                        ProcessTag::GitBlame(buffer_handle) => self
+                            .ctx
+                            .editor
+                            .buffers
+                            .get_mut(buffer_handle)
+                            .git_blame
+                            .on_process_output(bytes),
+                        ProcessTag::GitDiff(buffer_handle) => self
+                            .ctx
+                            .editor
+                            .buffers
+                            .get_mut(buffer_handle)
+                            .git_diff
+                            .on_process_output(bytes),
+                        ProcessTag::GitIndex(..) => (),
+                        ProcessTag::Formatter(buffer_handle) => self
+                            .ctx
+                            .editor
+                            .buffers
+                            .get_mut(buffer_handle)
+                            .formatter
+                            .on_process_output(bytes),
                         ProcessTag::Plugin { plugin_handle, id } => {
                             PluginCollection::on_process_output(
                                 &mut self.ctx,
@@ -200,7 +270,7 @@ impl ServerApplication {
                     self.ctx.trigger_event_handlers();
                     self.ctx.platform.buf_pool.release(buf);
                 }
-                PlatformEvent::ProcessExit { tag } => {
+                PlatformEvent::ProcessExit { tag, success } => {
                     match tag {
                         ProcessTag::Ignored => (),
                         ProcessTag::Buffer(index) => self.ctx.editor.buffers.on_process_exit(
@@ -214,6 +284,25 @@ impl ServerApplication {
                                 self.ctx.editor.registers.get(REGISTER_READLINE_INPUT),
                             )
                         }
+                        ProcessTag::GitBlame(buffer_handle) => {
+                            let buffer = self.ctx.editor.buffers.get_mut(buffer_handle);
+                            buffer.git_blame.on_process_exit(
+                                success,
+                                &buffer.path,
+                                &mut self.ctx.editor.logger,
+                            );
+                        }
+                        ProcessTag::GitDiff(buffer_handle) => {
+                            let buffer = self.ctx.editor.buffers.get_mut(buffer_handle);
+                            buffer.git_diff.on_process_exit();
+                            buffer.update_git_diff();
+                        }
+                        ProcessTag::GitIndex(buffer_handle, operation) => {
+                            operation.on_process_exit(&mut self.ctx, buffer_handle, success)
+                        }
+                        ProcessTag::Formatter(buffer_handle) => {
+                            format::on_process_exit(&mut self.ctx.editor, buffer_handle, success)
+                        }
                         ProcessTag::Plugin { plugin_handle, id } => {
                             PluginCollection::on_process_exit(&mut self.ctx, plugin_handle, id)
                         }
@@ -328,6 +417,7 @@ where
         if let Some(output) = &mut self.output {
             let _ = output.write_all(ui::EXIT_ALTERNATE_BUFFER_CODE);
             let _ = output.write_all(ui::SHOW_CURSOR_CODE);
+            let _ = output.write_all(ui::RESET_CURSOR_SHAPE_CODE);
             let _ = output.write_all(ui::RESET_STYLE_CODE);
             let _ = output.flush();
         }
@@ -1,26 +1,33 @@
 use std::{
     fmt,
-    fs::File,
-    io,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
     ops::{Add, Range, RangeBounds, Sub},
     path::{Component, Path, PathBuf},
     process::{Command, Stdio},
-    str::CharIndices,
+    str::{CharIndices, FromStr},
 };
 
 use crate::{
     buffer_history::{BufferHistory, Edit, EditKind},
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    config::Config,
     cursor::Cursor,
-    editor_utils::{find_delimiter_pair_at, ResidualStrBytes},
+    editor_utils::{
+        find_delimiter_pair_at, hash_bytes, hash_more_bytes, open_file_for_writing,
+        ResidualStrBytes,
+    },
     events::{
         BufferEditMutGuard, BufferRangeDeletesMutGuard, BufferTextInsertsMutGuard, EditorEvent,
         EditorEventTextInsert, EditorEventWriter,
     },
+    format::FormatterProcess,
+    git::{GitBlame, GitDiff},
     help,
     pattern::Pattern,
     platform::{Platform, PlatformProcessHandle, PlatformRequest, PooledBuf, ProcessTag},
     plugin::PluginHandle,
+    spell::{BufferMisspellings, SpellChecker},
     syntax::{HighlightResult, HighlightedBuffer, SyntaxCollection, SyntaxHandle},
     word_database::{WordDatabase, WordIter, WordKind},
 };
@@ -79,6 +86,93 @@ impl Sub for DisplayLen {
     }
 }
 
+static NO_TAB_STOPS: TabStops = TabStops(Vec::new());
+
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct TabStops(Vec<u32>);
+impl TabStops {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn tab_len(&self, tab_index: usize, column: usize, tab_size: u8) -> usize {
+        match self.0.get(tab_index) {
+            Some(&stop) => (stop as usize).saturating_sub(column).max(1),
+            None => tab_size as _,
+        }
+    }
+
+    pub fn line_len(&self, line: &str, display_len: DisplayLen, tab_size: u8) -> usize {
+        if self.is_empty() {
+            return display_len.total_len(tab_size);
+        }
+        let mut len = 0;
+        for d in CharDisplayDistances::new(line, tab_size).with_tab_stops(self) {
+            len = d.distance as _;
+        }
+        len
+    }
+}
+impl FromStr for TabStops {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut stops = Vec::new();
+        for stop in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            let stop: u32 = stop.parse().map_err(|_| ())?;
+            match stops.last() {
+                Some(&last) if stop <= last => return Err(()),
+                _ if stop == 0 => return Err(()),
+                _ => stops.push(stop),
+            }
+        }
+        Ok(Self(stops))
+    }
+}
+impl fmt::Display for TabStops {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, stop) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(",")?;
+            }
+            write!(f, "{}", stop)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        }
+    }
+}
+impl FromStr for LineEnding {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lf" => Ok(Self::Lf),
+            "crlf" => Ok(Self::Crlf),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Lf => f.write_str("lf"),
+            Self::Crlf => f.write_str("crlf"),
+        }
+    }
+}
+
 pub struct CharDisplayDistance {
     pub distance: u32,
     pub char: char,
@@ -88,6 +182,8 @@ pub struct CharDisplayDistances<'a> {
     char_indices: CharIndices<'a>,
     len: u32,
     tab_size: u8,
+    tab_stops: &'a TabStops,
+    tab_count: usize,
 }
 impl<'a> CharDisplayDistances<'a> {
     pub fn new(text: &'a str, tab_size: u8) -> Self {
@@ -95,13 +191,27 @@ impl<'a> CharDisplayDistances<'a> {
             char_indices: text.char_indices(),
             len: 0,
             tab_size,
+            tab_stops: &NO_TAB_STOPS,
+            tab_count: 0,
         }
     }
+
+    // tab stops are only honored when iterating forward
+    pub fn with_tab_stops(mut self, tab_stops: &'a TabStops) -> Self {
+        self.tab_stops = tab_stops;
+        self
+    }
 }
 impl<'a> CharDisplayDistances<'a> {
     fn calc_next(&mut self, char_index: usize, c: char) -> CharDisplayDistance {
         self.len += match c {
-            '\t' => self.tab_size as u32,
+            '\t' => {
+                let len = self
+                    .tab_stops
+                    .tab_len(self.tab_count, self.len as _, self.tab_size);
+                self.tab_count += 1;
+                len as u32
+            }
             _ => char_display_len(c) as u32,
         };
         CharDisplayDistance {
@@ -121,6 +231,7 @@ impl<'a> Iterator for CharDisplayDistances<'a> {
 impl<'a> DoubleEndedIterator for CharDisplayDistances<'a> {
     fn next_back(&mut self) -> Option<Self::Item> {
         let (i, c) = self.char_indices.next_back()?;
+        self.tab_stops = &NO_TAB_STOPS;
         Some(self.calc_next(i, c))
     }
 }
@@ -465,16 +576,28 @@ impl<'a> Drop for BufferBreakpointMutCollection<'a> {
     }
 }
 
+// lines always kept in a pool no matter how few were acquired recently
+const LINE_POOL_MIN_LEN: usize = 64;
+// a pool is trimmed once it holds this many times more lines than were acquired since the last trim
+const LINE_POOL_TRIM_FACTOR: usize = 4;
+
+pub const BYTE_ORDER_MARK: &[u8] = b"\xef\xbb\xbf";
+
 struct BufferLinePool {
     pool: Vec<BufferLine>,
+    acquired_count: usize,
 }
 
 impl BufferLinePool {
     pub const fn new() -> Self {
-        Self { pool: Vec::new() }
+        Self {
+            pool: Vec::new(),
+            acquired_count: 0,
+        }
     }
 
     pub fn acquire(&mut self) -> BufferLine {
+        self.acquired_count += 1;
         match self.pool.pop() {
             Some(mut line) => {
                 line.0.clear();
@@ -487,6 +610,15 @@ impl BufferLinePool {
     pub fn release(&mut self, line: BufferLine) {
         self.pool.push(line);
     }
+
+    pub fn trim(&mut self) {
+        let keep_len = self.acquired_count.max(LINE_POOL_MIN_LEN);
+        if self.pool.len() > keep_len * LINE_POOL_TRIM_FACTOR {
+            self.pool.truncate(keep_len);
+            self.pool.shrink_to_fit();
+        }
+        self.acquired_count = 0;
+    }
 }
 
 pub struct BufferLine(String);
@@ -628,6 +760,32 @@ impl BufferLine {
     }
 }
 
+pub struct LinesInRangeIter<'a> {
+    lines: &'a [BufferLine],
+    from: BufferPosition,
+    to: BufferPosition,
+}
+impl<'a> Iterator for LinesInRangeIter<'a> {
+    type Item = (&'a BufferLine, Range<usize>);
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.from.line_index > self.to.line_index {
+            return None;
+        }
+
+        let line = &self.lines[self.from.line_index as usize];
+        let start = self.from.column_byte_index as usize;
+        let end = if self.from.line_index == self.to.line_index {
+            self.to.column_byte_index as usize
+        } else {
+            line.as_str().len()
+        };
+
+        self.from.line_index += 1;
+        self.from.column_byte_index = 0;
+        Some((line, start..end))
+    }
+}
+
 pub struct TextRangeIter<'a> {
     content: &'a BufferContent,
     from: BufferPosition,
@@ -663,6 +821,8 @@ pub struct BufferContent {
     lines: Vec<BufferLine>,
     line_display_lens: Vec<DisplayLen>,
     line_pool: BufferLinePool,
+    line_ending: LineEnding,
+    byte_order_mark: bool,
 }
 
 impl BufferContent {
@@ -671,6 +831,8 @@ impl BufferContent {
             lines: vec![BufferLine::new()],
             line_display_lens: vec![DisplayLen::zero()],
             line_pool: BufferLinePool::new(),
+            line_ending: LineEnding::Lf,
+            byte_order_mark: false,
         }
     }
 
@@ -690,11 +852,26 @@ impl BufferContent {
         )
     }
 
+    // line ending that 'write' outputs. lines themselves never contain line breaks
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    // whether 'write' starts with an utf-8 byte order mark. it is never part of the first line
+    pub fn has_byte_order_mark(&self) -> bool {
+        self.byte_order_mark
+    }
+
+    // lines are read one at a time into pooled lines so the text is never held as a whole.
+    // this is also what 'Buffer::read_from_file' uses to load files.
+    // the line ending is taken from the first line break found
     pub fn read(&mut self, read: &mut dyn io::BufRead) -> io::Result<()> {
         for line in self.lines.drain(..) {
             self.line_pool.release(line);
         }
         self.line_display_lens.clear();
+        self.line_ending = LineEnding::Lf;
+        self.byte_order_mark = false;
 
         loop {
             let mut line = self.line_pool.acquire();
@@ -706,6 +883,9 @@ impl BufferContent {
                 Ok(_) => {
                     if line.0.ends_with('\n') {
                         line.0.pop();
+                        if self.lines.is_empty() && line.0.ends_with('\r') {
+                            self.line_ending = LineEnding::Crlf;
+                        }
                     }
                     if line.0.ends_with('\r') {
                         line.0.pop();
@@ -732,25 +912,69 @@ impl BufferContent {
             self.line_display_lens.push(DisplayLen::zero());
         }
 
-        let byte_order_mark = b"\xef\xbb\xbf";
         if self.lines[0]
             .as_str()
             .as_bytes()
-            .starts_with(byte_order_mark)
+            .starts_with(BYTE_ORDER_MARK)
         {
-            self.lines[0].delete_range(&mut self.line_display_lens[0], ..byte_order_mark.len());
+            self.lines[0].delete_range(&mut self.line_display_lens[0], ..BYTE_ORDER_MARK.len());
+            self.byte_order_mark = true;
         }
 
         Ok(())
     }
 
     pub fn write(&self, write: &mut dyn io::Write) -> io::Result<()> {
+        if self.byte_order_mark {
+            write.write_all(BYTE_ORDER_MARK)?;
+        }
+        let line_ending = self.line_ending.as_str();
         for line in &self.lines {
-            write!(write, "{}\n", line.as_str())?;
+            write!(write, "{}{}", line.as_str(), line_ending)?;
         }
         Ok(())
     }
 
+    // hash of the bytes that 'write' would output
+    pub fn hash(&self) -> u64 {
+        let line_ending = self.line_ending.as_str().as_bytes();
+        let mut hash = hash_bytes(if self.byte_order_mark {
+            BYTE_ORDER_MARK
+        } else {
+            &[]
+        });
+        for line in &self.lines {
+            hash = hash_more_bytes(hash, line.as_str().as_bytes());
+            hash = hash_more_bytes(hash, line_ending);
+        }
+        hash
+    }
+
+    // position of a byte offset into the text that 'write' outputs (with the buffer line ending
+    // and byte order mark). offsets past the end are clamped to it and those inside a char go
+    // back to its start
+    pub fn position_from_byte_offset(&self, offset: usize) -> BufferPosition {
+        let offset = if self.byte_order_mark {
+            offset.saturating_sub(BYTE_ORDER_MARK.len())
+        } else {
+            offset
+        };
+        let line_ending_len = self.line_ending.as_str().len();
+        let mut line_start = 0;
+        for (line_index, line) in self.lines.iter().enumerate() {
+            let line = line.as_str();
+            if offset < line_start + line.len() + line_ending_len {
+                let mut column_byte_index = (offset - line_start).min(line.len());
+                while !line.is_char_boundary(column_byte_index) {
+                    column_byte_index -= 1;
+                }
+                return BufferPosition::line_col(line_index as _, column_byte_index as _);
+            }
+            line_start += line.len() + line_ending_len;
+        }
+        self.end()
+    }
+
     pub fn saturate_position(&self, mut position: BufferPosition) -> BufferPosition {
         position.line_index = position.line_index.min((self.lines.len() - 1) as _);
         let line = self.lines[position.line_index as usize].as_str();
@@ -768,6 +992,16 @@ impl BufferContent {
         }
     }
 
+    pub fn lines_in_range(&self, range: BufferRange) -> LinesInRangeIter<'_> {
+        let from = self.saturate_position(range.from);
+        let to = self.saturate_position(range.to);
+        LinesInRangeIter {
+            lines: &self.lines,
+            from,
+            to,
+        }
+    }
+
     pub fn find_search_ranges(&self, pattern: &Pattern, ranges: &mut Vec<BufferRange>) {
         if pattern.is_empty() {
             return;
@@ -901,6 +1135,17 @@ impl BufferContent {
         self.lines.push(self.line_pool.acquire());
         self.line_display_lens.clear();
         self.line_display_lens.push(DisplayLen::zero());
+        self.line_ending = LineEnding::Lf;
+        self.byte_order_mark = false;
+    }
+
+    // frees pooled lines (and line storage) left over from edits much bigger than recent ones
+    pub(crate) fn trim_line_pool(&mut self) {
+        self.line_pool.trim();
+        if self.lines.capacity() > self.lines.len() * LINE_POOL_TRIM_FACTOR {
+            self.lines.shrink_to_fit();
+            self.line_display_lens.shrink_to_fit();
+        }
     }
 
     pub fn words_from(
@@ -925,6 +1170,20 @@ impl BufferContent {
         )
     }
 
+    // the end of every line but the last is its line break
+    pub fn char_at(&self, position: BufferPosition) -> Option<char> {
+        let line = self.lines.get(position.line_index as usize)?.as_str();
+        match line
+            .get(position.column_byte_index as usize..)?
+            .chars()
+            .next()
+        {
+            Some(c) => Some(c),
+            None if (position.line_index as usize) + 1 < self.lines.len() => Some('\n'),
+            None => None,
+        }
+    }
+
     pub fn word_at(&self, position: BufferPosition) -> WordRefWithPosition {
         let position = self.saturate_position(position);
         self.lines[position.line_index as usize]
@@ -1056,6 +1315,80 @@ impl BufferContent {
 
         Some(BufferRange::between(left_position, right_position))
     }
+
+    pub fn find_matching_bracket(&self, position: BufferPosition) -> Option<MatchingBracket> {
+        let position = self.saturate_position(position);
+        let line = self.lines[position.line_index as usize].as_str();
+        let c = line[position.column_byte_index as usize..].chars().next()?;
+
+        for &(left, right) in BRACKET_PAIRS {
+            if c == left {
+                return match self.find_balanced_chars_at(position, left, right) {
+                    Some(range) => Some(MatchingBracket::Matched(range.to)),
+                    None => Some(MatchingBracket::Unmatched),
+                };
+            } else if c == right {
+                return match self.find_balanced_chars_at(position, left, right) {
+                    Some(range) => Some(MatchingBracket::Matched(BufferPosition::line_col(
+                        range.from.line_index,
+                        range.from.column_byte_index - left.len_utf8() as BufferPositionIndex,
+                    ))),
+                    None => Some(MatchingBracket::Unmatched),
+                };
+            }
+        }
+
+        None
+    }
+
+    pub fn detect_indentation(&self) -> Option<DetectedIndentation> {
+        let mut tab_line_count = 0;
+        let mut space_line_count = 0;
+        let mut space_deltas = [0; 9];
+        let mut previous_space_count = 0;
+
+        for line in &self.lines {
+            let line = line.as_str();
+            let trimmed_line = line.trim_start();
+            if trimmed_line.is_empty() {
+                continue;
+            }
+
+            let space_count = line.len() - line.trim_start_matches(' ').len();
+            if line.starts_with('\t') {
+                tab_line_count += 1;
+                previous_space_count = 0;
+                continue;
+            }
+            if line[space_count..].starts_with('\t') {
+                continue;
+            }
+
+            if space_count > 0 {
+                space_line_count += 1;
+            }
+            let delta = space_count.abs_diff(previous_space_count);
+            if (2..space_deltas.len()).contains(&delta) {
+                space_deltas[delta] += 1;
+            }
+            previous_space_count = space_count;
+        }
+
+        let mut best_delta = 0;
+        for (delta, &count) in space_deltas.iter().enumerate() {
+            if count > space_deltas[best_delta] {
+                best_delta = delta;
+            }
+        }
+
+        if tab_line_count > 0 && tab_line_count >= space_line_count {
+            Some(DetectedIndentation::Tabs)
+        } else if best_delta > 0 {
+            Some(DetectedIndentation::Spaces(best_delta as _))
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Display for BufferContent {
@@ -1117,6 +1450,7 @@ pub struct BufferProperties {
     pub saving_enabled: bool,
     pub file_backed_enabled: bool,
     pub word_database_enabled: bool,
+    pub tail_enabled: bool,
 }
 impl BufferProperties {
     pub fn text() -> Self {
@@ -1125,6 +1459,7 @@ impl BufferProperties {
             saving_enabled: true,
             file_backed_enabled: true,
             word_database_enabled: true,
+            tail_enabled: false,
         }
     }
 
@@ -1134,6 +1469,7 @@ impl BufferProperties {
             saving_enabled: false,
             file_backed_enabled: false,
             word_database_enabled: false,
+            tail_enabled: false,
         }
     }
 
@@ -1143,6 +1479,14 @@ impl BufferProperties {
             saving_enabled: false,
             file_backed_enabled: true,
             word_database_enabled: false,
+            tail_enabled: false,
+        }
+    }
+
+    pub fn tail() -> Self {
+        Self {
+            tail_enabled: true,
+            ..Self::log()
         }
     }
 
@@ -1152,16 +1496,37 @@ impl BufferProperties {
             saving_enabled: false,
             file_backed_enabled: false,
             word_database_enabled: false,
+            tail_enabled: false,
         }
     }
 }
 
+static BRACKET_PAIRS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MatchingBracket {
+    Matched(BufferPosition),
+    Unmatched,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DetectedIndentation {
+    Tabs,
+    Spaces(u8),
+}
+
 #[derive(Clone, Copy)]
 pub struct BufferIndentationConfig {
     pub indent_with_tabs: bool,
     pub tab_size: u8,
 }
 
+#[derive(Default)]
+struct BufferTail {
+    offset: u64,
+    pending_line_break: bool,
+}
+
 pub struct Buffer {
     alive: bool,
     handle: BufferHandle,
@@ -1174,7 +1539,17 @@ pub struct Buffer {
     breakpoints: BufferBreakpointCollection,
     search_ranges: Vec<BufferRange>,
     needs_save: bool,
+    saved_content_hash: Option<u64>,
     pub properties: BufferProperties,
+    pub wrap_lines: Option<bool>,
+    pub spell_check: Option<bool>,
+    pub detected_indentation: Option<DetectedIndentation>,
+    highlighting_enabled: bool,
+    pub git_blame: GitBlame,
+    pub git_diff: GitDiff,
+    pub formatter: FormatterProcess,
+    pub misspellings: BufferMisspellings,
+    tail: BufferTail,
 }
 
 impl Buffer {
@@ -1191,7 +1566,17 @@ impl Buffer {
             breakpoints: BufferBreakpointCollection::default(),
             search_ranges: Vec::new(),
             needs_save: false,
+            saved_content_hash: None,
             properties: BufferProperties::default(),
+            wrap_lines: None,
+            spell_check: None,
+            detected_indentation: None,
+            highlighting_enabled: true,
+            git_blame: GitBlame::default(),
+            git_diff: GitDiff::default(),
+            formatter: FormatterProcess::default(),
+            misspellings: BufferMisspellings::default(),
+            tail: BufferTail::default(),
         }
     }
 
@@ -1207,8 +1592,18 @@ impl Buffer {
         self.lints.clear();
         self.breakpoints.clear();
         self.search_ranges.clear();
+        self.git_blame.clear();
+        self.git_diff.clear();
+        self.formatter.clear();
+        self.misspellings.clear();
         self.needs_save = false;
+        self.saved_content_hash = None;
         self.properties = BufferProperties::default();
+        self.wrap_lines = None;
+        self.spell_check = None;
+        self.detected_indentation = None;
+        self.highlighting_enabled = true;
+        self.tail = BufferTail::default();
     }
 
     fn remove_all_words_from_database(&mut self, word_database: &mut WordDatabase) {
@@ -1235,6 +1630,28 @@ impl Buffer {
         }
     }
 
+    pub fn wraps_lines(&self, default: bool) -> bool {
+        self.wrap_lines.unwrap_or(default)
+    }
+
+    // detected indentation takes precedence over the 'indent_with_tabs' and 'tab_size' configs
+    pub fn indentation_config(&self, config: &Config) -> BufferIndentationConfig {
+        match self.detected_indentation {
+            Some(DetectedIndentation::Tabs) => BufferIndentationConfig {
+                indent_with_tabs: true,
+                tab_size: config.tab_size,
+            },
+            Some(DetectedIndentation::Spaces(tab_size)) => BufferIndentationConfig {
+                indent_with_tabs: false,
+                tab_size,
+            },
+            None => BufferIndentationConfig {
+                indent_with_tabs: config.indent_with_tabs,
+                tab_size: config.tab_size,
+            },
+        }
+    }
+
     pub fn content(&self) -> &BufferContent {
         &self.content
     }
@@ -1243,9 +1660,29 @@ impl Buffer {
         &self.highlighted
     }
 
+    pub fn highlighting_enabled(&self) -> bool {
+        self.highlighting_enabled
+    }
+
+    pub fn set_highlighting_enabled(&mut self, enabled: bool) {
+        if self.highlighting_enabled != enabled {
+            self.highlighting_enabled = enabled;
+            self.highlighted.clear();
+        }
+    }
+
+    pub fn syntax_handle(&self) -> SyntaxHandle {
+        self.syntax_handle
+    }
+
     pub fn update_highlighting(&mut self, syntaxes: &SyntaxCollection) -> HighlightResult {
+        let syntax_handle = if self.highlighting_enabled {
+            self.syntax_handle
+        } else {
+            SyntaxHandle::default()
+        };
         self.highlighted
-            .highlight_dirty_lines(syntaxes.get(self.syntax_handle), &self.content)
+            .highlight_dirty_lines(syntaxes.get(syntax_handle), &self.content)
     }
 
     pub fn refresh_syntax(&mut self, syntaxes: &SyntaxCollection) {
@@ -1262,6 +1699,36 @@ impl Buffer {
         }
     }
 
+    pub fn refresh_git_diff_base(&mut self, platform: &mut Platform, current_directory: &Path) {
+        if !self.properties.file_backed_enabled || self.path.as_os_str().is_empty() {
+            return;
+        }
+        self.git_diff
+            .spawn_process(platform, self.handle, current_directory, &self.path);
+    }
+
+    pub fn update_git_diff(&mut self) {
+        self.git_diff.update(&self.content);
+    }
+
+    pub fn spell_checks(&self, spell_checker: &SpellChecker) -> bool {
+        match self.spell_check {
+            Some(enabled) => enabled,
+            None => match self.path.to_str() {
+                Some(path) => spell_checker.checks_path(path),
+                None => false,
+            },
+        }
+    }
+
+    pub fn update_misspellings(&mut self, spell_checker: &SpellChecker) {
+        if self.spell_checks(spell_checker) {
+            self.misspellings.update(spell_checker, &self.content);
+        } else {
+            self.misspellings.clear();
+        }
+    }
+
     pub fn breakpoints(&self) -> &[BufferBreakpoint] {
         &self.breakpoints.breakpoints
     }
@@ -1279,6 +1746,31 @@ impl Buffer {
         self.properties.saving_enabled && self.needs_save
     }
 
+    // hash of the content as it was last read from or written to its file
+    pub fn saved_content_hash(&self) -> Option<u64> {
+        self.saved_content_hash
+    }
+
+    // only changes how the buffer is written so it is not an undoable edit
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.content.line_ending != line_ending {
+            self.content.line_ending = line_ending;
+            self.needs_save = true;
+        }
+    }
+
+    pub fn spawn_formatter_process(&mut self, platform: &mut Platform, command: Command) {
+        self.formatter
+            .spawn_process(platform, self.handle, command, &self.content);
+    }
+
+    pub fn set_byte_order_mark(&mut self, byte_order_mark: bool) {
+        if self.content.byte_order_mark != byte_order_mark {
+            self.content.byte_order_mark = byte_order_mark;
+            self.needs_save = true;
+        }
+    }
+
     pub fn insert_text(
         &mut self,
         word_database: &mut WordDatabase,
@@ -1287,6 +1779,8 @@ impl Buffer {
         events: &mut BufferTextInsertsMutGuard,
     ) -> BufferRange {
         self.search_ranges.clear();
+        self.git_blame.clear();
+        self.git_diff.invalidate();
         let position = self.content.saturate_position(position);
 
         if text.is_empty() {
@@ -1352,6 +1846,8 @@ impl Buffer {
         events: &mut BufferRangeDeletesMutGuard,
     ) {
         self.search_ranges.clear();
+        self.git_blame.clear();
+        self.git_diff.invalidate();
         range.from = self.content.saturate_position(range.from);
         range.to = self.content.saturate_position(range.to);
 
@@ -1565,6 +2061,10 @@ impl Buffer {
         self.history.commit_edits();
     }
 
+    pub(crate) fn evict_oldest_history_groups(&mut self, max_group_count: usize) {
+        self.history.evict_oldest_groups(max_group_count);
+    }
+
     pub fn undo(
         &mut self,
         word_database: &mut WordDatabase,
@@ -1592,6 +2092,8 @@ impl Buffer {
         I: 'a + Clone + ExactSizeIterator<Item = Edit<'a>>,
     {
         self.search_ranges.clear();
+        self.git_blame.clear();
+        self.git_diff.invalidate();
         self.needs_save = true;
 
         let content = &mut self.content;
@@ -1647,8 +2149,12 @@ impl Buffer {
         }
 
         self.needs_save = false;
+        self.saved_content_hash = None;
         self.history.clear();
         self.search_ranges.clear();
+        self.git_blame.clear();
+        self.git_diff.invalidate();
+        self.misspellings.invalidate();
 
         events.enqueue(EditorEvent::BufferRead {
             handle: self.handle,
@@ -1679,6 +2185,19 @@ impl Buffer {
                     clear_buffer(self, word_database);
                     let mut reader = io::BufReader::new(file);
                     self.content.read(&mut reader)?;
+                    self.saved_content_hash = Some(self.content.hash());
+
+                    if self.properties.tail_enabled {
+                        let mut file = reader.into_inner();
+                        self.tail = BufferTail::default();
+                        self.tail.offset = file.seek(SeekFrom::End(0))?;
+                        if self.tail.offset > 0 {
+                            let mut last_byte = [0];
+                            file.seek(SeekFrom::End(-1))?;
+                            file.read_exact(&mut last_byte)?;
+                            self.tail.pending_line_break = last_byte[0] == b'\n';
+                        }
+                    }
                 }
                 Err(error) => {
                     if self.properties.saving_enabled {
@@ -1701,6 +2220,79 @@ impl Buffer {
         Ok(())
     }
 
+    pub(crate) fn read_file_tail(
+        &mut self,
+        word_database: &mut WordDatabase,
+        events: &mut EditorEventWriter,
+    ) -> bool {
+        let len = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return false,
+        };
+        if len == self.tail.offset {
+            return false;
+        }
+
+        if len < self.tail.offset {
+            let range = BufferRange::between(BufferPosition::zero(), self.content.end());
+            self.delete_range(
+                word_database,
+                range,
+                &mut events.buffer_range_deletes_mut_guard(self.handle),
+            );
+            self.tail = BufferTail::default();
+        }
+
+        let mut bytes = Vec::new();
+        let read_result = File::open(&self.path).and_then(|mut file| {
+            file.seek(SeekFrom::Start(self.tail.offset))?;
+            file.take(len - self.tail.offset).read_to_end(&mut bytes)
+        });
+        if read_result.is_err() {
+            return false;
+        }
+
+        // an incomplete char at the end is left to be read again once the rest of it is written
+        let text_len = match std::str::from_utf8(&bytes) {
+            Err(error) if error.error_len().is_none() => error.valid_up_to(),
+            _ => bytes.len(),
+        };
+        if text_len == 0 {
+            return false;
+        }
+        self.tail.offset += text_len as u64;
+        let text = String::from_utf8_lossy(&bytes[..text_len]);
+
+        let needs_save = self.needs_save;
+        let mut events = events.buffer_text_inserts_mut_guard(self.handle);
+        for line in text.split_inclusive('\n') {
+            if self.tail.pending_line_break {
+                self.tail.pending_line_break = false;
+                let position = self.content.end();
+                self.insert_text(word_database, position, "\n", &mut events);
+            }
+
+            let line = match line.strip_suffix('\n') {
+                Some(line) => {
+                    self.tail.pending_line_break = true;
+                    line.strip_suffix('\r').unwrap_or(line)
+                }
+                None => line,
+            };
+            let position = self.content.end();
+            self.insert_text(word_database, position, line, &mut events);
+        }
+        self.needs_save = needs_save;
+
+        true
+    }
+
+    pub fn append_to_file(&self, path: &Path) -> Result<(), BufferWriteError> {
+        let file = open_file_for_writing(path, true)?;
+        self.content.write(&mut io::BufWriter::new(file))?;
+        Ok(())
+    }
+
     pub fn write_to_file(
         &mut self,
         new_path: Option<&Path>,
@@ -1723,6 +2315,7 @@ impl Buffer {
         if self.properties.file_backed_enabled {
             let file = File::create(&self.path)?;
             self.content.write(&mut io::BufWriter::new(file))?;
+            self.saved_content_hash = Some(self.content.hash());
         }
 
         self.needs_save = false;
@@ -1733,6 +2326,25 @@ impl Buffer {
         });
         Ok(())
     }
+
+    // like 'write_to_file' but skips writing when the content did not change since it was last
+    // read from or written to its file. returns whether it was written
+    pub fn write_to_file_if_changed(
+        &mut self,
+        events: &mut EditorEventWriter,
+    ) -> Result<bool, BufferWriteError> {
+        if !self.properties.saving_enabled {
+            return Err(BufferWriteError::SavingDisabled);
+        }
+
+        if self.saved_content_hash == Some(self.content.hash()) {
+            self.needs_save = false;
+            return Ok(false);
+        }
+
+        self.write_to_file(None, events)?;
+        Ok(true)
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
@@ -1826,6 +2438,13 @@ impl BufferCollection {
         self.buffers.iter().filter(|b| b.alive)
     }
 
+    // also goes through removed buffers as their lines remain pooled for when they're reused
+    pub(crate) fn trim_line_pools(&mut self) {
+        for buffer in &mut self.buffers {
+            buffer.content.trim_line_pool();
+        }
+    }
+
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Buffer> {
         self.buffers.iter_mut().filter(|b| b.alive)
     }
@@ -1874,6 +2493,7 @@ impl BufferCollection {
             let range = insert.range;
             buffer.highlighted.insert_range(range);
             buffer.lints.insert_range(range);
+            buffer.misspellings.insert_range(range);
             if buffer.breakpoints.insert_range(range) {
                 breakpoints_changed = true;
             }
@@ -1908,6 +2528,7 @@ impl BufferCollection {
         for &range in deletes {
             buffer.highlighted.delete_range(range);
             buffer.lints.delete_range(range);
+            buffer.misspellings.delete_range(range);
             if buffer.breakpoints.delete_range(range) {
                 breakpoints_changed = true;
             }
@@ -2068,12 +2689,119 @@ mod tests {
         assert_eq!(8, display_len("xxxx\t"));
     }
 
+    #[test]
+    fn tab_stops_display_distance() {
+        let tab_stops: TabStops = "6, 10".parse().unwrap();
+        let display_len = |text: &str| tab_stops.line_len(text, DisplayLen::from(text), 4);
+
+        assert_eq!(0, display_len(""));
+        assert_eq!(6, display_len("\t"));
+        assert_eq!(6, display_len("ab\t"));
+        assert_eq!(10, display_len("\t\t"));
+        assert_eq!(14, display_len("\t\t\t"));
+        assert_eq!(9, display_len("abcdefgh\t"));
+        assert_eq!(10, display_len("abcdefgh\t\t"));
+
+        assert_eq!("6,10", tab_stops.to_string());
+        assert!("".parse::<TabStops>().unwrap().is_empty());
+        assert!("6,4".parse::<TabStops>().is_err());
+        assert!("0".parse::<TabStops>().is_err());
+        assert!("a".parse::<TabStops>().is_err());
+    }
+
+    #[test]
+    fn line_pool_trim() {
+        let mut pool = BufferLinePool::new();
+        let lines: Vec<_> = (0..1000).map(|_| pool.acquire()).collect();
+        for line in lines {
+            pool.release(line);
+        }
+
+        pool.trim();
+        assert_eq!(1000, pool.pool.len());
+
+        let line = pool.acquire();
+        pool.release(line);
+        pool.trim();
+        assert_eq!(LINE_POOL_MIN_LEN, pool.pool.len());
+
+        pool.trim();
+        assert_eq!(LINE_POOL_MIN_LEN, pool.pool.len());
+    }
+
     fn buffer_from_str(text: &str) -> BufferContent {
         let mut buffer = BufferContent::new();
         buffer.insert_text(BufferPosition::zero(), text);
         buffer
     }
 
+    #[test]
+    fn buffer_content_read() {
+        let mut buffer = buffer_from_str("previous\ncontent");
+        let text = "\u{feff}first line\r\nsecond ç line\n\nlast";
+        let mut reader = io::BufReader::with_capacity(4, text.as_bytes());
+        buffer.read(&mut reader).unwrap();
+        assert_eq!(4, buffer.lines().len());
+        assert_eq!("first line\nsecond ç line\n\nlast", buffer.to_string());
+        assert_eq!(LineEnding::Crlf, buffer.line_ending());
+        assert!(buffer.has_byte_order_mark());
+        assert_eq!(
+            DisplayLen::from("second ç line").total_len(4),
+            buffer.line_display_lens()[1].total_len(4)
+        );
+
+        let mut output = Vec::new();
+        buffer.write(&mut output).unwrap();
+        assert_eq!(
+            &b"\xef\xbb\xbffirst line\r\nsecond \xc3\xa7 line\r\n\r\nlast\r\n"[..],
+            &output[..]
+        );
+
+        let mut reader = io::BufReader::new(&b"first\nsecond\r\n"[..]);
+        buffer.read(&mut reader).unwrap();
+        assert_eq!(LineEnding::Lf, buffer.line_ending());
+        assert!(!buffer.has_byte_order_mark());
+
+        let mut reader = io::BufReader::new(&b"invalid \xff utf8"[..]);
+        assert!(buffer.read(&mut reader).is_err());
+        assert_eq!("", buffer.to_string());
+    }
+
+    #[test]
+    fn buffer_content_position_from_byte_offset() {
+        let buffer = buffer_from_str("ab\nçd\n\nlast");
+        let position = |offset| buffer.position_from_byte_offset(offset);
+        assert_eq!(BufferPosition::line_col(0, 0), position(0));
+        assert_eq!(BufferPosition::line_col(0, 2), position(2));
+        assert_eq!(BufferPosition::line_col(1, 0), position(3));
+        assert_eq!(BufferPosition::line_col(1, 0), position(4));
+        assert_eq!(BufferPosition::line_col(1, 2), position(5));
+        assert_eq!(BufferPosition::line_col(2, 0), position(7));
+        assert_eq!(BufferPosition::line_col(3, 0), position(8));
+        assert_eq!(BufferPosition::line_col(3, 4), position(12));
+        assert_eq!(BufferPosition::line_col(3, 4), position(100));
+
+        let mut buffer = buffer_from_str("ab\nçd\n\nlast");
+        buffer.line_ending = LineEnding::Crlf;
+        let position = |offset| buffer.position_from_byte_offset(offset);
+        assert_eq!(BufferPosition::line_col(0, 2), position(2));
+        assert_eq!(BufferPosition::line_col(0, 2), position(3));
+        assert_eq!(BufferPosition::line_col(1, 0), position(4));
+        assert_eq!(BufferPosition::line_col(1, 0), position(5));
+        assert_eq!(BufferPosition::line_col(1, 2), position(6));
+        assert_eq!(BufferPosition::line_col(2, 0), position(9));
+        assert_eq!(BufferPosition::line_col(3, 0), position(11));
+        assert_eq!(BufferPosition::line_col(3, 4), position(15));
+
+        let mut buffer = buffer_from_str("ab\nçd");
+        buffer.byte_order_mark = true;
+        let position = |offset| buffer.position_from_byte_offset(offset);
+        assert_eq!(BufferPosition::line_col(0, 0), position(0));
+        assert_eq!(BufferPosition::line_col(0, 0), position(3));
+        assert_eq!(BufferPosition::line_col(0, 2), position(5));
+        assert_eq!(BufferPosition::line_col(1, 0), position(6));
+    }
+
     #[test]
     fn buffer_utf8_support() {
         let mut buffer = buffer_from_str("abd");
@@ -2138,6 +2866,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn buffer_content_hash() {
+        let mut buffer = buffer_from_str("first\nsecond");
+        let mut written = Vec::new();
+        buffer.write(&mut written).unwrap();
+        assert_eq!(hash_bytes(&written), buffer.hash());
+
+        let hash = buffer.hash();
+        let range = buffer.insert_text(BufferPosition::line_col(1, 0), "x");
+        assert_ne!(hash, buffer.hash());
+        buffer.delete_range(range);
+        assert_eq!(hash, buffer.hash());
+
+        assert_ne!(buffer_from_str("ab").hash(), buffer_from_str("a\nb").hash());
+    }
+
     #[test]
     fn buffer_content_delete_range() {
         let mut buffer = buffer_from_str("abc");
@@ -2385,6 +3129,39 @@ mod tests {
         assert_eq!(None, text_range.next());
     }
 
+    #[test]
+    fn buffer_content_lines_in_range() {
+        fn reconstruct(buffer: &BufferContent, range: BufferRange) -> String {
+            let mut text = String::new();
+            for (line, range) in buffer.lines_in_range(range) {
+                text.push_str(&line.as_str()[range]);
+                text.push('\n');
+            }
+            text.pop();
+            text
+        }
+
+        let buffer = buffer_from_str("abc\ndef\n\nghi");
+        for &(from, to) in &[
+            ((0, 0), (0, 0)),
+            ((0, 1), (0, 2)),
+            ((0, 2), (3, 1)),
+            ((0, 3), (1, 0)),
+            ((1, 1), (2, 0)),
+            ((0, 0), (3, 3)),
+        ] {
+            let range = BufferRange::between(
+                BufferPosition::line_col(from.0, from.1),
+                BufferPosition::line_col(to.0, to.1),
+            );
+            let mut text = String::new();
+            for t in buffer.text_range(range) {
+                text.push_str(t);
+            }
+            assert_eq!(text, reconstruct(&buffer, range));
+        }
+    }
+
     #[test]
     fn buffer_content_word_at() {
         fn col(column: usize) -> BufferPosition {
@@ -2411,6 +3188,17 @@ mod tests {
         assert_word(buffer.word_at(col(10)), col(10), WordKind::Whitespace, " ");
     }
 
+    #[test]
+    fn buffer_content_char_at() {
+        let buffer = buffer_from_str("aé\nb");
+        assert_eq!(Some('a'), buffer.char_at(BufferPosition::line_col(0, 0)));
+        assert_eq!(Some('é'), buffer.char_at(BufferPosition::line_col(0, 1)));
+        assert_eq!(Some('\n'), buffer.char_at(BufferPosition::line_col(0, 3)));
+        assert_eq!(Some('b'), buffer.char_at(BufferPosition::line_col(1, 0)));
+        assert_eq!(None, buffer.char_at(BufferPosition::line_col(1, 1)));
+        assert_eq!(None, buffer.char_at(BufferPosition::line_col(5, 0)));
+    }
+
     #[test]
     fn buffer_content_words_from() {
         fn col(column: usize) -> BufferPosition {
@@ -2449,6 +3237,41 @@ mod tests {
         assert!(rw.next().is_none());
     }
 
+    #[test]
+    fn buffer_content_find_matching_bracket() {
+        fn pos(line_index: usize, column_byte_index: usize) -> BufferPosition {
+            BufferPosition::line_col(line_index as _, column_byte_index as _)
+        }
+
+        let buffer = buffer_from_str("a(b[c]\n{d})\n(]");
+        assert_eq!(None, buffer.find_matching_bracket(pos(0, 0)));
+        assert_eq!(None, buffer.find_matching_bracket(pos(0, 6)));
+        assert_eq!(
+            Some(MatchingBracket::Matched(pos(1, 3))),
+            buffer.find_matching_bracket(pos(0, 1))
+        );
+        assert_eq!(
+            Some(MatchingBracket::Matched(pos(0, 1))),
+            buffer.find_matching_bracket(pos(1, 3))
+        );
+        assert_eq!(
+            Some(MatchingBracket::Matched(pos(0, 5))),
+            buffer.find_matching_bracket(pos(0, 3))
+        );
+        assert_eq!(
+            Some(MatchingBracket::Matched(pos(1, 0))),
+            buffer.find_matching_bracket(pos(1, 2))
+        );
+        assert_eq!(
+            Some(MatchingBracket::Unmatched),
+            buffer.find_matching_bracket(pos(2, 0))
+        );
+        assert_eq!(
+            Some(MatchingBracket::Unmatched),
+            buffer.find_matching_bracket(pos(2, 1))
+        );
+    }
+
     #[test]
     fn buffer_find_balanced_chars() {
         let buffer = buffer_from_str("(\n(\na\n)\nbc)");
@@ -2497,6 +3320,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn buffer_indentation_config() {
+        let config = Config {
+            tab_size: 8,
+            indent_with_tabs: false,
+            ..Default::default()
+        };
+        let mut buffer = Buffer::new(BufferHandle(0));
+
+        let indentation_config = buffer.indentation_config(&config);
+        assert!(!indentation_config.indent_with_tabs);
+        assert_eq!(8, indentation_config.tab_size);
+
+        buffer.detected_indentation = Some(DetectedIndentation::Tabs);
+        let indentation_config = buffer.indentation_config(&config);
+        assert!(indentation_config.indent_with_tabs);
+        assert_eq!(8, indentation_config.tab_size);
+
+        buffer.detected_indentation = Some(DetectedIndentation::Spaces(2));
+        let indentation_config = buffer.indentation_config(&config);
+        assert!(!indentation_config.indent_with_tabs);
+        assert_eq!(2, indentation_config.tab_size);
+        assert_eq!(8, config.tab_size);
+    }
+
+    #[test]
+    fn buffer_content_detect_indentation() {
+        let buffer = buffer_from_str("");
+        assert_eq!(None, buffer.detect_indentation());
+
+        let buffer = buffer_from_str("a\nb\n  c");
+        assert_eq!(
+            Some(DetectedIndentation::Spaces(2)),
+            buffer.detect_indentation()
+        );
+
+        let buffer = buffer_from_str("a {\n    b {\n        c\n    }\n\n    d\n}");
+        assert_eq!(
+            Some(DetectedIndentation::Spaces(4)),
+            buffer.detect_indentation()
+        );
+
+        let buffer = buffer_from_str("a {\n  b {\n    c\n  }\n}\n/*\n *\n */");
+        assert_eq!(
+            Some(DetectedIndentation::Spaces(2)),
+            buffer.detect_indentation()
+        );
+
+        let buffer = buffer_from_str("a {\n\tb {\n\t\tc\n\t}\n}");
+        assert_eq!(Some(DetectedIndentation::Tabs), buffer.detect_indentation());
+
+        let buffer = buffer_from_str("a\n\tb\n\tc\n  d");
+        assert_eq!(Some(DetectedIndentation::Tabs), buffer.detect_indentation());
+    }
+
     #[test]
     fn buffer_display_len() {
         fn len(buffer: &BufferContent, line: usize) -> usize {
@@ -2668,4 +3546,22 @@ mod tests {
         buffer.fix_line_indentation(indentation_config, 1, &mut events);
         assert_eq!("        second", buffer.content().lines()[1].as_str());
     }
+
+    #[test]
+    fn buffer_spell_check_toggle() {
+        let mut spell_checker = SpellChecker::default();
+        spell_checker.set_check_glob("**/*.md");
+
+        let mut buffer = Buffer::new(BufferHandle(0));
+        buffer.path = PathBuf::from("notes.md");
+        assert!(buffer.spell_checks(&spell_checker));
+        buffer.spell_check = Some(false);
+        assert!(!buffer.spell_checks(&spell_checker));
+
+        buffer.path = PathBuf::from("main.rs");
+        buffer.spell_check = None;
+        assert!(!buffer.spell_checks(&spell_checker));
+        buffer.spell_check = Some(true);
+        assert!(buffer.spell_checks(&spell_checker));
+    }
 }
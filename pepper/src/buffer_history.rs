@@ -373,6 +373,44 @@ impl BufferHistory {
         }
     }
 
+    // only already undoable groups are evicted as redoing depends on every group before it
+    pub fn evict_oldest_groups(&mut self, max_group_count: usize) {
+        let undo_group_count = match self.state {
+            HistoryState::IterIndex { group_index } => group_index,
+            HistoryState::InsertGroup { .. } => self.group_ranges.len(),
+        };
+        let evicted_group_count = self
+            .group_ranges
+            .len()
+            .saturating_sub(max_group_count)
+            .min(undo_group_count);
+        if evicted_group_count == 0 {
+            return;
+        }
+
+        let evicted_edit_count = self.group_ranges[evicted_group_count - 1].end;
+        let evicted_text_len = match evicted_edit_count.checked_sub(1) {
+            Some(i) => self.edits[i].text_range.end,
+            None => 0,
+        };
+
+        self.texts.drain(..evicted_text_len as usize);
+        self.edits.drain(..evicted_edit_count);
+        for edit in &mut self.edits {
+            edit.text_range.start -= evicted_text_len;
+            edit.text_range.end -= evicted_text_len;
+        }
+        self.group_ranges.drain(..evicted_group_count);
+        for range in &mut self.group_ranges {
+            range.start -= evicted_edit_count;
+            range.end -= evicted_edit_count;
+        }
+        match &mut self.state {
+            HistoryState::IterIndex { group_index } => *group_index -= evicted_group_count,
+            HistoryState::InsertGroup { edit_index } => *edit_index -= evicted_edit_count,
+        }
+    }
+
     pub fn undo_edits(
         &mut self,
     ) -> impl Clone + ExactSizeIterator<Item = Edit> + DoubleEndedIterator<Item = Edit> {
@@ -1287,4 +1325,59 @@ mod tests {
             assert!(edits.next().is_none());
         }
     }
+
+    #[test]
+    fn evict_oldest_groups() {
+        fn insert(history: &mut BufferHistory, column: usize, text: &str) {
+            history.add_edit(Edit {
+                kind: EditKind::Insert,
+                range: buffer_range((0, column), (0, column + text.len())),
+                text,
+            });
+            history.commit_edits();
+        }
+
+        let mut history = BufferHistory::new();
+        insert(&mut history, 0, "a");
+        insert(&mut history, 1, "bb");
+        insert(&mut history, 3, "ccc");
+        insert(&mut history, 6, "dddd");
+
+        assert_eq!("dddd", history.undo_edits().next().unwrap().text);
+        assert_eq!("ccc", history.undo_edits().next().unwrap().text);
+        assert_eq!("bb", history.undo_edits().next().unwrap().text);
+
+        history.evict_oldest_groups(2);
+        assert_eq!(0, history.undo_edits().count());
+        assert_eq!("bb", history.redo_edits().next().unwrap().text);
+        assert_eq!("ccc", history.redo_edits().next().unwrap().text);
+        assert_eq!("dddd", history.redo_edits().next().unwrap().text);
+        assert_eq!(0, history.redo_edits().count());
+
+        history.evict_oldest_groups(2);
+        assert_eq!("dddd", history.undo_edits().next().unwrap().text);
+        assert_eq!("ccc", history.undo_edits().next().unwrap().text);
+        assert_eq!(0, history.undo_edits().count());
+
+        assert_eq!("ccc", history.redo_edits().next().unwrap().text);
+        history.add_edit(Edit {
+            kind: EditKind::Insert,
+            range: buffer_range((0, 6), (0, 7)),
+            text: "e",
+        });
+        history.evict_oldest_groups(0);
+        history.add_edit(Edit {
+            kind: EditKind::Insert,
+            range: buffer_range((0, 7), (0, 8)),
+            text: "f",
+        });
+        let mut edits = history.undo_edits();
+        let edit = edits.next().unwrap();
+        assert_eq!(EditKind::Delete, edit.kind);
+        assert_eq!("ef", edit.text);
+        assert_eq!(buffer_range((0, 6), (0, 8)), edit.range);
+        assert!(edits.next().is_none());
+        drop(edits);
+        assert_eq!(0, history.undo_edits().count());
+    }
 }
@@ -1,6 +1,7 @@
 use crate::{
     buffer::{
         Buffer, BufferCollection, BufferHandle, BufferIndentationConfig, CharDisplayDistances,
+        TabStops,
     },
     buffer_history::EditKind,
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
@@ -10,11 +11,19 @@ use crate::{
     word_database::{WordDatabase, WordIter, WordKind},
 };
 
-pub enum CursorMovement {
+pub enum CursorMovement<'a> {
     ColumnsForward(usize),
     ColumnsBackward(usize),
-    LinesForward { count: usize, tab_size: u8 },
-    LinesBackward { count: usize, tab_size: u8 },
+    LinesForward {
+        count: usize,
+        tab_size: u8,
+        tab_stops: &'a TabStops,
+    },
+    LinesBackward {
+        count: usize,
+        tab_size: u8,
+        tab_stops: &'a TabStops,
+    },
     WordsForward(usize),
     WordsBackward(usize),
     WordEndForward(usize),
@@ -23,6 +32,9 @@ pub enum CursorMovement {
     End,
     FirstLine,
     LastLine,
+    MatchingBracket,
+    ParagraphsForward(usize),
+    ParagraphsBackward(usize),
 }
 
 #[derive(Clone, Copy)]
@@ -173,8 +185,12 @@ impl BufferView {
                     }
                 }
             }
-            CursorMovement::LinesForward { count: n, tab_size } => {
-                cursors.save_display_distances(buffer, tab_size);
+            CursorMovement::LinesForward {
+                count: n,
+                tab_size,
+                tab_stops,
+            } => {
+                cursors.save_display_distances(buffer, tab_size, tab_stops);
                 for i in 0..cursors[..].len() {
                     let saved_display_distance = cursors.get_saved_display_distance(i);
                     let c = &mut cursors[i];
@@ -187,6 +203,7 @@ impl BufferView {
                     if let Some(distance) = saved_display_distance {
                         let line = buffer.lines()[c.position.line_index as usize].as_str();
                         c.position.column_byte_index = CharDisplayDistances::new(line, tab_size)
+                            .with_tab_stops(tab_stops)
                             .find(|d| d.distance > distance as _)
                             .map(|d| d.char_index as usize)
                             .unwrap_or(line.len())
@@ -195,8 +212,12 @@ impl BufferView {
                     c.position = buffer.saturate_position(c.position);
                 }
             }
-            CursorMovement::LinesBackward { count: n, tab_size } => {
-                cursors.save_display_distances(buffer, tab_size);
+            CursorMovement::LinesBackward {
+                count: n,
+                tab_size,
+                tab_stops,
+            } => {
+                cursors.save_display_distances(buffer, tab_size, tab_stops);
                 for i in 0..cursors[..].len() {
                     let saved_display_distance = cursors.get_saved_display_distance(i);
                     let c = &mut cursors[i];
@@ -204,6 +225,7 @@ impl BufferView {
                     if let Some(distance) = saved_display_distance {
                         let line = buffer.lines()[c.position.line_index as usize].as_str();
                         c.position.column_byte_index = CharDisplayDistances::new(line, tab_size)
+                            .with_tab_stops(tab_stops)
                             .find(|d| d.distance > distance as _)
                             .map(|d| d.char_index as usize)
                             .unwrap_or(line.len())
@@ -365,6 +387,96 @@ impl BufferView {
                     c.position = buffer.saturate_position(c.position);
                 }
             }
+            CursorMovement::ParagraphsForward(n) => {
+                let is_blank =
+                    |line_index: usize| buffer.lines()[line_index].as_str().trim().is_empty();
+                let line_count = buffer.lines().len();
+                for c in &mut cursors[..] {
+                    let mut line_index = c.position.line_index as usize;
+                    for _ in 0..n {
+                        while line_index < line_count && is_blank(line_index) {
+                            line_index += 1;
+                        }
+                        while line_index < line_count && !is_blank(line_index) {
+                            line_index += 1;
+                        }
+                    }
+
+                    c.position = if line_index < line_count {
+                        BufferPosition::line_col(line_index as _, 0)
+                    } else {
+                        buffer.end()
+                    };
+                }
+            }
+            CursorMovement::ParagraphsBackward(n) => {
+                let is_blank =
+                    |line_index: usize| buffer.lines()[line_index].as_str().trim().is_empty();
+                for c in &mut cursors[..] {
+                    let mut line_index = c.position.line_index as usize;
+                    for _ in 0..n {
+                        while line_index > 0 && is_blank(line_index) {
+                            line_index -= 1;
+                        }
+                        while line_index > 0 && !is_blank(line_index) {
+                            line_index -= 1;
+                        }
+                    }
+
+                    c.position = BufferPosition::line_col(line_index as _, 0);
+                }
+            }
+            CursorMovement::MatchingBracket => {
+                fn is_bracket(c: char) -> bool {
+                    matches!(c, '(' | ')' | '[' | ']' | '{' | '}' | '<' | '>')
+                }
+
+                for c in &mut cursors[..] {
+                    let mut position = c.position;
+
+                    let line = buffer.lines()[position.line_index as usize].as_str();
+                    if position.column_byte_index >= line.len() as _ {
+                        match line.char_indices().next_back() {
+                            Some((i, _)) => position.column_byte_index = i as _,
+                            None => continue,
+                        }
+                    }
+
+                    let rest = &line[position.column_byte_index as usize..];
+                    let cursor_char = match rest.chars().next() {
+                        Some(c) if is_bracket(c) || matches!(c, '|' | '"' | '\'' | '`') => c,
+                        _ => match rest.char_indices().find(|&(_, c)| is_bracket(c)) {
+                            Some((i, c)) => {
+                                position.column_byte_index += i as BufferPositionIndex;
+                                c
+                            }
+                            None => continue,
+                        },
+                    };
+
+                    let range = match cursor_char {
+                        '(' | ')' => buffer.find_balanced_chars_at(position, '(', ')'),
+                        '[' | ']' => buffer.find_balanced_chars_at(position, '[', ']'),
+                        '{' | '}' => buffer.find_balanced_chars_at(position, '{', '}'),
+                        '<' | '>' => buffer.find_balanced_chars_at(position, '<', '>'),
+                        d => buffer.find_delimiter_pair_at(position, d),
+                    };
+
+                    if let Some(range) = range {
+                        let from = BufferPosition::line_col(
+                            range.from.line_index,
+                            range.from.column_byte_index - 1,
+                        );
+                        let to = range.to;
+
+                        if position == from {
+                            c.position = to;
+                        } else if position == to {
+                            c.position = from;
+                        }
+                    }
+                }
+            }
         }
 
         if let CursorMovementKind::PositionAndAnchor = movement_kind {
@@ -690,6 +802,20 @@ impl BufferViewCollection {
         }
     }
 
+    pub(crate) fn on_buffer_tail(&self, buffer: &Buffer, events: &mut EditorEventWriter) {
+        let buffer_handle = buffer.handle();
+        let end = buffer.content().end();
+
+        for view in self.buffer_views.iter() {
+            if view.alive && view.buffer_handle == buffer_handle {
+                events.fix_cursors_mut_guard(view.handle).add(Cursor {
+                    anchor: end,
+                    position: end,
+                });
+            }
+        }
+    }
+
     pub(crate) fn on_buffer_read(&mut self, buffer: &Buffer) {
         let buffer_handle = buffer.handle();
         let buffer = buffer.content();
@@ -828,10 +954,59 @@ mod tests {
         assert_movement(&mut ctx, 2..2, 0..0, CursorMovement::WordsBackward(6));
         assert_movement(&mut ctx, 2..2, 0..0, CursorMovement::WordsBackward(999));
 
+        let no_tab_stops = TabStops::default();
+        let tab_stops: TabStops = "6".parse().unwrap();
+        let lines_forward = |tab_stops| CursorMovement::LinesForward {
+            count: 1,
+            tab_size: 4,
+            tab_stops,
+        };
+        let lines_backward = |tab_stops| CursorMovement::LinesBackward {
+            count: 1,
+            tab_size: 4,
+            tab_stops,
+        };
+        let mut ctx = TestContext::with_buffer("abcdefgh\n\tb");
+        assert_movement(&mut ctx, 0..6, 1..2, lines_forward(&no_tab_stops));
+        assert_movement(&mut ctx, 0..6, 1..1, lines_forward(&tab_stops));
+        assert_movement(&mut ctx, 1..1, 0..6, lines_backward(&tab_stops));
+        assert_movement(&mut ctx, 1..1, 0..4, lines_backward(&no_tab_stops));
+
         let mut ctx = TestContext::with_buffer("123\n  abc def\nghi");
         assert_movement(&mut ctx, 1..0, 1..2, CursorMovement::WordsForward(1));
         assert_movement(&mut ctx, 1..9, 2..0, CursorMovement::WordsForward(1));
         assert_movement(&mut ctx, 1..2, 1..0, CursorMovement::WordsBackward(1));
         assert_movement(&mut ctx, 2..0, 1..9, CursorMovement::WordsBackward(1));
+
+        fn assert_paragraph(
+            ctx: &mut TestContext,
+            from: (usize, usize),
+            to: (usize, usize),
+            movement: CursorMovement,
+        ) {
+            assert_movement(ctx, from.0..from.1, to.0..to.1, movement);
+        }
+
+        let mut ctx = TestContext::with_buffer("a\nb\n\n\n \nc\nd\n\ne");
+        let next = CursorMovement::ParagraphsForward;
+        let prev = CursorMovement::ParagraphsBackward;
+        assert_paragraph(&mut ctx, (0, 0), (2, 0), next(1));
+        assert_paragraph(&mut ctx, (1, 1), (2, 0), next(1));
+        assert_paragraph(&mut ctx, (2, 0), (7, 0), next(1));
+        assert_paragraph(&mut ctx, (3, 0), (7, 0), next(1));
+        assert_paragraph(&mut ctx, (0, 0), (7, 0), next(2));
+        assert_paragraph(&mut ctx, (7, 0), (8, 1), next(1));
+        assert_paragraph(&mut ctx, (8, 0), (8, 1), next(1));
+        assert_paragraph(&mut ctx, (0, 0), (8, 1), next(999));
+
+        assert_paragraph(&mut ctx, (8, 1), (7, 0), prev(1));
+        assert_paragraph(&mut ctx, (7, 0), (4, 0), prev(1));
+        assert_paragraph(&mut ctx, (6, 1), (4, 0), prev(1));
+        assert_paragraph(&mut ctx, (5, 0), (4, 0), prev(1));
+        assert_paragraph(&mut ctx, (4, 0), (0, 0), prev(1));
+        assert_paragraph(&mut ctx, (8, 1), (4, 0), prev(2));
+        assert_paragraph(&mut ctx, (1, 0), (0, 0), prev(1));
+        assert_paragraph(&mut ctx, (0, 0), (0, 0), prev(1));
+        assert_paragraph(&mut ctx, (8, 1), (0, 0), prev(999));
     }
 }
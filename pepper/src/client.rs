@@ -3,11 +3,15 @@ use std::{fmt, path::Path};
 use crate::{
     buffer::{BufferCollection, BufferHandle, BufferProperties, CharDisplayDistances},
     buffer_position::BufferPositionIndex,
-    buffer_view::{BufferView, BufferViewCollection, BufferViewHandle},
+    buffer_view::{
+        BufferView, BufferViewCollection, BufferViewHandle, CursorMovement, CursorMovementKind,
+    },
+    config::Config,
     editor::Editor,
     editor_utils::ResidualStrBytes,
     navigation_history::{NavigationHistory, NavigationMovement},
     serialization::{DeserializeError, Deserializer, Serialize, Serializer},
+    ui,
 };
 
 #[derive(Clone, Copy, Eq, PartialEq)]
@@ -29,6 +33,13 @@ pub enum ViewAnchor {
     Bottom,
 }
 
+pub enum ViewScroll {
+    HalfPageDown,
+    HalfPageUp,
+    PageDown,
+    PageUp,
+}
+
 pub struct Client {
     active: bool,
     handle: ClientHandle,
@@ -113,20 +124,106 @@ impl Client {
             };
 
             let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
-            let main_cursor_padding_top = self.find_main_cursor_padding_top(
-                buffer_view,
-                &editor.buffers,
-                editor.config.tab_size,
-            );
+            let main_cursor_padding_top =
+                self.find_main_cursor_padding_top(buffer_view, &editor.buffers, &editor.config);
             buffer_view.scroll = main_cursor_padding_top.saturating_sub(height_offset) as _;
         }
     }
 
+    pub fn scroll_view(
+        &self,
+        editor: &mut Editor,
+        scroll: ViewScroll,
+        count: usize,
+        keep_cursor_row: bool,
+        movement_kind: CursorMovementKind,
+    ) {
+        if !self.has_ui() {
+            return;
+        }
+
+        let buffer_view_handle = match self.buffer_view_handle {
+            Some(handle) => handle,
+            None => return,
+        };
+
+        let height = self.viewport_size.1.saturating_sub(1) as usize;
+        let tab_stops = &editor.config.tab_stops;
+
+        let page_height = height
+            .saturating_sub(editor.config.page_scroll_overlap as _)
+            .max(1);
+        let (offset, forward) = match scroll {
+            ViewScroll::HalfPageDown => ((height / 2).max(1), true),
+            ViewScroll::HalfPageUp => ((height / 2).max(1), false),
+            ViewScroll::PageDown => (page_height, true),
+            ViewScroll::PageUp => (page_height, false),
+        };
+        let offset = offset * count.max(1);
+
+        let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
+        let buffer = editor.buffers.get(buffer_view.buffer_handle);
+        let tab_size = buffer.indentation_config(&editor.config).tab_size;
+        let width = self.wrap_width(&editor.config, buffer.wraps_lines(editor.config.wrap_lines));
+        let gutter_width = buffer.git_diff.gutter_width();
+        let buffer = buffer.content();
+
+        let mut total_height = 0;
+        for (line, &display_len) in buffer.lines().iter().zip(buffer.line_display_lens()) {
+            let line_len = tab_stops.line_len(line.as_str(), display_len, tab_size);
+            total_height += 1 + (gutter_width + line_len) / width;
+        }
+        let max_scroll = total_height.saturating_sub(height);
+
+        let previous_scroll = buffer_view.scroll as usize;
+        let scroll = if forward {
+            (previous_scroll + offset).min(max_scroll.max(previous_scroll))
+        } else {
+            previous_scroll.saturating_sub(offset)
+        };
+        buffer_view.scroll = scroll as _;
+
+        let movement = if keep_cursor_row {
+            if forward {
+                CursorMovement::LinesForward {
+                    count: offset,
+                    tab_size,
+                    tab_stops: &editor.config.tab_stops,
+                }
+            } else {
+                CursorMovement::LinesBackward {
+                    count: offset,
+                    tab_size,
+                    tab_stops: &editor.config.tab_stops,
+                }
+            }
+        } else {
+            let main_cursor_padding_top =
+                self.find_main_cursor_padding_top(buffer_view, &editor.buffers, &editor.config);
+            if main_cursor_padding_top < scroll {
+                CursorMovement::LinesForward {
+                    count: scroll - main_cursor_padding_top,
+                    tab_size,
+                    tab_stops: &editor.config.tab_stops,
+                }
+            } else if main_cursor_padding_top >= scroll + height {
+                CursorMovement::LinesBackward {
+                    count: main_cursor_padding_top + 1 - (scroll + height),
+                    tab_size,
+                    tab_stops: &editor.config.tab_stops,
+                }
+            } else {
+                return;
+            }
+        };
+        buffer_view.move_cursors(&editor.buffers, movement, movement_kind);
+    }
+
     pub(crate) fn scroll_to_main_cursor(
         &self,
         buffer_views: &mut BufferViewCollection,
         buffers: &BufferCollection,
-        tab_size: u8,
+        config: &Config,
         margin_bottom: usize,
     ) -> BufferPositionIndex {
         if !self.has_ui() {
@@ -141,7 +238,7 @@ impl Client {
             Some(buffer_view_handle) => {
                 let buffer_view = buffer_views.get_mut(buffer_view_handle);
                 let main_cursor_padding_top =
-                    self.find_main_cursor_padding_top(buffer_view, buffers, tab_size);
+                    self.find_main_cursor_padding_top(buffer_view, buffers, config);
 
                 let mut scroll = buffer_view.scroll as usize;
                 if main_cursor_padding_top < scroll.saturating_sub(half_height) {
@@ -218,26 +315,46 @@ impl Client {
         }
     }
 
+    fn wrap_width(&self, config: &Config, wrap_lines: bool) -> usize {
+        if wrap_lines {
+            self.viewport_size.0 as usize - ui::minimap_width(config, self.viewport_size.0)
+        } else {
+            usize::MAX
+        }
+    }
+
     fn find_main_cursor_padding_top(
         &self,
         buffer_view: &BufferView,
         buffers: &BufferCollection,
-        tab_size: u8,
+        config: &Config,
     ) -> usize {
-        let width = self.viewport_size.0 as usize;
+        let tab_stops = &config.tab_stops;
+        let buffer = buffers.get(buffer_view.buffer_handle);
+        let tab_size = buffer.indentation_config(config).tab_size;
+        let width = self.wrap_width(config, buffer.wraps_lines(config.wrap_lines));
+        let gutter_width = buffer.git_diff.gutter_width();
 
-        let buffer = buffers.get(buffer_view.buffer_handle).content();
+        let buffer = buffer.content();
         let position = buffer_view.cursors.main_cursor().position;
 
-        let mut height = position.line_index as usize;
-        for display_len in &buffer.line_display_lens()[..position.line_index as usize] {
-            height += display_len.total_len(tab_size) / width;
+        let line_count = position.line_index as usize;
+        let mut height = line_count;
+        for (line, &display_len) in buffer.lines()[..line_count]
+            .iter()
+            .zip(&buffer.line_display_lens()[..line_count])
+        {
+            let line_len = tab_stops.line_len(line.as_str(), display_len, tab_size);
+            height += (gutter_width + line_len) / width;
         }
 
         let cursor_line = buffer.lines()[position.line_index as usize].as_str();
         let cursor_line = &cursor_line[..position.column_byte_index as usize];
-        if let Some(d) = CharDisplayDistances::new(cursor_line, tab_size).last() {
-            height += d.distance as usize / width;
+        if let Some(d) = CharDisplayDistances::new(cursor_line, tab_size)
+            .with_tab_stops(tab_stops)
+            .last()
+        {
+            height += (gutter_width + d.distance as usize) / width;
         }
 
         height
@@ -120,9 +120,41 @@ pub enum CompletionSource {
     Buffers,
     Files,
     HelpPages,
+    Flags(&'static [CommandFlag]),
     Custom(&'static [&'static str]),
 }
 
+// a command option that can appear before its positional arguments. names ending with '=' take a
+// value right after it which is completed from 'value_completion'
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommandFlag {
+    pub name: &'static str,
+    pub value_completion: CompletionSource,
+}
+impl CommandFlag {
+    pub const fn switch(name: &'static str) -> Self {
+        Self {
+            name,
+            value_completion: CompletionSource::Custom(&[]),
+        }
+    }
+
+    pub const fn option(name: &'static str, value_completion: CompletionSource) -> Self {
+        Self {
+            name,
+            value_completion,
+        }
+    }
+
+    pub fn matches(&self, arg: &str) -> bool {
+        if self.name.ends_with('=') {
+            arg.starts_with(self.name)
+        } else {
+            arg == self.name
+        }
+    }
+}
+
 pub struct CommandArgs<'command>(pub(crate) &'command str);
 impl<'command> CommandArgs<'command> {
     pub fn try_next(&mut self) -> Option<&'command str> {
@@ -398,8 +430,41 @@ pub type CommandFn = fn(ctx: &mut EditorContext, io: &mut CommandIO) -> Result<(
 pub struct Command {
     plugin_handle: Option<PluginHandle>,
     pub completions: &'static [CompletionSource],
+    pub flags: &'static [CommandFlag],
     command_fn: CommandFn,
 }
+impl Command {
+    pub fn plugin_handle(&self) -> Option<PluginHandle> {
+        self.plugin_handle
+    }
+
+    // returns the completion source for 'arg', the argument being typed after 'previous_args',
+    // together with how many of its leading bytes are not part of the completion pattern.
+    // flags are not counted as positional arguments
+    pub fn arg_completion<'a, I>(&self, previous_args: I, arg: &str) -> (CompletionSource, usize)
+    where
+        I: Iterator<Item = &'a str>,
+    {
+        if !self.flags.is_empty() && arg.starts_with('-') {
+            return match self
+                .flags
+                .iter()
+                .find(|f| f.name.ends_with('=') && arg.starts_with(f.name))
+            {
+                Some(flag) => (flag.value_completion, flag.name.len()),
+                None => (CompletionSource::Flags(self.flags), 0),
+            };
+        }
+
+        let index = previous_args
+            .filter(|a| !self.flags.iter().any(|f| f.matches(a)))
+            .count();
+        match self.completions.get(index) {
+            Some(&completion_source) => (completion_source, 0),
+            None => (CompletionSource::Custom(&[]), 0),
+        }
+    }
+}
 
 struct Macro {
     name_range: Range<u16>,
@@ -572,6 +637,8 @@ impl CommandManager {
         this
     }
 
+    // a leading 'CompletionSource::Flags' declares the command flags instead of completing
+    // its first positional argument
     pub fn register_command(
         &mut self,
         plugin_handle: Option<PluginHandle>,
@@ -579,10 +646,15 @@ impl CommandManager {
         completions: &'static [CompletionSource],
         command_fn: CommandFn,
     ) {
+        let (flags, completions) = match completions {
+            [CompletionSource::Flags(flags), completions @ ..] => (*flags, completions),
+            _ => (&[][..], completions),
+        };
         self.command_names.push(name);
         self.commands.push(Command {
             plugin_handle,
             completions,
+            flags,
             command_fn,
         });
     }
@@ -768,6 +840,10 @@ impl CommandManager {
             None => (command_name, false),
         };
 
+        if ctx.editor.config.session_stats {
+            ctx.editor.session_stats.command_count += 1;
+        }
+
         if let Some(command) = ctx.editor.commands.find_command(command_name) {
             let plugin_handle = command.plugin_handle;
             let command_fn = command.command_fn;
@@ -1040,6 +1116,70 @@ mod tests {
         assert_eq!(None, commands.next());
     }
 
+    #[test]
+    fn command_arg_completion() {
+        static COMPLETIONS: &[CompletionSource] = &[
+            CompletionSource::Custom(&["a", "b"]),
+            CompletionSource::Files,
+        ];
+        static FLAGS: &[CommandFlag] = &[
+            CommandFlag::switch("-switch"),
+            CommandFlag::option("-option=", CompletionSource::Buffers),
+        ];
+        let command = Command {
+            plugin_handle: None,
+            completions: COMPLETIONS,
+            flags: FLAGS,
+            command_fn: |_, _| Ok(()),
+        };
+        let completion = |previous_args: &[&'static str], arg| {
+            command.arg_completion(previous_args.iter().copied(), arg)
+        };
+
+        let no_completion = (CompletionSource::Custom(&[]), 0);
+        assert_eq!((COMPLETIONS[0], 0), completion(&[], ""));
+        assert_eq!((COMPLETIONS[0], 0), completion(&[], "a"));
+        assert_eq!((COMPLETIONS[1], 0), completion(&["a"], ""));
+        assert_eq!(no_completion, completion(&["a", "src"], ""));
+
+        assert_eq!((CompletionSource::Flags(FLAGS), 0), completion(&[], "-"));
+        assert_eq!(
+            (CompletionSource::Flags(FLAGS), 0),
+            completion(&["a"], "-sw")
+        );
+        assert_eq!((CompletionSource::Buffers, 8), completion(&[], "-option=x"));
+        assert_eq!((COMPLETIONS[0], 0), completion(&["-switch"], ""));
+        assert_eq!(
+            (COMPLETIONS[1], 0),
+            completion(&["-switch", "-option=x", "a"], "")
+        );
+        assert_eq!(no_completion, completion(&["-unknown", "a"], ""));
+
+        let command = Command {
+            flags: &[],
+            ..command
+        };
+        let completion = command.arg_completion(["-a"].iter().copied(), "-");
+        assert_eq!((COMPLETIONS[1], 0), completion);
+    }
+
+    #[test]
+    fn register_command_flags() {
+        static FLAGS: &[CommandFlag] = &[CommandFlag::switch("-switch")];
+        static COMPLETIONS: &[CompletionSource] =
+            &[CompletionSource::Flags(FLAGS), CompletionSource::Files];
+
+        let mut commands = CommandManager::new();
+        commands.register_command(None, "flagged", COMPLETIONS, |_, _| Ok(()));
+        let command = commands.find_command("flagged").unwrap();
+        assert_eq!(FLAGS, command.flags);
+        assert_eq!(&[CompletionSource::Files], command.completions);
+
+        let command = commands.find_command("if").unwrap();
+        assert!(command.flags.iter().any(|f| f.matches("-if-modified")));
+        assert!(command.flags.iter().any(|f| f.matches("-if-filetype=md")));
+    }
+
     #[test]
     fn command_tokenizer() {
         let mut tokens = CommandTokenizer("cmd arg1 arg2");
@@ -1,22 +1,44 @@
-use std::{env, path::Path, process::Stdio};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::Stdio,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
-    buffer::{BufferProperties, BufferReadError, BufferWriteError},
+    buffer::{
+        char_display_len, BufferContent, BufferProperties, BufferReadError, BufferWriteError,
+        CharDisplayDistances, DetectedIndentation, DisplayLen, BYTE_ORDER_MARK,
+    },
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
-    command::{CommandError, CommandIO, CommandManager, CompletionSource},
+    buffer_view::{BufferView, CursorMovement, CursorMovementKind},
+    client::{ClientHandle, ViewAnchor, ViewScroll},
+    command::{CommandError, CommandFlag, CommandIO, CommandManager, CompletionSource},
     config::{ParseConfigError, CONFIG_NAMES},
     cursor::Cursor,
     editor::{EditorContext, EditorFlow},
     editor_utils::{
-        parse_path_and_ranges, parse_process_command, validate_process_command, LogKind,
-        RegisterKey, REGISTER_READLINE_INPUT,
+        align_table_row, ascii_equivalent, eval_math, expand_debug_print_template,
+        find_ordered_list_item_number, format_utc_date_time, invisible_char_name,
+        measure_table_row, open_file_for_writing, parse_path_and_ranges, parse_process_command,
+        parse_unicode_code_points, retab_line, split_on_delimiter, validate_process_command,
+        write_markdown_toc, LogKind, RegisterKey, MARKDOWN_TOC_BEGIN_MARKER,
+        MARKDOWN_TOC_END_MARKER, REGISTER_READLINE_INPUT, REGISTER_READLINE_PROMPT,
     },
     events::BufferEditMutGuard,
-    help,
-    mode::{picker, readline, ModeKind},
+    git::{self, GitIndexOperation},
+    glob::Glob,
+    help, json,
+    mode::{insert, picker, readline, ModeKind},
+    navigation_history::NavigationHistory,
     platform::{PlatformRequest, ProcessTag},
-    syntax::TokenKind,
+    quickfix,
+    syntax::{HighlightResult, TokenKind},
     theme::{Color, THEME_COLOR_NAMES},
+    ui,
     word_database::{WordIndicesIter, WordKind},
 };
 
@@ -26,37 +48,115 @@ pub fn register_commands(commands: &mut CommandManager) {
     };
 
     r("help", &[CompletionSource::HelpPages], |ctx, io| {
-        let help_page_name = io.args.try_next().unwrap_or("");
-        io.args.assert_empty()?;
+        fn open_command_help(
+            ctx: &mut EditorContext,
+            client_handle: ClientHandle,
+            name: &str,
+        ) -> Result<(), CommandError> {
+            use std::fmt::Write;
 
-        let client_handle = io.client_handle()?;
+            let mut text = ctx.editor.string_pool.acquire();
+            let _ = writeln!(text, "# `{}`", name);
+            if let Some(source) = ctx.editor.commands.macros.find(name) {
+                let _ = write!(text, "macro command defined as:\n```\n{}\n```\n", source);
+            } else if let Some(command) = ctx.editor.commands.find_command(name) {
+                if command.plugin_handle().is_some() {
+                    text.push_str("command registered by a plugin\n");
+                } else {
+                    text.push_str("builtin command\n");
+                }
+                for flag in command.flags {
+                    let _ = writeln!(text, "- accepts flag `{}`", flag.name);
+                }
+                for (i, completion) in command.completions.iter().enumerate() {
+                    let _ = write!(text, "- argument {} completes ", i + 1);
+                    match completion {
+                        CompletionSource::Commands => text.push_str("command names"),
+                        CompletionSource::Expansions => text.push_str("expansion names"),
+                        CompletionSource::Buffers => text.push_str("buffer paths"),
+                        CompletionSource::Files => text.push_str("file paths"),
+                        CompletionSource::HelpPages => text.push_str("help pages"),
+                        CompletionSource::Flags(_) => text.push_str("flags"),
+                        CompletionSource::Custom(values) => {
+                            for (i, value) in values.iter().enumerate() {
+                                if i > 0 {
+                                    text.push_str(", ");
+                                }
+                                let _ = write!(text, "`{}`", value);
+                            }
+                        }
+                    }
+                    text.push('\n');
+                }
+            }
+            text.push_str("\nthere is no documentation for this command in the help pages\n");
+
+            let mut buffer_path = ctx.editor.string_pool.acquire();
+            let _ = write!(buffer_path, "help-{}.md", name);
+            let result = ctx.editor.buffer_view_handle_from_path(
+                client_handle,
+                Path::new(&buffer_path),
+                BufferProperties::scratch(),
+                true,
+            );
+            ctx.editor.string_pool.release(buffer_path);
+            let handle = match result {
+                Ok(handle) => handle,
+                Err(error) => {
+                    ctx.editor.string_pool.release(text);
+                    return Err(CommandError::BufferReadError(error));
+                }
+            };
 
-        let mut buffer_path = ctx.editor.string_pool.acquire();
-        buffer_path.push_str(help::HELP_PREFIX);
-        buffer_path.push_str(help_page_name);
+            let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer_handle);
+            let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                BufferPosition::zero(),
+                &text,
+                events.to_text_inserts(),
+            );
+            std::mem::drop(events);
+            buffer.commit_edits();
+            ctx.editor.string_pool.release(text);
 
-        let buffer_properties = BufferProperties {
-            history_enabled: false,
-            saving_enabled: false,
-            file_backed_enabled: true,
-            word_database_enabled: false,
-        };
+            let client = ctx.clients.get_mut(client_handle);
+            client.set_buffer_view_handle(Some(handle), &ctx.editor.buffer_views);
+            Ok(())
+        }
 
-        let result = ctx.editor.buffer_view_handle_from_path(
-            client_handle,
-            Path::new(&buffer_path),
-            buffer_properties,
-            true,
-        );
-        ctx.editor.string_pool.release(buffer_path);
-        let handle = result.map_err(CommandError::BufferReadError)?;
+        let keyword = io.args.try_next().unwrap_or("");
+        io.args.assert_empty()?;
 
-        let client = ctx.clients.get_mut(client_handle);
-        client.set_buffer_view_handle(Some(handle), &ctx.editor.buffer_views);
+        let client_handle = io.client_handle()?;
 
-        let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
-        cursors.clear();
+        let mut page_name = keyword;
+        let mut line_index = 0;
+        if !keyword.is_empty() && !help::help_page_names().any(|n| n == keyword) {
+            if let Some((name, index, _)) = help::help_topics().find(|&(_, _, t)| t == keyword) {
+                page_name = name;
+                line_index = index;
+            } else if ctx.editor.commands.find_command(keyword).is_some()
+                || ctx.editor.commands.macros.find(keyword).is_some()
+            {
+                return open_command_help(ctx, client_handle, keyword);
+            }
+        }
 
+        help::open_page(ctx, client_handle, page_name, line_index)
+            .map_err(CommandError::BufferReadError)
+    });
+
+    r("help-topics", &[], |ctx, io| {
+        io.args.assert_empty()?;
+        picker::help_topics::enter_mode(ctx);
         Ok(())
     });
 
@@ -85,6 +185,32 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
+    static ECHO_COMPLETIONS: &[CompletionSource] = &[CompletionSource::Flags(&[
+        CommandFlag::switch("-error"),
+        CommandFlag::switch("-info"),
+    ])];
+    r("echo", ECHO_COMPLETIONS, |ctx, io| {
+        let mut arg = io.args.try_next();
+        let log_kind = match arg {
+            Some("-error") => LogKind::Error,
+            Some("-info") => LogKind::Info,
+            _ => LogKind::Status,
+        };
+        if let Some("-error" | "-info") = arg {
+            arg = io.args.try_next();
+        }
+
+        let mut write = ctx.editor.logger.write(log_kind);
+        if let Some(arg) = arg {
+            write.str(arg);
+        }
+        while let Some(arg) = io.args.try_next() {
+            write.str(" ");
+            write.str(arg);
+        }
+        Ok(())
+    });
+
     r("open-log", &[], |ctx, io| {
         io.args.assert_empty()?;
 
@@ -128,6 +254,25 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
+    r("stats", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        if !ctx.editor.config.session_stats {
+            ctx.editor
+                .logger
+                .write(LogKind::Status)
+                .str("session stats are disabled (see the `session_stats` config)");
+            return Ok(());
+        }
+
+        let stats = &ctx.editor.session_stats;
+        ctx.editor.logger.write(LogKind::Status).fmt(format_args!(
+            "keys: {}\ncommands: {}\nbuffers opened: {}",
+            stats.key_count, stats.command_count, stats.buffer_open_count,
+        ));
+        Ok(())
+    });
+
     r("quit", &[], |ctx, io| {
         io.args.assert_empty()?;
         if ctx.clients.iter().count() == 1 {
@@ -154,6 +299,7 @@ pub fn register_commands(commands: &mut CommandManager) {
                 "scratch" => properties = BufferProperties::scratch(),
                 "log" => properties = BufferProperties::log(),
                 "output" => properties = BufferProperties::output(),
+                "tail" => properties = BufferProperties::tail(),
                 "history-enabled" => properties.history_enabled = true,
                 "history-disabled" => properties.history_enabled = false,
                 "saving-enabled" => properties.saving_enabled = true,
@@ -162,6 +308,8 @@ pub fn register_commands(commands: &mut CommandManager) {
                 "file-backed-disabled" => properties.file_backed_enabled = false,
                 "word-database-enabled" => properties.word_database_enabled = true,
                 "word-database-disabled" => properties.word_database_enabled = false,
+                "tail-enabled" => properties.tail_enabled = true,
+                "tail-disabled" => properties.tail_enabled = false,
                 _ => return Err(CommandError::NoSuchBufferProperty),
             }
             path = arg;
@@ -197,21 +345,346 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
-    r("save", &[CompletionSource::Files], |ctx, io| {
-        let path = io.args.try_next().map(|p| Path::new(p));
+    r("related-file-rule", &[], |ctx, io| {
+        let suffix = io.args.next()?;
+        let related_suffix = io.args.next()?;
+        io.args.assert_empty()?;
+
+        if suffix.is_empty() || related_suffix.is_empty() {
+            return Err(CommandError::OtherStatic("invalid related file rule"));
+        }
+        ctx.editor.related_file_rules.add(suffix, related_suffix);
+        Ok(())
+    });
+
+    r("toggle-related", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let path = ctx
+            .editor
+            .buffers
+            .get(buffer_handle)
+            .path
+            .to_str()
+            .ok_or(CommandError::OtherStatic("no related file found"))?;
+
+        ctx.editor.picker.clear();
+        let mut related_path = ctx.editor.string_pool.acquire();
+        let current_directory = &ctx.editor.current_directory;
+        let picker = &mut ctx.editor.picker;
+        let mut count = 0;
+        ctx.editor.related_file_rules.for_each_related_path(
+            path,
+            &mut related_path,
+            |related_path| {
+                if current_directory.join(related_path).is_file()
+                    && !picker.custom_entries().any(|e| e == related_path)
+                {
+                    picker.add_custom_entry(related_path);
+                    count += 1;
+                }
+            },
+        );
+        ctx.editor.string_pool.release(related_path);
+
+        match count {
+            0 => Err(CommandError::OtherStatic("no related file found")),
+            1 => {
+                let related_path = ctx.editor.picker.custom_entries().next().unwrap();
+                let related_path = ctx.editor.string_pool.acquire_with(related_path);
+                ctx.editor.picker.clear();
+
+                let result = ctx.editor.buffer_view_handle_from_path(
+                    client_handle,
+                    Path::new(&related_path),
+                    BufferProperties::text(),
+                    false,
+                );
+                ctx.editor.string_pool.release(related_path);
+                let handle = result.map_err(CommandError::BufferReadError)?;
+
+                let client = ctx.clients.get_mut(client_handle);
+                client.set_buffer_view_handle(Some(handle), &ctx.editor.buffer_views);
+                Ok(())
+            }
+            _ => {
+                ctx.editor
+                    .registers
+                    .set(REGISTER_READLINE_PROMPT, "related:");
+                ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+                ctx.editor.picker.move_cursor(0);
+                picker::custom::enter_mode(ctx, "open \"@picker-entry()\"");
+                Ok(())
+            }
+        }
+    });
+
+    r("browse", &[CompletionSource::Files], |ctx, io| {
+        let directory = match io.args.try_next() {
+            Some(directory) => PathBuf::from(directory),
+            None => match io.current_buffer_handle(ctx) {
+                Ok(buffer_handle) => {
+                    let buffer = ctx.editor.buffers.get(buffer_handle);
+                    buffer
+                        .path
+                        .parent()
+                        .map(Path::to_path_buf)
+                        .unwrap_or_default()
+                }
+                Err(_) => PathBuf::new(),
+            },
+        };
+        io.args.assert_empty()?;
+
+        picker::browse::enter_mode(ctx, &directory);
+        Ok(())
+    });
+
+    fn create_parent_directories(path: &Path) -> Result<Option<&Path>, CommandError> {
+        let parent = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => return Ok(None),
+        };
+        let created_directory = match parent.ancestors().take_while(|p| !p.exists()).last() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory,
+            _ => return Ok(None),
+        };
+
+        match fs::create_dir_all(parent) {
+            Ok(()) => Ok(Some(created_directory)),
+            Err(error) => Err(CommandError::OtherOwned(format!(
+                "could not create directory {:?}: {}",
+                parent, error
+            ))),
+        }
+    }
+
+    static SAVE_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Flags(&[
+            CommandFlag::switch("-append"),
+            CommandFlag::switch("-mkdir"),
+        ]),
+        CompletionSource::Files,
+    ];
+    r("save", SAVE_COMPLETIONS, |ctx, io| {
+        let mut append = false;
+        let mut mkdir = false;
+        let mut path = None;
+        while let Some(arg) = io.args.try_next() {
+            match arg {
+                "-append" => append = true,
+                "-mkdir" => mkdir = true,
+                _ => {
+                    path = Some(Path::new(arg));
+                    break;
+                }
+            }
+        }
         io.args.assert_empty()?;
+        if append && path.is_none() {
+            return Err(CommandError::TooFewArguments);
+        }
 
         let buffer_handle = io.current_buffer_handle(ctx)?;
         let buffer = ctx.editor.buffers.get_mut(buffer_handle);
 
-        buffer
-            .write_to_file(path, ctx.editor.events.writer())
-            .map_err(CommandError::BufferWriteError)?;
+        let created_directory = if mkdir {
+            create_parent_directories(path.unwrap_or(&buffer.path))?.map(Path::to_path_buf)
+        } else {
+            None
+        };
+
+        let mut write = if let (true, Some(path)) = (append, path) {
+            buffer
+                .append_to_file(path)
+                .map_err(CommandError::BufferWriteError)?;
+
+            let mut write = ctx.editor.logger.write(LogKind::Status);
+            write.fmt(format_args!("buffer appended to {:?}", path));
+            write
+        } else {
+            buffer
+                .write_to_file(path, ctx.editor.events.writer())
+                .map_err(CommandError::BufferWriteError)?;
+
+            let mut write = ctx.editor.logger.write(LogKind::Status);
+            write.fmt(format_args!("buffer saved to {:?}", &buffer.path));
+            write
+        };
+
+        if let Some(directory) = created_directory {
+            write.fmt(format_args!(" (created directory {:?})", directory));
+        }
+        Ok(())
+    });
+
+    static WRITE_RANGE_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Flags(&[CommandFlag::switch("-append")]),
+        CompletionSource::Files,
+    ];
+    r("write-range", WRITE_RANGE_COMPLETIONS, |ctx, io| {
+        let mut path = io.args.next()?;
+        let append = path == "-append";
+        if append {
+            path = io.args.next()?;
+        }
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        if buffer_view.cursors[..]
+            .iter()
+            .all(|cursor| cursor.anchor == cursor.position)
+        {
+            return Err(CommandError::OtherStatic("no selection"));
+        }
+
+        let content = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+        let mut line_ranges = Vec::new();
+        selected_line_ranges(buffer_view, content, &mut line_ranges);
+
+        let write_lines = || -> io::Result<usize> {
+            let file = open_file_for_writing(Path::new(path), append)?;
+            let mut file = io::BufWriter::new(file);
+            // the byte order mark only belongs at the start of a file
+            let starts_at_first_line = matches!(line_ranges.first(), Some(&(0, _)));
+            if content.has_byte_order_mark() && starts_at_first_line && !append {
+                file.write_all(BYTE_ORDER_MARK)?;
+            }
+            let line_ending = content.line_ending().as_str();
+            let mut line_count = 0;
+            for &(from_line_index, to_line_index) in &line_ranges {
+                let to_line_len = content.lines()[to_line_index as usize].as_str().len();
+                let range = BufferRange::between(
+                    BufferPosition::line_col(from_line_index, 0),
+                    BufferPosition::line_col(to_line_index, to_line_len as _),
+                );
+                for (line, range) in content.lines_in_range(range) {
+                    file.write_all(line.as_str()[range].as_bytes())?;
+                    file.write_all(line_ending.as_bytes())?;
+                    line_count += 1;
+                }
+            }
+            file.flush()?;
+            Ok(line_count)
+        };
+        let line_count = write_lines()
+            .map_err(|error| CommandError::BufferWriteError(BufferWriteError::from(error)))?;
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("{} lines written to {:?}", line_count, path));
+        Ok(())
+    });
+
+    r("export-html", &[CompletionSource::Files], |ctx, io| {
+        use std::fmt::Write;
+
+        let path = io.args.try_next();
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_handle = ctx
+            .editor
+            .buffer_views
+            .get(buffer_view_handle)
+            .buffer_handle;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        while let HighlightResult::Pending = buffer.update_highlighting(&ctx.editor.syntaxes) {}
+
+        let mut path_buf = String::new();
+        let path = match path {
+            Some(path) => path,
+            None => {
+                let buffer_path = buffer.path.to_str().unwrap_or("");
+                if buffer_path.is_empty() {
+                    return Err(CommandError::BufferWriteError(
+                        BufferWriteError::CouldNotWriteToFile,
+                    ));
+                }
+                path_buf.push_str(buffer_path);
+                path_buf.push_str(".html");
+                &path_buf
+            }
+        };
+
+        let theme = &ctx.editor.theme;
+        let title = buffer
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("");
+
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>");
+        push_html_escaped(&mut html, title);
+        html.push_str("</title>\n</head>\n<body>\n");
+        let _ = write!(
+            html,
+            "<pre style=\"background-color:#{:06x};color:#{:06x}\">",
+            theme.normal_background.into_u32(),
+            theme.token_text.into_u32(),
+        );
+        for (line_index, line) in buffer.content().lines().iter().enumerate() {
+            buffer
+                .highlighted()
+                .for_each_line_span(line_index, line.as_str(), |kind, text| {
+                    if let TokenKind::Text | TokenKind::Whitespace = kind {
+                        push_html_escaped(&mut html, text);
+                    } else {
+                        let color = theme.token_color(kind).into_u32();
+                        let _ = write!(html, "<span style=\"color:#{:06x}\">", color);
+                        push_html_escaped(&mut html, text);
+                        html.push_str("</span>");
+                    }
+                });
+            html.push('\n');
+        }
+        html.push_str("</pre>\n</body>\n</html>\n");
+
+        fs::write(path, html)
+            .map_err(|error| CommandError::BufferWriteError(BufferWriteError::from(error)))?;
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("exported html to {:?}", path));
+        Ok(())
+    });
+
+    r("rename-file", &[CompletionSource::Files], |ctx, io| {
+        let new_path = Path::new(io.args.next()?);
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+
+        if !io.bang && new_path.exists() {
+            return Err(CommandError::OtherStatic("path already exists"));
+        }
+        if buffer.path.is_file() {
+            fs::rename(&buffer.path, new_path)
+                .map_err(|_| CommandError::OtherStatic("could not rename file"))?;
+        }
+
+        let mut old_path = ctx.editor.string_pool.acquire();
+        if let Some(path) = buffer.path.to_str() {
+            old_path.push_str(path);
+        }
+        buffer.set_path(new_path);
+        ctx.editor
+            .events
+            .writer()
+            .enqueue_buffer_rename(buffer_handle, &old_path);
 
         ctx.editor
             .logger
             .write(LogKind::Status)
-            .fmt(format_args!("buffer saved to {:?}", &buffer.path));
+            .fmt(format_args!("renamed {:?} to {:?}", &old_path, new_path));
+        ctx.editor.string_pool.release(old_path);
         Ok(())
     });
 
@@ -221,9 +694,9 @@ pub fn register_commands(commands: &mut CommandManager) {
         let mut count = 0;
         let mut maybe_error = None;
         for buffer in ctx.editor.buffers.iter_mut() {
-            match buffer.write_to_file(None, ctx.editor.events.writer()) {
-                Ok(()) => count += 1,
-                Err(BufferWriteError::SavingDisabled) => (),
+            match buffer.write_to_file_if_changed(ctx.editor.events.writer()) {
+                Ok(true) => count += 1,
+                Ok(false) | Err(BufferWriteError::SavingDisabled) => (),
                 Err(error) => maybe_error = Some(CommandError::BufferWriteError(error)),
             }
         }
@@ -303,6 +776,32 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
+    r("delete-file", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get(buffer_handle);
+        if !buffer.properties.saving_enabled || !buffer.properties.file_backed_enabled {
+            return Err(CommandError::OtherStatic("buffer is not backed by a file"));
+        }
+        if !io.bang {
+            return Err(CommandError::OtherStatic("use '!' to confirm"));
+        }
+
+        fs::remove_file(&buffer.path)
+            .map_err(|_| CommandError::OtherStatic("could not delete file"))?;
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("deleted {:?}", &buffer.path));
+
+        ctx.editor
+            .buffers
+            .defer_remove(buffer_handle, ctx.editor.events.writer());
+
+        Ok(())
+    });
+
     r("close-all", &[], |ctx, io| {
         io.args.assert_empty()?;
 
@@ -398,6 +897,16 @@ pub fn register_commands(commands: &mut CommandManager) {
         }
     });
 
+    r("set-leader", &[], |ctx, io| {
+        let leader = io.args.next()?;
+        io.args.assert_empty()?;
+
+        match ctx.editor.keymaps.set_leader(leader) {
+            Ok(()) => Ok(()),
+            Err(error) => Err(CommandError::KeyMapError(error)),
+        }
+    });
+
     static SYNTAX_COMPLETIONS: &[CompletionSource] = &[CompletionSource::Custom(&[
         "keywords", "types", "symbols", "literals", "strings", "comments", "texts",
     ])];
@@ -534,17 +1043,9 @@ pub fn register_commands(commands: &mut CommandManager) {
         io.args.assert_empty()?;
 
         let client_handle = io.client_handle()?;
-        let buffer_view_handle = ctx
-            .editor
-            .buffer_view_handle_from_path(
-                client_handle,
-                Path::new("lints.refs"),
-                BufferProperties::scratch(),
-                true,
-            )
-            .map_err(CommandError::BufferReadError)?;
 
-        let mut content = ctx.editor.string_pool.acquire();
+        let quickfix = &mut ctx.editor.quickfix;
+        quickfix.clear("lints");
         for buffer in ctx.editor.buffers.iter() {
             let buffer_path = match buffer.path.to_str() {
                 Some(path) => path,
@@ -552,51 +1053,68 @@ pub fn register_commands(commands: &mut CommandManager) {
             };
 
             for lint in buffer.lints.all() {
-                use std::fmt::Write;
-
                 let lint_message = lint.message(&buffer.lints);
-                let _ = write!(
-                    content,
-                    "{}:{}:{}\n",
-                    buffer_path, lint.range.from, lint_message
-                );
+                quickfix.add(buffer_path, lint.range.from, lint_message);
             }
         }
-        if content.ends_with('\n') {
-            content.pop();
-        }
 
-        let buffer_handle = ctx
-            .editor
-            .buffer_views
-            .get(buffer_view_handle)
-            .buffer_handle;
-        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
-        let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
-        buffer.delete_range(
-            &mut ctx.editor.word_database,
-            range,
-            &mut ctx
-                .editor
-                .events
-                .writer()
-                .buffer_range_deletes_mut_guard(buffer_handle),
-        );
-        buffer.insert_text(
-            &mut ctx.editor.word_database,
-            BufferPosition::zero(),
-            &content,
-            &mut ctx
-                .editor
-                .events
-                .writer()
-                .buffer_text_inserts_mut_guard(buffer_handle),
-        );
+        quickfix::show(ctx, client_handle).map_err(CommandError::BufferReadError)?;
+        Ok(())
+    });
 
-        ctx.editor.string_pool.release(content);
+    r("list-todos", &[], |ctx, io| {
+        io.args.assert_empty()?;
 
-        let client = ctx.clients.get_mut(client_handle);
-        client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+        let client_handle = io.client_handle()?;
+        let buffer_handle = if io.bang {
+            None
+        } else {
+            Some(io.current_buffer_handle(ctx)?)
+        };
+
+        let markers = &ctx.editor.config.todo_markers;
+        let quickfix = &mut ctx.editor.quickfix;
+        quickfix.clear("todos");
+        for buffer in ctx.editor.buffers.iter() {
+            if buffer_handle.map(|h| h != buffer.handle()).unwrap_or(false)
+                || !buffer.properties.file_backed_enabled
+            {
+                continue;
+            }
+            let buffer_path = match buffer.path.to_str() {
+                Some(path) => path,
+                None => continue,
+            };
+
+            let lines = buffer.content().lines();
+            let mut add_todos = |line_index: usize, range: Range<usize>| {
+                let text = &lines[line_index].as_str()[range.clone()];
+                if let Some(index) = find_todo_marker(text, markers) {
+                    let position =
+                        BufferPosition::line_col(line_index as _, (range.start + index) as _);
+                    quickfix.add(buffer_path, position, text[index..].trim_end());
+                }
+            };
+
+            // only comments are searched when the buffer's syntax knows what they look like
+            let syntax = ctx.editor.syntaxes.get(buffer.syntax_handle());
+            if syntax.has_rule(TokenKind::Comment) {
+                syntax.for_each_comment(buffer.content(), add_todos);
+            } else {
+                for (line_index, line) in lines.iter().enumerate() {
+                    add_todos(line_index, 0..line.as_str().len());
+                }
+            }
+        }
+
+        if quickfix.is_empty() {
+            ctx.editor
+                .logger
+                .write(LogKind::Status)
+                .str("no todos found");
+            return Ok(());
+        }
+        quickfix::show(ctx, client_handle).map_err(CommandError::BufferReadError)?;
         Ok(())
     });
 
@@ -673,225 +1191,2460 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
-    r("copy-command", &[], |ctx, io| {
-        let command = io.args.next()?;
+    r("toggle-wrap", &[], |ctx, io| {
         io.args.assert_empty()?;
-        ctx.platform.copy_command.clear();
-        ctx.platform.copy_command.push_str(command);
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        let wrap_lines = !buffer.wraps_lines(ctx.editor.config.wrap_lines);
+        buffer.wrap_lines = Some(wrap_lines);
+
+        let message = if wrap_lines {
+            "line wrapping enabled"
+        } else {
+            "line wrapping disabled"
+        };
+        ctx.editor.logger.write(LogKind::Status).str(message);
         Ok(())
     });
 
-    r("paste-command", &[], |ctx, io| {
-        let command = io.args.next()?;
+    r(
+        "set-eol",
+        &[CompletionSource::Custom(&["lf", "crlf"])],
+        |ctx, io| {
+            let line_ending = io.args.try_next();
+            io.args.assert_empty()?;
+
+            let buffer_handle = io.current_buffer_handle(ctx)?;
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            match line_ending {
+                Some(line_ending) => {
+                    let line_ending = line_ending
+                        .parse()
+                        .map_err(|_| CommandError::OtherStatic("invalid set-eol argument"))?;
+                    buffer.set_line_ending(line_ending);
+                }
+                None => ctx
+                    .editor
+                    .logger
+                    .write(LogKind::Status)
+                    .fmt(format_args!("{}", buffer.content().line_ending())),
+            }
+            Ok(())
+        },
+    );
+
+    r(
+        "set-bom",
+        &[CompletionSource::Custom(&["on", "off"])],
+        |ctx, io| {
+            let byte_order_mark = io.args.try_next();
+            io.args.assert_empty()?;
+
+            let buffer_handle = io.current_buffer_handle(ctx)?;
+            let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+            match byte_order_mark {
+                Some("on") => buffer.set_byte_order_mark(true),
+                Some("off") => buffer.set_byte_order_mark(false),
+                Some(_) => return Err(CommandError::OtherStatic("invalid set-bom argument")),
+                None => {
+                    let message = if buffer.content().has_byte_order_mark() {
+                        "byte order mark on"
+                    } else {
+                        "byte order mark off"
+                    };
+                    ctx.editor.logger.write(LogKind::Status).str(message);
+                }
+            }
+            Ok(())
+        },
+    );
+
+    r("toggle-highlight", &[], |ctx, io| {
         io.args.assert_empty()?;
-        ctx.platform.paste_command.clear();
-        ctx.platform.paste_command.push_str(command);
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        let highlighting_enabled = !buffer.highlighting_enabled();
+        buffer.set_highlighting_enabled(highlighting_enabled);
+
+        let message = if highlighting_enabled {
+            "syntax highlighting enabled"
+        } else {
+            "syntax highlighting disabled"
+        };
+        ctx.editor.logger.write(LogKind::Status).str(message);
         Ok(())
     });
 
-    r("enqueue-keys", &[], |ctx, io| {
-        let keys = io.args.next()?;
+    r("toggle-spell-check", &[], |ctx, io| {
         io.args.assert_empty()?;
 
+        let buffer_handle = io.current_buffer_handle(ctx)?;
         ctx.editor
-            .buffered_keys
-            .parse(keys)
-            .map_err(|e| CommandError::KeyParseError(e.error))?;
+            .spell_checker
+            .set_check_glob(&ctx.editor.config.spell_check);
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        let spell_check = !buffer.spell_checks(&ctx.editor.spell_checker);
+        buffer.spell_check = Some(spell_check);
+        ctx.editor.update_misspellings();
+
+        let message = if spell_check {
+            "spell checking enabled"
+        } else {
+            "spell checking disabled"
+        };
+        ctx.editor.logger.write(LogKind::Status).str(message);
         Ok(())
     });
 
-    r("insert-text", &[], |ctx, io| {
-        let text = io.args.next()?;
+    r("git-blame", &[], |ctx, io| {
         io.args.assert_empty()?;
 
-        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
-        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
-        buffer_view.delete_text_in_cursor_ranges(
-            &mut ctx.editor.buffers,
-            &mut ctx.editor.word_database,
-            ctx.editor.events.writer(),
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        if !buffer.git_blame.is_empty() {
+            buffer.git_blame.clear();
+            return Ok(());
+        }
+        if !buffer.properties.file_backed_enabled || buffer.path.as_os_str().is_empty() {
+            return Err(CommandError::OtherStatic("buffer is not backed by a file"));
+        }
+        if buffer.needs_save() {
+            return Err(CommandError::UnsavedChanges);
+        }
+
+        buffer.git_blame.spawn_process(
+            &mut ctx.platform,
+            buffer_handle,
+            &ctx.editor.current_directory,
+            &buffer.path,
         );
+        Ok(())
+    });
 
-        ctx.trigger_event_handlers();
+    fn git_index_operation(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        operation: GitIndexOperation,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
 
-        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
-        buffer_view.insert_text_at_cursor_positions(
-            &mut ctx.editor.buffers,
-            &mut ctx.editor.word_database,
-            text,
-            ctx.editor.events.writer(),
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get(buffer_handle);
+        if !buffer.properties.file_backed_enabled || buffer.path.as_os_str().is_empty() {
+            return Err(CommandError::OtherStatic("buffer is not backed by a file"));
+        }
+        let path = ctx.editor.current_directory.join(&buffer.path);
+        if git::find_repository_root(&path).is_none() {
+            return Err(CommandError::OtherStatic("not inside a git repository"));
+        }
+
+        operation.spawn_process(
+            &mut ctx.platform,
+            buffer_handle,
+            &ctx.editor.current_directory,
+            &buffer.path,
         );
+        Ok(())
+    }
+
+    r("git-stage", &[], |ctx, io| {
+        git_index_operation(ctx, io, GitIndexOperation::Stage)
+    });
+    r("git-unstage", &[], |ctx, io| {
+        git_index_operation(ctx, io, GitIndexOperation::Unstage)
+    });
+
+    fn goto_quickfix_entry(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        forward: bool,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let quickfix = &ctx.editor.quickfix;
+        let len = quickfix.len();
+        if len == 0 {
+            return Err(CommandError::OtherStatic("no quickfix entries"));
+        }
+        let index = match (quickfix.current_index(), forward) {
+            (None, true) => 0,
+            (None, false) => len - 1,
+            (Some(index), true) => (index + 1) % len,
+            (Some(index), false) => (index + len - 1) % len,
+        };
+
+        quickfix::goto_entry(ctx, client_handle, index).map_err(CommandError::BufferReadError)
+    }
+
+    r("quickfix", &[], |ctx, io| {
+        io.args.assert_empty()?;
+        let client_handle = io.client_handle()?;
+        quickfix::show(ctx, client_handle).map_err(CommandError::BufferReadError)?;
+        Ok(())
+    });
+
+    fn filter_quickfix(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        keep_matches: bool,
+    ) -> Result<(), CommandError> {
+        let pattern = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        if let Err(error) = ctx.editor.aux_pattern.compile_searcher(pattern) {
+            return Err(CommandError::PatternError(error));
+        }
+
+        let pattern = &ctx.editor.aux_pattern;
+        let search_anchor = pattern.search_anchor();
+        let mut line = ctx.editor.string_pool.acquire();
+
+        let previous_len = ctx.editor.quickfix.len();
+        ctx.editor.quickfix.retain(|entry| {
+            use std::fmt::Write;
+
+            line.clear();
+            let _ = write!(line, "{}:{}:{}", entry.path, entry.position, entry.message);
+            let matches = pattern.match_indices(&line, search_anchor).next().is_some();
+            matches == keep_matches
+        });
+        let removed_count = previous_len - ctx.editor.quickfix.len();
+        ctx.editor.string_pool.release(line);
 
+        quickfix::show(ctx, client_handle).map_err(CommandError::BufferReadError)?;
         ctx.editor
-            .buffers
-            .get_mut(buffer_view.buffer_handle)
-            .commit_edits();
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("{} entries removed", removed_count));
         Ok(())
+    }
+
+    r("keep-quickfix", &[], |ctx, io| {
+        filter_quickfix(ctx, io, true)
+    });
+    r("remove-quickfix", &[], |ctx, io| {
+        filter_quickfix(ctx, io, false)
     });
 
-    fn change_case(
+    r("next-quickfix", &[], |ctx, io| {
+        goto_quickfix_entry(ctx, io, true)
+    });
+    r("prev-quickfix", &[], |ctx, io| {
+        goto_quickfix_entry(ctx, io, false)
+    });
+
+    fn goto_git_hunk(
         ctx: &mut EditorContext,
         io: &mut CommandIO,
-        to_lower: bool,
+        forward: bool,
     ) -> Result<(), CommandError> {
         io.args.assert_empty()?;
 
+        let client_handle = io.client_handle()?;
         let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+
         let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let line_index = buffer_view.cursors.main_cursor().position.line_index;
         let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        buffer.update_git_diff();
+
+        let hunks = buffer.git_diff.hunks();
+        let hunk = if forward {
+            hunks
+                .iter()
+                .find(|h| h.sign_line_range().0 > line_index)
+                .or(hunks.first())
+        } else {
+            hunks
+                .iter()
+                .rev()
+                .find(|h| h.sign_line_range().0 < line_index)
+                .or(hunks.last())
+        };
+        let line_index = match hunk {
+            Some(hunk) => hunk.sign_line_range().0,
+            None => return Err(CommandError::OtherStatic("no git hunk")),
+        };
 
-        let mut cursor_texts = ctx.editor.string_pool.acquire();
+        let client = ctx.clients.get_mut(client_handle);
+        NavigationHistory::save_snapshot(client, &ctx.editor.buffer_views);
 
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        let position = BufferPosition::line_col(line_index, 0);
         {
-            let mut events = ctx
-                .editor
-                .events
-                .writer()
-                .buffer_range_deletes_mut_guard(buffer.handle());
-            for cursor in buffer_view.cursors[..].iter().rev() {
-                let range = cursor.to_range();
-                for text in buffer.content().text_range(range) {
-                    cursor_texts.push_str(text);
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+
+        let client = ctx.clients.get(client_handle);
+        client.set_view_anchor(&mut ctx.editor, ViewAnchor::Center);
+        Ok(())
+    }
+
+    r("next-hunk", &[], |ctx, io| goto_git_hunk(ctx, io, true));
+    r("prev-hunk", &[], |ctx, io| goto_git_hunk(ctx, io, false));
+
+    r("revert-hunk", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let line_index = buffer_view.cursors.main_cursor().position.line_index;
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        buffer.update_git_diff();
+
+        let hunk = match buffer.git_diff.hunks().iter().find(|h| {
+            let (from, to) = h.sign_line_range();
+            from <= line_index && line_index < to
+        }) {
+            Some(&hunk) => hunk,
+            None => return Err(CommandError::OtherStatic("no git hunk")),
+        };
+
+        let mut text = ctx.editor.string_pool.acquire();
+        let (delete_range, insert_position) =
+            buffer
+                .git_diff
+                .revert_hunk_edit(buffer.content(), &hunk, &mut text);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+        if let Some(range) = delete_range {
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+        }
+        if !text.is_empty() {
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                insert_position,
+                &text,
+                events.to_text_inserts(),
+            );
+        }
+        std::mem::drop(events);
+        buffer.commit_edits();
+        buffer.update_git_diff();
+        ctx.editor.string_pool.release(text);
+        Ok(())
+    });
+
+    fn goto_misspelling(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        forward: bool,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        ctx.editor.update_misspellings();
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let position = buffer_view.cursors.main_cursor().position;
+        let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+
+        let misspellings = buffer.misspellings.ranges();
+        let misspelling = if forward {
+            misspellings
+                .iter()
+                .find(|r| r.from > position)
+                .or(misspellings.first())
+        } else {
+            misspellings
+                .iter()
+                .rev()
+                .find(|r| r.to < position)
+                .or(misspellings.last())
+        };
+        let range = match misspelling {
+            Some(&range) => range,
+            None => return Err(CommandError::OtherStatic("no misspelling")),
+        };
+
+        let client = ctx.clients.get_mut(client_handle);
+        NavigationHistory::save_snapshot(client, &ctx.editor.buffer_views);
+
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        {
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: range.from,
+                position: range.to,
+            });
+        }
+
+        let client = ctx.clients.get(client_handle);
+        client.set_view_anchor(&mut ctx.editor, ViewAnchor::Center);
+        Ok(())
+    }
+
+    r("next-misspelling", &[], |ctx, io| {
+        goto_misspelling(ctx, io, true)
+    });
+    r("prev-misspelling", &[], |ctx, io| {
+        goto_misspelling(ctx, io, false)
+    });
+
+    r("add-to-dictionary", &[], |ctx, io| {
+        let word = io.args.try_next();
+        io.args.assert_empty()?;
+
+        if ctx.editor.config.spell_user_dictionary.is_empty() {
+            return Err(CommandError::OtherStatic(
+                "no user dictionary (see spell_user_dictionary)",
+            ));
+        }
+
+        let mut text = ctx.editor.string_pool.acquire();
+        match word {
+            Some(word) => text.push_str(word),
+            None => {
+                let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+                let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+                let position = buffer_view.cursors.main_cursor().position;
+                let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+                let misspelling = buffer
+                    .misspellings
+                    .ranges()
+                    .iter()
+                    .find(|r| r.from <= position && position <= r.to);
+                match misspelling {
+                    Some(&range) => {
+                        for t in buffer.content().text_range(range) {
+                            text.push_str(t);
+                        }
+                    }
+                    None => {
+                        let word = buffer.content().word_at(position);
+                        if word.kind == WordKind::Identifier {
+                            text.push_str(word.text);
+                        }
+                    }
                 }
-                cursor_texts.push('\0');
-                buffer.delete_range(&mut ctx.editor.word_database, range, &mut events);
             }
         }
 
-        if to_lower {
-            cursor_texts.make_ascii_lowercase();
-        } else {
-            cursor_texts.make_ascii_uppercase();
+        let word = text.trim();
+        if word.is_empty() || word.contains(char::is_whitespace) {
+            ctx.editor.string_pool.release(text);
+            return Err(CommandError::OtherStatic("invalid dictionary word"));
         }
 
-        {
-            let mut cursor_texts_splits = cursor_texts.split_terminator('\0').rev();
-            let mut events = ctx
-                .editor
-                .events
-                .writer()
-                .buffer_text_inserts_mut_guard(buffer.handle());
-            for cursor in buffer_view.cursors[..].iter() {
-                let range = cursor.to_range();
-                let cursor_text = cursor_texts_splits.next().unwrap();
-                buffer.insert_text(
-                    &mut ctx.editor.word_database,
-                    range.from,
-                    cursor_text,
-                    &mut events,
-                );
+        ctx.editor.spell_checker.reload(
+            &ctx.editor.config.spell_dictionary,
+            &ctx.editor.config.spell_user_dictionary,
+            &ctx.editor.current_directory,
+            &mut ctx.editor.logger,
+        );
+        let result = ctx
+            .editor
+            .spell_checker
+            .append_to_user_dictionary(word, &ctx.editor.current_directory);
+        let result = match result {
+            Ok(()) => {
+                ctx.editor
+                    .logger
+                    .write(LogKind::Status)
+                    .fmt(format_args!("added '{}' to the dictionary", word));
+                Ok(())
+            }
+            Err(error) => Err(CommandError::OtherOwned(format!(
+                "could not write to '{}': {}",
+                ctx.editor.config.spell_user_dictionary, error
+            ))),
+        };
+        ctx.editor.string_pool.release(text);
+        result
+    });
+
+    fn set_view_anchor(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        anchor: ViewAnchor,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        io.current_buffer_view_handle(ctx)?;
+
+        let client = ctx.clients.get(client_handle);
+        client.set_view_anchor(&mut ctx.editor, anchor);
+        Ok(())
+    }
+
+    r("view-center", &[], |ctx, io| {
+        set_view_anchor(ctx, io, ViewAnchor::Center)
+    });
+    r("view-top", &[], |ctx, io| {
+        set_view_anchor(ctx, io, ViewAnchor::Top)
+    });
+    r("view-bottom", &[], |ctx, io| {
+        set_view_anchor(ctx, io, ViewAnchor::Bottom)
+    });
+
+    r("goto-percent", &[], |ctx, io| {
+        let percent: usize = io
+            .args
+            .next()?
+            .parse()
+            .map_err(|_| CommandError::OtherStatic("invalid goto-percent argument"))?;
+        io.args.assert_empty()?;
+        let percent = percent.min(100);
+
+        let client_handle = io.client_handle()?;
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+
+        let client = ctx.clients.get_mut(client_handle);
+        NavigationHistory::save_snapshot(client, &ctx.editor.buffer_views);
+
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        let lines = ctx
+            .editor
+            .buffers
+            .get(buffer_view.buffer_handle)
+            .content()
+            .lines();
+        let line_index = (percent * lines.len()).div_ceil(100);
+        let line_index = line_index.saturating_sub(1);
+        let line = lines[line_index].as_str();
+        let column_byte_index = line.len() - line.trim_start().len();
+
+        let position = BufferPosition::line_col(line_index as _, column_byte_index as _);
+        {
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+
+        let client = ctx.clients.get(client_handle);
+        client.set_view_anchor(&mut ctx.editor, ViewAnchor::Center);
+        Ok(())
+    });
+
+    r("goto-byte", &[], |ctx, io| {
+        let offset: usize = io
+            .args
+            .next()?
+            .parse()
+            .map_err(|_| CommandError::OtherStatic("invalid goto-byte argument"))?;
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+
+        let client = ctx.clients.get_mut(client_handle);
+        NavigationHistory::save_snapshot(client, &ctx.editor.buffer_views);
+
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        let position = ctx
+            .editor
+            .buffers
+            .get(buffer_view.buffer_handle)
+            .content()
+            .position_from_byte_offset(offset);
+        {
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+
+        let client = ctx.clients.get(client_handle);
+        client.set_view_anchor(&mut ctx.editor, ViewAnchor::Center);
+        Ok(())
+    });
+
+    r("goto-minimap-row", &[], |ctx, io| {
+        let row: usize = io
+            .args
+            .next()?
+            .parse()
+            .map_err(|_| CommandError::OtherStatic("invalid goto-minimap-row argument"))?;
+        io.args.assert_empty()?;
+        if row == 0 {
+            return Err(CommandError::OtherStatic(
+                "invalid goto-minimap-row argument",
+            ));
+        }
+
+        let client_handle = io.client_handle()?;
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+
+        let client = ctx.clients.get_mut(client_handle);
+        let height = client.viewport_size.1.saturating_sub(1) as usize;
+        NavigationHistory::save_snapshot(client, &ctx.editor.buffer_views);
+
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        let lines = ctx
+            .editor
+            .buffers
+            .get(buffer_view.buffer_handle)
+            .content()
+            .lines();
+        let lines_per_row = ui::minimap_lines_per_row(lines.len(), height);
+        let line_index = ((row - 1) * lines_per_row).min(lines.len() - 1);
+        let line = lines[line_index].as_str();
+        let column_byte_index = line.len() - line.trim_start().len();
+
+        let position = BufferPosition::line_col(line_index as _, column_byte_index as _);
+        {
+            let mut cursors = buffer_view.cursors.mut_guard();
+            cursors.clear();
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+
+        let client = ctx.clients.get(client_handle);
+        client.set_view_anchor(&mut ctx.editor, ViewAnchor::Center);
+        Ok(())
+    });
+
+    fn scroll_view(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        scroll: ViewScroll,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        io.current_buffer_view_handle(ctx)?;
+
+        let movement_kind = ctx.editor.mode.normal_state.movement_kind;
+        let client = ctx.clients.get(client_handle);
+        client.scroll_view(&mut ctx.editor, scroll, 1, !io.bang, movement_kind);
+        Ok(())
+    }
+
+    r("scroll-half-page-down", &[], |ctx, io| {
+        scroll_view(ctx, io, ViewScroll::HalfPageDown)
+    });
+    r("scroll-half-page-up", &[], |ctx, io| {
+        scroll_view(ctx, io, ViewScroll::HalfPageUp)
+    });
+    r("scroll-page-down", &[], |ctx, io| {
+        scroll_view(ctx, io, ViewScroll::PageDown)
+    });
+    r("scroll-page-up", &[], |ctx, io| {
+        scroll_view(ctx, io, ViewScroll::PageUp)
+    });
+
+    fn move_cursors(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        movement: CursorMovement,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let movement_kind = if io.bang {
+            CursorMovementKind::PositionOnly
+        } else {
+            CursorMovementKind::PositionAndAnchor
+        };
+        ctx.editor
+            .buffer_views
+            .get_mut(buffer_view_handle)
+            .move_cursors(&ctx.editor.buffers, movement, movement_kind);
+        Ok(())
+    }
+
+    r("move-to-matching-bracket", &[], |ctx, io| {
+        move_cursors(ctx, io, CursorMovement::MatchingBracket)
+    });
+
+    r("next-paragraph", &[], |ctx, io| {
+        move_cursors(ctx, io, CursorMovement::ParagraphsForward(1))
+    });
+    r("prev-paragraph", &[], |ctx, io| {
+        move_cursors(ctx, io, CursorMovement::ParagraphsBackward(1))
+    });
+
+    static SPLIT_SELECTION_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Custom(&["same", "end"])];
+    r(
+        "split-selection-into-cursors",
+        SPLIT_SELECTION_COMPLETIONS,
+        |ctx, io| {
+            let at_line_end = match io.args.try_next() {
+                None | Some("same") => false,
+                Some("end") => true,
+                Some(_) => {
+                    return Err(CommandError::OtherStatic(
+                        "invalid split-selection-into-cursors argument",
+                    ))
+                }
+            };
+            io.args.assert_empty()?;
+
+            let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+            let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+            let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
+            let tab_size = buffer.indentation_config(&ctx.editor.config).tab_size;
+            let tab_stops = &ctx.editor.config.tab_stops;
+            let buffer = buffer.content();
+
+            let mut cursors = buffer_view.cursors.mut_guard();
+            let cursor_count = cursors[..].len();
+            for i in 0..cursor_count {
+                let cursor = cursors[i];
+                let range = cursor.to_range();
+                if range.from.line_index == range.to.line_index {
+                    continue;
+                }
+
+                let position_line = buffer.lines()[cursor.position.line_index as usize].as_str();
+                let position_line = &position_line[..cursor.position.column_byte_index as usize];
+                let distance =
+                    tab_stops.line_len(position_line, DisplayLen::from(position_line), tab_size)
+                        as u32;
+
+                for line_index in range.from.line_index..=range.to.line_index {
+                    let line = buffer.lines()[line_index as usize].as_str();
+                    let column_byte_index = if at_line_end {
+                        line.len()
+                    } else {
+                        CharDisplayDistances::new(line, tab_size)
+                            .with_tab_stops(tab_stops)
+                            .find(|d| d.distance > distance)
+                            .map(|d| d.char_index as _)
+                            .unwrap_or(line.len())
+                    };
+                    let position = BufferPosition::line_col(line_index, column_byte_index as _);
+                    let cursor = Cursor {
+                        anchor: position,
+                        position,
+                    };
+
+                    if line_index == range.from.line_index {
+                        cursors[i] = cursor;
+                    } else {
+                        cursors.add(cursor);
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    );
+
+    r("merge-cursors-into-selection", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+
+        let mut cursors = buffer_view.cursors.mut_guard();
+        let mut range = cursors[0].to_range();
+        for cursor in &cursors[1..] {
+            let cursor_range = cursor.to_range();
+            range.from = range.from.min(cursor_range.from);
+            range.to = range.to.max(cursor_range.to);
+        }
+
+        let main_cursor = *cursors.main_cursor();
+        cursors.clear();
+        if main_cursor.position < main_cursor.anchor {
+            cursors.add(Cursor {
+                anchor: range.to,
+                position: range.from,
+            });
+        } else {
+            cursors.add(Cursor {
+                anchor: range.from,
+                position: range.to,
+            });
+        }
+
+        Ok(())
+    });
+
+    r("flip-selection", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        for cursor in &mut buffer_view.cursors.mut_guard()[..] {
+            std::mem::swap(&mut cursor.anchor, &mut cursor.position);
+        }
+
+        Ok(())
+    });
+
+    static COLLAPSE_SELECTION_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Custom(&["position", "anchor"])];
+    r(
+        "collapse-selection",
+        COLLAPSE_SELECTION_COMPLETIONS,
+        |ctx, io| {
+            let to_anchor = match io.args.try_next() {
+                None | Some("position") => false,
+                Some("anchor") => true,
+                Some(_) => {
+                    return Err(CommandError::OtherStatic(
+                        "invalid collapse-selection argument",
+                    ))
+                }
+            };
+            io.args.assert_empty()?;
+
+            let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+            let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+            for cursor in &mut buffer_view.cursors.mut_guard()[..] {
+                if to_anchor {
+                    cursor.position = cursor.anchor;
+                } else {
+                    cursor.anchor = cursor.position;
+                }
+            }
+
+            Ok(())
+        },
+    );
+
+    r("copy-command", &[], |ctx, io| {
+        let command = io.args.next()?;
+        io.args.assert_empty()?;
+        ctx.platform.copy_command.clear();
+        ctx.platform.copy_command.push_str(command);
+        Ok(())
+    });
+
+    r("paste-command", &[], |ctx, io| {
+        let command = io.args.next()?;
+        io.args.assert_empty()?;
+        ctx.platform.paste_command.clear();
+        ctx.platform.paste_command.push_str(command);
+        Ok(())
+    });
+
+    r("enqueue-keys", &[], |ctx, io| {
+        let keys = io.args.next()?;
+        io.args.assert_empty()?;
+
+        ctx.editor
+            .buffered_keys
+            .parse(keys)
+            .map_err(|e| CommandError::KeyParseError(e.error))?;
+        Ok(())
+    });
+
+    r("complete", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        ctx.editor.enter_mode(ModeKind::Insert);
+        insert::trigger_completion(ctx, client_handle, buffer_view_handle);
+
+        Ok(())
+    });
+
+    r("insert-text", &[], |ctx, io| {
+        let text = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.delete_text_in_cursor_ranges(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.trigger_event_handlers();
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.insert_text_at_cursor_positions(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            text,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.editor
+            .buffers
+            .get_mut(buffer_view.buffer_handle)
+            .commit_edits();
+        Ok(())
+    });
+
+    r("insert-unicode", &[], |ctx, io| {
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+
+        let mut text = ctx.editor.string_pool.acquire();
+        while let Some(arg) = io.args.try_next() {
+            if !parse_unicode_code_points(arg, &mut text) {
+                text.clear();
+                break;
+            }
+        }
+        if text.is_empty() {
+            ctx.editor.string_pool.release(text);
+            return Err(CommandError::OtherStatic("invalid unicode code point"));
+        }
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.delete_text_in_cursor_ranges(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.trigger_event_handlers();
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.insert_text_at_cursor_positions(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            &text,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.editor
+            .buffers
+            .get_mut(buffer_view.buffer_handle)
+            .commit_edits();
+        ctx.editor.string_pool.release(text);
+        Ok(())
+    });
+
+    r("replace-invisible-chars", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        let invisible_chars = &ctx.editor.config.invisible_chars;
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        // going backwards keeps the positions yet to be visited valid
+        let mut count = 0;
+        for line_index in (0..buffer.content().lines().len()).rev() {
+            let mut column_byte_index = buffer.content().lines()[line_index].as_str().len();
+            loop {
+                let line = &buffer.content().lines()[line_index].as_str()[..column_byte_index];
+                let (index, c) = match line.char_indices().next_back() {
+                    Some(char_index) => char_index,
+                    None => break,
+                };
+                column_byte_index = index;
+
+                let equivalent = match ascii_equivalent(c) {
+                    Some(equivalent) if invisible_chars.contains(c) => equivalent,
+                    _ => continue,
+                };
+                let position = BufferPosition::line_col(line_index as _, index as _);
+                let range = BufferRange::between(
+                    position,
+                    BufferPosition::line_col(line_index as _, (index + c.len_utf8()) as _),
+                );
+                buffer.delete_range(
+                    &mut ctx.editor.word_database,
+                    range,
+                    events.to_range_deletes(),
+                );
+                if !equivalent.is_empty() {
+                    buffer.insert_text(
+                        &mut ctx.editor.word_database,
+                        position,
+                        equivalent,
+                        events.to_text_inserts(),
+                    );
+                }
+                count += 1;
+            }
+        }
+
+        std::mem::drop(events);
+        buffer.commit_edits();
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("replaced {} invisible chars", count));
+        Ok(())
+    });
+
+    r("char-info", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let position = buffer_view.cursors.main_cursor().position;
+        let c = match ctx
+            .editor
+            .buffers
+            .get(buffer_view.buffer_handle)
+            .content()
+            .char_at(position)
+        {
+            Some(c) => c,
+            None => {
+                ctx.editor
+                    .logger
+                    .write(LogKind::Status)
+                    .str("end of buffer");
+                return Ok(());
+            }
+        };
+
+        let mut write = ctx.editor.logger.write(LogKind::Status);
+        write.fmt(format_args!("'{}' U+{:04X}", c.escape_debug(), c as u32));
+        if let Some(name) = invisible_char_name(c) {
+            write.fmt(format_args!(" {}", name));
+        }
+        write.str(" utf-8:");
+        for b in c.encode_utf8(&mut [0; 4]).bytes() {
+            write.fmt(format_args!(" {:02x}", b));
+        }
+        write.fmt(format_args!(" width: {}", char_display_len(c)));
+        Ok(())
+    });
+
+    r("insert-sequence", &[], |ctx, io| {
+        use std::fmt::Write;
+
+        fn parse_arg<T>(arg: Option<&str>, default: T) -> Result<T, CommandError>
+        where
+            T: std::str::FromStr,
+        {
+            match arg {
+                Some(arg) => arg
+                    .parse()
+                    .map_err(|_| CommandError::OtherStatic("invalid insert-sequence argument")),
+                None => Ok(default),
+            }
+        }
+
+        let start: i64 = parse_arg(io.args.try_next(), 1)?;
+        let step: i64 = parse_arg(io.args.try_next(), 1)?;
+        let width: usize = parse_arg(io.args.try_next(), 0)?;
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.delete_text_in_cursor_ranges(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.trigger_event_handlers();
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let mut events = ctx
+            .editor
+            .events
+            .writer()
+            .buffer_text_inserts_mut_guard(buffer.handle());
+
+        let mut text = ctx.editor.string_pool.acquire();
+        for (i, cursor) in buffer_view.cursors[..].iter().enumerate().rev() {
+            let number = start.wrapping_add(step.wrapping_mul(i as _));
+            text.clear();
+            let _ = write!(text, "{:01$}", number, width);
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                cursor.position,
+                &text,
+                &mut events,
+            );
+        }
+        ctx.editor.string_pool.release(text);
+
+        buffer.commit_edits();
+        Ok(())
+    });
+
+    const INSERT_FILE_MAX_LEN: u64 = 4 * 1024 * 1024;
+    r("insert-file", &[CompletionSource::Files], |ctx, io| {
+        let path = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+
+        // relative paths are relative to the current directory, same as the path completions
+        let path = ctx.editor.current_directory.join(path);
+        let metadata =
+            fs::metadata(&path).map_err(|error| CommandError::BufferReadError(error.into()))?;
+        if metadata.len() > INSERT_FILE_MAX_LEN {
+            return Err(CommandError::OtherStatic("file is too large to insert"));
+        }
+        let bytes = fs::read(&path).map_err(|error| CommandError::BufferReadError(error.into()))?;
+        let mut text = String::from_utf8(bytes)
+            .map_err(|_| CommandError::OtherStatic("file is not valid utf-8"))?;
+        if let Some(stripped) = text.strip_prefix('\u{feff}') {
+            text = stripped.into();
+        }
+        if text.contains('\r') {
+            text = text.replace("\r\n", "\n");
+        }
+
+        buffer_view.insert_text_at_cursor_positions(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            &text,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.editor
+            .buffers
+            .get_mut(buffer_view.buffer_handle)
+            .commit_edits();
+        Ok(())
+    });
+
+    static INSERT_DATE_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Custom(&["iso-date", "iso-datetime"])];
+    r("insert-date", INSERT_DATE_COMPLETIONS, |ctx, io| {
+        let format = match io.args.try_next() {
+            None | Some("iso-date") => "%Y-%m-%d",
+            Some("iso-datetime") => "%Y-%m-%dT%H:%M:%SZ",
+            Some(format) => format,
+        };
+        io.args.assert_empty()?;
+
+        let unix_seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut text = ctx.editor.string_pool.acquire();
+        format_utc_date_time(unix_seconds, format, &mut text);
+
+        let buffer_view_handle = match io.current_buffer_view_handle(ctx) {
+            Ok(handle) => handle,
+            Err(error) => {
+                ctx.editor.string_pool.release(text);
+                return Err(error);
+            }
+        };
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.delete_text_in_cursor_ranges(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            ctx.editor.events.writer(),
+        );
+
+        ctx.trigger_event_handlers();
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        buffer_view.insert_text_at_cursor_positions(
+            &mut ctx.editor.buffers,
+            &mut ctx.editor.word_database,
+            &text,
+            ctx.editor.events.writer(),
+        );
+        ctx.editor.string_pool.release(text);
+
+        ctx.editor
+            .buffers
+            .get_mut(buffer_view.buffer_handle)
+            .commit_edits();
+        Ok(())
+    });
+
+    fn change_case(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        to_lower: bool,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut cursor_texts = ctx.editor.string_pool.acquire();
+
+        {
+            let mut events = ctx
+                .editor
+                .events
+                .writer()
+                .buffer_range_deletes_mut_guard(buffer.handle());
+            for cursor in buffer_view.cursors[..].iter().rev() {
+                let range = cursor.to_range();
+                for text in buffer.content().text_range(range) {
+                    cursor_texts.push_str(text);
+                }
+                cursor_texts.push('\0');
+                buffer.delete_range(&mut ctx.editor.word_database, range, &mut events);
+            }
+        }
+
+        if to_lower {
+            cursor_texts.make_ascii_lowercase();
+        } else {
+            cursor_texts.make_ascii_uppercase();
+        }
+
+        {
+            let mut cursor_texts_splits = cursor_texts.split_terminator('\0').rev();
+            let mut events = ctx
+                .editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(buffer.handle());
+            for cursor in buffer_view.cursors[..].iter() {
+                let range = cursor.to_range();
+                let cursor_text = cursor_texts_splits.next().unwrap();
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.from,
+                    cursor_text,
+                    &mut events,
+                );
+            }
+        }
+
+        ctx.editor.string_pool.release(cursor_texts);
+        buffer.commit_edits();
+
+        {
+            let mut events = ctx
+                .editor
+                .events
+                .writer()
+                .fix_cursors_mut_guard(buffer_view_handle);
+            for &cursor in buffer_view.cursors[..].iter() {
+                events.add(cursor);
+            }
+        }
+
+        Ok(())
+    }
+
+    r("to-lowercase", &[], |ctx, io| change_case(ctx, io, true));
+    r("to-uppercase", &[], |ctx, io| change_case(ctx, io, false));
+
+    r("eval-math", &[], |ctx, io| {
+        use std::fmt::Write;
+
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut expression = ctx.editor.string_pool.acquire();
+        let mut result = ctx.editor.string_pool.acquire();
+        let mut errors = ctx.editor.string_pool.acquire();
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+        for (i, cursor) in buffer_view.cursors[..].iter().enumerate().rev() {
+            let range = cursor.to_range();
+            expression.clear();
+            for text in buffer.content().text_range(range) {
+                expression.push_str(text);
+            }
+
+            let value = match eval_math(&expression) {
+                Ok(value) => value,
+                Err(error) => {
+                    if !errors.is_empty() {
+                        errors.push('\n');
+                    }
+                    let _ = write!(errors, "cursor {}: {}", i, error);
+                    continue;
+                }
+            };
+
+            result.clear();
+            if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+                let _ = write!(result, "{}", value as i64);
+            } else {
+                let _ = write!(result, "{}", value);
+            }
+
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                &result,
+                events.to_text_inserts(),
+            );
+        }
+        std::mem::drop(events);
+
+        buffer.commit_edits();
+
+        if !errors.is_empty() {
+            ctx.editor
+                .logger
+                .write(LogKind::Error)
+                .fmt(format_args!("eval-math: {}", &errors));
+        }
+
+        ctx.editor.string_pool.release(expression);
+        ctx.editor.string_pool.release(result);
+        ctx.editor.string_pool.release(errors);
+
+        Ok(())
+    });
+
+    // reformats the text of each selection as json or the whole buffer when nothing is selected.
+    // nothing is changed if any of them is not valid json
+    fn reformat_json(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        format: &dyn Fn(&str, &mut Vec<u8>),
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut ranges: Vec<_> = buffer_view.cursors[..]
+            .iter()
+            .map(|c| c.to_range())
+            .filter(|r| r.from != r.to)
+            .collect();
+        if ranges.is_empty() {
+            ranges.push(BufferRange::between(
+                BufferPosition::zero(),
+                buffer.content().end(),
+            ));
+        }
+
+        let mut text = ctx.editor.string_pool.acquire();
+        let mut edits = Vec::with_capacity(ranges.len());
+        for range in ranges {
+            text.clear();
+            for t in buffer.content().text_range(range) {
+                text.push_str(t);
+            }
+
+            if let Err(offset) = json::validate(&text) {
+                let bytes = &text.as_bytes()[..offset];
+                let position = match bytes.iter().rposition(|&b| b == b'\n') {
+                    Some(i) => {
+                        let line_count = bytes.iter().filter(|&&b| b == b'\n').count();
+                        BufferPosition::line_col(
+                            range.from.line_index + line_count as BufferPositionIndex,
+                            (bytes.len() - i - 1) as _,
+                        )
+                    }
+                    None => BufferPosition::line_col(
+                        range.from.line_index,
+                        range.from.column_byte_index + bytes.len() as BufferPositionIndex,
+                    ),
+                };
+                ctx.editor.string_pool.release(text);
+                return Err(CommandError::OtherOwned(format!(
+                    "invalid json at {}",
+                    position
+                )));
+            }
+
+            let mut formatted = Vec::new();
+            format(&text, &mut formatted);
+            edits.push((range, String::from_utf8_lossy(&formatted).into_owned()));
+        }
+        ctx.editor.string_pool.release(text);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+        for (range, text) in edits.iter().rev() {
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                *range,
+                events.to_range_deletes(),
+            );
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                text,
+                events.to_text_inserts(),
+            );
+        }
+        std::mem::drop(events);
+
+        buffer.commit_edits();
+        Ok(())
+    }
+
+    r("pretty-print", &[], |ctx, io| {
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let indentation_config = ctx
+            .editor
+            .buffers
+            .get(buffer_handle)
+            .indentation_config(&ctx.editor.config);
+        let indent = if indentation_config.indent_with_tabs {
+            "\t".into()
+        } else {
+            " ".repeat(indentation_config.tab_size as _)
+        };
+        reformat_json(ctx, io, &|text, formatted| {
+            json::pretty_print(text, &indent, formatted)
+        })
+    });
+
+    r("minify-json", &[], |ctx, io| {
+        reformat_json(ctx, io, &json::minify)
+    });
+
+    static TOGGLE_COMMENT_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Custom(&[]),
+        CompletionSource::Custom(&["aligned", "zero"]),
+    ];
+    r("toggle-comment", TOGGLE_COMMENT_COMPLETIONS, |ctx, io| {
+        let comment_prefix = io.args.next()?;
+        let aligned = match io.args.try_next() {
+            None | Some("aligned") => true,
+            Some("zero") => false,
+            Some(_) => return Err(CommandError::OtherStatic("invalid toggle-comment argument")),
+        };
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        let mut all_lines_commented = true;
+
+        let mut previous_toggle_line_index = BufferPositionIndex::MAX;
+        'cursor_loop: for cursor in &buffer_view.cursors[..] {
+            let range = cursor.to_range();
+            let from_line_index = previous_toggle_line_index
+                .wrapping_add(1)
+                .max(range.from.line_index);
+            let to_line_index = range.to.line_index;
+            previous_toggle_line_index = to_line_index;
+
+            for line_index in from_line_index..=to_line_index {
+                let line = buffer.content().lines()[line_index as usize]
+                    .as_str()
+                    .trim_start();
+                if !line.is_empty() && !line.starts_with(comment_prefix) {
+                    all_lines_commented = false;
+                    break 'cursor_loop;
+                }
+            }
+        }
+
+        let mut previous_toggle_line_index = BufferPositionIndex::MAX;
+        for cursor in &buffer_view.cursors[..] {
+            let range = cursor.to_range();
+            let from_line_index = previous_toggle_line_index
+                .wrapping_add(1)
+                .max(range.from.line_index);
+            let to_line_index = range.to.line_index;
+            previous_toggle_line_index = to_line_index;
+
+            // the common indentation of the non blank lines, so prefixes are inserted aligned
+            let mut common_indentation_len = BufferPositionIndex::MAX;
+            if aligned {
+                for line_index in from_line_index..=to_line_index {
+                    let line = buffer.content().lines()[line_index as usize].as_str();
+                    let indentation_len = line.len() - line.trim_start().len();
+                    if indentation_len < line.len() {
+                        common_indentation_len = common_indentation_len.min(indentation_len as _);
+                    }
+                }
+            }
+
+            for line_index in from_line_index..=to_line_index {
+                let line = &buffer.content().lines()[line_index as usize];
+                let mut position = BufferPosition::line_col(line_index, 0);
+                let word = line.word_at(0);
+                if word.kind == WordKind::Whitespace {
+                    position.column_byte_index += word.text.len() as BufferPositionIndex;
+                }
+
+                let line_text = &line.as_str()[position.column_byte_index as usize..];
+                if !line_text.starts_with(comment_prefix) {
+                    if !line_text.is_empty() {
+                        let column_byte_index = if aligned { common_indentation_len } else { 0 };
+                        buffer.insert_text(
+                            &mut ctx.editor.word_database,
+                            BufferPosition::line_col(line_index, column_byte_index),
+                            comment_prefix,
+                            events.to_text_inserts(),
+                        );
+                    }
+                } else if all_lines_commented {
+                    let to_column_byte_index =
+                        position.column_byte_index + comment_prefix.len() as BufferPositionIndex;
+                    let range = BufferRange::between(
+                        position,
+                        BufferPosition::line_col(line_index, to_column_byte_index),
+                    );
+                    buffer.delete_range(
+                        &mut ctx.editor.word_database,
+                        range,
+                        events.to_range_deletes(),
+                    );
+                }
+            }
+        }
+
+        buffer.commit_edits();
+
+        Ok(())
+    });
+
+    r("debug-comment", &[], |ctx, io| {
+        let comment_prefix = io.args.next()?;
+        io.args.assert_empty()?;
+
+        if ctx.editor.config.debug_comment_marker.is_empty() {
+            return Err(CommandError::OtherStatic("debug_comment_marker is empty"));
+        }
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut prefix = ctx.editor.string_pool.acquire_with(comment_prefix);
+        prefix.push(' ');
+        prefix.push_str(&ctx.editor.config.debug_comment_marker);
+        prefix.push(' ');
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        let mut previous_line_index = BufferPositionIndex::MAX;
+        for cursor in &buffer_view.cursors[..] {
+            let range = cursor.to_range();
+            let from_line_index = previous_line_index
+                .wrapping_add(1)
+                .max(range.from.line_index);
+            let to_line_index = range.to.line_index;
+            previous_line_index = to_line_index;
+
+            let mut common_indentation_len = BufferPositionIndex::MAX;
+            for line_index in from_line_index..=to_line_index {
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                let indentation_len = line.len() - line.trim_start().len();
+                if indentation_len < line.len() {
+                    common_indentation_len = common_indentation_len.min(indentation_len as _);
+                }
+            }
+
+            for line_index in from_line_index..=to_line_index {
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                if line.trim_start().is_empty() {
+                    continue;
+                }
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    BufferPosition::line_col(line_index, common_indentation_len),
+                    &prefix,
+                    events.to_text_inserts(),
+                );
+            }
+        }
+
+        std::mem::drop(events);
+        buffer.commit_edits();
+        ctx.editor.string_pool.release(prefix);
+        Ok(())
+    });
+
+    r("remove-debug-comments", &[], |ctx, io| {
+        let comment_prefix = io.args.next()?;
+        io.args.assert_empty()?;
+
+        if ctx.editor.config.debug_comment_marker.is_empty() {
+            return Err(CommandError::OtherStatic("debug_comment_marker is empty"));
+        }
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+
+        let mut prefix = ctx.editor.string_pool.acquire_with(comment_prefix);
+        prefix.push(' ');
+        prefix.push_str(&ctx.editor.config.debug_comment_marker);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        // removing from the last line keeps the line indices of the ones before it valid
+        let mut count = 0;
+        for line_index in (0..buffer.content().lines().len()).rev() {
+            let line = buffer.content().lines()[line_index].as_str();
+            let indentation_len = line.len() - line.trim_start().len();
+            let rest = match line[indentation_len..].strip_prefix(&prefix[..]) {
+                Some(rest) => rest,
+                None => continue,
+            };
+            let len = prefix.len() + if rest.starts_with(' ') { 1 } else { 0 };
+
+            let range = BufferRange::between(
+                BufferPosition::line_col(line_index as _, indentation_len as _),
+                BufferPosition::line_col(line_index as _, (indentation_len + len) as _),
+            );
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+            count += 1;
+        }
+
+        std::mem::drop(events);
+        buffer.commit_edits();
+        ctx.editor.string_pool.release(prefix);
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("removed {} debug comments", count));
+        Ok(())
+    });
+
+    r("debug-print-rule", &[], |ctx, io| {
+        let glob = io.args.next()?;
+        let template = io.args.next()?;
+        io.args.assert_empty()?;
+
+        if !template.contains("$SEL") {
+            return Err(CommandError::OtherStatic(
+                "debug print template has no '$SEL'",
+            ));
+        }
+        ctx.editor
+            .debug_print_rules
+            .add(glob, template)
+            .map_err(CommandError::InvalidGlob)
+    });
+
+    r("debug-print", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let template = buffer
+            .path
+            .to_str()
+            .and_then(|path| ctx.editor.debug_print_rules.template_for(path))
+            .ok_or(CommandError::OtherStatic(
+                "no debug print rule for this buffer",
+            ))?;
+
+        let mut selection = ctx.editor.string_pool.acquire();
+        let mut text = ctx.editor.string_pool.acquire();
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        // going backwards keeps the positions of the cursors yet to be visited valid
+        for cursor in buffer_view.cursors[..].iter().rev() {
+            let content = buffer.content();
+            let mut range = cursor.to_range();
+            if range.from == range.to {
+                let word = content.word_at(range.from);
+                if word.kind != WordKind::Identifier {
+                    continue;
+                }
+                range = BufferRange::between(word.position, word.end_position());
+            }
+
+            selection.clear();
+            for text in content.text_range(range) {
+                selection.push_str(text);
+            }
+
+            let line = content.lines()[range.to.line_index as usize].as_str();
+            let indentation_len = line.len() - line.trim_start().len();
+            text.clear();
+            text.push('\n');
+            text.push_str(&line[..indentation_len]);
+            expand_debug_print_template(template, &selection, &mut text);
+
+            let position = BufferPosition::line_col(range.to.line_index, line.len() as _);
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                position,
+                &text,
+                events.to_text_inserts(),
+            );
+        }
+
+        std::mem::drop(events);
+        buffer.commit_edits();
+        ctx.editor.string_pool.release(selection);
+        ctx.editor.string_pool.release(text);
+        Ok(())
+    });
+
+    fn edit_lines_affix(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        is_prefix: bool,
+        add: bool,
+    ) -> Result<(), CommandError> {
+        let affix = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        // going backwards keeps the positions yet to be visited valid
+        let mut next_edit_line_index = BufferPositionIndex::MAX;
+        for cursor in buffer_view.cursors[..].iter().rev() {
+            if next_edit_line_index == 0 {
+                break;
+            }
+
+            let range = cursor.to_range();
+            let from_line_index = range.from.line_index;
+            let to_line_index = range.to.line_index.min(next_edit_line_index - 1);
+            if from_line_index > to_line_index {
+                continue;
+            }
+            next_edit_line_index = from_line_index;
+
+            for line_index in (from_line_index..=to_line_index).rev() {
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                let column_byte_index = if is_prefix { 0 } else { line.len() };
+
+                if add {
+                    buffer.insert_text(
+                        &mut ctx.editor.word_database,
+                        BufferPosition::line_col(line_index, column_byte_index as _),
+                        affix,
+                        events.to_text_inserts(),
+                    );
+                } else {
+                    let range = if is_prefix && line.starts_with(affix) {
+                        column_byte_index..column_byte_index + affix.len()
+                    } else if !is_prefix && line.ends_with(affix) {
+                        column_byte_index - affix.len()..column_byte_index
+                    } else {
+                        continue;
+                    };
+                    let range = BufferRange::between(
+                        BufferPosition::line_col(line_index, range.start as _),
+                        BufferPosition::line_col(line_index, range.end as _),
+                    );
+                    buffer.delete_range(
+                        &mut ctx.editor.word_database,
+                        range,
+                        events.to_range_deletes(),
+                    );
+                }
+            }
+        }
+
+        buffer.commit_edits();
+
+        Ok(())
+    }
+
+    r("prefix-lines", &[], |ctx, io| {
+        edit_lines_affix(ctx, io, true, true)
+    });
+    r("suffix-lines", &[], |ctx, io| {
+        edit_lines_affix(ctx, io, false, true)
+    });
+    r("strip-prefix", &[], |ctx, io| {
+        edit_lines_affix(ctx, io, true, false)
+    });
+    r("strip-suffix", &[], |ctx, io| {
+        edit_lines_affix(ctx, io, false, false)
+    });
+
+    fn selected_line_ranges(
+        buffer_view: &BufferView,
+        content: &BufferContent,
+        line_ranges: &mut Vec<(BufferPositionIndex, BufferPositionIndex)>,
+    ) {
+        let cursors = &buffer_view.cursors[..];
+        if let [cursor] = cursors {
+            if cursor.anchor == cursor.position {
+                let last_line_index = content.lines().len() - 1;
+                line_ranges.push((0, last_line_index as _));
+                return;
+            }
+        }
+
+        let mut previous_line_index = BufferPositionIndex::MAX;
+        for cursor in cursors {
+            let range = cursor.to_range();
+            let from_line_index = previous_line_index
+                .wrapping_add(1)
+                .max(range.from.line_index);
+            let to_line_index = range.to.line_index;
+            if from_line_index <= to_line_index {
+                line_ranges.push((from_line_index, to_line_index));
+                previous_line_index = to_line_index;
+            }
+        }
+    }
+
+    fn selected_line_ranges_without_trailing_empty_line(
+        buffer_view: &BufferView,
+        content: &BufferContent,
+        line_ranges: &mut Vec<(BufferPositionIndex, BufferPositionIndex)>,
+    ) {
+        selected_line_ranges(buffer_view, content, line_ranges);
+        if let [cursor] = &buffer_view.cursors[..] {
+            if cursor.anchor == cursor.position {
+                let lines = content.lines();
+                if let Some((_, to_line_index)) = line_ranges.last_mut() {
+                    if *to_line_index > 0 && lines[*to_line_index as usize].as_str().is_empty() {
+                        *to_line_index -= 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn whole_lines_range(
+        content: &BufferContent,
+        from_line_index: BufferPositionIndex,
+        to_line_index: BufferPositionIndex,
+    ) -> BufferRange {
+        let lines = content.lines();
+        if (to_line_index as usize) + 1 < lines.len() {
+            BufferRange::between(
+                BufferPosition::line_col(from_line_index, 0),
+                BufferPosition::line_col(to_line_index + 1, 0),
+            )
+        } else {
+            let to = BufferPosition::line_col(
+                to_line_index,
+                lines[to_line_index as usize].as_str().len() as _,
+            );
+            let from = match from_line_index.checked_sub(1) {
+                Some(line_index) => BufferPosition::line_col(
+                    line_index,
+                    lines[line_index as usize].as_str().len() as _,
+                ),
+                None => BufferPosition::zero(),
+            };
+            BufferRange::between(from, to)
+        }
+    }
+
+    fn filter_lines(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        keep_matches: bool,
+    ) -> Result<(), CommandError> {
+        let pattern = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        if let Err(error) = ctx.editor.aux_pattern.compile_searcher(pattern) {
+            return Err(CommandError::PatternError(error));
+        }
+
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut line_ranges = Vec::new();
+        selected_line_ranges(buffer_view, buffer.content(), &mut line_ranges);
+
+        let pattern = &ctx.editor.aux_pattern;
+        let search_anchor = pattern.search_anchor();
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+        let mut removed_count = 0;
+
+        for &(from_line_index, to_line_index) in line_ranges.iter().rev() {
+            let mut line_index = to_line_index + 1;
+            while line_index > from_line_index {
+                let run_to_line_index = line_index - 1;
+                while line_index > from_line_index {
+                    let line = buffer.content().lines()[line_index as usize - 1].as_str();
+                    let matches = pattern.match_indices(line, search_anchor).next().is_some();
+                    if matches == keep_matches {
+                        break;
+                    }
+                    line_index -= 1;
+                }
+
+                if line_index <= run_to_line_index {
+                    let range = whole_lines_range(buffer.content(), line_index, run_to_line_index);
+                    buffer.delete_range(
+                        &mut ctx.editor.word_database,
+                        range,
+                        events.to_range_deletes(),
+                    );
+                    removed_count += run_to_line_index - line_index + 1;
+                } else {
+                    line_index -= 1;
+                }
+            }
+        }
+
+        buffer.commit_edits();
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("{} lines removed", removed_count));
+        Ok(())
+    }
+
+    r("keep-lines", &[], |ctx, io| filter_lines(ctx, io, true));
+    r("remove-lines", &[], |ctx, io| filter_lines(ctx, io, false));
+
+    static UNIQ_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Custom(&["consecutive", "all"]),
+        CompletionSource::Custom(&["case-sensitive", "ignore-case"]),
+    ];
+    r("uniq", UNIQ_COMPLETIONS, |ctx, io| {
+        let all_duplicates = match io.args.try_next() {
+            None | Some("consecutive") => false,
+            Some("all") => true,
+            Some(_) => return Err(CommandError::OtherStatic("invalid uniq argument")),
+        };
+        let ignore_case = match io.args.try_next() {
+            None | Some("case-sensitive") => false,
+            Some("ignore-case") => true,
+            Some(_) => return Err(CommandError::OtherStatic("invalid uniq argument")),
+        };
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut line_ranges = Vec::new();
+        selected_line_ranges(buffer_view, buffer.content(), &mut line_ranges);
+
+        let mut seen_lines = HashSet::new();
+        let mut removed_ranges: Vec<(BufferPositionIndex, BufferPositionIndex)> = Vec::new();
+        let mut removed_count = 0;
+
+        for &(from_line_index, to_line_index) in &line_ranges {
+            let mut previous_line = None;
+            for line_index in from_line_index..=to_line_index {
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                let line = if ignore_case {
+                    line.to_lowercase()
+                } else {
+                    line.into()
+                };
+
+                let is_duplicate = if all_duplicates {
+                    !seen_lines.insert(line)
+                } else {
+                    let is_duplicate = previous_line.as_ref() == Some(&line);
+                    previous_line = Some(line);
+                    is_duplicate
+                };
+                if !is_duplicate {
+                    continue;
+                }
+
+                removed_count += 1;
+                match removed_ranges.last_mut() {
+                    Some((_, to)) if *to + 1 == line_index => *to = line_index,
+                    _ => removed_ranges.push((line_index, line_index)),
+                }
+            }
+        }
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+        for &(from_line_index, to_line_index) in removed_ranges.iter().rev() {
+            let range = whole_lines_range(buffer.content(), from_line_index, to_line_index);
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+        }
+        buffer.commit_edits();
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("{} lines removed", removed_count));
+        Ok(())
+    });
+
+    r("reverse-lines", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut line_ranges = Vec::new();
+        selected_line_ranges_without_trailing_empty_line(
+            buffer_view,
+            buffer.content(),
+            &mut line_ranges,
+        );
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+        let mut reversed_text = ctx.editor.string_pool.acquire();
+
+        for &(from_line_index, to_line_index) in line_ranges.iter().rev() {
+            if from_line_index == to_line_index {
+                continue;
+            }
+
+            let lines = buffer.content().lines();
+            reversed_text.clear();
+            for line_index in (from_line_index..=to_line_index).rev() {
+                reversed_text.push_str(lines[line_index as usize].as_str());
+                reversed_text.push('\n');
+            }
+            reversed_text.pop();
+
+            let range = BufferRange::between(
+                BufferPosition::line_col(from_line_index, 0),
+                BufferPosition::line_col(
+                    to_line_index,
+                    lines[to_line_index as usize].as_str().len() as _,
+                ),
+            );
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                &reversed_text,
+                events.to_text_inserts(),
+            );
+        }
+
+        ctx.editor.string_pool.release(reversed_text);
+        buffer.commit_edits();
+
+        Ok(())
+    });
+
+    r("renumber-list", &[], |ctx, io| {
+        use std::fmt::Write;
+
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        let mut number_text = ctx.editor.string_pool.acquire();
+        let mut levels: Vec<(usize, u32)> = Vec::new();
+
+        let mut previous_renumber_line_index = BufferPositionIndex::MAX;
+        for cursor in &buffer_view.cursors[..] {
+            let range = cursor.to_range();
+            if range.from.line_index > previous_renumber_line_index.wrapping_add(1) {
+                levels.clear();
+            }
+            let from_line_index = previous_renumber_line_index
+                .wrapping_add(1)
+                .max(range.from.line_index);
+            let to_line_index = range.to.line_index;
+            previous_renumber_line_index = to_line_index;
+
+            for line_index in from_line_index..=to_line_index {
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                let number_range = match find_ordered_list_item_number(line) {
+                    Some(range) => range,
+                    None => {
+                        if !line.trim().is_empty() {
+                            let indentation = line.len() - line.trim_start().len();
+                            while matches!(levels.last(), Some(&(i, _)) if i >= indentation) {
+                                levels.pop();
+                            }
+                        }
+                        continue;
+                    }
+                };
+
+                let indentation = number_range.start;
+                while matches!(levels.last(), Some(&(i, _)) if i > indentation) {
+                    levels.pop();
+                }
+                let number = match levels.last_mut() {
+                    Some((i, number)) if *i == indentation => {
+                        *number = number.saturating_add(1);
+                        *number
+                    }
+                    _ => {
+                        let number = line[number_range.clone()].parse().unwrap_or(1);
+                        levels.push((indentation, number));
+                        number
+                    }
+                };
+
+                number_text.clear();
+                let _ = write!(number_text, "{}", number);
+                if line[number_range.clone()] == number_text[..] {
+                    continue;
+                }
+
+                let range = BufferRange::between(
+                    BufferPosition::line_col(line_index, number_range.start as _),
+                    BufferPosition::line_col(line_index, number_range.end as _),
+                );
+                buffer.delete_range(
+                    &mut ctx.editor.word_database,
+                    range,
+                    events.to_range_deletes(),
+                );
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.from,
+                    &number_text,
+                    events.to_text_inserts(),
+                );
+            }
+        }
+
+        ctx.editor.string_pool.release(number_text);
+        buffer.commit_edits();
+
+        Ok(())
+    });
+
+    r("md-toc", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let cursor_line_index = buffer_view.cursors.main_cursor().position.line_index;
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let lines = buffer.content().lines();
+
+        let mut toc = ctx.editor.string_pool.acquire();
+        write_markdown_toc(lines.iter().map(|l| l.as_str()), &mut toc);
+
+        let is_marker = |line_index: usize, marker| lines[line_index].as_str().trim() == marker;
+        let begin_line_index = (0..lines.len()).find(|&i| is_marker(i, MARKDOWN_TOC_BEGIN_MARKER));
+        let end_line_index = begin_line_index.and_then(|begin| {
+            (begin + 1..lines.len()).find(|&i| is_marker(i, MARKDOWN_TOC_END_MARKER))
+        });
+
+        // update the contents of an existing toc block or insert a new one
+        let range = match (begin_line_index, end_line_index) {
+            (Some(begin), Some(end)) => BufferRange::between(
+                BufferPosition::line_col(begin as u32 + 1, 0),
+                BufferPosition::line_col(end as _, 0),
+            ),
+            (Some(begin), None) => {
+                toc.insert(0, '\n');
+                toc.push_str(MARKDOWN_TOC_END_MARKER);
+                let position =
+                    BufferPosition::line_col(begin as _, lines[begin].as_str().len() as _);
+                BufferRange::between(position, position)
+            }
+            (None, _) => {
+                toc.insert(0, '\n');
+                toc.insert_str(0, MARKDOWN_TOC_BEGIN_MARKER);
+                toc.push_str(MARKDOWN_TOC_END_MARKER);
+                toc.push('\n');
+                let position = BufferPosition::line_col(cursor_line_index, 0);
+                BufferRange::between(position, position)
+            }
+        };
+
+        let mut previous_toc = ctx.editor.string_pool.acquire();
+        for text in buffer.content().text_range(range) {
+            previous_toc.push_str(text);
+        }
+        if previous_toc != toc {
+            let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+            buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                &toc,
+                events.to_text_inserts(),
+            );
+            buffer.commit_edits();
+        }
+
+        ctx.editor.string_pool.release(previous_toc);
+        ctx.editor.string_pool.release(toc);
+        Ok(())
+    });
+
+    static SPLIT_ON_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Custom(&[]),
+        CompletionSource::Custom(&["keep", "trim"]),
+        CompletionSource::Custom(&["split-quoted", "skip-quoted"]),
+    ];
+    r("split-on", SPLIT_ON_COMPLETIONS, |ctx, io| {
+        let delimiter = io.args.next()?;
+        let trim = match io.args.try_next() {
+            None | Some("keep") => false,
+            Some("trim") => true,
+            Some(_) => return Err(CommandError::OtherStatic("invalid split-on argument")),
+        };
+        let respect_quotes = match io.args.try_next() {
+            None | Some("split-quoted") => false,
+            Some("skip-quoted") => true,
+            Some(_) => return Err(CommandError::OtherStatic("invalid split-on argument")),
+        };
+        io.args.assert_empty()?;
+        if delimiter.is_empty() {
+            return Err(CommandError::OtherStatic("invalid split-on argument"));
+        }
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        let mut text = ctx.editor.string_pool.acquire();
+        let mut split_text = ctx.editor.string_pool.acquire();
+        let mut cursors = Vec::with_capacity(buffer_view.cursors[..].len());
+
+        for &cursor in buffer_view.cursors[..].iter().rev() {
+            let range = cursor.to_range();
+            if range.from == range.to {
+                cursors.push(cursor);
+                continue;
+            }
+
+            text.clear();
+            for t in buffer.content().text_range(range) {
+                text.push_str(t);
+            }
+            split_text.clear();
+            split_on_delimiter(&text, delimiter, trim, respect_quotes, &mut split_text);
+
+            buffer.delete_range(
+                &mut ctx.editor.word_database,
+                range,
+                events.to_range_deletes(),
+            );
+            let insert_range = buffer.insert_text(
+                &mut ctx.editor.word_database,
+                range.from,
+                &split_text,
+                events.to_text_inserts(),
+            );
+
+            // cursors after this one were already split and need to follow the edit
+            for cursor in &mut cursors {
+                cursor.anchor = cursor.anchor.delete(range).insert(insert_range);
+                cursor.position = cursor.position.delete(range).insert(insert_range);
             }
+            cursors.push(Cursor {
+                anchor: insert_range.from,
+                position: insert_range.to,
+            });
         }
+        std::mem::drop(events);
 
-        ctx.editor.string_pool.release(cursor_texts);
         buffer.commit_edits();
 
-        {
-            let mut events = ctx
-                .editor
-                .events
-                .writer()
-                .fix_cursors_mut_guard(buffer_view_handle);
-            for &cursor in buffer_view.cursors[..].iter() {
-                events.add(cursor);
-            }
+        ctx.editor.string_pool.release(text);
+        ctx.editor.string_pool.release(split_text);
+
+        let mut events = ctx
+            .editor
+            .events
+            .writer()
+            .fix_cursors_mut_guard(buffer_view_handle);
+        for &cursor in cursors.iter().rev() {
+            events.add(cursor);
         }
 
         Ok(())
-    }
-
-    r("to-lowercase", &[], |ctx, io| change_case(ctx, io, true));
-    r("to-uppercase", &[], |ctx, io| change_case(ctx, io, false));
+    });
 
-    r("toggle-comment", &[], |ctx, io| {
-        let comment_prefix = io.args.next()?;
+    static RETAB_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Custom(&["spaces", "tabs"]),
+        CompletionSource::Custom(&["leading", "all"]),
+    ];
+    r("retab", RETAB_COMPLETIONS, |ctx, io| {
+        let to_tabs = match io.args.try_next() {
+            None => None,
+            Some("spaces") => Some(false),
+            Some("tabs") => Some(true),
+            Some(_) => return Err(CommandError::OtherStatic("invalid retab argument")),
+        };
+        let leading_only = match io.args.try_next() {
+            None | Some("leading") => true,
+            Some("all") => false,
+            Some(_) => return Err(CommandError::OtherStatic("invalid retab argument")),
+        };
         io.args.assert_empty()?;
 
         let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
         let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
         let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let indentation_config = buffer.indentation_config(&ctx.editor.config);
+        let to_tabs = to_tabs.unwrap_or(indentation_config.indent_with_tabs);
+        let tab_size = indentation_config.tab_size;
 
         let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
 
-        let mut all_lines_commented = true;
+        let mut retabbed_line = ctx.editor.string_pool.acquire();
 
-        let mut previous_toggle_line_index = BufferPositionIndex::MAX;
-        'cursor_loop: for cursor in &buffer_view.cursors[..] {
+        let mut previous_retab_line_index = BufferPositionIndex::MAX;
+        for cursor in &buffer_view.cursors[..] {
             let range = cursor.to_range();
-            let from_line_index = previous_toggle_line_index
+            let from_line_index = previous_retab_line_index
                 .wrapping_add(1)
                 .max(range.from.line_index);
             let to_line_index = range.to.line_index;
-            previous_toggle_line_index = to_line_index;
+            previous_retab_line_index = to_line_index;
 
             for line_index in from_line_index..=to_line_index {
-                let line = buffer.content().lines()[line_index as usize]
-                    .as_str()
-                    .trim_start();
-                if !line.is_empty() && !line.starts_with(comment_prefix) {
-                    all_lines_commented = false;
-                    break 'cursor_loop;
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                retabbed_line.clear();
+                retab_line(line, tab_size, to_tabs, leading_only, &mut retabbed_line);
+                if line == &retabbed_line[..] {
+                    continue;
                 }
+
+                let range = BufferRange::between(
+                    BufferPosition::line_col(line_index, 0),
+                    BufferPosition::line_col(line_index, line.len() as _),
+                );
+                buffer.delete_range(
+                    &mut ctx.editor.word_database,
+                    range,
+                    events.to_range_deletes(),
+                );
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.from,
+                    &retabbed_line,
+                    events.to_text_inserts(),
+                );
             }
         }
 
-        let mut previous_toggle_line_index = BufferPositionIndex::MAX;
+        ctx.editor.string_pool.release(retabbed_line);
+        buffer.commit_edits();
+
+        Ok(())
+    });
+
+    r("align-table", &[], |ctx, io| {
+        let delimiter = io.args.next()?;
+        io.args.assert_empty()?;
+        if delimiter.is_empty() {
+            return Err(CommandError::OtherStatic("invalid align-table argument"));
+        }
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        let tab_size = buffer.indentation_config(&ctx.editor.config).tab_size;
+
+        let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+
+        let mut widths = Vec::new();
+        let mut aligned_line = ctx.editor.string_pool.acquire();
+
+        let mut previous_align_line_index = BufferPositionIndex::MAX;
         for cursor in &buffer_view.cursors[..] {
             let range = cursor.to_range();
-            let from_line_index = previous_toggle_line_index
+            let from_line_index = previous_align_line_index
                 .wrapping_add(1)
                 .max(range.from.line_index);
             let to_line_index = range.to.line_index;
-            previous_toggle_line_index = to_line_index;
+            previous_align_line_index = to_line_index;
 
+            widths.clear();
             for line_index in from_line_index..=to_line_index {
-                let line = &buffer.content().lines()[line_index as usize];
-                let mut position = BufferPosition::line_col(line_index, 0);
-                let word = line.word_at(0);
-                if word.kind == WordKind::Whitespace {
-                    position.column_byte_index += word.text.len() as BufferPositionIndex;
-                }
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                measure_table_row(line, delimiter, tab_size, &mut widths);
+            }
 
-                let line = &line.as_str()[position.column_byte_index as usize..];
-                if !line.starts_with(comment_prefix) {
-                    if !line.is_empty() {
-                        buffer.insert_text(
-                            &mut ctx.editor.word_database,
-                            position,
-                            comment_prefix,
-                            events.to_text_inserts(),
-                        );
-                    }
-                } else if all_lines_commented {
-                    let to_column_byte_index =
-                        position.column_byte_index + comment_prefix.len() as BufferPositionIndex;
-                    let range = BufferRange::between(
-                        position,
-                        BufferPosition::line_col(line_index, to_column_byte_index),
-                    );
-                    buffer.delete_range(
-                        &mut ctx.editor.word_database,
-                        range,
-                        events.to_range_deletes(),
-                    );
+            for line_index in from_line_index..=to_line_index {
+                let line = buffer.content().lines()[line_index as usize].as_str();
+                aligned_line.clear();
+                align_table_row(line, delimiter, tab_size, &widths, &mut aligned_line);
+                if line == &aligned_line[..] {
+                    continue;
                 }
+
+                let range = BufferRange::between(
+                    BufferPosition::line_col(line_index, 0),
+                    BufferPosition::line_col(line_index, line.len() as _),
+                );
+                buffer.delete_range(
+                    &mut ctx.editor.word_database,
+                    range,
+                    events.to_range_deletes(),
+                );
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    range.from,
+                    &aligned_line,
+                    events.to_text_inserts(),
+                );
             }
         }
 
+        ctx.editor.string_pool.release(aligned_line);
         buffer.commit_edits();
 
         Ok(())
     });
 
+    r("detect-indent", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        match buffer.content().detect_indentation() {
+            Some(indentation) => {
+                buffer.detected_indentation = Some(indentation);
+                let mut write = ctx.editor.logger.write(LogKind::Status);
+                match indentation {
+                    DetectedIndentation::Tabs => write.str("detected indentation: tabs"),
+                    DetectedIndentation::Spaces(tab_size) => {
+                        write.fmt(format_args!("detected indentation: {} spaces", tab_size))
+                    }
+                }
+            }
+            None => ctx
+                .editor
+                .logger
+                .write(LogKind::Status)
+                .str("could not detect indentation"),
+        }
+        Ok(())
+    });
+
     r("set-register", &[], |ctx, io| {
         let key = io.args.next()?;
         let value = io.args.next()?;
         io.args.assert_empty()?;
 
         let key = RegisterKey::from_str(key).ok_or(CommandError::InvalidRegisterKey)?;
-        let register = ctx.editor.registers.get_mut(key);
-        register.clear();
-        register.push_str(value);
+        ctx.editor.registers.set(key, value);
         Ok(())
     });
 
@@ -903,6 +3656,128 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
+    static COPY_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Flags(&[CommandFlag::switch("-rtf")])];
+    r("copy-highlighted", COPY_COMPLETIONS, |ctx, io| {
+        use std::fmt::Write;
+
+        let rtf = match io.args.try_next() {
+            Some("-rtf") => true,
+            Some(_) => return Err(CommandError::TooManyArguments),
+            None => false,
+        };
+        io.args.assert_empty()?;
+
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
+        if buffer_view.cursors[..]
+            .iter()
+            .all(|cursor| cursor.anchor == cursor.position)
+        {
+            return Err(CommandError::OtherStatic("no selection"));
+        }
+
+        let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+        while let HighlightResult::Pending = buffer.update_highlighting(&ctx.editor.syntaxes) {}
+
+        static TOKEN_KINDS: [TokenKind; 8] = [
+            TokenKind::Keyword,
+            TokenKind::Type,
+            TokenKind::Symbol,
+            TokenKind::Literal,
+            TokenKind::String,
+            TokenKind::Comment,
+            TokenKind::Text,
+            TokenKind::Whitespace,
+        ];
+
+        let theme = &ctx.editor.theme;
+        let mut text = ctx.editor.string_pool.acquire();
+        if rtf {
+            text.push_str(
+                "{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}{\\colortbl;",
+            );
+            for &kind in &TOKEN_KINDS {
+                let color = theme.token_color(kind);
+                let _ = write!(text, "\\red{}\\green{}\\blue{};", color.0, color.1, color.2);
+            }
+            text.push_str("}\\f0 ");
+        }
+
+        let content = buffer.content();
+        let highlighted = buffer.highlighted();
+        let mut last_range: Option<BufferRange> = None;
+        let mut current_kind = None;
+        for cursor in &buffer_view.cursors[..] {
+            let range = cursor.to_range();
+            if let Some(last_range) = last_range {
+                if range.from.line_index > last_range.to.line_index {
+                    push_highlighted_line_break(&mut text, rtf);
+                }
+            }
+            last_range = Some(range);
+
+            for line_index in range.from.line_index..=range.to.line_index {
+                let line = content.lines()[line_index as usize].as_str();
+                let from = if line_index == range.from.line_index {
+                    range.from.column_byte_index as usize
+                } else {
+                    push_highlighted_line_break(&mut text, rtf);
+                    0
+                };
+                let to = if line_index == range.to.line_index {
+                    range.to.column_byte_index as usize
+                } else {
+                    line.len()
+                };
+
+                let mut span_start = 0;
+                highlighted.for_each_line_span(line_index as _, line, |kind, span| {
+                    let span_end = span_start + span.len();
+                    let span_from = from.max(span_start);
+                    let span_to = to.min(span_end);
+                    span_start = span_end;
+                    if span_to <= span_from {
+                        return;
+                    }
+
+                    let kind = match kind {
+                        TokenKind::Whitespace => TokenKind::Text,
+                        kind => kind,
+                    };
+                    if current_kind != Some(kind) {
+                        current_kind = Some(kind);
+                        if rtf {
+                            let color_index =
+                                TOKEN_KINDS.iter().position(|&k| k == kind).unwrap_or(0) + 1;
+                            let _ = write!(text, "\\cf{} ", color_index);
+                        } else {
+                            let color = theme.token_color(kind);
+                            let _ = write!(text, "\x1b[38;2;{};{};{}m", color.0, color.1, color.2);
+                        }
+                    }
+
+                    let span = &line[span_from..span_to];
+                    if rtf {
+                        push_rtf_escaped(&mut text, span);
+                    } else {
+                        text.push_str(span);
+                    }
+                });
+            }
+        }
+
+        if rtf {
+            text.push('}');
+        } else {
+            text.push_str("\x1b[0m");
+        }
+
+        ctx.platform.write_to_clipboard(&text);
+        ctx.editor.string_pool.release(text);
+        Ok(())
+    });
+
     r("set-env", &[], |_, io| {
         let key = io.args.next()?;
         let value = io.args.next()?;
@@ -993,6 +3868,126 @@ pub fn register_commands(commands: &mut CommandManager) {
         Ok(())
     });
 
+    static VIEW_COMMAND_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Flags(&[CommandFlag::option(
+            "-filetype=",
+            CompletionSource::Custom(&[]),
+        )])];
+    r("view-command", VIEW_COMMAND_COMPLETIONS, |ctx, io| {
+        let mut command_text = io.args.next()?;
+        let filetype = command_text.strip_prefix("-filetype=");
+        if filetype.is_some() {
+            command_text = io.args.next()?;
+        }
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let command =
+            parse_process_command(command_text).ok_or(CommandError::InvalidProcessCommand)?;
+
+        let buffer = ctx.editor.buffers.add_new();
+        buffer.properties = BufferProperties::output();
+
+        let mut path = ctx.editor.string_pool.acquire_with(command_text);
+        if let Some(filetype) = filetype {
+            path.push('.');
+            path.push_str(filetype);
+        }
+        buffer.set_path(Path::new(&path));
+        ctx.editor.string_pool.release(path);
+        buffer.refresh_syntax(&ctx.editor.syntaxes);
+
+        let buffer_handle = buffer.handle();
+        let buffer_view_handle = ctx
+            .editor
+            .buffer_views
+            .add_new(client_handle, buffer_handle);
+        ctx.clients
+            .get_mut(client_handle)
+            .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+
+        ctx.editor.buffers.spawn_insert_process(
+            &mut ctx.platform,
+            command,
+            buffer_handle,
+            BufferPosition::zero(),
+            None,
+        );
+
+        ctx.editor
+            .logger
+            .write(LogKind::Diagnostic)
+            .fmt(format_args!("view-command '{}'", command_text));
+
+        Ok(())
+    });
+
+    r("format-with", &[], |ctx, io| {
+        let command_text = io.args.next()?;
+        io.args.assert_empty()?;
+
+        let command =
+            parse_process_command(command_text).ok_or(CommandError::InvalidProcessCommand)?;
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        if buffer.formatter.is_running() {
+            return Err(CommandError::OtherStatic("formatter already running"));
+        }
+        buffer.spawn_formatter_process(&mut ctx.platform, command);
+
+        ctx.editor
+            .logger
+            .write(LogKind::Diagnostic)
+            .fmt(format_args!("format-with '{}'", command_text));
+        Ok(())
+    });
+
+    r("formatter-rule", &[], |ctx, io| {
+        let glob = io.args.next()?;
+        let command_text = io.args.next()?;
+        io.args.assert_empty()?;
+
+        if !validate_process_command(command_text) {
+            return Err(CommandError::InvalidProcessCommand);
+        }
+        ctx.editor
+            .formatter_rules
+            .add(glob, command_text)
+            .map_err(CommandError::InvalidGlob)
+    });
+
+    // plugins (like the lsp one) get the first chance to format the buffer
+    r("format", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx)?;
+        for plugin_handle in ctx.plugins.handles() {
+            let on_format = ctx.plugins.get(plugin_handle).on_format;
+            if on_format(plugin_handle, ctx, buffer_handle) {
+                return Ok(());
+            }
+        }
+
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        let command_text = buffer
+            .path
+            .to_str()
+            .and_then(|path| ctx.editor.formatter_rules.command_for(path))
+            .ok_or(CommandError::OtherStatic("no formatter for this buffer"))?;
+        let command =
+            parse_process_command(command_text).ok_or(CommandError::InvalidProcessCommand)?;
+        if buffer.formatter.is_running() {
+            return Err(CommandError::OtherStatic("formatter already running"));
+        }
+        buffer.spawn_formatter_process(&mut ctx.platform, command);
+
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .fmt(format_args!("formatting with '{}'", command_text));
+        Ok(())
+    });
+
     r("replace-with-output", &[], |ctx, io| {
         let command_text = io.args.next()?;
         io.args.assert_empty()?;
@@ -1071,22 +4066,67 @@ pub fn register_commands(commands: &mut CommandManager) {
         }
     });
 
+    fn eval_if_predicate(
+        ctx: &EditorContext,
+        io: &CommandIO,
+        predicate: &str,
+    ) -> Result<bool, CommandError> {
+        let buffer_view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_handle = ctx
+            .editor
+            .buffer_views
+            .get(buffer_view_handle)
+            .buffer_handle;
+        let buffer = ctx.editor.buffers.get(buffer_handle);
+
+        if predicate == "-if-modified" {
+            Ok(buffer.needs_save())
+        } else if let Some(filetype) = predicate.strip_prefix("-if-filetype=") {
+            let extension = buffer.path.extension().and_then(|e| e.to_str());
+            Ok(extension == Some(filetype))
+        } else if let Some(glob) = predicate.strip_prefix("-if-path-matches=") {
+            let mut path_glob = Glob::default();
+            path_glob.compile(glob).map_err(CommandError::InvalidGlob)?;
+            Ok(buffer
+                .path
+                .to_str()
+                .map(|path| path_glob.matches(path))
+                .unwrap_or(false))
+        } else {
+            Err(CommandError::InvalidIfOp)
+        }
+    }
+
     static IF_COMPLETIONS: &[CompletionSource] = &[
+        CompletionSource::Flags(&[
+            CommandFlag::switch("-if-modified"),
+            CommandFlag::option("-if-filetype=", CompletionSource::Custom(&[])),
+            CommandFlag::option("-if-path-matches=", CompletionSource::Custom(&[])),
+        ]),
         CompletionSource::Custom(&[]),
         CompletionSource::Custom(&["==", "!="]),
     ];
     r("if", IF_COMPLETIONS, |ctx, io| {
         let left_expr = io.args.next()?;
-        let op = io.args.next()?;
-        let right_expr = io.args.next()?;
-        let continuation = io.args.next()?;
-        io.args.assert_empty()?;
+        let should_execute;
+        let continuation;
+        if left_expr.starts_with("-if-") {
+            continuation = io.args.next()?;
+            io.args.assert_empty()?;
 
-        let should_execute = match op {
-            "==" => left_expr == right_expr,
-            "!=" => left_expr != right_expr,
-            _ => return Err(CommandError::InvalidIfOp),
-        };
+            should_execute = eval_if_predicate(ctx, io, left_expr)?;
+        } else {
+            let op = io.args.next()?;
+            let right_expr = io.args.next()?;
+            continuation = io.args.next()?;
+            io.args.assert_empty()?;
+
+            should_execute = match op {
+                "==" => left_expr == right_expr,
+                "!=" => left_expr != right_expr,
+                _ => return Err(CommandError::InvalidIfOp),
+            };
+        }
 
         if !should_execute {
             return Ok(());
@@ -1101,3 +4141,66 @@ pub fn register_commands(commands: &mut CommandManager) {
         }
     });
 }
+
+// byte index of the first of the comma separated markers to appear in text as a whole word
+fn find_todo_marker(text: &str, markers: &str) -> Option<usize> {
+    let is_word_byte = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let mut first = None;
+    for marker in markers.split(',').map(str::trim).filter(|m| !m.is_empty()) {
+        for (index, _) in text.match_indices(marker) {
+            let end = index + marker.len();
+            let bytes = text.as_bytes();
+            if index > 0 && is_word_byte(bytes[index - 1])
+                || end < bytes.len() && is_word_byte(bytes[end])
+            {
+                continue;
+            }
+            if first.map(|f| index < f).unwrap_or(true) {
+                first = Some(index);
+            }
+            break;
+        }
+    }
+    first
+}
+
+fn push_html_escaped(html: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            _ => html.push(c),
+        }
+    }
+}
+
+fn push_highlighted_line_break(text: &mut String, rtf: bool) {
+    if rtf {
+        text.push_str("\\line\n");
+    } else {
+        text.push('\n');
+    }
+}
+
+fn push_rtf_escaped(text: &mut String, span: &str) {
+    use std::fmt::Write;
+
+    for c in span.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                text.push('\\');
+                text.push(c);
+            }
+            '\t' => text.push_str("\\tab "),
+            c if c.is_ascii() => text.push(c),
+            c => {
+                let mut units = [0; 2];
+                for &unit in c.encode_utf16(&mut units).iter() {
+                    let _ = write!(text, "\\u{}?", unit as i16);
+                }
+            }
+        }
+    }
+}
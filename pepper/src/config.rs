@@ -1,4 +1,6 @@
-use std::fmt;
+use std::{fmt, str::FromStr};
+
+use crate::{buffer::TabStops, editor_utils::parse_unicode_code_points};
 
 pub enum ParseConfigError {
     NoSuchConfig,
@@ -13,6 +15,134 @@ impl fmt::Display for ParseConfigError {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CompletionTrigger {
+    Auto,
+    Triggers,
+    Manual,
+}
+impl FromStr for CompletionTrigger {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "triggers" => Ok(Self::Triggers),
+            "manual" => Ok(Self::Manual),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for CompletionTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Triggers => f.write_str("triggers"),
+            Self::Manual => f.write_str("manual"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CursorShape {
+    Block,
+    Underline,
+    Bar,
+}
+impl FromStr for CursorShape {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "block" => Ok(Self::Block),
+            "underline" => Ok(Self::Underline),
+            "bar" => Ok(Self::Bar),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for CursorShape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Block => f.write_str("block"),
+            Self::Underline => f.write_str("underline"),
+            Self::Bar => f.write_str("bar"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+    Info = 3,
+    Hint = 4,
+}
+impl FromStr for DiagnosticSeverity {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "warning" => Ok(Self::Warning),
+            "info" => Ok(Self::Info),
+            "hint" => Ok(Self::Hint),
+            _ => Err(()),
+        }
+    }
+}
+impl fmt::Display for DiagnosticSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Error => f.write_str("error"),
+            Self::Warning => f.write_str("warning"),
+            Self::Info => f.write_str("info"),
+            Self::Hint => f.write_str("hint"),
+        }
+    }
+}
+
+// written as space separated code points like 'U+00A0 U+200B'
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct CodePointSet(Vec<char>);
+impl CodePointSet {
+    pub fn invisible_chars() -> Self {
+        Self(vec![
+            '\u{a0}', '\u{ad}', '\u{2007}', '\u{2009}', '\u{200b}', '\u{200c}', '\u{200d}',
+            '\u{200e}', '\u{200f}', '\u{202f}', '\u{2060}', '\u{feff}',
+        ])
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, c: char) -> bool {
+        self.0.contains(&c)
+    }
+}
+impl FromStr for CodePointSet {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        let mut chars = String::new();
+        if !parse_unicode_code_points(s, &mut chars) {
+            return Err(());
+        }
+        Ok(Self(chars.chars().collect()))
+    }
+}
+impl fmt::Display for CodePointSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, &c) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "U+{:04X}", c as u32)?;
+        }
+        Ok(())
+    }
+}
+
 macro_rules! config_values {
     ($($name:ident: $type:ty = $default:expr,)*) => {
         pub static CONFIG_NAMES: &[&str] = &[$(stringify!($name),)*];
@@ -67,14 +197,44 @@ macro_rules! config_values {
 
 config_values! {
     tab_size: u8 = 4,
+    tab_stops: TabStops = TabStops::default(),
     indent_with_tabs: bool = false,
+    detect_indentation: bool = false,
 
     visual_empty: char = '~',
     visual_space: char = '.',
     visual_tab_first: char = '|',
     visual_tab_repeat: char = ' ',
+    invisible_chars: CodePointSet = CodePointSet::invisible_chars(),
+
+    wrap_lines: bool = true,
+    terminal_cursor: bool = true,
+    normal_cursor_shape: CursorShape = CursorShape::Block,
+    insert_cursor_shape: CursorShape = CursorShape::Bar,
+    page_scroll_overlap: u8 = 2,
+    keymap_timeout: u32 = 1000,
+    git_gutter: bool = false,
+    minimap: bool = false,
+    minimap_width: u8 = 12,
+    trim_line_pools: bool = true,
+    undo_history_limit: u32 = 0,
+
+    spell_check: String = String::from("**/*.{md,markdown,txt}"),
+    spell_dictionary: String = String::new(),
+    spell_user_dictionary: String = String::new(),
 
+    todo_markers: String = String::from("TODO,FIXME,HACK,XXX"),
+    debug_comment_marker: String = String::from("DEBUG:"),
+
+    auto_close_tags: String = String::from("**/*.{html,htm,xhtml,xml,svg,jsx,tsx,vue}"),
+
+    completion_trigger: CompletionTrigger = CompletionTrigger::Auto,
     completion_min_len: u8 = 3,
+    completion_debounce: u32 = 100,
+    signature_help_trigger: bool = true,
+    min_diagnostic_severity: DiagnosticSeverity = DiagnosticSeverity::Hint,
     picker_max_height: u8 = 8,
     status_bar_max_height: u8 = 8,
+
+    session_stats: bool = false,
 }
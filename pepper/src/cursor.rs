@@ -4,7 +4,7 @@ use std::{
 };
 
 use crate::{
-    buffer::{BufferContent, CharDisplayDistances},
+    buffer::{BufferContent, DisplayLen, TabStops},
     buffer_position::{BufferPosition, BufferRange},
 };
 
@@ -187,16 +187,18 @@ impl<'a> CursorCollectionMutGuard<'a> {
         self.inner.cursors.swap_remove(index)
     }
 
-    pub fn save_display_distances(&mut self, buffer: &BufferContent, tab_size: u8) {
+    pub fn save_display_distances(
+        &mut self,
+        buffer: &BufferContent,
+        tab_size: u8,
+        tab_stops: &TabStops,
+    ) {
         self.clear_display_distances = false;
         if self.inner.saved_display_distances.is_empty() {
             for c in &self.inner.cursors {
                 let line = &buffer.lines()[c.position.line_index as usize].as_str()
                     [..c.position.column_byte_index as usize];
-                let distance = CharDisplayDistances::new(line, tab_size)
-                    .last()
-                    .map(|d| d.distance)
-                    .unwrap_or(0);
+                let distance = tab_stops.line_len(line, DisplayLen::from(line), tab_size) as _;
 
                 self.inner.saved_display_distances.push(distance);
             }
@@ -1,6 +1,7 @@
 use std::{
     fmt,
     path::{Path, PathBuf},
+    time::{Duration, Instant},
 };
 
 use crate::{
@@ -11,8 +12,9 @@ use crate::{
     command::CommandManager,
     config::Config,
     editor_utils::{
-        KeyMapCollection, Logger, LoggerStatusBarDisplay, MatchResult, PickerEntriesProcessBuf,
-        RegisterCollection, RegisterKey, StringPool,
+        DebugPrintRules, FormatterRules, KeyMapCollection, Logger, LoggerStatusBarDisplay,
+        MatchResult, PickerEntriesProcessBuf, RegisterCollection, RegisterKey, RelatedFileRules,
+        SessionStats, StringPool,
     },
     events::{
         ClientEvent, EditorEvent, EditorEventIter, EditorEventQueue, KeyParseAllError, KeyParser,
@@ -23,6 +25,8 @@ use crate::{
     picker::Picker,
     platform::{Key, KeyCode, Platform, PlatformRequest},
     plugin::{PluginCollection, PluginHandle},
+    quickfix::QuickfixList,
+    spell::SpellChecker,
     syntax::{HighlightResult, SyntaxCollection},
     theme::Theme,
     ui,
@@ -140,7 +144,7 @@ impl EditorContext {
             let scroll = c.scroll_to_main_cursor(
                 &mut self.editor.buffer_views,
                 &self.editor.buffers,
-                self.editor.config.tab_size,
+                &self.editor.config,
                 margin_bottom,
             );
 
@@ -212,6 +216,15 @@ impl EditorContext {
                     EditorEvent::BufferRead { handle } => {
                         let buffer = self.editor.buffers.get_mut(handle);
                         buffer.refresh_syntax(&self.editor.syntaxes);
+                        if self.editor.config.detect_indentation {
+                            buffer.detected_indentation = buffer.content().detect_indentation();
+                        }
+                        if self.editor.config.git_gutter {
+                            buffer.refresh_git_diff_base(
+                                &mut self.platform,
+                                &self.editor.current_directory,
+                            );
+                        }
                         self.editor.buffer_views.on_buffer_read(buffer);
                     }
                     EditorEvent::BufferWrite { handle, new_path } => {
@@ -219,6 +232,12 @@ impl EditorContext {
                         if new_path {
                             buffer.refresh_syntax(&self.editor.syntaxes);
                         }
+                        if self.editor.config.git_gutter {
+                            buffer.refresh_git_diff_base(
+                                &mut self.platform,
+                                &self.editor.current_directory,
+                            );
+                        }
 
                         for client in self.clients.iter() {
                             if client.stdin_buffer_handle() == Some(buffer.handle()) {
@@ -255,6 +274,10 @@ impl EditorContext {
                             .buffer_views
                             .remove_buffer_views_with_buffer(handle);
                     }
+                    EditorEvent::BufferRename { handle, .. } => {
+                        let buffer = self.editor.buffers.get_mut(handle);
+                        buffer.refresh_syntax(&self.editor.syntaxes);
+                    }
                     EditorEvent::FixCursors { handle, cursors } => {
                         let event_reader = self.editor.events.reader();
                         let buffer_view = self.editor.buffer_views.get_mut(handle);
@@ -297,13 +320,20 @@ pub struct Editor {
     pub word_database: WordDatabase,
 
     pub buffered_keys: BufferedKeys,
+    pending_keys_since: Option<(ClientHandle, Instant)>,
     pub recording_macro: Option<RegisterKey>,
     pub registers: RegisterCollection,
+    pub related_file_rules: RelatedFileRules,
+    pub debug_print_rules: DebugPrintRules,
+    pub formatter_rules: FormatterRules,
     pub picker: Picker,
+    pub quickfix: QuickfixList,
+    pub spell_checker: SpellChecker,
     pub string_pool: StringPool,
 
     pub logger: Logger,
     pub aux_pattern: Pattern,
+    pub session_stats: SessionStats,
 
     pub commands: CommandManager,
     pub events: EditorEventQueue,
@@ -328,13 +358,20 @@ impl Editor {
             word_database: WordDatabase::new(),
 
             buffered_keys: BufferedKeys::default(),
+            pending_keys_since: None,
             recording_macro: None,
             registers: RegisterCollection::new(),
+            related_file_rules: RelatedFileRules::default(),
+            debug_print_rules: DebugPrintRules::default(),
+            formatter_rules: FormatterRules::default(),
             picker: Picker::default(),
+            quickfix: QuickfixList::default(),
+            spell_checker: SpellChecker::default(),
             string_pool: StringPool::default(),
 
             logger: Logger::new(),
             aux_pattern: Pattern::new(),
+            session_stats: SessionStats::default(),
 
             commands: CommandManager::new(),
             events: EditorEventQueue::default(),
@@ -397,6 +434,9 @@ impl Editor {
                     Err(error)
                 }
                 None => {
+                    if self.config.session_stats {
+                        self.session_stats.buffer_open_count += 1;
+                    }
                     let handle = self
                         .buffer_views
                         .add_new(client_handle, result.buffer_handle);
@@ -421,21 +461,41 @@ impl Editor {
     }
 
     pub fn execute_keys(
+        ctx: &mut EditorContext,
+        client_handle: ClientHandle,
+        keys: KeysIterator,
+    ) -> EditorFlow {
+        Self::execute_keys_with_keymaps(ctx, client_handle, keys, true)
+    }
+
+    fn execute_keys_with_keymaps(
         ctx: &mut EditorContext,
         client_handle: ClientHandle,
         mut keys: KeysIterator,
+        wait_for_longer_keymaps: bool,
     ) -> EditorFlow {
         let start_index = keys.index;
 
         match ctx.editor.keymaps.matches(
             ctx.editor.mode.kind(),
             &ctx.editor.buffered_keys.0[start_index..],
+            wait_for_longer_keymaps,
         ) {
             MatchResult::None => (),
-            MatchResult::Prefix => return EditorFlow::Continue,
-            MatchResult::ReplaceWith(replaced_keys) => {
-                ctx.editor.buffered_keys.0.truncate(start_index);
-                ctx.editor.buffered_keys.0.extend_from_slice(replaced_keys);
+            MatchResult::Prefix => {
+                if start_index == 0 {
+                    ctx.editor.pending_keys_since = Some((client_handle, Instant::now()));
+                }
+                return EditorFlow::Continue;
+            }
+            MatchResult::ReplaceWith {
+                len,
+                keys: replaced_keys,
+            } => {
+                ctx.editor.buffered_keys.0.splice(
+                    start_index..start_index + len,
+                    replaced_keys.iter().copied(),
+                );
             }
         }
 
@@ -486,6 +546,7 @@ impl Editor {
                     },
                 };
 
+                ctx.editor.pending_keys_since = None;
                 if ctx.clients.focus_client(client_handle) {
                     ctx.editor.recording_macro = None;
                     ctx.editor.buffered_keys.0.clear();
@@ -494,6 +555,9 @@ impl Editor {
 
                 if key.code != KeyCode::None {
                     ctx.editor.logger.clear_status_bar_message();
+                    if ctx.editor.config.session_stats {
+                        ctx.editor.session_stats.key_count += 1;
+                    }
                 }
                 ctx.editor.buffered_keys.0.push(key);
                 Self::execute_keys(ctx, client_handle, KeysIterator { index: 0 })
@@ -537,6 +601,97 @@ impl Editor {
     }
 
     pub(crate) fn on_idle(&mut self) {
+        for buffer in self.buffers.iter_mut() {
+            if buffer.properties.tail_enabled
+                && buffer.read_file_tail(&mut self.word_database, self.events.writer())
+            {
+                self.buffer_views
+                    .on_buffer_tail(buffer, self.events.writer());
+            }
+            buffer.update_git_diff();
+        }
+        if self.config.trim_line_pools {
+            self.buffers.trim_line_pools();
+        }
+        self.update_misspellings();
+        if self.config.undo_history_limit > 0 {
+            let max_group_count = self.config.undo_history_limit as _;
+            for buffer in self.buffers.iter_mut() {
+                buffer.evict_oldest_history_groups(max_group_count);
+            }
+        }
+
         self.events.writer().enqueue(EditorEvent::Idle);
     }
+
+    pub(crate) fn update_misspellings(&mut self) {
+        self.spell_checker.reload(
+            &self.config.spell_dictionary,
+            &self.config.spell_user_dictionary,
+            &self.current_directory,
+            &mut self.logger,
+        );
+        self.spell_checker.set_check_glob(&self.config.spell_check);
+
+        for buffer in self.buffers.iter_mut() {
+            buffer.update_misspellings(&self.spell_checker);
+        }
+    }
+
+    pub(crate) fn needs_idle_polling(&self) -> bool {
+        self.buffers.iter().any(|b| b.properties.tail_enabled)
+    }
+
+    // time left until the keys waiting on a longer keymap are executed as they are
+    pub(crate) fn pending_keys_timeout(&self) -> Option<Duration> {
+        let (_, since) = self.pending_keys_since?;
+        match self.config.keymap_timeout {
+            0 => None,
+            timeout => {
+                let timeout = Duration::from_millis(timeout as _);
+                Some(timeout.saturating_sub(since.elapsed()))
+            }
+        }
+    }
+
+    // keys left pending by a client that has disconnected are dropped
+    pub(crate) fn on_client_left(&mut self, client_handle: ClientHandle) {
+        if let Some((pending_client_handle, _)) = self.pending_keys_since {
+            if pending_client_handle == client_handle {
+                self.pending_keys_since = None;
+                self.buffered_keys.0.clear();
+            }
+        }
+    }
+
+    pub(crate) fn on_pending_keys_timeout(
+        ctx: &mut EditorContext,
+    ) -> Option<(ClientHandle, EditorFlow)> {
+        if ctx.editor.pending_keys_timeout()? > Duration::ZERO {
+            return None;
+        }
+
+        let (client_handle, _) = ctx.editor.pending_keys_since.take()?;
+        let flow =
+            Self::execute_keys_with_keymaps(ctx, client_handle, KeysIterator { index: 0 }, false);
+        Some((client_handle, flow))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending_keys_of_left_client_are_dropped() {
+        let mut editor = Editor::new(PathBuf::new(), String::new());
+        editor.pending_keys_since = Some((ClientHandle(0), Instant::now()));
+
+        editor.on_client_left(ClientHandle(1));
+        assert!(editor.pending_keys_since.is_some());
+
+        editor.on_client_left(ClientHandle(0));
+        assert!(editor.pending_keys_since.is_none());
+        assert!(editor.pending_keys_timeout().is_none());
+    }
 }
@@ -1,11 +1,12 @@
-use std::{env, fmt, fs, io, path::Path, process::Command};
+use std::{env, fmt, fs, io, ops::Range, path::Path, process::Command};
 
 use crate::{
-    buffer::char_display_len,
+    buffer::{char_display_len, DisplayLen},
     buffer_position::BufferRangesParser,
     command::CommandTokenizer,
     editor::{BufferedKeys, KeysIterator},
     events::{KeyParseAllError, KeyParser},
+    glob::{Glob, InvalidGlobError},
     mode::ModeKind,
     picker::Picker,
     platform::{Key, KeyCode, Platform},
@@ -15,7 +16,7 @@ use crate::{
 pub enum MatchResult<'a> {
     None,
     Prefix,
-    ReplaceWith(&'a [Key]),
+    ReplaceWith { len: usize, keys: &'a [Key] },
 }
 
 #[derive(Debug)]
@@ -23,6 +24,7 @@ pub enum ParseKeyMapError {
     CantRemapPluginMode,
     From(KeyParseAllError),
     To(KeyParseAllError),
+    Leader(KeyParseAllError),
 }
 impl fmt::Display for ParseKeyMapError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -30,6 +32,7 @@ impl fmt::Display for ParseKeyMapError {
             Self::CantRemapPluginMode => write!(f, "can not remap plugin mode"),
             Self::From(error) => write!(f, "invalid 'from' binding '{}'", error),
             Self::To(error) => write!(f, "invalid 'to' binding '{}'", error),
+            Self::Leader(error) => write!(f, "invalid leader '{}'", error),
         }
     }
 }
@@ -39,24 +42,61 @@ struct KeyMap {
     to: Vec<Key>,
 }
 
-#[derive(Default)]
 pub struct KeyMapCollection {
     maps: [Vec<KeyMap>; 5],
+    leader: Vec<Key>,
 }
 
 impl KeyMapCollection {
+    pub const LEADER_TOKEN: &'static str = "<leader>";
+
+    pub fn set_leader(&mut self, leader: &str) -> Result<(), ParseKeyMapError> {
+        let mut keys = Vec::new();
+        for key in KeyParser::new(leader) {
+            match key {
+                Ok(key) => keys.push(key),
+                Err(error) => return Err(ParseKeyMapError::Leader(error)),
+            }
+        }
+        self.leader = keys;
+        Ok(())
+    }
+
     pub fn parse_and_map(
         &mut self,
         mode: ModeKind,
         from: &str,
         to: &str,
     ) -> Result<(), ParseKeyMapError> {
-        fn parse_keys(text: &str) -> Result<Vec<Key>, KeyParseAllError> {
+        fn parse_keys(text: &str, leader: &[Key]) -> Result<Vec<Key>, KeyParseAllError> {
             let mut keys = Vec::new();
-            for key in KeyParser::new(text) {
-                match key {
-                    Ok(key) => keys.push(key),
-                    Err(error) => return Err(error),
+            let mut offset = 0;
+            loop {
+                let rest = &text[offset..];
+                let (segment, next_offset) = match rest.find(KeyMapCollection::LEADER_TOKEN) {
+                    Some(i) => (
+                        &rest[..i],
+                        Some(offset + i + KeyMapCollection::LEADER_TOKEN.len()),
+                    ),
+                    None => (rest, None),
+                };
+
+                for key in KeyParser::new(segment) {
+                    match key {
+                        Ok(key) => keys.push(key),
+                        Err(mut error) => {
+                            error.index += offset;
+                            return Err(error);
+                        }
+                    }
+                }
+
+                match next_offset {
+                    Some(next_offset) => {
+                        keys.extend_from_slice(leader);
+                        offset = next_offset;
+                    }
+                    None => break,
                 }
             }
             Ok(keys)
@@ -67,8 +107,8 @@ impl KeyMapCollection {
         }
 
         let map = KeyMap {
-            from: parse_keys(from).map_err(ParseKeyMapError::From)?,
-            to: parse_keys(to).map_err(ParseKeyMapError::To)?,
+            from: parse_keys(from, &self.leader).map_err(ParseKeyMapError::From)?,
+            to: parse_keys(to, &self.leader).map_err(ParseKeyMapError::To)?,
         };
 
         let maps = &mut self.maps[mode as usize];
@@ -83,7 +123,7 @@ impl KeyMapCollection {
         Ok(())
     }
 
-    pub fn matches(&self, mode: ModeKind, keys: &[Key]) -> MatchResult<'_> {
+    pub fn matches(&self, mode: ModeKind, keys: &[Key], wait_for_longer: bool) -> MatchResult<'_> {
         if let ModeKind::Plugin = mode {
             return MatchResult::None;
         }
@@ -91,19 +131,45 @@ impl KeyMapCollection {
         let maps = &self.maps[mode as usize];
 
         let mut has_prefix = false;
+        let mut longest_match: Option<&KeyMap> = None;
         for map in maps {
-            if map.from.iter().zip(keys.iter()).all(|(a, b)| a == b) {
-                has_prefix = true;
-                if map.from.len() == keys.len() {
-                    return MatchResult::ReplaceWith(&map.to);
+            if map.from.is_empty() {
+                continue;
+            }
+            if map.from.len() > keys.len() {
+                has_prefix = has_prefix || map.from.starts_with(keys);
+            } else if keys.starts_with(&map.from) {
+                match longest_match {
+                    Some(m) if m.from.len() >= map.from.len() => (),
+                    _ => longest_match = Some(map),
                 }
             }
         }
 
-        if has_prefix {
-            MatchResult::Prefix
-        } else {
-            MatchResult::None
+        if has_prefix && wait_for_longer {
+            return MatchResult::Prefix;
+        }
+
+        match longest_match {
+            Some(map) => MatchResult::ReplaceWith {
+                len: map.from.len(),
+                keys: &map.to,
+            },
+            None => MatchResult::None,
+        }
+    }
+}
+
+impl Default for KeyMapCollection {
+    fn default() -> Self {
+        Self {
+            maps: Default::default(),
+            leader: vec![Key {
+                code: KeyCode::Char(' '),
+                shift: false,
+                control: false,
+                alt: false,
+            }],
         }
     }
 }
@@ -278,6 +344,10 @@ impl Logger {
         }
     }
 
+    pub fn status_bar_message(&self) -> &str {
+        &self.status_bar_message
+    }
+
     pub fn is_status_bar_message_empty(&self) -> bool {
         self.status_bar_message.is_empty()
     }
@@ -514,8 +584,104 @@ impl RegisterKey {
 
 const REGISTERS_LEN: usize = (b'z' - b'a' + 1) as _;
 
+// only counted while the `session_stats` config is enabled
+#[derive(Default)]
+pub struct SessionStats {
+    pub key_count: u64,
+    pub command_count: u64,
+    pub buffer_open_count: u64,
+}
+
+// pairs of path suffixes that make two files related (like 'foo.h' and 'foo.c')
+#[derive(Default)]
+pub struct RelatedFileRules {
+    rules: Vec<(String, String)>,
+}
+impl RelatedFileRules {
+    pub fn add(&mut self, suffix: &str, related_suffix: &str) {
+        if !self
+            .rules
+            .iter()
+            .any(|(a, b)| a == suffix && b == related_suffix)
+        {
+            self.rules.push((suffix.into(), related_suffix.into()));
+        }
+    }
+
+    // calls 'f' with each path related to 'path' (which may not exist) in the order rules were added
+    pub fn for_each_related_path(&self, path: &str, output: &mut String, mut f: impl FnMut(&str)) {
+        for (a, b) in &self.rules {
+            for (suffix, related_suffix) in [(a, b), (b, a)] {
+                if let Some(stem) = path.strip_suffix(&suffix[..]) {
+                    output.clear();
+                    output.push_str(stem);
+                    output.push_str(related_suffix);
+                    f(output);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct DebugPrintRules {
+    rules: Vec<(Glob, String)>,
+}
+impl DebugPrintRules {
+    pub fn add(&mut self, glob: &str, template: &str) -> Result<(), InvalidGlobError> {
+        let mut path_glob = Glob::default();
+        path_glob.compile(glob)?;
+        self.rules.push((path_glob, template.into()));
+        Ok(())
+    }
+
+    // rules added later take precedence so configs can override the default ones
+    pub fn template_for(&self, path: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matches(path))
+            .map(|(_, template)| &template[..])
+    }
+}
+
+#[derive(Default)]
+pub struct FormatterRules {
+    rules: Vec<(Glob, String)>,
+}
+impl FormatterRules {
+    pub fn add(&mut self, glob: &str, command: &str) -> Result<(), InvalidGlobError> {
+        let mut path_glob = Glob::default();
+        path_glob.compile(glob)?;
+        self.rules.push((path_glob, command.into()));
+        Ok(())
+    }
+
+    // rules added later take precedence so configs can override the default ones
+    pub fn command_for(&self, path: &str) -> Option<&str> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.matches(path))
+            .map(|(_, command)| &command[..])
+    }
+}
+
+// each '$SEL' in the template is replaced by 'selection'
+pub fn expand_debug_print_template(template: &str, selection: &str, output: &mut String) {
+    let mut parts = template.split("$SEL");
+    if let Some(part) = parts.next() {
+        output.push_str(part);
+    }
+    for part in parts {
+        output.push_str(selection);
+        output.push_str(part);
+    }
+}
+
 pub struct RegisterCollection {
     registers: [String; REGISTERS_LEN],
+    linewise: [bool; REGISTERS_LEN],
 }
 
 impl RegisterCollection {
@@ -523,6 +689,7 @@ impl RegisterCollection {
         const DEFAULT_STRING: String = String::new();
         Self {
             registers: [DEFAULT_STRING; REGISTERS_LEN],
+            linewise: [false; REGISTERS_LEN],
         }
     }
 
@@ -538,6 +705,15 @@ impl RegisterCollection {
         let register = self.get_mut(key);
         register.clear();
         register.push_str(value);
+        self.linewise[key.0 as usize] = false;
+    }
+
+    pub fn is_linewise(&self, key: RegisterKey) -> bool {
+        self.linewise[key.0 as usize]
+    }
+
+    pub fn set_linewise(&mut self, key: RegisterKey, linewise: bool) {
+        self.linewise[key.0 as usize] = linewise;
     }
 }
 
@@ -610,8 +786,12 @@ impl PickerEntriesProcessBuf {
 }
 
 // FNV-1a : https://en.wikipedia.org/wiki/Fowler–Noll–Vo_hash_function
-pub const fn hash_bytes(mut bytes: &[u8]) -> u64 {
-    let mut hash: u64 = 0xcbf29ce484222325;
+pub const fn hash_bytes(bytes: &[u8]) -> u64 {
+    hash_more_bytes(0xcbf29ce484222325, bytes)
+}
+
+// continues a 'hash_bytes' hash as if 'bytes' were appended to the hashed bytes
+pub const fn hash_more_bytes(mut hash: u64, mut bytes: &[u8]) -> u64 {
     while let [b, rest @ ..] = bytes {
         hash ^= *b as u64;
         hash = hash.wrapping_mul(0x100000001b3);
@@ -626,6 +806,29 @@ pub const fn is_char_boundary(b: u8) -> bool {
     (b as i8) >= -0x40
 }
 
+pub fn open_file_for_writing(path: &Path, append: bool) -> io::Result<fs::File> {
+    use io::{Read, Seek, SeekFrom, Write};
+
+    if !append {
+        return fs::File::create(path);
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(path)?;
+    if file.metadata()?.len() > 0 {
+        let mut last_byte = [0];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last_byte)?;
+        if last_byte[0] != b'\n' {
+            file.write_all(b"\n")?;
+        }
+    }
+    Ok(file)
+}
+
 pub fn to_absolute_path_string(base_path: &str, path: &str, absolute_path: &mut String) {
     if Path::new(path).is_relative() {
         absolute_path.push_str(base_path);
@@ -789,6 +992,601 @@ pub fn find_path_and_ranges_at(text: &str, index: usize) -> (&str, BufferRangesP
     }
 }
 
+pub fn find_ordered_list_item_number(line: &str) -> Option<Range<usize>> {
+    let from = line.len() - line.trim_start().len();
+    let rest = &line[from..];
+    let to = from
+        + rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+    if from == to {
+        return None;
+    }
+
+    let mut chars = line[to..].chars();
+    match chars.next() {
+        Some('.' | ')') => (),
+        _ => return None,
+    }
+    match chars.next() {
+        None | Some(' ' | '\t') => Some(from..to),
+        _ => None,
+    }
+}
+
+// parses whitespace separated code points like 'U+00E9' (or 'u+e9') into their chars,
+// so multi code point sequences such as 'U+0065 U+0301' can also be entered
+pub fn parse_unicode_code_points(text: &str, output: &mut String) -> bool {
+    let mut parsed_any = false;
+    for code_point in text.split_whitespace() {
+        let hex = match code_point.get(..2) {
+            Some("U+" | "u+") => &code_point[2..],
+            _ => return false,
+        };
+        if hex.is_empty() || hex.len() > 6 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return false;
+        }
+        let c = u32::from_str_radix(hex, 16).ok().and_then(char::from_u32);
+        match c {
+            Some(c) => output.push(c),
+            None => return false,
+        }
+        parsed_any = true;
+    }
+    parsed_any
+}
+
+// there's no unicode names table, so only the chars that are hard to tell apart by sight are named
+pub fn invisible_char_name(c: char) -> Option<&'static str> {
+    let name = match c {
+        '\t' => "CHARACTER TABULATION",
+        '\n' => "LINE FEED",
+        '\r' => "CARRIAGE RETURN",
+        ' ' => "SPACE",
+        '\u{a0}' => "NO-BREAK SPACE",
+        '\u{ad}' => "SOFT HYPHEN",
+        '\u{2002}' => "EN SPACE",
+        '\u{2003}' => "EM SPACE",
+        '\u{2009}' => "THIN SPACE",
+        '\u{200b}' => "ZERO WIDTH SPACE",
+        '\u{200c}' => "ZERO WIDTH NON-JOINER",
+        '\u{200d}' => "ZERO WIDTH JOINER",
+        '\u{200e}' => "LEFT-TO-RIGHT MARK",
+        '\u{200f}' => "RIGHT-TO-LEFT MARK",
+        '\u{2028}' => "LINE SEPARATOR",
+        '\u{2029}' => "PARAGRAPH SEPARATOR",
+        '\u{202f}' => "NARROW NO-BREAK SPACE",
+        '\u{2060}' => "WORD JOINER",
+        '\u{3000}' => "IDEOGRAPHIC SPACE",
+        '\u{feff}' => "ZERO WIDTH NO-BREAK SPACE",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// zero width chars have no equivalent and are removed, while None means there's no safe replacement
+pub fn ascii_equivalent(c: char) -> Option<&'static str> {
+    let equivalent = match c {
+        '\u{a0}' | '\u{2000}'..='\u{200a}' | '\u{202f}' | '\u{205f}' | '\u{3000}' => " ",
+        '\u{ad}' | '\u{200b}'..='\u{200f}' | '\u{2060}' | '\u{feff}' => "",
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => "-",
+        '\u{2018}'..='\u{201b}' => "'",
+        '\u{201c}'..='\u{201f}' => "\"",
+        _ => return None,
+    };
+    Some(equivalent)
+}
+
+pub fn format_utc_date_time(unix_seconds: u64, format: &str, output: &mut String) {
+    use fmt::Write;
+
+    let days = unix_seconds / (24 * 60 * 60);
+    let day_seconds = unix_seconds % (24 * 60 * 60);
+
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_offset = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_offset + 2) / 5 + 1;
+    let month = if month_offset < 10 {
+        month_offset + 3
+    } else {
+        month_offset - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as u64;
+
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        let _ = match chars.next() {
+            Some('Y') => write!(output, "{:04}", year),
+            Some('m') => write!(output, "{:02}", month),
+            Some('d') => write!(output, "{:02}", day),
+            Some('H') => write!(output, "{:02}", day_seconds / (60 * 60)),
+            Some('M') => write!(output, "{:02}", day_seconds / 60 % 60),
+            Some('S') => write!(output, "{:02}", day_seconds % 60),
+            Some('s') => write!(output, "{}", unix_seconds),
+            Some('%') => {
+                output.push('%');
+                Ok(())
+            }
+            Some(c) => {
+                output.push('%');
+                output.push(c);
+                Ok(())
+            }
+            None => {
+                output.push('%');
+                Ok(())
+            }
+        };
+    }
+}
+
+pub fn retab_line(
+    line: &str,
+    tab_size: u8,
+    to_tabs: bool,
+    leading_only: bool,
+    output: &mut String,
+) {
+    let tab_size = tab_size as usize;
+    if tab_size == 0 {
+        output.push_str(line);
+        return;
+    }
+
+    let mut column = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some(&(i, c)) = chars.peek() {
+        if c != ' ' && c != '\t' {
+            if leading_only {
+                output.push_str(&line[i..]);
+                return;
+            }
+
+            chars.next();
+            output.push(c);
+            column += char_display_len(c) as usize;
+            continue;
+        }
+
+        let from_column = column;
+        let mut has_tab = false;
+        while let Some(&(_, c)) = chars.peek() {
+            match c {
+                ' ' => column += 1,
+                '\t' => {
+                    column += tab_size - column % tab_size;
+                    has_tab = true;
+                }
+                _ => break,
+            }
+            chars.next();
+        }
+
+        let mut current_column = from_column;
+        if to_tabs && (has_tab || column - from_column > 1) {
+            loop {
+                let next_tab_stop = current_column + tab_size - current_column % tab_size;
+                if next_tab_stop > column {
+                    break;
+                }
+                output.push('\t');
+                current_column = next_tab_stop;
+            }
+        }
+        for _ in current_column..column {
+            output.push(' ');
+        }
+    }
+}
+
+// the first cell includes the row indentation so that rows with different indentations still align
+fn table_cells<'a>(line: &'a str, delimiter: &'a str) -> impl Iterator<Item = &'a str> {
+    line.split(delimiter)
+        .enumerate()
+        .map(|(i, cell)| if i == 0 { cell.trim_end() } else { cell.trim() })
+}
+
+fn is_markdown_table_separator(cell: &str) -> bool {
+    let cell = cell.strip_prefix(':').unwrap_or(cell);
+    let cell = cell.strip_suffix(':').unwrap_or(cell);
+    !cell.is_empty() && cell.bytes().all(|b| b == b'-')
+}
+
+// grows 'widths' so that each column fits the display width of its cells in 'line'
+pub fn measure_table_row(line: &str, delimiter: &str, tab_size: u8, widths: &mut Vec<usize>) {
+    if delimiter.is_empty() {
+        return;
+    }
+    for (i, cell) in table_cells(line, delimiter).enumerate() {
+        let width = DisplayLen::from(cell).total_len(tab_size);
+        match widths.get_mut(i) {
+            Some(w) => *w = (*w).max(width),
+            None => widths.push(width),
+        }
+    }
+}
+
+// pads each cell in 'line' to its column width. rows may have fewer columns than 'widths'
+pub fn align_table_row(
+    line: &str,
+    delimiter: &str,
+    tab_size: u8,
+    widths: &[usize],
+    output: &mut String,
+) {
+    if delimiter.is_empty() {
+        output.push_str(line);
+        return;
+    }
+
+    let cell_count = table_cells(line, delimiter).count();
+    for (i, cell) in table_cells(line, delimiter).enumerate() {
+        if i > 0 {
+            output.push_str(delimiter);
+            output.push(' ');
+        }
+
+        let width = widths.get(i).copied().unwrap_or(0);
+        let padding = width.saturating_sub(DisplayLen::from(cell).total_len(tab_size));
+        let separator = cell.trim_start();
+        if delimiter == "|" && is_markdown_table_separator(separator) {
+            output.push_str(&cell[..cell.len() - separator.len()]);
+            let has_left_colon = separator.starts_with(':');
+            let has_right_colon = separator.ends_with(':');
+            if has_left_colon {
+                output.push(':');
+            }
+            let dash_count = separator.len() - has_left_colon as usize - has_right_colon as usize;
+            output.extend(std::iter::repeat_n('-', dash_count + padding));
+            if has_right_colon {
+                output.push(':');
+            }
+        } else {
+            output.push_str(cell);
+            if i + 1 < cell_count {
+                output.extend(std::iter::repeat_n(' ', padding));
+            }
+        }
+
+        if i + 1 < cell_count && width > 0 {
+            output.push(' ');
+        }
+    }
+
+    let trimmed_len = output.trim_end().len();
+    output.truncate(trimmed_len);
+}
+
+pub static MARKDOWN_TOC_BEGIN_MARKER: &str = "<!-- toc -->";
+pub static MARKDOWN_TOC_END_MARKER: &str = "<!-- tocstop -->";
+
+// returns the level and title of an atx heading (like '## title')
+pub fn markdown_heading(line: &str) -> Option<(usize, &str)> {
+    let title = line.trim_start_matches('#');
+    let level = line.len() - title.len();
+    if !(1..=6).contains(&level) || !(title.is_empty() || title.starts_with(' ')) {
+        return None;
+    }
+    let title = title.trim();
+    // an optional closing sequence of '#' needs to be preceded by a space
+    let title = match title.trim_end_matches('#') {
+        "" => "",
+        t if t.ends_with(' ') => t.trim_end(),
+        _ => title,
+    };
+    Some((level, title))
+}
+
+// appends the anchor a heading with 'title' gets when rendered (github style)
+pub fn markdown_heading_slug(title: &str, output: &mut String) {
+    for c in title.chars() {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            output.extend(c.to_lowercase());
+        } else if c == ' ' {
+            output.push('-');
+        }
+    }
+}
+
+// writes a nested list linking to every heading in 'lines' (ignoring code blocks)
+pub fn write_markdown_toc<'a, I>(lines: I, output: &mut String)
+where
+    I: Iterator<Item = &'a str> + Clone,
+{
+    let headings = lines
+        .scan(false, |in_code_block, line| {
+            if line.trim_start().starts_with("```") {
+                *in_code_block = !*in_code_block;
+                return Some(None);
+            }
+            if *in_code_block {
+                return Some(None);
+            }
+            Some(markdown_heading(line))
+        })
+        .flatten();
+
+    let min_level = match headings.clone().map(|(level, _)| level).min() {
+        Some(level) => level,
+        None => return,
+    };
+
+    let mut slugs = Vec::new();
+    let mut slug = String::new();
+    for (level, title) in headings {
+        slug.clear();
+        markdown_heading_slug(title, &mut slug);
+        let count = slugs.iter().filter(|s| *s == &slug).count();
+        slugs.push(slug.clone());
+
+        for _ in min_level..level {
+            output.push_str("  ");
+        }
+        output.push_str("- [");
+        output.push_str(title);
+        output.push_str("](#");
+        output.push_str(&slug);
+        if count > 0 {
+            use fmt::Write;
+            let _ = write!(output, "-{}", count);
+        }
+        output.push_str(")\n");
+    }
+}
+
+// replaces each 'delimiter' in 'text' with a line break. when 'respect_quotes' is set, delimiters
+// inside single or double quoted segments are kept
+pub fn split_on_delimiter(
+    text: &str,
+    delimiter: &str,
+    trim: bool,
+    respect_quotes: bool,
+    output: &mut String,
+) {
+    fn push_item(item: &str, trim: bool, is_first: bool, output: &mut String) {
+        if !is_first {
+            output.push('\n');
+        }
+        if trim {
+            output.push_str(item.trim());
+        } else {
+            output.push_str(item);
+        }
+    }
+
+    if delimiter.is_empty() {
+        output.push_str(text);
+        return;
+    }
+
+    let mut item_start = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = text.char_indices();
+    while let Some((i, c)) = chars.next() {
+        if respect_quotes {
+            match quote {
+                Some(_) if escaped => {
+                    escaped = false;
+                    continue;
+                }
+                Some(_) if c == '\\' => {
+                    escaped = true;
+                    continue;
+                }
+                Some(q) => {
+                    if c == q {
+                        quote = None;
+                    }
+                    continue;
+                }
+                None if c == '"' || c == '\'' => {
+                    quote = Some(c);
+                    continue;
+                }
+                None => (),
+            }
+        }
+
+        if text[i..].starts_with(delimiter) {
+            push_item(&text[item_start..i], trim, item_start == 0, output);
+            item_start = i + delimiter.len();
+            for _ in delimiter.chars().skip(1) {
+                chars.next();
+            }
+        }
+    }
+    push_item(&text[item_start..], trim, item_start == 0, output);
+}
+
+// html elements that never have a closing tag
+static VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+// given the text before a cursor that ends with a just typed '>', returns the name of the tag it
+// opens. returns none for closing, self-closing and void element tags. when 'is_jsx' is set, tags
+// right after an identifier are taken to be generic type arguments as in 'useState<string>'
+pub fn closing_tag_name(text: &str, is_jsx: bool) -> Option<&str> {
+    let text = text.strip_suffix('>')?;
+    let tag_start = text.rfind('<')?;
+    if is_jsx && text[..tag_start].ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '.')
+    {
+        return None;
+    }
+
+    let tag = &text[tag_start + 1..];
+    let name_len = tag
+        .find(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.')))
+        .unwrap_or(tag.len());
+    let (name, attributes) = tag.split_at(name_len);
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+    if !attributes.is_empty() && !attributes.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let mut quote = None;
+    let mut brace_depth = 0;
+    for c in attributes.chars() {
+        match quote {
+            Some(q) => {
+                if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' | '`' => quote = Some(c),
+                '{' => brace_depth += 1,
+                '}' => brace_depth -= 1,
+                '>' if brace_depth == 0 => return None,
+                _ => (),
+            },
+        }
+    }
+    if quote.is_some() || brace_depth != 0 || attributes.ends_with('/') {
+        return None;
+    }
+
+    if VOID_ELEMENTS.iter().any(|e| e.eq_ignore_ascii_case(name)) {
+        return None;
+    }
+
+    Some(name)
+}
+
+pub enum EvalMathError {
+    UnexpectedEnd,
+    UnexpectedChar(char),
+    InvalidNumber,
+    DivisionByZero,
+}
+impl fmt::Display for EvalMathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => f.write_str("unexpected end of expression"),
+            Self::UnexpectedChar(c) => write!(f, "unexpected char '{}'", c),
+            Self::InvalidNumber => f.write_str("invalid number"),
+            Self::DivisionByZero => f.write_str("division by zero"),
+        }
+    }
+}
+
+pub fn eval_math(expression: &str) -> Result<f64, EvalMathError> {
+    struct Parser<'a>(&'a str);
+    impl<'a> Parser<'a> {
+        fn peek(&mut self) -> Option<char> {
+            self.0 = self.0.trim_start();
+            self.0.chars().next()
+        }
+
+        fn consume(&mut self, c: char) -> bool {
+            if self.peek() == Some(c) {
+                self.0 = &self.0[c.len_utf8()..];
+                true
+            } else {
+                false
+            }
+        }
+
+        fn expression(&mut self) -> Result<f64, EvalMathError> {
+            let mut value = self.term()?;
+            loop {
+                if self.consume('+') {
+                    value += self.term()?;
+                } else if self.consume('-') {
+                    value -= self.term()?;
+                } else {
+                    break Ok(value);
+                }
+            }
+        }
+
+        fn term(&mut self) -> Result<f64, EvalMathError> {
+            let mut value = self.factor()?;
+            loop {
+                if self.consume('*') {
+                    value *= self.factor()?;
+                } else if self.consume('/') {
+                    let divisor = self.factor()?;
+                    if divisor == 0.0 {
+                        break Err(EvalMathError::DivisionByZero);
+                    }
+                    value /= divisor;
+                } else if self.consume('%') {
+                    let divisor = self.factor()?;
+                    if divisor == 0.0 {
+                        break Err(EvalMathError::DivisionByZero);
+                    }
+                    value %= divisor;
+                } else {
+                    break Ok(value);
+                }
+            }
+        }
+
+        fn factor(&mut self) -> Result<f64, EvalMathError> {
+            match self.peek() {
+                None => Err(EvalMathError::UnexpectedEnd),
+                Some('-') => {
+                    self.consume('-');
+                    Ok(-self.factor()?)
+                }
+                Some('+') => {
+                    self.consume('+');
+                    self.factor()
+                }
+                Some('(') => {
+                    self.consume('(');
+                    let value = self.expression()?;
+                    match self.peek() {
+                        Some(')') => {
+                            self.consume(')');
+                            Ok(value)
+                        }
+                        Some(c) => Err(EvalMathError::UnexpectedChar(c)),
+                        None => Err(EvalMathError::UnexpectedEnd),
+                    }
+                }
+                Some(c) if c.is_ascii_digit() || c == '.' => {
+                    let len = self
+                        .0
+                        .find(|c: char| !c.is_ascii_digit() && c != '.' && c != '_')
+                        .unwrap_or(self.0.len());
+                    let (number, rest) = self.0.split_at(len);
+                    self.0 = rest;
+                    if number.contains('_') {
+                        let number: String = number.chars().filter(|&c| c != '_').collect();
+                        number.parse().map_err(|_| EvalMathError::InvalidNumber)
+                    } else {
+                        number.parse().map_err(|_| EvalMathError::InvalidNumber)
+                    }
+                }
+                Some(c) => Err(EvalMathError::UnexpectedChar(c)),
+            }
+        }
+    }
+
+    let mut parser = Parser(expression);
+    let value = parser.expression()?;
+    match parser.peek() {
+        Some(c) => Err(EvalMathError::UnexpectedChar(c)),
+        None => Ok(value),
+    }
+}
+
 pub fn validate_process_command(command: &str) -> bool {
     CommandTokenizer(command).next().is_some()
 }
@@ -889,6 +1687,71 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_keymap_leader() {
+        fn parse(text: &str) -> Vec<Key> {
+            KeyParser::new(text).map(|k| k.unwrap()).collect()
+        }
+
+        fn assert_replaced_with(keymaps: &KeyMapCollection, from: &str, to: &str) {
+            match keymaps.matches(ModeKind::Normal, &parse(from), true) {
+                MatchResult::ReplaceWith { keys, .. } => assert_eq!(parse(to), keys),
+                _ => panic!("'{}' did not match", from),
+            }
+        }
+
+        let mut keymaps = KeyMapCollection::default();
+        keymaps
+            .parse_and_map(ModeKind::Normal, "<leader>ff", "<leader>x")
+            .unwrap();
+        assert_replaced_with(&keymaps, "<space>ff", "<space>x");
+
+        keymaps.set_leader(",").unwrap();
+        keymaps
+            .parse_and_map(ModeKind::Normal, "a<leader>b<leader>", "c")
+            .unwrap();
+        assert_replaced_with(&keymaps, "a,b,", "c");
+        assert_replaced_with(&keymaps, "<space>ff", "<space>x");
+
+        match keymaps.parse_and_map(ModeKind::Normal, "<leader><c-", "") {
+            Err(ParseKeyMapError::From(error)) => assert!(error.index >= 8),
+            _ => panic!(),
+        }
+        assert!(keymaps.set_leader("<leader>").is_err());
+    }
+
+    #[test]
+    fn test_keymap_prefix_of_longer_mapping() {
+        fn parse(text: &str) -> Vec<Key> {
+            KeyParser::new(text).map(|k| k.unwrap()).collect()
+        }
+
+        let mut keymaps = KeyMapCollection::default();
+        keymaps.parse_and_map(ModeKind::Normal, "g", "a").unwrap();
+        keymaps.parse_and_map(ModeKind::Normal, "gg", "b").unwrap();
+
+        assert!(matches!(
+            keymaps.matches(ModeKind::Normal, &parse("g"), true),
+            MatchResult::Prefix
+        ));
+        match keymaps.matches(ModeKind::Normal, &parse("g"), false) {
+            MatchResult::ReplaceWith { len: 1, keys } => assert_eq!(parse("a"), keys),
+            _ => panic!(),
+        }
+        match keymaps.matches(ModeKind::Normal, &parse("gg"), true) {
+            MatchResult::ReplaceWith { len: 2, keys } => assert_eq!(parse("b"), keys),
+            _ => panic!(),
+        }
+        match keymaps.matches(ModeKind::Normal, &parse("gx"), true) {
+            MatchResult::ReplaceWith { len: 1, keys } => assert_eq!(parse("a"), keys),
+            _ => panic!(),
+        }
+        assert!(matches!(
+            keymaps.matches(ModeKind::Normal, &parse("x"), true),
+            MatchResult::None
+        ));
+    }
+
     #[test]
     fn test_find_delimiter_pair_at() {
         let text = "|a|bcd|efg|";
@@ -900,6 +1763,292 @@ mod tests {
         assert_eq!(None, find_delimiter_pair_at(text, 11, '|'));
     }
 
+    #[test]
+    fn test_ascii_equivalent() {
+        assert_eq!(None, ascii_equivalent('a'));
+        assert_eq!(None, ascii_equivalent('é'));
+        assert_eq!(Some(" "), ascii_equivalent('\u{a0}'));
+        assert_eq!(Some(""), ascii_equivalent('\u{200b}'));
+        assert_eq!(Some(""), ascii_equivalent('\u{feff}'));
+        assert_eq!(Some("-"), ascii_equivalent('\u{2014}'));
+        assert_eq!(Some("'"), ascii_equivalent('\u{2019}'));
+        assert_eq!(Some("\""), ascii_equivalent('\u{201c}'));
+    }
+
+    #[test]
+    fn test_parse_unicode_code_points() {
+        fn parse(text: &str) -> Option<String> {
+            let mut output = String::new();
+            if parse_unicode_code_points(text, &mut output) {
+                Some(output)
+            } else {
+                None
+            }
+        }
+
+        assert_eq!(None, parse(""));
+        assert_eq!(None, parse("00E9"));
+        assert_eq!(None, parse("U+"));
+        assert_eq!(None, parse("U+xyz"));
+        assert_eq!(None, parse("U++E9"));
+        assert_eq!(None, parse("U+D800"));
+        assert_eq!(None, parse("U+110000"));
+        assert_eq!(None, parse("U+0000041"));
+        assert_eq!(Some("\u{e9}".into()), parse("U+00E9"));
+        assert_eq!(Some("\u{1f600}".into()), parse("u+1f600"));
+        assert_eq!(Some("e\u{301}".into()), parse(" U+0065  U+0301 "));
+    }
+
+    #[test]
+    fn test_find_ordered_list_item_number() {
+        assert_eq!(None, find_ordered_list_item_number(""));
+        assert_eq!(None, find_ordered_list_item_number("text"));
+        assert_eq!(None, find_ordered_list_item_number("1"));
+        assert_eq!(None, find_ordered_list_item_number("1.5"));
+        assert_eq!(None, find_ordered_list_item_number("- item"));
+        assert_eq!(Some(0..1), find_ordered_list_item_number("1."));
+        assert_eq!(Some(0..1), find_ordered_list_item_number("1. item"));
+        assert_eq!(Some(0..2), find_ordered_list_item_number("12) item"));
+        assert_eq!(Some(2..4), find_ordered_list_item_number("  34.\titem"));
+    }
+
+    #[test]
+    fn test_format_utc_date_time() {
+        fn format(unix_seconds: u64, format: &str) -> String {
+            let mut output = String::new();
+            format_utc_date_time(unix_seconds, format, &mut output);
+            output
+        }
+
+        assert_eq!("1970-01-01", format(0, "%Y-%m-%d"));
+        assert_eq!("00:00:00", format(0, "%H:%M:%S"));
+        assert_eq!(
+            "2000-02-29T12:34:56Z",
+            format(951827696, "%Y-%m-%dT%H:%M:%SZ")
+        );
+        assert_eq!(
+            "2021-12-31 23:59:59",
+            format(1640995199, "%Y-%m-%d %H:%M:%S")
+        );
+        assert_eq!("%x % 1640995199 %", format(1640995199, "%x % %s %%"));
+    }
+
+    #[test]
+    fn test_retab_line() {
+        fn retab(line: &str, to_tabs: bool, leading_only: bool) -> String {
+            let mut output = String::new();
+            retab_line(line, 4, to_tabs, leading_only, &mut output);
+            output
+        }
+
+        assert_eq!("", retab("", false, true));
+        assert_eq!("", retab("", true, true));
+        assert_eq!("        a\tb", retab("\t\ta\tb", false, true));
+        assert_eq!("    ab  c", retab("\tab\tc", false, false));
+        assert_eq!("      a", retab("\t  a", false, true));
+        assert_eq!("      a", retab("  \t  a", false, true));
+        assert_eq!("\t\ta    b", retab("        a    b", true, true));
+        assert_eq!("\t  a", retab("      a", true, true));
+        assert_eq!("\ta\tb c", retab("    a   b c", true, false));
+        assert_eq!(" a", retab(" a", true, true));
+        assert_eq!("\t", retab("  \t", true, true));
+    }
+
+    #[test]
+    fn align_table() {
+        fn align(lines: &[&str], delimiter: &str) -> Vec<String> {
+            let mut widths = Vec::new();
+            for line in lines {
+                measure_table_row(line, delimiter, 4, &mut widths);
+            }
+            lines
+                .iter()
+                .map(|line| {
+                    let mut output = String::new();
+                    align_table_row(line, delimiter, 4, &widths, &mut output);
+                    output
+                })
+                .collect()
+        }
+
+        assert_eq!(
+            vec!["| a   | bb |", "| :-- | -: |", "| ccc | d  |"],
+            align(&["|a|bb|", "|:-|-:|", "| ccc | d |"], "|"),
+        );
+        assert_eq!(
+            vec!["a   , bb , c", "ccc , d", "  x , y  , z"],
+            align(&["a,bb,c", "ccc,d", "  x,y,z"], ","),
+        );
+        assert_eq!(vec!["a", "bb , c"], align(&["a", "bb,c"], ","));
+        assert_eq!(
+            vec!["  abc | d", "  --- | :-:"],
+            align(&["  abc|d", "  -|:-:"], "|"),
+        );
+        assert_eq!(vec!["a,b"], align(&["a,b"], ""));
+    }
+
+    #[test]
+    fn markdown_toc() {
+        assert_eq!(Some((1, "title")), markdown_heading("# title"));
+        assert_eq!(Some((2, "title")), markdown_heading("##  title ##"));
+        assert_eq!(Some((3, "C#")), markdown_heading("### C#"));
+        assert_eq!(Some((1, "")), markdown_heading("#"));
+        assert_eq!(None, markdown_heading("#title"));
+        assert_eq!(None, markdown_heading("####### title"));
+        assert_eq!(None, markdown_heading("title"));
+
+        let mut slug = String::new();
+        markdown_heading_slug("Hello, `World` 2_a-b!", &mut slug);
+        assert_eq!("hello-world-2_a-b", slug);
+
+        let text = "# doc\n## Install\n```\n# not a heading\n```\n### From Source\n## Install\n";
+        let mut toc = String::new();
+        write_markdown_toc(text.lines(), &mut toc);
+        assert_eq!(
+            "- [doc](#doc)\n  - [Install](#install)\n    - [From Source](#from-source)\n  - [Install](#install-1)\n",
+            toc,
+        );
+    }
+
+    #[test]
+    fn related_file_rules() {
+        let mut rules = RelatedFileRules::default();
+        rules.add(".h", ".c");
+        rules.add(".h", ".cpp");
+        rules.add("/mod.rs", ".rs");
+        rules.add(".h", ".c");
+
+        let related = |path| {
+            let mut paths = Vec::new();
+            rules.for_each_related_path(path, &mut String::new(), |p| paths.push(p.to_string()));
+            paths
+        };
+
+        assert!(related("README.md").is_empty());
+        assert_eq!(vec!["src/a.c", "src/a.cpp"], related("src/a.h"));
+        assert_eq!(vec!["src/a.h"], related("src/a.c"));
+        assert_eq!(
+            vec!["src/a.rs", "src/a/mod/mod.rs"],
+            related("src/a/mod.rs")
+        );
+        assert_eq!(vec!["src/a/mod.rs"], related("src/a.rs"));
+    }
+
+    #[test]
+    fn debug_print_rules() {
+        let mut rules = DebugPrintRules::default();
+        rules.add("**/*.rs", "dbg!($SEL);").unwrap();
+        rules.add("**/*.{js,ts}", "console.log($SEL);").unwrap();
+        rules
+            .add("**/*.rs", "println!(\"$SEL = {:?}\", $SEL);")
+            .unwrap();
+        assert!(rules.add("{", "").is_err());
+
+        assert_eq!(None, rules.template_for("README.md"));
+        assert_eq!(Some("console.log($SEL);"), rules.template_for("src/a.ts"));
+        let template = rules.template_for("src/a.rs").unwrap();
+
+        let mut output = String::new();
+        expand_debug_print_template(template, "a.b", &mut output);
+        assert_eq!("println!(\"a.b = {:?}\", a.b);", output);
+    }
+
+    #[test]
+    fn formatter_rules() {
+        let mut rules = FormatterRules::default();
+        rules.add("**/*.rs", "rustfmt").unwrap();
+        rules.add("**/*.py", "black -q -").unwrap();
+        rules.add("**/*.rs", "rustfmt --edition 2021").unwrap();
+        assert!(rules.add("{", "").is_err());
+
+        assert_eq!(None, rules.command_for("README.md"));
+        assert_eq!(Some("black -q -"), rules.command_for("src/a.py"));
+        assert_eq!(
+            Some("rustfmt --edition 2021"),
+            rules.command_for("src/a.rs")
+        );
+    }
+
+    #[test]
+    fn test_split_on_delimiter() {
+        fn split(text: &str, delimiter: &str, trim: bool, respect_quotes: bool) -> String {
+            let mut output = String::new();
+            split_on_delimiter(text, delimiter, trim, respect_quotes, &mut output);
+            output
+        }
+
+        assert_eq!("", split("", ",", false, false));
+        assert_eq!("a", split("a", ",", false, false));
+        assert_eq!("a\n b\n c", split("a, b, c", ",", false, false));
+        assert_eq!("a\nb\nc", split(" a , b,c ", ",", true, false));
+        assert_eq!("a\nb\n", split("a, b, ", ", ", false, false));
+        assert_eq!("a\n\"b\n c\"", split("a,\"b, c\"", ",", false, false));
+        assert_eq!("a\n\"b, c\"", split("a,\"b, c\"", ",", false, true));
+        assert_eq!("'a\\', b'\nc", split("'a\\', b',c", ",", false, true));
+        assert_eq!(
+            "\u{e1}\n\u{e9}",
+            split("\u{e1}::\u{e9}", "::", false, false)
+        );
+        assert_eq!("a,b", split("a,b", "", false, false));
+    }
+
+    #[test]
+    fn test_closing_tag_name() {
+        assert_eq!(None, closing_tag_name("", false));
+        assert_eq!(None, closing_tag_name("<div", false));
+        assert_eq!(Some("div"), closing_tag_name("<div>", false));
+        assert_eq!(
+            Some("div"),
+            closing_tag_name("  <a href=\"#\"></a><div>", false)
+        );
+        assert_eq!(
+            Some("my-item"),
+            closing_tag_name("<my-item class='a > b'>", false)
+        );
+        assert_eq!(Some("svg:g"), closing_tag_name("<svg:g>", false));
+        assert_eq!(
+            Some("Foo.Bar"),
+            closing_tag_name("<Foo.Bar x={a > b}>", false)
+        );
+        assert_eq!(None, closing_tag_name("</div>", false));
+        assert_eq!(None, closing_tag_name("<br/>", false));
+        assert_eq!(None, closing_tag_name("<img src=\"a.png\" />", false));
+        assert_eq!(None, closing_tag_name("<BR>", false));
+        assert_eq!(None, closing_tag_name("<!DOCTYPE html>", false));
+        assert_eq!(None, closing_tag_name("<?xml version=\"1.0\"?>", false));
+        assert_eq!(None, closing_tag_name("<>", false));
+        assert_eq!(Some("b"), closing_tag_name("some<b>", false));
+        assert_eq!(None, closing_tag_name("useState<string>", true));
+        assert_eq!(Some("div"), closing_tag_name("return (<div>", true));
+        assert_eq!(None, closing_tag_name("<div onClick={() =>", false));
+    }
+
+    #[test]
+    fn test_eval_math() {
+        fn eval(expression: &str) -> Option<f64> {
+            eval_math(expression).ok()
+        }
+
+        assert_eq!(None, eval(""));
+        assert_eq!(None, eval("1 +"));
+        assert_eq!(None, eval("(1 + 2"));
+        assert_eq!(None, eval("1 + 2)"));
+        assert_eq!(None, eval("1 / 0"));
+        assert_eq!(None, eval("1 % 0"));
+        assert_eq!(None, eval("1.2.3"));
+        assert_eq!(None, eval("a"));
+        assert_eq!(Some(3.0), eval("3"));
+        assert_eq!(Some(1.5), eval(" 1.5 "));
+        assert_eq!(Some(1000.0), eval("1_000"));
+        assert_eq!(Some(7.0), eval("1 + 2 * 3"));
+        assert_eq!(Some(9.0), eval("(1 + 2) * 3"));
+        assert_eq!(Some(1.0), eval("7 % 3"));
+        assert_eq!(Some(2.5), eval("5 / 2"));
+        assert_eq!(Some(-4.0), eval("-(1 + 3)"));
+        assert_eq!(Some(4.0), eval("2 - -2"));
+        assert_eq!(Some(0.0), eval("10 - 4 - 6"));
+    }
+
     #[test]
     fn test_parse_path() {
         fn parse(text: &str) -> &str {
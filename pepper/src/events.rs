@@ -21,6 +21,17 @@ impl EditorEventTextInsert {
     }
 }
 
+#[derive(Clone, Copy)]
+pub struct EditorEventText {
+    from: u32,
+    to: u32,
+}
+impl EditorEventText {
+    pub fn as_str<'a>(&self, reader: &'a EditorEventReader) -> &'a str {
+        &reader.0.texts[self.from as usize..self.to as usize]
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct EditorEventTextInserts {
     from: u32,
@@ -74,6 +85,10 @@ pub enum EditorEvent {
     BufferClose {
         handle: BufferHandle,
     },
+    BufferRename {
+        handle: BufferHandle,
+        old_path: EditorEventText,
+    },
     FixCursors {
         handle: BufferViewHandle,
         cursors: EditorEventCursors,
@@ -124,6 +139,16 @@ impl EditorEventWriter {
         self.0.events.push(event);
     }
 
+    pub(crate) fn enqueue_buffer_rename(&mut self, handle: BufferHandle, old_path: &str) {
+        let from = self.0.texts.len() as _;
+        self.0.texts.push_str(old_path);
+        let to = self.0.texts.len() as _;
+        self.0.events.push(EditorEvent::BufferRename {
+            handle,
+            old_path: EditorEventText { from, to },
+        });
+    }
+
     pub fn buffer_text_inserts_mut_guard(
         &mut self,
         handle: BufferHandle,
@@ -0,0 +1,200 @@
+use std::process::{Command, Stdio};
+
+use crate::{
+    buffer::{BufferContent, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    editor::Editor,
+    editor_utils::LogKind,
+    events::BufferEditMutGuard,
+    platform::{Platform, PlatformProcessHandle, PlatformRequest, ProcessTag},
+};
+
+// an external formatter that reads the whole buffer from its stdin and writes it formatted to its
+// stdout. its output is only applied if it exits successfully and wrote something for a non empty
+// buffer so that partial output from a failed run never replaces the buffer
+#[derive(Default)]
+pub struct FormatterProcess {
+    input: Vec<u8>,
+    output: Vec<u8>,
+    content_hash: u64,
+    waiting_for_process: bool,
+}
+
+impl FormatterProcess {
+    pub fn is_running(&self) -> bool {
+        self.waiting_for_process
+    }
+
+    pub fn clear(&mut self) {
+        self.input.clear();
+        self.output.clear();
+        self.waiting_for_process = false;
+    }
+
+    pub(crate) fn spawn_process(
+        &mut self,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        mut command: Command,
+        content: &BufferContent,
+    ) {
+        self.input.clear();
+        for line in content.lines() {
+            self.input.extend_from_slice(line.as_str().as_bytes());
+            self.input.push(b'\n');
+        }
+        self.output.clear();
+        self.content_hash = content.hash();
+        self.waiting_for_process = true;
+
+        command.stdin(Stdio::piped());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::Formatter(buffer_handle),
+            command,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub(crate) fn on_process_spawned(
+        &mut self,
+        platform: &mut Platform,
+        handle: PlatformProcessHandle,
+    ) {
+        // the input is closed even if the buffer was closed meanwhile so the formatter can exit
+        if self.waiting_for_process {
+            let mut buf = platform.buf_pool.acquire();
+            buf.write().extend_from_slice(&self.input);
+            platform
+                .requests
+                .enqueue(PlatformRequest::WriteToProcess { handle, buf });
+        }
+        platform
+            .requests
+            .enqueue(PlatformRequest::CloseProcessInput { handle });
+    }
+
+    pub(crate) fn on_process_output(&mut self, bytes: &[u8]) {
+        if self.waiting_for_process {
+            self.output.extend_from_slice(bytes);
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum FormatResult<'a> {
+    Unchanged,
+    Formatted(&'a str),
+    Failed(&'static str),
+}
+
+fn format_result<'a>(success: bool, input: &[u8], output: &'a [u8]) -> FormatResult<'a> {
+    if !success {
+        return FormatResult::Failed("formatter failed");
+    }
+    if output == input {
+        return FormatResult::Unchanged;
+    }
+    if output.is_empty() {
+        return match input {
+            b"\n" => FormatResult::Unchanged,
+            _ => FormatResult::Failed("formatter wrote no output"),
+        };
+    }
+    let text = match std::str::from_utf8(output) {
+        Ok(text) => text,
+        Err(_) => return FormatResult::Failed("formatter output is not valid utf-8"),
+    };
+    // every buffer line is written followed by a line break
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let text = text.strip_suffix('\r').unwrap_or(text);
+    FormatResult::Formatted(text)
+}
+
+// the whole buffer is replaced as a single undo group and cursors are kept at their line and
+// column, which is close enough to where they were as formatting mostly changes whitespace
+pub(crate) fn on_process_exit(editor: &mut Editor, buffer_handle: BufferHandle, success: bool) {
+    let buffer = editor.buffers.get_mut(buffer_handle);
+    let formatter = &mut buffer.formatter;
+    if !formatter.waiting_for_process {
+        return;
+    }
+    formatter.waiting_for_process = false;
+    let input = std::mem::take(&mut formatter.input);
+    let output = std::mem::take(&mut formatter.output);
+    let content_hash = formatter.content_hash;
+
+    match format_result(success, &input, &output) {
+        FormatResult::Unchanged => (),
+        FormatResult::Failed(error) => editor.logger.write(LogKind::Error).str(error),
+        FormatResult::Formatted(_) if buffer.content().hash() != content_hash => editor
+            .logger
+            .write(LogKind::Error)
+            .str("buffer changed while formatting"),
+        FormatResult::Formatted(text) => {
+            buffer.commit_edits();
+
+            let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+            let mut events = BufferEditMutGuard::new(editor.events.writer(), buffer_handle);
+            buffer.delete_range(&mut editor.word_database, range, events.to_range_deletes());
+            buffer.insert_text(
+                &mut editor.word_database,
+                BufferPosition::zero(),
+                text,
+                events.to_text_inserts(),
+            );
+            std::mem::drop(events);
+            buffer.commit_edits();
+
+            for buffer_view in editor.buffer_views.iter() {
+                if buffer_view.buffer_handle != buffer_handle {
+                    continue;
+                }
+                let mut fix_cursors = editor
+                    .events
+                    .writer()
+                    .fix_cursors_mut_guard(buffer_view.handle());
+                for &cursor in &buffer_view.cursors[..] {
+                    fix_cursors.add(cursor);
+                }
+            }
+        }
+    }
+
+    let formatter = &mut editor.buffers.get_mut(buffer_handle).formatter;
+    formatter.input = input;
+    formatter.output = output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatter_output() {
+        assert_eq!(FormatResult::Unchanged, format_result(true, b"a\n", b"a\n"));
+        assert_eq!(
+            FormatResult::Formatted("a\n  b"),
+            format_result(true, b"a\nb\n", b"a\n  b\n")
+        );
+        assert_eq!(
+            FormatResult::Formatted("a"),
+            format_result(true, b"a\n\n", b"a\r\n")
+        );
+        assert_eq!(FormatResult::Unchanged, format_result(true, b"\n", b""));
+        assert!(matches!(
+            format_result(true, b"a\n", b""),
+            FormatResult::Failed(_)
+        ));
+        assert!(matches!(
+            format_result(true, b"a\n", b"\xff\n"),
+            FormatResult::Failed(_)
+        ));
+        assert!(matches!(
+            format_result(false, b"a\nb\n", b"a\n"),
+            FormatResult::Failed(_)
+        ));
+    }
+}
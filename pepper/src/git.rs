@@ -0,0 +1,770 @@
+use std::{
+    ffi::OsString,
+    fmt::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use crate::{
+    buffer::{BufferContent, BufferHandle},
+    buffer_position::{BufferPosition, BufferRange},
+    editor::EditorContext,
+    editor_utils::{format_utc_date_time, LogKind, Logger},
+    platform::{Platform, PlatformRequest, ProcessTag},
+};
+
+// above this many edits, the rest of the changed region is reported as a single hunk
+const MAX_DIFF_EDIT_DISTANCE: usize = 1000;
+
+#[derive(Default)]
+pub struct GitBlame {
+    annotations: String,
+    line_ranges: Vec<(u32, u32)>,
+    process_output: Vec<u8>,
+    waiting_for_process: bool,
+}
+
+impl GitBlame {
+    pub fn is_empty(&self) -> bool {
+        self.line_ranges.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.annotations.clear();
+        self.line_ranges.clear();
+        self.process_output.clear();
+        self.waiting_for_process = false;
+    }
+
+    pub fn line_annotation(&self, line_index: usize) -> Option<&str> {
+        let &(from, to) = self.line_ranges.get(line_index)?;
+        if from < to {
+            Some(&self.annotations[from as usize..to as usize])
+        } else {
+            None
+        }
+    }
+
+    pub fn spawn_process(
+        &mut self,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        current_directory: &Path,
+        path: &Path,
+    ) {
+        self.clear();
+        self.waiting_for_process = true;
+
+        let mut command = Command::new("git");
+        command.current_dir(current_directory);
+        command.args(["blame", "--line-porcelain", "--"]);
+        command.arg(path);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::GitBlame(buffer_handle),
+            command,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub(crate) fn on_process_output(&mut self, bytes: &[u8]) {
+        if self.waiting_for_process {
+            self.process_output.extend_from_slice(bytes);
+        }
+    }
+
+    pub(crate) fn on_process_exit(&mut self, success: bool, path: &Path, logger: &mut Logger) {
+        if !self.waiting_for_process {
+            return;
+        }
+        self.waiting_for_process = false;
+
+        if !success {
+            self.process_output.clear();
+            logger
+                .write(LogKind::Error)
+                .fmt(format_args!("could not git blame '{}'", path.display()));
+            return;
+        }
+
+        let output = std::mem::take(&mut self.process_output);
+        if let Ok(output) = std::str::from_utf8(&output) {
+            self.parse_line_porcelain(output);
+        }
+        self.process_output = output;
+        self.process_output.clear();
+    }
+
+    fn parse_line_porcelain(&mut self, output: &str) {
+        let mut commit = "";
+        let mut line_number: usize = 0;
+        let mut author = "";
+        let mut author_time: u64 = 0;
+        let mut author_tz = 0;
+        let mut summary = "";
+
+        for line in output.lines() {
+            if let Some(text) = line.strip_prefix("author ") {
+                author = text;
+            } else if let Some(text) = line.strip_prefix("author-time ") {
+                author_time = text.parse().unwrap_or(0);
+            } else if let Some(text) = line.strip_prefix("author-tz ") {
+                author_tz = parse_timezone_offset(text);
+            } else if let Some(text) = line.strip_prefix("summary ") {
+                summary = text;
+            } else if line.starts_with('\t') {
+                let line_index = line_number.saturating_sub(1);
+                if self.line_ranges.len() <= line_index {
+                    self.line_ranges.resize(line_index + 1, (0, 0));
+                }
+
+                let from = self.annotations.len();
+                if commit.bytes().all(|b| b == b'0') {
+                    self.annotations.push_str("not committed yet");
+                } else {
+                    let short_commit = &commit[..commit.len().min(8)];
+                    let _ = write!(self.annotations, "{} {} ", short_commit, author);
+                    let time = author_time.saturating_add_signed(author_tz);
+                    format_utc_date_time(time, "%Y-%m-%d", &mut self.annotations);
+                    self.annotations.push(' ');
+                    self.annotations.push_str(summary);
+                }
+                self.line_ranges[line_index] = (from as _, self.annotations.len() as _);
+            } else {
+                let mut words = line.split(' ');
+                match (words.next(), words.next(), words.next()) {
+                    (Some(hash), Some(_), Some(number))
+                        if hash.len() >= 40 && hash.bytes().all(|b| b.is_ascii_hexdigit()) =>
+                    {
+                        commit = hash;
+                        line_number = number.parse().unwrap_or(1).max(1);
+                    }
+                    _ => (),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GitHunkKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GitHunk {
+    pub kind: GitHunkKind,
+    pub from_line_index: u32,
+    pub to_line_index: u32,
+    pub base_from_line_index: u32,
+    pub base_to_line_index: u32,
+}
+
+impl GitHunk {
+    // removed hunks have no lines in the buffer so they are shown at the line above them
+    pub fn sign_line_range(&self) -> (u32, u32) {
+        match self.kind {
+            GitHunkKind::Removed => {
+                let line_index = self.from_line_index.saturating_sub(1);
+                (line_index, line_index + 1)
+            }
+            _ => (self.from_line_index, self.to_line_index),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct GitDiff {
+    base: String,
+    has_base: bool,
+    needs_update: bool,
+    hunks: Vec<GitHunk>,
+    process_output: Vec<u8>,
+    waiting_for_process: bool,
+}
+
+impl GitDiff {
+    pub fn gutter_width(&self) -> usize {
+        if self.has_base {
+            1
+        } else {
+            0
+        }
+    }
+
+    pub fn hunks(&self) -> &[GitHunk] {
+        &self.hunks
+    }
+
+    pub fn line_sign(&self, line_index: usize) -> Option<GitHunkKind> {
+        let line_index = line_index as u32;
+        let index = self
+            .hunks
+            .partition_point(|h| h.sign_line_range().1 <= line_index);
+        let hunk = self.hunks.get(index)?;
+        if hunk.sign_line_range().0 <= line_index {
+            Some(hunk.kind)
+        } else {
+            None
+        }
+    }
+
+    pub fn base_lines(&self, hunk: &GitHunk) -> impl Iterator<Item = &str> {
+        self.base
+            .lines()
+            .skip(hunk.base_from_line_index as _)
+            .take((hunk.base_to_line_index - hunk.base_from_line_index) as _)
+    }
+
+    // fills 'text' with the base lines of the hunk and returns the buffer range they replace
+    // together with where they should be inserted
+    pub fn revert_hunk_edit(
+        &self,
+        content: &BufferContent,
+        hunk: &GitHunk,
+        text: &mut String,
+    ) -> (Option<BufferRange>, BufferPosition) {
+        text.clear();
+        for (i, line) in self.base_lines(hunk).enumerate() {
+            if i > 0 {
+                text.push('\n');
+            }
+            text.push_str(line);
+        }
+
+        let line_count = content.lines().len() as u32;
+        let line_end = |line_index: u32| {
+            let len = content.lines()[line_index as usize].as_str().len();
+            BufferPosition::line_col(line_index, len as _)
+        };
+        let has_base_lines = hunk.base_from_line_index < hunk.base_to_line_index;
+
+        let insert_position = BufferPosition::line_col(hunk.from_line_index, 0);
+        if hunk.from_line_index < hunk.to_line_index {
+            let mut range = BufferRange::between(insert_position, line_end(hunk.to_line_index - 1));
+            if !has_base_lines {
+                if hunk.to_line_index < line_count {
+                    range.to = BufferPosition::line_col(hunk.to_line_index, 0);
+                } else if hunk.from_line_index > 0 {
+                    range.from = line_end(hunk.from_line_index - 1);
+                }
+            }
+            (Some(range), range.from)
+        } else if !has_base_lines {
+            (None, insert_position)
+        } else if hunk.from_line_index < line_count {
+            text.push('\n');
+            (None, insert_position)
+        } else {
+            text.insert(0, '\n');
+            (None, line_end(line_count - 1))
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.base.clear();
+        self.has_base = false;
+        self.needs_update = false;
+        self.hunks.clear();
+        self.process_output.clear();
+        self.waiting_for_process = false;
+    }
+
+    pub(crate) fn invalidate(&mut self) {
+        self.needs_update = self.has_base;
+    }
+
+    pub fn spawn_process(
+        &mut self,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        current_directory: &Path,
+        path: &Path,
+    ) {
+        let path = current_directory.join(path);
+        let (directory, file_name) = match (path.parent(), path.file_name()) {
+            (Some(directory), Some(file_name)) => (directory, file_name),
+            _ => return,
+        };
+
+        self.process_output.clear();
+        self.waiting_for_process = true;
+
+        // ':./' makes the index path relative to the current directory instead of the repository root
+        let mut object = OsString::from(":./");
+        object.push(file_name);
+
+        let mut command = Command::new("git");
+        command.current_dir(directory);
+        command.arg("show");
+        command.arg(object);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::GitDiff(buffer_handle),
+            command,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub(crate) fn on_process_output(&mut self, bytes: &[u8]) {
+        if self.waiting_for_process {
+            self.process_output.extend_from_slice(bytes);
+        }
+    }
+
+    pub(crate) fn on_process_exit(&mut self) {
+        if !self.waiting_for_process {
+            return;
+        }
+        self.waiting_for_process = false;
+
+        self.base.clear();
+        self.hunks.clear();
+        // git prints nothing to stdout when the file is not tracked
+        self.has_base = !self.process_output.is_empty();
+        self.needs_update = self.has_base;
+        if let Ok(base) = std::str::from_utf8(&self.process_output) {
+            self.base.push_str(base);
+        }
+        self.process_output.clear();
+    }
+
+    pub fn update(&mut self, content: &BufferContent) {
+        if !self.needs_update {
+            return;
+        }
+        self.needs_update = false;
+
+        let base: Vec<_> = self.base.lines().collect();
+        let lines: Vec<_> = content.lines().iter().map(|l| l.as_str()).collect();
+        diff_lines(&base, &lines, &mut self.hunks);
+    }
+}
+
+pub fn find_repository_root(path: &Path) -> Option<&Path> {
+    path.ancestors().find(|p| p.join(".git").exists())
+}
+
+#[derive(Clone, Copy)]
+pub enum GitIndexOperation {
+    Stage,
+    Unstage,
+}
+
+impl GitIndexOperation {
+    pub fn spawn_process(
+        self,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        current_directory: &Path,
+        path: &Path,
+    ) {
+        let mut command = Command::new("git");
+        command.current_dir(current_directory);
+        match self {
+            Self::Stage => command.args(["add", "--"]),
+            Self::Unstage => command.args(["reset", "--quiet", "--"]),
+        };
+        command.arg(path);
+        command.stdin(Stdio::null());
+        command.stdout(Stdio::piped());
+        command.stderr(Stdio::null());
+
+        platform.requests.enqueue(PlatformRequest::SpawnProcess {
+            tag: ProcessTag::GitIndex(buffer_handle, self),
+            command,
+            buf_len: 4 * 1024,
+        });
+    }
+
+    pub(crate) fn on_process_exit(
+        self,
+        ctx: &mut EditorContext,
+        buffer_handle: BufferHandle,
+        success: bool,
+    ) {
+        let buffer = ctx.editor.buffers.get_mut(buffer_handle);
+        if !success {
+            let action = match self {
+                Self::Stage => "stage",
+                Self::Unstage => "unstage",
+            };
+            ctx.editor.logger.write(LogKind::Error).fmt(format_args!(
+                "could not {} '{}'",
+                action,
+                buffer.path.display()
+            ));
+            return;
+        }
+
+        if ctx.editor.config.git_gutter {
+            buffer.refresh_git_diff_base(&mut ctx.platform, &ctx.editor.current_directory);
+        }
+
+        let action = match self {
+            Self::Stage => "staged",
+            Self::Unstage => "unstaged",
+        };
+        ctx.editor.logger.write(LogKind::Status).fmt(format_args!(
+            "{} '{}'",
+            action,
+            buffer.path.display()
+        ));
+    }
+}
+
+pub fn diff_lines(base: &[&str], lines: &[&str], hunks: &mut Vec<GitHunk>) {
+    #[derive(Clone, Copy)]
+    enum Op {
+        Keep,
+        Delete,
+        Insert,
+    }
+
+    hunks.clear();
+
+    let prefix_len = base.iter().zip(lines).take_while(|(a, b)| a == b).count();
+    let suffix_len = base[prefix_len..]
+        .iter()
+        .rev()
+        .zip(lines[prefix_len..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &base[prefix_len..base.len() - suffix_len];
+    let b = &lines[prefix_len..lines.len() - suffix_len];
+
+    let mut ops = Vec::new();
+    let max_len = a.len() + b.len();
+    let offset = max_len as isize + 1;
+    let mut v = vec![0; 2 * max_len + 3];
+    let mut trace = Vec::new();
+    let mut edit_distance = None;
+
+    for d in 0..=max_len.min(MAX_DIFF_EDIT_DISTANCE) as isize {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d
+                || (k != d && v[(offset + k - 1) as usize] < v[(offset + k + 1) as usize])
+            {
+                v[(offset + k + 1) as usize]
+            } else {
+                v[(offset + k - 1) as usize] + 1
+            };
+            let mut y = (x as isize - k) as usize;
+            while x < a.len() && y < b.len() && a[x] == b[y] {
+                x += 1;
+                y += 1;
+            }
+            v[(offset + k) as usize] = x;
+
+            if x >= a.len() && y >= b.len() {
+                edit_distance = Some(d);
+                break;
+            }
+        }
+
+        trace.extend_from_slice(&v[(offset - d) as usize..=(offset + d) as usize]);
+        if edit_distance.is_some() {
+            break;
+        }
+    }
+
+    match edit_distance {
+        Some(edit_distance) => {
+            let mut x = a.len();
+            let mut y = b.len();
+            for d in (1..=edit_distance).rev() {
+                let k = x as isize - y as isize;
+                // trace of step 'd - 1' starts at the sum of the lengths of all previous steps
+                let previous_start = ((d - 1) * (d - 1)) as usize;
+                let previous_v = |k: isize| trace[previous_start + (k + d - 1) as usize];
+
+                let previous_k = if k == -d || (k != d && previous_v(k - 1) < previous_v(k + 1)) {
+                    k + 1
+                } else {
+                    k - 1
+                };
+                let previous_x = previous_v(previous_k);
+                let previous_y = (previous_x as isize - previous_k) as usize;
+
+                let (snake_x, op) = if previous_k == k + 1 {
+                    (previous_x, Op::Insert)
+                } else {
+                    (previous_x + 1, Op::Delete)
+                };
+                for _ in snake_x..x {
+                    ops.push(Op::Keep);
+                }
+                ops.push(op);
+
+                x = previous_x;
+                y = previous_y;
+            }
+            for _ in 0..x {
+                ops.push(Op::Keep);
+            }
+            ops.reverse();
+        }
+        None => {
+            ops.resize(a.len(), Op::Delete);
+            ops.resize(a.len() + b.len(), Op::Insert);
+        }
+    }
+
+    let mut base_line_index = prefix_len;
+    let mut line_index = prefix_len;
+    let mut delete_count = 0;
+    let mut insert_count = 0;
+    for op in ops.iter().copied().chain(std::iter::once(Op::Keep)) {
+        match op {
+            Op::Keep => {
+                if delete_count > 0 || insert_count > 0 {
+                    let kind = match (delete_count, insert_count) {
+                        (0, _) => GitHunkKind::Added,
+                        (_, 0) => GitHunkKind::Removed,
+                        _ => GitHunkKind::Modified,
+                    };
+                    hunks.push(GitHunk {
+                        kind,
+                        from_line_index: (line_index - insert_count) as _,
+                        to_line_index: line_index as _,
+                        base_from_line_index: (base_line_index - delete_count) as _,
+                        base_to_line_index: base_line_index as _,
+                    });
+                    delete_count = 0;
+                    insert_count = 0;
+                }
+                base_line_index += 1;
+                line_index += 1;
+            }
+            Op::Delete => {
+                base_line_index += 1;
+                delete_count += 1;
+            }
+            Op::Insert => {
+                line_index += 1;
+                insert_count += 1;
+            }
+        }
+    }
+}
+
+fn parse_timezone_offset(text: &str) -> i64 {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value: i64 = digits.parse().unwrap_or(0);
+    sign * ((value / 100) * 60 * 60 + (value % 100) * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_git_blame_line_porcelain() {
+        let output = concat!(
+            "0123456789abcdef0123456789abcdef01234567 1 1 2\n",
+            "author Jane Doe\n",
+            "author-mail <jane@doe.com>\n",
+            "author-time 1700000000\n",
+            "author-tz -0300\n",
+            "committer Jane Doe\n",
+            "summary first commit\n",
+            "filename file.txt\n",
+            "\tfirst line\n",
+            "0123456789abcdef0123456789abcdef01234567 2 2\n",
+            "author Jane Doe\n",
+            "author-time 1700000000\n",
+            "author-tz -0300\n",
+            "summary first commit\n",
+            "filename file.txt\n",
+            "\tsecond line\n",
+            "0000000000000000000000000000000000000000 3 3 1\n",
+            "author Not Committed Yet\n",
+            "summary Version of file.txt from file.txt\n",
+            "filename file.txt\n",
+            "\tthird line\n",
+        );
+
+        let mut blame = GitBlame::default();
+        blame.parse_line_porcelain(output);
+
+        assert_eq!(
+            Some("01234567 Jane Doe 2023-11-14 first commit"),
+            blame.line_annotation(0)
+        );
+        assert_eq!(
+            Some("01234567 Jane Doe 2023-11-14 first commit"),
+            blame.line_annotation(1)
+        );
+        assert_eq!(Some("not committed yet"), blame.line_annotation(2));
+        assert_eq!(None, blame.line_annotation(3));
+    }
+
+    #[test]
+    fn git_blame_exit() {
+        let mut logger = Logger::new();
+        let mut blame = GitBlame {
+            waiting_for_process: true,
+            ..Default::default()
+        };
+        blame.on_process_output(b"invalid output");
+        blame.on_process_exit(false, Path::new("file.txt"), &mut logger);
+        assert!(blame.is_empty());
+        assert_eq!(
+            "could not git blame 'file.txt'",
+            logger.status_bar_message()
+        );
+    }
+
+    #[test]
+    fn git_blame_is_kept_on_search() {
+        use crate::{buffer::BufferCollection, pattern::Pattern};
+
+        let mut buffers = BufferCollection::default();
+        let buffer = buffers.add_new();
+        buffer.git_blame.parse_line_porcelain(concat!(
+            "0123456789abcdef0123456789abcdef01234567 1 1 1\n",
+            "author Jane Doe\n",
+            "author-time 1700000000\n",
+            "summary first commit\n",
+            "\tfirst line\n",
+        ));
+
+        let mut pattern = Pattern::new();
+        pattern.compile_searcher("line").unwrap();
+        buffer.set_search(&pattern);
+        assert!(!buffer.git_blame.is_empty());
+        assert_eq!(
+            Some("01234567 Jane Doe 2023-11-14 first commit"),
+            buffer.git_blame.line_annotation(0)
+        );
+    }
+
+    #[test]
+    fn diff_lines_into_hunks() {
+        fn hunk(kind: GitHunkKind, lines: (u32, u32), base_lines: (u32, u32)) -> GitHunk {
+            GitHunk {
+                kind,
+                from_line_index: lines.0,
+                to_line_index: lines.1,
+                base_from_line_index: base_lines.0,
+                base_to_line_index: base_lines.1,
+            }
+        }
+
+        let mut hunks = Vec::new();
+
+        diff_lines(&["a", "b"], &["a", "b"], &mut hunks);
+        assert!(hunks.is_empty());
+
+        diff_lines(&[], &["a", "b"], &mut hunks);
+        assert_eq!(&[hunk(GitHunkKind::Added, (0, 2), (0, 0))], &hunks[..]);
+
+        diff_lines(&["a", "b"], &[], &mut hunks);
+        assert_eq!(&[hunk(GitHunkKind::Removed, (0, 0), (0, 2))], &hunks[..]);
+
+        let base = ["a", "b", "c", "d", "e", "f"];
+        let lines = ["a", "x", "c", "d", "n", "e"];
+        diff_lines(&base, &lines, &mut hunks);
+        assert_eq!(
+            &[
+                hunk(GitHunkKind::Modified, (1, 2), (1, 2)),
+                hunk(GitHunkKind::Added, (4, 5), (4, 4)),
+                hunk(GitHunkKind::Removed, (6, 6), (5, 6)),
+            ],
+            &hunks[..]
+        );
+
+        let base = ["a", "b", "c"];
+        let lines = ["b", "c", "a"];
+        diff_lines(&base, &lines, &mut hunks);
+        assert_eq!(
+            &[
+                hunk(GitHunkKind::Removed, (0, 0), (0, 1)),
+                hunk(GitHunkKind::Added, (2, 3), (3, 3)),
+            ],
+            &hunks[..]
+        );
+
+        let diff = GitDiff {
+            hunks,
+            ..Default::default()
+        };
+        assert_eq!(Some(GitHunkKind::Removed), diff.line_sign(0));
+        assert_eq!(None, diff.line_sign(1));
+        assert_eq!(Some(GitHunkKind::Added), diff.line_sign(2));
+        assert_eq!(None, diff.line_sign(3));
+    }
+
+    #[test]
+    fn revert_git_hunk() {
+        fn revert_first_hunk(base: &str, text: &str) -> String {
+            let mut diff = GitDiff {
+                base: base.into(),
+                has_base: true,
+                needs_update: true,
+                ..Default::default()
+            };
+            let mut content = BufferContent::new();
+            content.insert_text(BufferPosition::zero(), text);
+            diff.update(&content);
+
+            let hunk = diff.hunks()[0];
+            let mut text = String::new();
+            let (delete_range, insert_position) = diff.revert_hunk_edit(&content, &hunk, &mut text);
+            if let Some(range) = delete_range {
+                content.delete_range(range);
+            }
+            content.insert_text(insert_position, &text);
+            content.to_string()
+        }
+
+        assert_eq!("a\nc", revert_first_hunk("a\nc", "a\nb\nc"));
+        assert_eq!("a\nb\nc", revert_first_hunk("a\nb\nc", "a\nc"));
+        assert_eq!("a\nb\nc", revert_first_hunk("a\nb\nc", "a\nx\ny\nc"));
+        assert_eq!("a\n\nc", revert_first_hunk("a\n\nc", "a\nx\nc"));
+
+        assert_eq!("a", revert_first_hunk("a", "a\nb"));
+        assert_eq!("a\nb", revert_first_hunk("a\nb", "a"));
+        assert_eq!("b", revert_first_hunk("b", "a\nb"));
+        assert_eq!("a\nb", revert_first_hunk("a\nb", "b"));
+        assert_eq!("a\nb", revert_first_hunk("a\nb", "a\nx"));
+    }
+
+    #[test]
+    fn git_index_operation_exit() {
+        use crate::{client::ClientManager, editor::Editor, plugin::PluginCollection};
+
+        let mut ctx = EditorContext {
+            editor: Editor::new(std::path::PathBuf::new(), String::new()),
+            platform: Platform::default(),
+            clients: ClientManager::default(),
+            plugins: PluginCollection::default(),
+        };
+        let buffer = ctx.editor.buffers.add_new();
+        buffer.set_path(Path::new("file.txt"));
+        let buffer_handle = buffer.handle();
+
+        GitIndexOperation::Stage.on_process_exit(&mut ctx, buffer_handle, false);
+        assert_eq!(
+            "could not stage 'file.txt'",
+            ctx.editor.logger.status_bar_message()
+        );
+
+        GitIndexOperation::Unstage.on_process_exit(&mut ctx, buffer_handle, true);
+        assert_eq!(
+            "unstaged 'file.txt'",
+            ctx.editor.logger.status_bar_message()
+        );
+    }
+}
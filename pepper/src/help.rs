@@ -1,9 +1,17 @@
 use std::{
     io,
+    path::Path,
     sync::atomic::{AtomicPtr, Ordering},
 };
 
-use crate::ResourceFile;
+use crate::{
+    buffer::{BufferProperties, BufferReadError},
+    buffer_position::BufferPosition,
+    client::ClientHandle,
+    cursor::Cursor,
+    editor::EditorContext,
+    ResourceFile,
+};
 
 pub const HELP_PREFIX: &str = "help://";
 
@@ -77,6 +85,66 @@ pub(crate) fn help_page_names() -> impl Iterator<Item = &'static str> {
     HelpPageIterator::new().map(|r| r.name)
 }
 
+// iterates the markdown headings of all help pages as (page name, line index, title) tuples
+pub(crate) fn help_topics() -> impl Iterator<Item = (&'static str, usize, &'static str)> {
+    HelpPageIterator::new()
+        .filter(|page| page.name.ends_with(".md"))
+        .flat_map(|page| {
+            let mut in_code_block = false;
+            page.content
+                .lines()
+                .enumerate()
+                .filter_map(move |(line_index, line)| {
+                    if line.starts_with("```") {
+                        in_code_block = !in_code_block;
+                        return None;
+                    }
+                    if in_code_block {
+                        return None;
+                    }
+
+                    let title = line.trim_start_matches('#');
+                    if title.len() == line.len() || !title.starts_with(' ') {
+                        return None;
+                    }
+                    let title = title.trim().trim_matches('`');
+                    Some((page.name, line_index, title))
+                })
+        })
+}
+
+// opens the help page 'page_name' (or the main one if it's empty) with a cursor at 'line_index'
+pub(crate) fn open_page(
+    ctx: &mut EditorContext,
+    client_handle: ClientHandle,
+    page_name: &str,
+    line_index: usize,
+) -> Result<(), BufferReadError> {
+    let mut buffer_path = ctx.editor.string_pool.acquire();
+    buffer_path.push_str(HELP_PREFIX);
+    buffer_path.push_str(page_name);
+    let result = ctx.editor.buffer_view_handle_from_path(
+        client_handle,
+        Path::new(&buffer_path),
+        BufferProperties::log(),
+        true,
+    );
+    ctx.editor.string_pool.release(buffer_path);
+    let handle = result?;
+
+    let client = ctx.clients.get_mut(client_handle);
+    client.set_buffer_view_handle(Some(handle), &ctx.editor.buffer_views);
+
+    let position = BufferPosition::line_col(line_index as _, 0);
+    let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+    cursors.clear();
+    cursors.add(Cursor {
+        anchor: position,
+        position,
+    });
+    Ok(())
+}
+
 #[derive(Default)]
 pub(crate) struct HelpPageName<'a>(&'a str);
 pub(crate) fn parse_help_page_name(page_name: &str) -> Option<HelpPageName> {
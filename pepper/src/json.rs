@@ -0,0 +1,263 @@
+// json helpers used by the 'pretty-print' and 'minify-json' commands. they work on the json
+// text directly so that strings and numbers are kept exactly as written
+
+// checks that 'text' is a single valid json value surrounded only by whitespace.
+// on error, returns the byte offset where the json stops being valid
+pub fn validate(text: &str) -> Result<(), usize> {
+    let bytes = text.as_bytes();
+    let index = skip_whitespace(bytes, 0);
+    let index = validate_value(bytes, index)?;
+    let index = skip_whitespace(bytes, index);
+    if index < bytes.len() {
+        Err(index)
+    } else {
+        Ok(())
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut index: usize) -> usize {
+    while bytes.get(index).is_some_and(u8::is_ascii_whitespace) {
+        index += 1;
+    }
+    index
+}
+
+fn validate_value(bytes: &[u8], index: usize) -> Result<usize, usize> {
+    match bytes.get(index) {
+        Some(b'{') => validate_sequence(bytes, index, b'}'),
+        Some(b'[') => validate_sequence(bytes, index, b']'),
+        Some(b'"') => validate_string(bytes, index),
+        Some(b't') => validate_literal(bytes, index, b"true"),
+        Some(b'f') => validate_literal(bytes, index, b"false"),
+        Some(b'n') => validate_literal(bytes, index, b"null"),
+        Some(b'-' | b'0'..=b'9') => validate_number(bytes, index),
+        _ => Err(index),
+    }
+}
+
+// objects and arrays only differ by their members having a key
+fn validate_sequence(bytes: &[u8], index: usize, close: u8) -> Result<usize, usize> {
+    let mut index = skip_whitespace(bytes, index + 1);
+    if bytes.get(index) == Some(&close) {
+        return Ok(index + 1);
+    }
+    loop {
+        if close == b'}' {
+            if bytes.get(index) != Some(&b'"') {
+                return Err(index);
+            }
+            index = skip_whitespace(bytes, validate_string(bytes, index)?);
+            if bytes.get(index) != Some(&b':') {
+                return Err(index);
+            }
+            index = skip_whitespace(bytes, index + 1);
+        }
+        index = skip_whitespace(bytes, validate_value(bytes, index)?);
+        match bytes.get(index) {
+            Some(b',') => index = skip_whitespace(bytes, index + 1),
+            Some(&b) if b == close => return Ok(index + 1),
+            _ => return Err(index),
+        }
+    }
+}
+
+fn validate_string(bytes: &[u8], mut index: usize) -> Result<usize, usize> {
+    index += 1;
+    loop {
+        match bytes.get(index) {
+            Some(b'"') => return Ok(index + 1),
+            Some(b'\\') => match bytes.get(index + 1) {
+                Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => index += 2,
+                Some(b'u') => {
+                    for i in index + 2..index + 6 {
+                        if !bytes.get(i).is_some_and(u8::is_ascii_hexdigit) {
+                            return Err(i);
+                        }
+                    }
+                    index += 6;
+                }
+                _ => return Err(index + 1),
+            },
+            Some(&b) if b >= 0x20 => index += 1,
+            _ => return Err(index),
+        }
+    }
+}
+
+fn validate_literal(bytes: &[u8], index: usize, literal: &[u8]) -> Result<usize, usize> {
+    for (i, b) in literal.iter().enumerate() {
+        if bytes.get(index + i) != Some(b) {
+            return Err(index + i);
+        }
+    }
+    Ok(index + literal.len())
+}
+
+fn validate_number(bytes: &[u8], mut index: usize) -> Result<usize, usize> {
+    fn digits(bytes: &[u8], mut index: usize) -> Result<usize, usize> {
+        let start = index;
+        while bytes.get(index).is_some_and(u8::is_ascii_digit) {
+            index += 1;
+        }
+        if index > start {
+            Ok(index)
+        } else {
+            Err(index)
+        }
+    }
+
+    if bytes[index] == b'-' {
+        index += 1;
+    }
+    index = digits(bytes, index)?;
+    if bytes.get(index) == Some(&b'.') {
+        index = digits(bytes, index + 1)?;
+    }
+    if let Some(b'e' | b'E') = bytes.get(index) {
+        index += 1;
+        if let Some(b'+' | b'-') = bytes.get(index) {
+            index += 1;
+        }
+        index = digits(bytes, index)?;
+    }
+    Ok(index)
+}
+
+// removes whitespace outside of strings from json 'text' keeping everything else exactly as
+// written. 'text' is expected to be valid json
+pub fn minify(text: &str, buf: &mut Vec<u8>) {
+    let mut in_string = false;
+    let mut escaped = false;
+    for &b in text.as_bytes() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+        } else if b.is_ascii_whitespace() {
+            continue;
+        } else if b == b'"' {
+            in_string = true;
+        }
+        buf.push(b);
+    }
+}
+
+// reformats json 'text' so that every array element and object member is in its own line
+// prefixed by 'indent' repeated for each nesting level. strings and numbers are kept exactly
+// as written. 'text' is expected to be valid json
+pub fn pretty_print(text: &str, indent: &str, buf: &mut Vec<u8>) {
+    fn append_line_break(buf: &mut Vec<u8>, indent: &str, depth: usize) {
+        buf.push(b'\n');
+        for _ in 0..depth {
+            buf.extend_from_slice(indent.as_bytes());
+        }
+    }
+
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, &b) in bytes.iter().enumerate() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            buf.push(b);
+            continue;
+        }
+
+        match b {
+            b'"' => {
+                in_string = true;
+                buf.push(b);
+            }
+            b'{' | b'[' => {
+                buf.push(b);
+                depth += 1;
+                // empty arrays and objects stay in a single line
+                let next = bytes[i + 1..].iter().find(|b| !b.is_ascii_whitespace());
+                if !matches!(next, Some(b'}' | b']')) {
+                    append_line_break(buf, indent, depth);
+                }
+            }
+            b'}' | b']' => {
+                depth -= 1;
+                if !matches!(buf.last(), Some(b'{' | b'[')) {
+                    append_line_break(buf, indent, depth);
+                }
+                buf.push(b);
+            }
+            b',' => {
+                buf.push(b);
+                append_line_break(buf, indent, depth);
+            }
+            b':' => buf.extend_from_slice(b": "),
+            _ if b.is_ascii_whitespace() => (),
+            _ => buf.push(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn validate() {
+        assert_eq!(
+            Ok(()),
+            super::validate(" {\"a\": [1, -2.5e3, true, null, \"\\u00e1\"]} ")
+        );
+        assert_eq!(Ok(()), super::validate("[]"));
+        assert_eq!(Ok(()), super::validate("\"\\\" \\n\""));
+        assert_eq!(Err(0), super::validate(""));
+        assert_eq!(Err(5), super::validate("[1, 2"));
+        assert_eq!(Err(4), super::validate("[1, ]"));
+        assert_eq!(Err(1), super::validate("{1: 2}"));
+        assert_eq!(Err(5), super::validate("{\"a\" 2}"));
+        assert_eq!(Err(4), super::validate("[tru]"));
+        assert_eq!(Err(3), super::validate("[1.]"));
+        assert_eq!(Err(2), super::validate("1 2"));
+        assert_eq!(Err(3), super::validate("\"\\uz\""));
+    }
+
+    #[test]
+    fn pretty_print() {
+        let mut buf = Vec::new();
+        super::pretty_print(
+            "{\"a\":[1,{\"b\":null}],\"c\":{},\"d\":[ ],\"e\":\"\u{00e1} {[,:]}\"}",
+            "  ",
+            &mut buf,
+        );
+        assert_eq!(
+            "{\n  \"a\": [\n    1,\n    {\n      \"b\": null\n    }\n  ],\n  \"c\": {},\n  \"d\": [],\n  \"e\": \"\u{00e1} {[,:]}\"\n}",
+            String::from_utf8(buf).unwrap()
+        );
+
+        let mut buf = Vec::new();
+        super::pretty_print(" [0.3, 1.50,\n\t1e5, -2.5E-3] ", "\t", &mut buf);
+        assert_eq!(
+            "[\n\t0.3,\n\t1.50,\n\t1e5,\n\t-2.5E-3\n]",
+            String::from_utf8(buf).unwrap()
+        );
+    }
+
+    #[test]
+    fn minify() {
+        let mut buf = Vec::new();
+        super::minify(
+            " {\n  \"a b\": [1.50, \"\\\" \u{00e1}\\\\\"],\n\t\"c\" : {}\n}\n",
+            &mut buf,
+        );
+        assert_eq!(
+            "{\"a b\":[1.50,\"\\\" \u{00e1}\\\\\"],\"c\":{}}",
+            String::from_utf8(buf).unwrap()
+        );
+    }
+}
@@ -10,15 +10,20 @@ pub mod cursor;
 pub mod editor;
 pub mod editor_utils;
 pub mod events;
+pub mod format;
+pub mod git;
 pub mod glob;
 pub mod help;
+pub mod json;
 pub mod mode;
 pub mod navigation_history;
 pub mod pattern;
 pub mod picker;
 pub mod platform;
 pub mod plugin;
+pub mod quickfix;
 pub mod serialization;
+pub mod spell;
 pub mod syntax;
 pub mod theme;
 pub mod ui;
@@ -5,7 +5,7 @@ use crate::{
 };
 
 mod command;
-mod insert;
+pub(crate) mod insert;
 mod normal;
 pub(crate) mod picker;
 pub(crate) mod readline;
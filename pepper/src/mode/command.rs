@@ -173,6 +173,7 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
     }
     ctx.editor.picker.clear_cursor();
 
+    let args = tokens.clone();
     let mut arg_count = 0;
 
     for token in tokens {
@@ -191,10 +192,10 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
     let mut completion_source = CompletionSource::Custom(&[]);
     if arg_count > 0 {
         if let Some(command) = ctx.editor.commands.find_command(command_name) {
-            let completion_index = arg_count - 1;
-            if completion_index < command.completions.len() {
-                completion_source = command.completions[completion_index];
-            }
+            let previous_args = args.take(arg_count - 1).map(|t| t.slice);
+            let (source, skip_len) = command.arg_completion(previous_args, pattern);
+            completion_source = source;
+            pattern = &pattern[skip_len..];
         }
     } else {
         completion_source = CompletionSource::Commands;
@@ -249,6 +250,11 @@ fn update_autocomplete_entries(ctx: &mut EditorContext) {
                     ctx.editor.picker.add_custom_entry(help_page_name);
                 }
             }
+            CompletionSource::Flags(flags) => {
+                for flag in flags {
+                    ctx.editor.picker.add_custom_entry(flag.name);
+                }
+            }
             CompletionSource::Custom(completions) => {
                 for completion in completions {
                     ctx.editor.picker.add_custom_entry(completion);
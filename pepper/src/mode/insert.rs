@@ -1,13 +1,19 @@
-use std::fmt::Write;
+use std::{
+    fmt::Write,
+    time::{Duration, Instant},
+};
 
 use crate::{
     buffer::BufferHandle,
-    buffer_position::{BufferPosition, BufferRange},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
     client::ClientHandle,
+    config::CompletionTrigger,
+    cursor::Cursor,
     editor::{Editor, EditorContext, EditorFlow, KeysIterator},
-    editor_utils::REGISTER_AUTO_MACRO,
+    editor_utils::{closing_tag_name, REGISTER_AUTO_MACRO},
     events::EditorEventTextInsert,
+    glob::Glob,
     mode::{ModeKind, ModeState},
     platform::{Key, KeyCode},
     plugin::{CompletionContext, PluginHandle},
@@ -19,6 +25,7 @@ pub struct State {
     editing_buffer_handle: Option<BufferHandle>,
     completion_positions: Vec<BufferPosition>,
     completing_plugin_handle: Option<PluginHandle>,
+    pending_completion: Option<(ClientHandle, BufferViewHandle, Instant)>,
 }
 
 impl State {
@@ -79,8 +86,13 @@ impl ModeState for State {
             }
         };
 
-        ctx.editor.mode.insert_state.editing_buffer_handle =
-            Some(ctx.editor.buffer_views.get(handle).buffer_handle);
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        ctx.editor.mode.insert_state.editing_buffer_handle = Some(buffer_handle);
+        let indentation_config = ctx
+            .editor
+            .buffers
+            .get(buffer_handle)
+            .indentation_config(&ctx.editor.config);
 
         let key = keys.next(&ctx.editor.buffered_keys);
         let register = ctx.editor.registers.get_mut(REGISTER_AUTO_MACRO);
@@ -112,7 +124,8 @@ impl ModeState for State {
                     &ctx.editor.buffers,
                     CursorMovement::LinesForward {
                         count: 1,
-                        tab_size: ctx.editor.config.tab_size,
+                        tab_size: indentation_config.tab_size,
+                        tab_stops: &ctx.editor.config.tab_stops,
                     },
                     CursorMovementKind::PositionAndAnchor,
                 );
@@ -124,7 +137,8 @@ impl ModeState for State {
                     &ctx.editor.buffers,
                     CursorMovement::LinesBackward {
                         count: 1,
-                        tab_size: ctx.editor.config.tab_size,
+                        tab_size: indentation_config.tab_size,
+                        tab_stops: &ctx.editor.config.tab_stops,
                     },
                     CursorMovementKind::PositionAndAnchor,
                 );
@@ -142,10 +156,10 @@ impl ModeState for State {
             }
             Key { code: KeyCode::Char('\t'), control: false, alt: false, .. } => {
                 static SPACES_BUF: &[u8; u8::MAX as usize] = &[b' '; u8::MAX as usize];
-                let text = if ctx.editor.config.indent_with_tabs {
+                let text = if indentation_config.indent_with_tabs {
                     "\t"
                 } else {
-                    let len = ctx.editor.config.tab_size as usize;
+                    let len = indentation_config.tab_size as usize;
                     unsafe { std::str::from_utf8_unchecked(&SPACES_BUF[..len]) }
                 };
 
@@ -201,6 +215,11 @@ impl ModeState for State {
                     s,
                     ctx.editor.events.writer(),
                 );
+
+                if c == '>' {
+                    ctx.trigger_event_handlers();
+                    auto_close_tags(&mut ctx.editor, handle);
+                }
             }
             Key { code: KeyCode::Backspace, shift: false, control: false, alt: false }
             | Key { code: KeyCode::Char('h'), shift: false, control: true, alt: false } => {
@@ -254,21 +273,156 @@ impl ModeState for State {
         };
 
         ctx.trigger_event_handlers();
-        update_completions(ctx, client_handle, handle);
+
+        // completions are only updated once no key has been typed for 'completion_debounce' ms
+        match ctx.editor.config.completion_debounce {
+            0 => update_completions(ctx, client_handle, handle, false),
+            _ => {
+                ctx.editor.mode.insert_state.pending_completion =
+                    Some((client_handle, handle, Instant::now()))
+            }
+        }
+
         Some(EditorFlow::Continue)
     }
 }
 
+fn auto_close_tags(editor: &mut Editor, buffer_view_handle: BufferViewHandle) {
+    if editor.config.auto_close_tags.is_empty() {
+        return;
+    }
+
+    let buffer_view = editor.buffer_views.get(buffer_view_handle);
+    let buffer = editor.buffers.get_mut(buffer_view.buffer_handle);
+    let path = match buffer.path.to_str() {
+        Some(path) => path,
+        None => return,
+    };
+    let mut glob = Glob::default();
+    if glob.compile(&editor.config.auto_close_tags).is_err() || !glob.matches(path) {
+        return;
+    }
+    let is_jsx = path.ends_with(".jsx") || path.ends_with(".tsx");
+
+    let mut closing_tags = editor.string_pool.acquire();
+    let mut has_closing_tags = false;
+    for cursor in buffer_view.cursors[..].iter() {
+        let position = cursor.position;
+        let line = buffer.content().lines()[position.line_index as usize].as_str();
+        let (before, after) = line.split_at(position.column_byte_index as usize);
+        if let Some(name) = closing_tag_name(before, is_jsx) {
+            let tag_start = closing_tags.len();
+            closing_tags.push_str("</");
+            closing_tags.push_str(name);
+            closing_tags.push('>');
+            if after.starts_with(&closing_tags[tag_start..]) {
+                closing_tags.truncate(tag_start);
+            } else {
+                has_closing_tags = true;
+            }
+        }
+        closing_tags.push('\0');
+    }
+
+    if has_closing_tags {
+        {
+            let mut events = editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(buffer.handle());
+            let tags = closing_tags.split_terminator('\0').rev();
+            for (cursor, tag) in buffer_view.cursors[..].iter().rev().zip(tags) {
+                if !tag.is_empty() {
+                    buffer.insert_text(
+                        &mut editor.word_database,
+                        cursor.position,
+                        tag,
+                        &mut events,
+                    );
+                }
+            }
+        }
+
+        // keep cursors before the inserted closing tags
+        let mut events = editor
+            .events
+            .writer()
+            .fix_cursors_mut_guard(buffer_view_handle);
+        let mut line_index = BufferPositionIndex::MAX;
+        let mut shift = 0;
+        let tags = closing_tags.split_terminator('\0');
+        for (cursor, tag) in buffer_view.cursors[..].iter().zip(tags) {
+            let mut position = cursor.position;
+            if position.line_index != line_index {
+                line_index = position.line_index;
+                shift = 0;
+            }
+            position.column_byte_index += shift;
+            shift += tag.len() as BufferPositionIndex;
+            events.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+    }
+
+    editor.string_pool.release(closing_tags);
+}
+
+pub(crate) fn trigger_completion(
+    ctx: &mut EditorContext,
+    client_handle: ClientHandle,
+    buffer_view_handle: BufferViewHandle,
+) {
+    ctx.editor.mode.insert_state.editing_buffer_handle = Some(
+        ctx.editor
+            .buffer_views
+            .get(buffer_view_handle)
+            .buffer_handle,
+    );
+    cancel_completion(&mut ctx.editor);
+    update_completions(ctx, client_handle, buffer_view_handle, true);
+}
+
+// time left until completions are updated for the keys typed last
+pub(crate) fn pending_completion_timeout(editor: &Editor) -> Option<Duration> {
+    let (_, _, since) = editor.mode.insert_state.pending_completion?;
+    let debounce = Duration::from_millis(editor.config.completion_debounce as _);
+    Some(debounce.saturating_sub(since.elapsed()))
+}
+
+pub(crate) fn on_pending_completion_timeout(ctx: &mut EditorContext) {
+    if pending_completion_timeout(&ctx.editor) != Some(Duration::ZERO) {
+        return;
+    }
+    flush_pending_completion(ctx);
+}
+
+fn flush_pending_completion(ctx: &mut EditorContext) {
+    let (client_handle, buffer_view_handle, _) =
+        match ctx.editor.mode.insert_state.pending_completion.take() {
+            Some(pending) => pending,
+            None => return,
+        };
+    if ctx.editor.mode.kind() == ModeKind::Insert
+        && ctx.clients.get(client_handle).buffer_view_handle() == Some(buffer_view_handle)
+    {
+        update_completions(ctx, client_handle, buffer_view_handle, false);
+    }
+}
+
 fn cancel_completion(editor: &mut Editor) {
     editor.picker.clear();
     editor.mode.insert_state.completion_positions.clear();
     editor.mode.insert_state.completing_plugin_handle = None;
+    editor.mode.insert_state.pending_completion = None;
 }
 
 fn update_completions(
     ctx: &mut EditorContext,
     client_handle: ClientHandle,
     buffer_view_handle: BufferViewHandle,
+    force: bool,
 ) {
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     let buffer_handle = buffer_view.buffer_handle;
@@ -303,8 +457,11 @@ fn update_completions(
             None => {
                 ctx.editor.picker.clear();
 
-                let completion_requested = word.kind == WordKind::Identifier
-                    && word.text.len() >= ctx.editor.config.completion_min_len as _;
+                let completion_trigger = ctx.editor.config.completion_trigger;
+                let completion_requested = force
+                    || (completion_trigger == CompletionTrigger::Auto
+                        && word.kind == WordKind::Identifier
+                        && word.text.len() >= ctx.editor.config.completion_min_len as _);
                 let completion_ctx = CompletionContext {
                     client_handle,
                     buffer_handle,
@@ -382,6 +539,8 @@ fn apply_completion(
     buffer_view_handle: BufferViewHandle,
     cursor_movement: isize,
 ) {
+    flush_pending_completion(ctx);
+
     ctx.editor.picker.move_cursor(cursor_movement);
     let entry = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
         Some((_, entry)) => entry,
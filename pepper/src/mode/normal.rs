@@ -1,10 +1,12 @@
 use std::{cmp::Ordering, fmt::Write, path::Path};
 
 use crate::{
-    buffer::{BufferContent, BufferHandle, BufferIndentationConfig, BufferProperties},
+    buffer::{
+        BufferContent, BufferHandle, BufferProperties, CharDisplayDistances, DisplayLen, TabStops,
+    },
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovement, CursorMovementKind},
-    client::{ClientHandle, ViewAnchor},
+    client::{ClientHandle, ViewAnchor, ViewScroll},
     cursor::Cursor,
     editor::{Editor, EditorContext, EditorFlow, KeysIterator},
     editor_utils::{
@@ -33,6 +35,7 @@ pub struct State {
     pub count: u32,
     last_copy_hash: u64,
     last_copy_ranges: Vec<(BufferPositionIndex, BufferPositionIndex)>,
+    last_copy_linewise: bool,
 }
 
 impl State {
@@ -82,6 +85,13 @@ impl State {
         keys: &mut KeysIterator,
         handle: BufferViewHandle,
     ) -> Option<EditorFlow> {
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        let indentation_config = ctx
+            .editor
+            .buffers
+            .get(buffer_handle)
+            .indentation_config(&ctx.editor.config);
+
         let state = &mut ctx.editor.mode.normal_state;
         let keys_from_index = keys.index;
         match keys.next(&ctx.editor.buffered_keys) {
@@ -104,7 +114,8 @@ impl State {
                 &ctx.editor.buffers,
                 CursorMovement::LinesForward {
                     count: state.count.max(1) as _,
-                    tab_size: ctx.editor.config.tab_size,
+                    tab_size: indentation_config.tab_size,
+                    tab_stops: &ctx.editor.config.tab_stops,
                 },
                 state.movement_kind,
             ),
@@ -117,7 +128,8 @@ impl State {
                 &ctx.editor.buffers,
                 CursorMovement::LinesBackward {
                     count: state.count.max(1) as _,
-                    tab_size: ctx.editor.config.tab_size,
+                    tab_size: indentation_config.tab_size,
+                    tab_stops: &ctx.editor.config.tab_stops,
                 },
                 state.movement_kind,
             ),
@@ -590,57 +602,11 @@ impl State {
                         control: false,
                         alt: false,
                         ..
-                    } => {
-                        let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
-                        for cursor in &mut buffer_view.cursors.mut_guard()[..] {
-                            let mut position = cursor.position;
-
-                            let line = buffer.lines()[position.line_index as usize].as_str();
-                            let cursor_char = if position.column_byte_index < line.len() as _ {
-                                match line[position.column_byte_index as usize..].chars().next() {
-                                    Some(c) => c,
-                                    None => continue,
-                                }
-                            } else {
-                                match line.char_indices().next_back() {
-                                    Some((i, c)) => {
-                                        position.column_byte_index = i as _;
-                                        c
-                                    }
-                                    None => continue,
-                                }
-                            };
-
-                            let range = match cursor_char {
-                                '(' | ')' => buffer.find_balanced_chars_at(position, '(', ')'),
-                                '[' | ']' => buffer.find_balanced_chars_at(position, '[', ']'),
-                                '{' | '}' => buffer.find_balanced_chars_at(position, '{', '}'),
-                                '<' | '>' => buffer.find_balanced_chars_at(position, '<', '>'),
-                                d @ ('|' | '"' | '\'' | '`') => {
-                                    buffer.find_delimiter_pair_at(position, d)
-                                }
-                                _ => continue,
-                            };
-
-                            if let Some(range) = range {
-                                let from = BufferPosition::line_col(
-                                    range.from.line_index,
-                                    range.from.column_byte_index - 1,
-                                );
-                                let to = range.to;
-
-                                if position == from {
-                                    cursor.position = to;
-                                } else if position == to {
-                                    cursor.position = from;
-                                }
-
-                                if let CursorMovementKind::PositionAndAnchor = state.movement_kind {
-                                    cursor.anchor = cursor.position;
-                                }
-                            }
-                        }
-                    }
+                    } => buffer_view.move_cursors(
+                        &ctx.editor.buffers,
+                        CursorMovement::MatchingBracket,
+                        state.movement_kind,
+                    ),
                     Key {
                         code: KeyCode::Char(c @ ('f' | 'F')),
                         control: false,
@@ -1131,14 +1097,15 @@ impl State {
                 alt: false,
                 ..
             } => {
-                let half_height = ctx.clients.get(client_handle).viewport_size.1 / 2;
-                ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                    &ctx.editor.buffers,
-                    CursorMovement::LinesForward {
-                        count: half_height as usize * state.count.max(1) as usize,
-                        tab_size: ctx.editor.config.tab_size,
-                    },
-                    state.movement_kind,
+                let count = state.count as _;
+                let movement_kind = state.movement_kind;
+                let client = ctx.clients.get(client_handle);
+                client.scroll_view(
+                    &mut ctx.editor,
+                    ViewScroll::HalfPageDown,
+                    count,
+                    true,
+                    movement_kind,
                 );
             }
             Key {
@@ -1148,14 +1115,51 @@ impl State {
                 alt: false,
                 ..
             } => {
-                let half_height = ctx.clients.get(client_handle).viewport_size.1 / 2;
-                ctx.editor.buffer_views.get_mut(handle).move_cursors(
-                    &ctx.editor.buffers,
-                    CursorMovement::LinesBackward {
-                        count: half_height as usize * state.count.max(1) as usize,
-                        tab_size: ctx.editor.config.tab_size,
-                    },
-                    state.movement_kind,
+                let count = state.count as _;
+                let movement_kind = state.movement_kind;
+                let client = ctx.clients.get(client_handle);
+                client.scroll_view(
+                    &mut ctx.editor,
+                    ViewScroll::HalfPageUp,
+                    count,
+                    true,
+                    movement_kind,
+                );
+            }
+            Key {
+                code: KeyCode::Char('f'),
+                shift: false,
+                control: true,
+                alt: false,
+                ..
+            } => {
+                let count = state.count as _;
+                let movement_kind = state.movement_kind;
+                let client = ctx.clients.get(client_handle);
+                client.scroll_view(
+                    &mut ctx.editor,
+                    ViewScroll::PageDown,
+                    count,
+                    true,
+                    movement_kind,
+                );
+            }
+            Key {
+                code: KeyCode::Char('b'),
+                shift: false,
+                control: true,
+                alt: false,
+                ..
+            } => {
+                let count = state.count as _;
+                let movement_kind = state.movement_kind;
+                let client = ctx.clients.get(client_handle);
+                client.scroll_view(
+                    &mut ctx.editor,
+                    ViewScroll::PageUp,
+                    count,
+                    true,
+                    movement_kind,
                 );
             }
             Key {
@@ -1203,7 +1207,7 @@ impl State {
                 control: false,
                 alt: false,
                 ..
-            } if ctx.editor.config.tab_size > 0 => {
+            } if indentation_config.tab_size > 0 => {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
                 let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
                 let count = state.count.max(1);
@@ -1233,7 +1237,7 @@ impl State {
                                 Some((i, c @ '\t')) => i + c.len_utf8(),
                                 Some((i, c @ ' ')) => {
                                     match chars
-                                        .take(ctx.editor.config.tab_size as usize - 1)
+                                        .take(indentation_config.tab_size as usize - 1)
                                         .take_while(|(_, c)| *c == ' ')
                                         .last()
                                     {
@@ -1262,12 +1266,12 @@ impl State {
                 control: false,
                 alt: false,
                 ..
-            } if ctx.editor.config.tab_size > 0 => {
-                let extender = if ctx.editor.config.indent_with_tabs {
+            } if indentation_config.tab_size > 0 => {
+                let extender = if indentation_config.indent_with_tabs {
                     let count = state.count.max(1) as _;
                     std::iter::repeat('\t').take(count)
                 } else {
-                    let tab_size = ctx.editor.config.tab_size as usize;
+                    let tab_size = indentation_config.tab_size as usize;
                     let count = state.count.max(1) as usize * tab_size;
                     std::iter::repeat(' ').take(count)
                 };
@@ -1331,12 +1335,8 @@ impl State {
                 control: false,
                 alt: false,
                 ..
-            } if ctx.editor.config.tab_size > 0 => {
+            } if indentation_config.tab_size > 0 => {
                 let buffer_view = ctx.editor.buffer_views.get(handle);
-                let indentation_config = BufferIndentationConfig {
-                    indent_with_tabs: ctx.editor.config.indent_with_tabs,
-                    tab_size: ctx.editor.config.tab_size,
-                };
                 buffer_view.fix_indentation_in_cursor_ranges(
                     indentation_config,
                     &mut ctx.editor.buffers,
@@ -1447,6 +1447,94 @@ impl State {
                         }
                     }
                 }
+                Key {
+                    code: KeyCode::Char('b'),
+                    control: false,
+                    alt: false,
+                    ..
+                } => {
+                    fn display_distance(
+                        line: &str,
+                        column_byte_index: usize,
+                        tab_size: u8,
+                        tab_stops: &TabStops,
+                    ) -> u32 {
+                        let line = &line[..column_byte_index];
+                        tab_stops.line_len(line, DisplayLen::from(line), tab_size) as _
+                    }
+
+                    fn column_byte_index(
+                        line: &str,
+                        distance: u32,
+                        tab_size: u8,
+                        tab_stops: &TabStops,
+                    ) -> usize {
+                        CharDisplayDistances::new(line, tab_size)
+                            .with_tab_stops(tab_stops)
+                            .find(|d| d.distance > distance)
+                            .map(|d| d.char_index as _)
+                            .unwrap_or(line.len())
+                    }
+
+                    let tab_size = indentation_config.tab_size;
+                    let tab_stops = &ctx.editor.config.tab_stops;
+                    let buffer_view = ctx.editor.buffer_views.get_mut(handle);
+                    let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle).content();
+
+                    let mut cursors = buffer_view.cursors.mut_guard();
+                    let cursor_count = cursors[..].len();
+
+                    for i in 0..cursor_count {
+                        let cursor = &mut cursors[i];
+                        if cursor.anchor.line_index == cursor.position.line_index {
+                            continue;
+                        }
+
+                        let anchor_line =
+                            buffer.lines()[cursor.anchor.line_index as usize].as_str();
+                        let anchor_distance = display_distance(
+                            anchor_line,
+                            cursor.anchor.column_byte_index as _,
+                            tab_size,
+                            tab_stops,
+                        );
+                        let position_line =
+                            buffer.lines()[cursor.position.line_index as usize].as_str();
+                        let position_distance = display_distance(
+                            position_line,
+                            cursor.position.column_byte_index as _,
+                            tab_size,
+                            tab_stops,
+                        );
+
+                        let range = BufferRange::between(cursor.anchor, cursor.position);
+                        let position_line_index = cursor.position.line_index;
+                        cursor.anchor.line_index = position_line_index;
+                        cursor.anchor.column_byte_index =
+                            column_byte_index(position_line, anchor_distance, tab_size, tab_stops)
+                                as _;
+
+                        for line_index in range.from.line_index..=range.to.line_index {
+                            if line_index == position_line_index {
+                                continue;
+                            }
+
+                            let line = buffer.lines()[line_index as usize].as_str();
+                            cursors.add(Cursor {
+                                anchor: BufferPosition::line_col(
+                                    line_index,
+                                    column_byte_index(line, anchor_distance, tab_size, tab_stops)
+                                        as _,
+                                ),
+                                position: BufferPosition::line_col(
+                                    line_index,
+                                    column_byte_index(line, position_distance, tab_size, tab_stops)
+                                        as _,
+                                ),
+                            });
+                        }
+                    }
+                }
                 Key {
                     code: KeyCode::Char('D'),
                     control: false,
@@ -1698,7 +1786,10 @@ impl State {
             } => {
                 let mut text = ctx.editor.string_pool.acquire();
                 ctx.platform.read_from_clipboard(&mut text);
-                paste_text(ctx, handle, &text);
+                let state = &ctx.editor.mode.normal_state;
+                let linewise =
+                    state.last_copy_linewise && state.last_copy_hash == hash_bytes(text.as_bytes());
+                paste_text(ctx, handle, &text, linewise);
                 ctx.editor.string_pool.release(text);
                 return Some(EditorFlow::Continue);
             }
@@ -1725,9 +1816,9 @@ impl State {
                             let mut text = ctx.editor.string_pool.acquire();
                             copy_text(ctx, handle, &mut text);
                             if !text.is_empty() {
-                                let register = ctx.editor.registers.get_mut(key);
-                                register.clear();
-                                register.push_str(&text);
+                                let linewise = ctx.editor.mode.normal_state.last_copy_linewise;
+                                ctx.editor.registers.set(key, &text);
+                                ctx.editor.registers.set_linewise(key, linewise);
                             }
                             ctx.editor.string_pool.release(text);
                         }
@@ -1735,7 +1826,8 @@ impl State {
                         if let Some(key) = RegisterKey::from_char(key) {
                             let register = ctx.editor.registers.get(key);
                             let text = ctx.editor.string_pool.acquire_with(register);
-                            paste_text(ctx, handle, &text);
+                            let linewise = ctx.editor.registers.is_linewise(key);
+                            paste_text(ctx, handle, &text, linewise);
                             ctx.editor.string_pool.release(text);
                             return Some(EditorFlow::Continue);
                         }
@@ -1792,6 +1884,7 @@ impl Default for State {
             count: 0,
             last_copy_hash: 0,
             last_copy_ranges: Vec::new(),
+            last_copy_linewise: false,
         }
     }
 }
@@ -2190,12 +2283,41 @@ fn copy_text(ctx: &mut EditorContext, buffer_view_handle: BufferViewHandle, text
     if !text.is_empty() {
         state.last_copy_hash = hash_bytes(text.as_bytes());
         state.last_copy_ranges.drain(..ranges_start);
+        // the last line is also a whole line even though it has no line break after it
+        let end = ctx
+            .editor
+            .buffers
+            .get(buffer_view.buffer_handle)
+            .content()
+            .end();
+        state.last_copy_linewise = buffer_view.cursors[..].iter().all(|c| {
+            let range = c.to_range();
+            range.from.column_byte_index == 0
+                && (range.to.column_byte_index == 0 && range.from.line_index < range.to.line_index
+                    || range.from != range.to && range.to == end)
+        });
     }
     state.movement_kind = CursorMovementKind::PositionAndAnchor;
 }
 
-fn paste_text(ctx: &mut EditorContext, buffer_view_handle: BufferViewHandle, text: &str) {
+fn paste_text(
+    ctx: &mut EditorContext,
+    buffer_view_handle: BufferViewHandle,
+    text: &str,
+    linewise: bool,
+) {
     let state = &mut ctx.editor.mode.normal_state;
+    if linewise {
+        // linewise text is pasted on its own line(s) above empty cursors instead of inline
+        let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+        for cursor in &mut buffer_view.cursors.mut_guard()[..] {
+            if cursor.anchor == cursor.position {
+                cursor.position.column_byte_index = 0;
+                cursor.anchor = cursor.position;
+            }
+        }
+    }
+
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     buffer_view.delete_text_in_cursor_ranges(
         &mut ctx.editor.buffers,
@@ -2221,20 +2343,33 @@ fn paste_text(ctx: &mut EditorContext, buffer_view_handle: BufferViewHandle, tex
             .buffer_text_inserts_mut_guard(buffer.handle());
         for (range, cursor) in ranges.iter().zip(cursors.iter()).rev() {
             let text = &text[range.0 as usize..range.1 as usize];
-            buffer.insert_text(
+            let inserted = buffer.insert_text(
                 &mut ctx.editor.word_database,
                 cursor.position,
                 text,
                 &mut events,
             );
+            if linewise && !text.ends_with('\n') {
+                buffer.insert_text(
+                    &mut ctx.editor.word_database,
+                    inserted.to,
+                    "\n",
+                    &mut events,
+                );
+            }
         }
     } else {
+        let mut line_text = ctx.editor.string_pool.acquire_with(text);
+        if linewise && !line_text.ends_with('\n') {
+            line_text.push('\n');
+        }
         buffer_view.insert_text_at_cursor_positions(
             &mut ctx.editor.buffers,
             &mut ctx.editor.word_database,
-            &text,
+            &line_text,
             ctx.editor.events.writer(),
         );
+        ctx.editor.string_pool.release(line_text);
     }
 
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
@@ -2490,3 +2625,110 @@ fn move_to_lint(ctx: &mut EditorContext, client_handle: ClientHandle, forward: b
         position,
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::PathBuf;
+
+    use crate::{client::ClientManager, platform::Platform, plugin::PluginCollection};
+
+    fn setup(text: &str) -> EditorContext {
+        let mut ctx = EditorContext {
+            editor: Editor::new(PathBuf::new(), String::new()),
+            platform: Platform::default(),
+            clients: ClientManager::default(),
+            plugins: PluginCollection::default(),
+        };
+        let buffer = ctx.editor.buffers.add_new();
+        let buffer_handle = buffer.handle();
+        buffer.insert_text(
+            &mut ctx.editor.word_database,
+            BufferPosition::zero(),
+            text,
+            &mut ctx
+                .editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(buffer_handle),
+        );
+
+        let client_handle = ClientHandle(0);
+        let buffer_view_handle = ctx
+            .editor
+            .buffer_views
+            .add_new(client_handle, buffer_handle);
+        ctx.clients.on_client_joined(client_handle);
+        ctx.clients
+            .get_mut(client_handle)
+            .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+        ctx.trigger_event_handlers();
+        ctx
+    }
+
+    fn set_cursors(ctx: &mut EditorContext, positions: &[(usize, usize)]) {
+        let handle = ctx
+            .clients
+            .get(ClientHandle(0))
+            .buffer_view_handle()
+            .unwrap();
+        let mut cursors = ctx.editor.buffer_views.get_mut(handle).cursors.mut_guard();
+        cursors.clear();
+        for &(line, column) in positions {
+            let position = BufferPosition::line_col(line as _, column as _);
+            cursors.add(Cursor {
+                anchor: position,
+                position,
+            });
+        }
+    }
+
+    fn execute_keys(ctx: &mut EditorContext, keys: &str) -> String {
+        let keys = ctx.editor.buffered_keys.parse(keys).ok().unwrap();
+        Editor::execute_keys(ctx, ClientHandle(0), keys);
+        ctx.trigger_event_handlers();
+
+        let mut text = String::new();
+        let handle = ctx
+            .clients
+            .get(ClientHandle(0))
+            .buffer_view_handle()
+            .unwrap();
+        let buffer_handle = ctx.editor.buffer_views.get(handle).buffer_handle;
+        let content = ctx.editor.buffers.get(buffer_handle).content();
+        for slice in content.text_range(BufferRange::between(BufferPosition::zero(), content.end()))
+        {
+            text.push_str(slice);
+        }
+        text
+    }
+
+    #[test]
+    fn linewise_copy_of_last_line() {
+        let register = RegisterKey::from_char('a').unwrap();
+
+        let mut ctx = setup("a\nb");
+        set_cursors(&mut ctx, &[(1, 0)]);
+        execute_keys(&mut ctx, "V<c-y>a");
+        assert!(ctx.editor.registers.is_linewise(register));
+        set_cursors(&mut ctx, &[(0, 1)]);
+        assert_eq!("b\na\nb", execute_keys(&mut ctx, "<c-y>A"));
+
+        let mut ctx = setup("a\nb\nc");
+        set_cursors(&mut ctx, &[(1, 0)]);
+        execute_keys(&mut ctx, "V<c-y>a");
+        assert!(ctx.editor.registers.is_linewise(register));
+        set_cursors(&mut ctx, &[(2, 1)]);
+        assert_eq!("a\nb\nb\nc", execute_keys(&mut ctx, "<c-y>A"));
+    }
+
+    #[test]
+    fn linewise_paste_with_cursors_on_same_line() {
+        let mut ctx = setup("a\nb\ncd");
+        set_cursors(&mut ctx, &[(1, 0)]);
+        execute_keys(&mut ctx, "V<c-y>a");
+        set_cursors(&mut ctx, &[(2, 0), (2, 1)]);
+        assert_eq!("a\nb\nb\ncd", execute_keys(&mut ctx, "<c-y>A"));
+    }
+}
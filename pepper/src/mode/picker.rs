@@ -1,3 +1,5 @@
+use std::path::PathBuf;
+
 use crate::{
     buffer::BufferProperties,
     client::ClientHandle,
@@ -19,6 +21,7 @@ pub struct State {
         ReadLinePoll,
     ) -> Option<EditorFlow>,
     continuation: String,
+    browse_directory: PathBuf,
 }
 
 impl Default for State {
@@ -26,6 +29,7 @@ impl Default for State {
         Self {
             on_client_keys: |_, _, _, _| Some(EditorFlow::Continue),
             continuation: String::new(),
+            browse_directory: PathBuf::new(),
         }
     }
 }
@@ -240,6 +244,214 @@ pub mod opened_buffers {
     }
 }
 
+pub mod help_topics {
+    use super::*;
+
+    use std::fmt::Write;
+
+    use crate::help;
+
+    pub fn enter_mode(ctx: &mut EditorContext) {
+        fn on_client_keys(
+            ctx: &mut EditorContext,
+            client_handle: ClientHandle,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorFlow::Continue),
+                ReadLinePoll::Submitted => (),
+                ReadLinePoll::Canceled => {
+                    ctx.editor.enter_mode(ModeKind::default());
+                    return Some(EditorFlow::Continue);
+                }
+            }
+
+            let topic = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                Some((_, entry)) => entry.split_once(": "),
+                _ => None,
+            };
+            let (page_name, line_index) = match topic.and_then(|(page_name, title)| {
+                help::help_topics().find(|&(p, _, t)| p == page_name && t == title)
+            }) {
+                Some((page_name, line_index, _)) => (page_name, line_index),
+                None => {
+                    ctx.editor.enter_mode(ModeKind::default());
+                    return Some(EditorFlow::Continue);
+                }
+            };
+
+            let _ = help::open_page(ctx, client_handle, page_name, line_index);
+
+            ctx.editor.enter_mode(ModeKind::default());
+            Some(EditorFlow::Continue)
+        }
+
+        ctx.editor.registers.set(REGISTER_READLINE_PROMPT, "help:");
+        ctx.editor.picker.clear();
+
+        let mut entry = ctx.editor.string_pool.acquire();
+        for (page_name, _, title) in help::help_topics() {
+            entry.clear();
+            let _ = write!(entry, "{}: {}", page_name, title);
+            ctx.editor.picker.add_custom_entry(&entry);
+        }
+        ctx.editor.string_pool.release(entry);
+
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+
+        ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+        ctx.editor.enter_mode(ModeKind::Picker);
+    }
+}
+
+pub mod browse {
+    use super::*;
+
+    use std::{fs, path::Path};
+
+    pub fn enter_mode(ctx: &mut EditorContext, directory: &Path) {
+        fn on_client_keys(
+            ctx: &mut EditorContext,
+            client_handle: ClientHandle,
+            _: &mut KeysIterator,
+            poll: ReadLinePoll,
+        ) -> Option<EditorFlow> {
+            match poll {
+                ReadLinePoll::Pending => return Some(EditorFlow::Continue),
+                ReadLinePoll::Submitted => (),
+                ReadLinePoll::Canceled => {
+                    ctx.editor.enter_mode(ModeKind::default());
+                    return Some(EditorFlow::Continue);
+                }
+            }
+
+            let entry = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                Some((_, entry)) => entry,
+                _ => {
+                    ctx.editor.enter_mode(ModeKind::default());
+                    return Some(EditorFlow::Continue);
+                }
+            };
+
+            let mut path = ctx.editor.mode.picker_state.browse_directory.clone();
+            match entry.strip_suffix('/') {
+                Some("..") => {
+                    path.pop();
+                }
+                Some(directory) => path.push(directory),
+                None => path.push(entry),
+            }
+
+            if path.is_dir() {
+                ctx.editor
+                    .registers
+                    .get_mut(REGISTER_READLINE_INPUT)
+                    .clear();
+                if !read_directory(ctx, path) {
+                    ctx.editor.enter_mode(ModeKind::default());
+                }
+                return Some(EditorFlow::Continue);
+            }
+
+            let path = path
+                .strip_prefix(&ctx.editor.current_directory)
+                .unwrap_or(&path);
+            match ctx.editor.buffer_view_handle_from_path(
+                client_handle,
+                path,
+                BufferProperties::text(),
+                false,
+            ) {
+                Ok(buffer_view_handle) => {
+                    let client = ctx.clients.get_mut(client_handle);
+                    client
+                        .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+                }
+                Err(error) => ctx
+                    .editor
+                    .logger
+                    .write(LogKind::Error)
+                    .fmt(format_args!("{}", error)),
+            }
+
+            ctx.editor.enter_mode(ModeKind::default());
+            Some(EditorFlow::Continue)
+        }
+
+        let directory = ctx.editor.current_directory.join(directory);
+        let directory = fs::canonicalize(&directory).unwrap_or(directory);
+        if read_directory(ctx, directory) {
+            ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+            ctx.editor.enter_mode(ModeKind::Picker);
+        }
+    }
+
+    fn read_directory(ctx: &mut EditorContext, directory: PathBuf) -> bool {
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(error) => {
+                ctx.editor.logger.write(LogKind::Error).fmt(format_args!(
+                    "could not read directory '{}': {}",
+                    directory.display(),
+                    error
+                ));
+                return false;
+            }
+        };
+
+        let mut directories = Vec::new();
+        let mut files = Vec::new();
+        for entry in entries.flatten() {
+            let name = match entry.file_name().into_string() {
+                Ok(name) => name,
+                Err(_) => continue,
+            };
+            if entry.path().is_dir() {
+                directories.push(name);
+            } else {
+                files.push(name);
+            }
+        }
+        directories.sort_unstable();
+        files.sort_unstable();
+
+        ctx.editor.picker.clear();
+        ctx.editor.picker.add_custom_entry("../");
+        let mut entry = ctx.editor.string_pool.acquire();
+        for directory in &directories {
+            entry.clear();
+            entry.push_str(directory);
+            entry.push('/');
+            ctx.editor.picker.add_custom_entry(&entry);
+        }
+        for file in &files {
+            ctx.editor.picker.add_custom_entry(file);
+        }
+
+        let prompt = &mut entry;
+        prompt.clear();
+        let display_directory = directory
+            .strip_prefix(&ctx.editor.current_directory)
+            .unwrap_or(&directory);
+        match display_directory.to_str() {
+            Some("") | None => prompt.push('.'),
+            Some(display_directory) => prompt.push_str(display_directory),
+        }
+        if !prompt.ends_with('/') {
+            prompt.push('/');
+        }
+        ctx.editor.registers.set(REGISTER_READLINE_PROMPT, prompt);
+        ctx.editor.string_pool.release(entry);
+
+        ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+        ctx.editor.picker.move_cursor(0);
+        ctx.editor.mode.picker_state.browse_directory = directory;
+        true
+    }
+}
+
 pub mod custom {
     use super::*;
 
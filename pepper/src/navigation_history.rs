@@ -190,6 +190,8 @@ mod tests {
 
     use std::path::PathBuf;
 
+    use crate::client::{ClientHandle, ClientManager};
+
     fn setup() -> (Editor, Client) {
         let mut client = Client::new();
         let mut editor = Editor::new(PathBuf::new(), String::new());
@@ -307,4 +309,49 @@ mod tests {
 
         assert_eq!(3, client.navigation_history.snapshots.len());
     }
+
+    #[test]
+    fn client_leaving_keeps_other_client_history() {
+        let mut editor = Editor::new(PathBuf::new(), String::new());
+        let mut clients = ClientManager::default();
+
+        let buffer_a = editor.buffers.add_new().handle();
+        let buffer_b = editor.buffers.add_new().handle();
+
+        let client_a = ClientHandle(0);
+        let client_b = ClientHandle(1);
+        clients.on_client_joined(client_a);
+        clients.on_client_joined(client_b);
+
+        let view = editor.buffer_views.add_new(client_a, buffer_a);
+        clients
+            .get_mut(client_a)
+            .set_buffer_view_handle(Some(view), &editor.buffer_views);
+
+        let view = editor.buffer_views.add_new(client_b, buffer_a);
+        clients
+            .get_mut(client_b)
+            .set_buffer_view_handle(Some(view), &editor.buffer_views);
+        let view = editor.buffer_views.add_new(client_b, buffer_b);
+        clients
+            .get_mut(client_b)
+            .set_buffer_view_handle(Some(view), &editor.buffer_views);
+
+        editor
+            .buffer_views
+            .remove_buffer_views_with_client(client_a);
+        clients.on_client_left(client_a);
+
+        assert_eq!(2, editor.buffers.iter().count());
+
+        let client = clients.get_mut(client_b);
+        assert!(Some(view) == client.buffer_view_handle());
+        assert_eq!(1, client.navigation_history.snapshots.len());
+
+        NavigationHistory::move_in_history(client, &mut editor, NavigationMovement::Backward);
+        assert_eq!(buffer_a.0 as usize, buffer_index(client, &editor));
+
+        NavigationHistory::move_in_history(client, &mut editor, NavigationMovement::Forward);
+        assert_eq!(buffer_b.0 as usize, buffer_index(client, &editor));
+    }
 }
@@ -19,6 +19,7 @@ pub struct Picker {
     fuzzy_matcher: FuzzyMatcher,
     custom_entries_len: usize,
     custom_entries_buffer: Vec<String>,
+    sorted_custom_entry_indices: Vec<usize>,
     filtered_entries: Vec<FilteredEntry>,
 
     cursor: Option<usize>,
@@ -95,6 +96,12 @@ impl Picker {
         entry
     }
 
+    pub fn custom_entries(&self) -> impl Iterator<Item = &str> {
+        self.custom_entries_buffer[..self.custom_entries_len]
+            .iter()
+            .map(String::as_str)
+    }
+
     pub fn add_custom_entry(&mut self, name: &str) {
         let entry = self.new_custom_entry();
         entry.push_str(name);
@@ -117,11 +124,7 @@ impl Picker {
     }
 
     pub fn sort_filtered_entries(&mut self) {
-        self.filtered_entries.sort_unstable_by(|a, b| {
-            let score_ord = b.score.cmp(&a.score);
-            let total_end_len_ord = a.total_end_len.cmp(&b.total_end_len);
-            score_ord.then(total_end_len_ord)
-        });
+        sort_filtered_entries(&mut self.filtered_entries);
     }
 
     pub fn filter(&mut self, word_indices: WordIndicesIter, pattern: &str) {
@@ -152,17 +155,81 @@ impl Picker {
         }
     }
 
+    // merges custom entries (usually from a language server) with words from the word database.
+    // custom entries always rank higher and words that are also a custom entry are left out.
+    // when there are custom entries, the word being completed itself is also left out
     pub fn filter_completion(&mut self, word_indices: WordIndicesIter, pattern: &str) {
+        self.filtered_entries.clear();
+
+        // sorting by text (then by index) lets duplicates be skipped keeping only their first
+        // occurrence and lets words be looked up among custom entries with a binary search
+        let custom_entries = &self.custom_entries_buffer[..self.custom_entries_len];
+        let mut sorted_indices = std::mem::take(&mut self.sorted_custom_entry_indices);
+        sorted_indices.clear();
+        sorted_indices.extend(0..custom_entries.len());
+        sorted_indices
+            .sort_unstable_by(|&a, &b| custom_entries[a].cmp(&custom_entries[b]).then(a.cmp(&b)));
+        sorted_indices.dedup_by(|b, a| custom_entries[*a] == custom_entries[*b]);
+
+        fn find_custom_entry(
+            sorted_indices: &[usize],
+            custom_entries: &[String],
+            entry: &str,
+        ) -> Option<usize> {
+            let index = sorted_indices
+                .binary_search_by(|&i| custom_entries[i].as_str().cmp(entry))
+                .ok()?;
+            Some(sorted_indices[index])
+        }
+
+        // custom entries are still filtered in their original order as it breaks score ties
+        for i in 0..self.custom_entries_len {
+            let custom_entries = &self.custom_entries_buffer[..self.custom_entries_len];
+            if find_custom_entry(&sorted_indices, custom_entries, &custom_entries[i]) == Some(i) {
+                self.filter_custom_entry(i, pattern);
+            }
+        }
+        let custom_filtered_len = self.filtered_entries.len();
+
+        let custom_entries = &self.custom_entries_buffer[..self.custom_entries_len];
+        for (i, word) in word_indices {
+            if !custom_entries.is_empty()
+                && (word == pattern
+                    || find_custom_entry(&sorted_indices, custom_entries, word).is_some())
+            {
+                continue;
+            }
+            let result = self.fuzzy_matcher.score(word, pattern);
+            if result.score != 0 {
+                self.filtered_entries.push(FilteredEntry {
+                    source: EntrySource::WordDatabase(i),
+                    score: result.score,
+                    total_end_len: result.total_end_len,
+                });
+            }
+        }
+
+        self.sorted_custom_entry_indices = sorted_indices;
+
+        let (custom_entries, word_entries) =
+            self.filtered_entries.split_at_mut(custom_filtered_len);
+        sort_filtered_entries(custom_entries);
+        sort_filtered_entries(word_entries);
+
+        let len = self.filtered_entries.len();
+        if len > 0 {
+            self.cursor = self.cursor.map(|c| c.min(len - 1));
+        } else {
+            self.cursor = None;
+        }
+
         if self.custom_entries_len == 0 {
-            self.filter(word_indices, pattern);
             if self.cursor.is_none() {
                 self.cursor = Some(0);
             }
             if self.len() == 1 {
                 self.clear();
             }
-        } else {
-            self.filter(WordIndicesIter::empty(), pattern);
         }
     }
 
@@ -182,7 +249,7 @@ impl Picker {
     }
 
     pub fn current_entry<'a>(&'a self, words: &'a WordDatabase) -> Option<(EntrySource, &'a str)> {
-        let entry = &self.filtered_entries[self.cursor?];
+        let entry = self.filtered_entries.get(self.cursor?)?;
         let source = entry.source;
         let entry = filtered_to_picker_entry(entry, &self.custom_entries_buffer, words);
         Some((source, entry))
@@ -199,6 +266,14 @@ impl Picker {
     }
 }
 
+fn sort_filtered_entries(entries: &mut [FilteredEntry]) {
+    entries.sort_unstable_by(|a, b| {
+        let score_ord = b.score.cmp(&a.score);
+        let total_end_len_ord = a.total_end_len.cmp(&b.total_end_len);
+        score_ord.then(total_end_len_ord)
+    });
+}
+
 fn filtered_to_picker_entry<'a>(
     entry: &FilteredEntry,
     custom_entries: &'a [String],
@@ -343,6 +418,38 @@ impl FuzzyMatcher {
 mod tests {
     use super::*;
 
+    #[test]
+    fn filter_completion_test() {
+        let mut words = WordDatabase::new();
+        words.add("format");
+        words.add("forward");
+        words.add("foreign");
+        words.add("for");
+
+        let mut picker = Picker::default();
+        picker.filter_completion(words.word_indices(), "for");
+        assert_eq!(4, picker.len());
+
+        picker.clear();
+        picker.add_custom_entry("foreign");
+        picker.add_custom_entry("for_each");
+        picker.add_custom_entry("foreign");
+        picker.filter_completion(words.word_indices(), "for");
+
+        let entries: Vec<_> = picker.entries(&words).collect();
+        assert_eq!(4, entries.len());
+        assert_eq!("foreign", entries[0]);
+        assert_eq!("for_each", entries[1]);
+        assert!(entries[2..].contains(&"format"));
+        assert!(entries[2..].contains(&"forward"));
+
+        picker.move_cursor(0);
+        assert!(matches!(
+            picker.current_entry(&words),
+            Some((EntrySource::Custom(0), "foreign"))
+        ));
+    }
+
     #[test]
     fn fuzzy_matcher_test() {
         fn assert_score(
@@ -4,7 +4,10 @@ use std::{
     process::{Command, Stdio},
 };
 
-use crate::{client::ClientHandle, editor_utils::parse_process_command, plugin::PluginHandle};
+use crate::{
+    buffer::BufferHandle, client::ClientHandle, editor_utils::parse_process_command,
+    git::GitIndexOperation, plugin::PluginHandle,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum KeyCode {
@@ -65,6 +68,7 @@ pub enum PlatformEvent {
     },
     ProcessExit {
         tag: ProcessTag,
+        success: bool,
     },
     IpcConnected {
         tag: IpcTag,
@@ -131,6 +135,10 @@ pub enum ProcessTag {
     Ignored,
     Buffer(u32),
     PickerEntries,
+    GitBlame(BufferHandle),
+    GitDiff(BufferHandle),
+    GitIndex(BufferHandle, GitIndexOperation),
+    Formatter(BufferHandle),
     Plugin {
         plugin_handle: PluginHandle,
         id: u32,
@@ -11,7 +11,7 @@ use std::{
 use crate::{
     application::{
         ApplicationConfig, ClientApplication, ServerApplication, CLIENT_CONNECTION_BUFFER_LEN,
-        CLIENT_STDIN_BUFFER_LEN, SERVER_CONNECTION_BUFFER_LEN, SERVER_IDLE_DURATION,
+        CLIENT_STDIN_BUFFER_LEN, SERVER_CONNECTION_BUFFER_LEN,
     },
     client::ClientHandle,
     platform::{
@@ -229,10 +229,10 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
         let kqueue_events = kqueue.wait(&mut kqueue_events, timeout);
         if kqueue_events.len() == 0 {
             match timeout {
-                Some(Duration::ZERO) => timeout = Some(SERVER_IDLE_DURATION),
+                Some(Duration::ZERO) => timeout = Some(application.activity_idle_timeout()),
                 Some(_) => {
                     events.push(PlatformEvent::Idle);
-                    timeout = None;
+                    timeout = application.idle_timeout();
                 }
                 None => continue,
             }
@@ -341,9 +341,9 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                                     event_sources.remove_index(source_index);
                                     kqueue.remove(Event::FdRead(fd));
                                 }
-                                process.kill();
+                                let success = process.kill();
                                 processes[index] = None;
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                events.push(PlatformEvent::ProcessExit { tag, success });
                             }
                         }
                     }
@@ -430,7 +430,10 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                         }
                     }
                     if !spawned {
-                        events.push(PlatformEvent::ProcessExit { tag });
+                        events.push(PlatformEvent::ProcessExit {
+                            tag,
+                            success: false,
+                        });
                     }
                 }
                 PlatformRequest::WriteToProcess { handle, buf } => {
@@ -442,9 +445,9 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                                 kqueue.remove(Event::FdRead(fd));
                             }
                             let tag = process.tag();
-                            process.kill();
+                            let success = process.kill();
                             processes[index] = None;
-                            events.push(PlatformEvent::ProcessExit { tag });
+                            events.push(PlatformEvent::ProcessExit { tag, success });
                         }
                     }
                     application.ctx.platform.buf_pool.release(buf);
@@ -463,8 +466,8 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                             kqueue.remove(Event::FdRead(fd));
                         }
                         let tag = process.tag();
-                        process.kill();
-                        events.push(PlatformEvent::ProcessExit { tag });
+                        let success = process.kill();
+                        events.push(PlatformEvent::ProcessExit { tag, success });
                     }
                 }
                 PlatformRequest::ConnectToIpc {
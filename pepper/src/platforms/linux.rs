@@ -11,7 +11,7 @@ use std::{
 use crate::{
     application::{
         ApplicationConfig, ClientApplication, ServerApplication, CLIENT_CONNECTION_BUFFER_LEN,
-        CLIENT_STDIN_BUFFER_LEN, SERVER_CONNECTION_BUFFER_LEN, SERVER_IDLE_DURATION,
+        CLIENT_STDIN_BUFFER_LEN, SERVER_CONNECTION_BUFFER_LEN,
     },
     client::ClientHandle,
     platform::{
@@ -185,10 +185,10 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
         let epoll_events_len = epoll_events.len();
         if epoll_events_len == 0 {
             match timeout {
-                Some(Duration::ZERO) => timeout = Some(SERVER_IDLE_DURATION),
+                Some(Duration::ZERO) => timeout = Some(application.activity_idle_timeout()),
                 Some(_) => {
                     events.push(PlatformEvent::Idle);
-                    timeout = None;
+                    timeout = application.idle_timeout();
                 }
                 None => continue,
             }
@@ -275,9 +275,9 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                                     event_sources.remove_index(source_index);
                                     epoll.remove(fd);
                                 }
-                                process.kill();
+                                let success = process.kill();
                                 processes[index] = None;
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                events.push(PlatformEvent::ProcessExit { tag, success });
                             }
                         }
                     }
@@ -363,7 +363,10 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                         }
                     }
                     if !spawned {
-                        events.push(PlatformEvent::ProcessExit { tag });
+                        events.push(PlatformEvent::ProcessExit {
+                            tag,
+                            success: false,
+                        });
                     }
                 }
                 PlatformRequest::WriteToProcess { handle, buf } => {
@@ -375,9 +378,9 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                                 epoll.remove(fd);
                             }
                             let tag = process.tag();
-                            process.kill();
+                            let success = process.kill();
                             processes[index] = None;
-                            events.push(PlatformEvent::ProcessExit { tag });
+                            events.push(PlatformEvent::ProcessExit { tag, success });
                         }
                     }
                     application.ctx.platform.buf_pool.release(buf);
@@ -396,8 +399,8 @@ fn run_server(config: ApplicationConfig, listener: UnixListener) {
                             epoll.remove(fd);
                         }
                         let tag = process.tag();
-                        process.kill();
-                        events.push(PlatformEvent::ProcessExit { tag });
+                        let success = process.kill();
+                        events.push(PlatformEvent::ProcessExit { tag, success });
                     }
                 }
                 PlatformRequest::ConnectToIpc {
@@ -477,14 +477,15 @@ impl Process {
         self.child.stdin = None;
     }
 
-    pub fn kill(&mut self) {
+    // returns whether the process had exited successfully
+    pub fn kill(&mut self) -> bool {
         if !self.alive {
-            return;
+            return false;
         }
 
         self.alive = false;
         let _ = self.child.kill();
-        let _ = self.child.wait();
+        matches!(self.child.wait(), Ok(status) if status.success())
     }
 }
 impl Drop for Process {
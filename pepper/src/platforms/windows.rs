@@ -66,7 +66,7 @@ use winapi::{
 use crate::{
     application::{
         ApplicationConfig, ClientApplication, ServerApplication, CLIENT_CONNECTION_BUFFER_LEN,
-        CLIENT_STDIN_BUFFER_LEN, SERVER_CONNECTION_BUFFER_LEN, SERVER_IDLE_DURATION,
+        CLIENT_STDIN_BUFFER_LEN, SERVER_CONNECTION_BUFFER_LEN,
     },
     client::ClientHandle,
     editor_utils::hash_bytes,
@@ -1059,15 +1059,16 @@ impl AsyncProcess {
         }
     }
 
-    pub fn kill(&mut self) {
+    // returns whether the process had exited successfully
+    pub fn kill(&mut self) -> bool {
         if !self.alive {
-            return;
+            return false;
         }
 
         self.alive = false;
         self.stdout = None;
         let _ = self.child.kill();
-        let _ = self.child.wait();
+        matches!(self.child.wait(), Ok(status) if status.success())
     }
 }
 impl Drop for AsyncProcess {
@@ -1373,10 +1374,10 @@ fn run_server(config: ApplicationConfig, pipe_path: &[u16]) {
             }
             None => {
                 match timeout {
-                    Some(Duration::ZERO) => timeout = Some(SERVER_IDLE_DURATION),
+                    Some(Duration::ZERO) => timeout = Some(application.activity_idle_timeout()),
                     Some(_) => {
                         events.push(PlatformEvent::Idle);
-                        timeout = None;
+                        timeout = application.idle_timeout();
                     }
                     None => continue,
                 }
@@ -1446,7 +1447,10 @@ fn run_server(config: ApplicationConfig, pipe_path: &[u16]) {
                                 }
                             }
                             if !spawned {
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                events.push(PlatformEvent::ProcessExit {
+                                    tag,
+                                    success: false,
+                                });
                             }
                         }
                         PlatformRequest::WriteToProcess { handle, buf } => {
@@ -1455,9 +1459,9 @@ fn run_server(config: ApplicationConfig, pipe_path: &[u16]) {
                                 if !process.write(buf.as_bytes()) {
                                     let tag = process.tag;
                                     process.dispose(&mut application.ctx.platform.buf_pool);
-                                    process.kill();
+                                    let success = process.kill();
                                     processes[index] = None;
-                                    events.push(PlatformEvent::ProcessExit { tag });
+                                    events.push(PlatformEvent::ProcessExit { tag, success });
                                 }
                             }
                             application.ctx.platform.buf_pool.release(buf);
@@ -1473,8 +1477,8 @@ fn run_server(config: ApplicationConfig, pipe_path: &[u16]) {
                             if let Some(mut process) = processes[index].take() {
                                 let tag = process.tag;
                                 process.dispose(&mut application.ctx.platform.buf_pool);
-                                process.kill();
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                let success = process.kill();
+                                events.push(PlatformEvent::ProcessExit { tag, success });
                             }
                         }
                         PlatformRequest::ConnectToIpc {
@@ -1607,9 +1611,9 @@ fn run_server(config: ApplicationConfig, pipe_path: &[u16]) {
                             Ok(Some(buf)) => events.push(PlatformEvent::ProcessOutput { tag, buf }),
                             Err(()) => {
                                 process.stdout = None;
-                                process.kill();
+                                let success = process.kill();
                                 processes[i as usize] = None;
-                                events.push(PlatformEvent::ProcessExit { tag });
+                                events.push(PlatformEvent::ProcessExit { tag, success });
                             }
                         }
                     }
@@ -39,6 +39,8 @@ pub struct Plugin {
         keys: &mut KeysIterator,
     ) -> Option<EditorFlow>,
     pub on_completion: fn(PluginHandle, &mut EditorContext, &CompletionContext) -> bool,
+    // returns whether the plugin started formatting the buffer
+    pub on_format: fn(PluginHandle, &mut EditorContext, BufferHandle) -> bool,
 }
 impl Default for Plugin {
     fn default() -> Self {
@@ -57,6 +59,7 @@ impl Default for Plugin {
 
             on_keys: |_, _, _, _| Some(EditorFlow::Continue),
             on_completion: |_, _, _| false,
+            on_format: |_, _, _| false,
         }
     }
 }
@@ -74,13 +77,13 @@ pub struct PluginCollection {
     plugins: Vec<Plugin>,
 }
 impl PluginCollection {
-    pub(crate) fn add(ctx: &mut EditorContext, definition: PluginDefinition) {
+    pub fn add(ctx: &mut EditorContext, definition: PluginDefinition) -> Option<PluginHandle> {
         help::add_help_pages(definition.help_pages);
 
         let handle = PluginHandle(ctx.plugins.plugins.len() as _);
-        if let Some(plugin) = (definition.instantiate)(handle, ctx) {
-            ctx.plugins.plugins.push(plugin);
-        };
+        let plugin = (definition.instantiate)(handle, ctx)?;
+        ctx.plugins.plugins.push(plugin);
+        Some(handle)
     }
 
     pub fn get_as<T>(&mut self, handle: PluginHandle) -> &mut T
@@ -0,0 +1,225 @@
+use std::{fmt::Write, ops::Range, path::Path};
+
+use crate::{
+    buffer::{BufferProperties, BufferReadError},
+    buffer_position::{BufferPosition, BufferRange},
+    buffer_view::BufferViewHandle,
+    client::{ClientHandle, ViewAnchor},
+    cursor::Cursor,
+    editor::EditorContext,
+    events::BufferEditMutGuard,
+};
+
+struct QuickfixEntryData {
+    path_range: Range<u32>,
+    message_range: Range<u32>,
+    position: BufferPosition,
+}
+
+pub struct QuickfixEntry<'a> {
+    pub path: &'a str,
+    pub position: BufferPosition,
+    pub message: &'a str,
+}
+
+#[derive(Default)]
+pub struct QuickfixList {
+    name: String,
+    texts: String,
+    entries: Vec<QuickfixEntryData>,
+    current_index: Option<usize>,
+}
+
+impl QuickfixList {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn current_index(&self) -> Option<usize> {
+        self.current_index
+    }
+
+    pub fn set_current_index(&mut self, index: usize) {
+        if index < self.entries.len() {
+            self.current_index = Some(index);
+        }
+    }
+
+    // starts a new list which will be shown in a '<name>.refs' buffer
+    pub fn clear(&mut self, name: &str) {
+        self.name.clear();
+        self.name.push_str(name);
+        self.texts.clear();
+        self.entries.clear();
+        self.current_index = None;
+    }
+
+    pub fn add(&mut self, path: &str, position: BufferPosition, message: &str) {
+        let path_start = self.texts.len();
+        self.texts.push_str(path);
+        let message_start = self.texts.len();
+        self.texts.push_str(message);
+        self.entries.push(QuickfixEntryData {
+            path_range: path_start as u32..message_start as u32,
+            message_range: message_start as u32..self.texts.len() as u32,
+            position,
+        });
+    }
+
+    pub fn entry(&self, index: usize) -> QuickfixEntry<'_> {
+        let entry = &self.entries[index];
+        QuickfixEntry {
+            path: &self.texts[entry.path_range.start as usize..entry.path_range.end as usize],
+            position: entry.position,
+            message: &self.texts
+                [entry.message_range.start as usize..entry.message_range.end as usize],
+        }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = QuickfixEntry<'_>> {
+        (0..self.entries.len()).map(|i| self.entry(i))
+    }
+
+    // the texts of removed entries are only reclaimed when the list is cleared
+    pub fn retain<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&QuickfixEntry) -> bool,
+    {
+        let texts = &self.texts;
+        self.entries.retain(|entry| {
+            predicate(&QuickfixEntry {
+                path: &texts[entry.path_range.start as usize..entry.path_range.end as usize],
+                position: entry.position,
+                message: &texts
+                    [entry.message_range.start as usize..entry.message_range.end as usize],
+            })
+        });
+        self.current_index = None;
+    }
+
+    // single line messages follow their location while multiline ones go into their own block
+    pub fn render(&self, text: &mut String) {
+        for entry in self.entries() {
+            let message = entry.message.trim_end_matches('\n');
+            if message.is_empty() {
+                let _ = writeln!(text, "{}:{}", entry.path, entry.position);
+            } else if message.contains('\n') {
+                let _ = write!(text, "{}:{}\n{}\n\n", entry.path, entry.position, message);
+            } else {
+                let _ = writeln!(text, "{}:{}:{}", entry.path, entry.position, message);
+            }
+        }
+        if text.ends_with('\n') {
+            text.pop();
+        }
+    }
+}
+
+pub fn show(
+    ctx: &mut EditorContext,
+    client_handle: ClientHandle,
+) -> Result<BufferViewHandle, BufferReadError> {
+    let mut path = ctx
+        .editor
+        .string_pool
+        .acquire_with(&ctx.editor.quickfix.name);
+    if path.is_empty() {
+        path.push_str("quickfix");
+    }
+    path.push_str(".refs");
+    let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
+        client_handle,
+        Path::new(&path),
+        BufferProperties::scratch(),
+        true,
+    );
+    ctx.editor.string_pool.release(path);
+    let buffer_view_handle = buffer_view_handle?;
+
+    let mut text = ctx.editor.string_pool.acquire();
+    ctx.editor.quickfix.render(&mut text);
+
+    let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+    let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
+    buffer.properties = BufferProperties::scratch();
+
+    let mut events = BufferEditMutGuard::new(ctx.editor.events.writer(), buffer.handle());
+    let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
+    buffer.delete_range(
+        &mut ctx.editor.word_database,
+        range,
+        events.to_range_deletes(),
+    );
+    buffer.insert_text(
+        &mut ctx.editor.word_database,
+        BufferPosition::zero(),
+        &text,
+        events.to_text_inserts(),
+    );
+    std::mem::drop(events);
+    ctx.editor.string_pool.release(text);
+
+    {
+        let mut cursors = buffer_view.cursors.mut_guard();
+        cursors.clear();
+        cursors.add(Cursor {
+            anchor: BufferPosition::zero(),
+            position: BufferPosition::zero(),
+        });
+    }
+
+    let client = ctx.clients.get_mut(client_handle);
+    client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+    Ok(buffer_view_handle)
+}
+
+pub fn goto_entry(
+    ctx: &mut EditorContext,
+    client_handle: ClientHandle,
+    index: usize,
+) -> Result<(), BufferReadError> {
+    ctx.editor.quickfix.set_current_index(index);
+    let entry = ctx.editor.quickfix.entry(index);
+    let position = entry.position;
+    let path = ctx.editor.string_pool.acquire_with(entry.path);
+
+    let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
+        client_handle,
+        Path::new(&path),
+        BufferProperties::text(),
+        false,
+    );
+    ctx.editor.string_pool.release(path);
+    let buffer_view_handle = buffer_view_handle?;
+
+    let client = ctx.clients.get_mut(client_handle);
+    client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+
+    let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+    let position = ctx
+        .editor
+        .buffers
+        .get(buffer_view.buffer_handle)
+        .content()
+        .saturate_position(position);
+    {
+        let mut cursors = buffer_view.cursors.mut_guard();
+        cursors.clear();
+        cursors.add(Cursor {
+            anchor: position,
+            position,
+        });
+    }
+
+    let client = ctx.clients.get(client_handle);
+    client.set_view_anchor(&mut ctx.editor, ViewAnchor::Center);
+    Ok(())
+}
@@ -0,0 +1,405 @@
+use std::{
+    cmp::Ordering,
+    collections::HashSet,
+    fs,
+    io::{self, Write},
+    ops::Range,
+    path::Path,
+};
+
+use crate::{
+    buffer::BufferContent,
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    editor_utils::{LogKind, Logger},
+    glob::Glob,
+    word_database::{WordIter, WordKind},
+};
+
+#[derive(Default)]
+pub struct SpellChecker {
+    words: HashSet<String>,
+    dictionary_path: String,
+    user_dictionary_path: String,
+    version: u32,
+    check_glob: Glob,
+    check_glob_pattern: String,
+    check_glob_compiled: bool,
+}
+
+impl SpellChecker {
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    // a capitalized word also matches its lowercase entry as it may just start a sentence
+    pub fn contains(&self, word: &str) -> bool {
+        if self.words.contains(word) {
+            return true;
+        }
+        match word.chars().next() {
+            Some(c) if c.is_uppercase() => self.words.contains(&word.to_lowercase()),
+            _ => false,
+        }
+    }
+
+    // both word lists are read again only when one of their paths changes
+    pub(crate) fn reload(
+        &mut self,
+        dictionary_path: &str,
+        user_dictionary_path: &str,
+        current_directory: &Path,
+        logger: &mut Logger,
+    ) {
+        if self.dictionary_path == dictionary_path
+            && self.user_dictionary_path == user_dictionary_path
+        {
+            return;
+        }
+
+        self.dictionary_path.clear();
+        self.dictionary_path.push_str(dictionary_path);
+        self.user_dictionary_path.clear();
+        self.user_dictionary_path.push_str(user_dictionary_path);
+        self.words.clear();
+        self.version = self.version.wrapping_add(1);
+
+        for path in [dictionary_path, user_dictionary_path] {
+            if path.is_empty() {
+                continue;
+            }
+            match fs::read_to_string(current_directory.join(path)) {
+                Ok(text) => {
+                    for line in text.lines() {
+                        self.add_dictionary_line(line);
+                    }
+                }
+                Err(error) => logger.write(LogKind::Error).fmt(format_args!(
+                    "could not load spell dictionary '{}': {}",
+                    path, error
+                )),
+            }
+        }
+    }
+
+    // the glob is compiled again only when the pattern changes
+    pub(crate) fn set_check_glob(&mut self, pattern: &str) {
+        if self.check_glob_pattern == pattern {
+            return;
+        }
+
+        self.check_glob_pattern.clear();
+        self.check_glob_pattern.push_str(pattern);
+        self.check_glob_compiled = !pattern.is_empty() && self.check_glob.compile(pattern).is_ok();
+    }
+
+    pub fn checks_path(&self, path: &str) -> bool {
+        self.check_glob_compiled && !path.is_empty() && self.check_glob.matches(path)
+    }
+
+    // accepts plain word lists (one word per line) as well as hunspell '.dic' files
+    // whose first line is the word count and whose words may be followed by '/<flags>'.
+    // affix flags are dropped, so only the stems of a '.dic' file are known words
+    fn add_dictionary_line(&mut self, line: &str) {
+        let word = match line.split_once('/') {
+            Some((word, _)) => word,
+            None => line,
+        };
+        let word = word.trim();
+        if word.is_empty() || word.starts_with('#') || word.bytes().all(|b| b.is_ascii_digit()) {
+            return;
+        }
+        self.words.insert(word.into());
+    }
+
+    pub fn add_word(&mut self, word: &str) {
+        if self.words.insert(word.into()) {
+            self.version = self.version.wrapping_add(1);
+        }
+    }
+
+    pub fn append_to_user_dictionary(
+        &mut self,
+        word: &str,
+        current_directory: &Path,
+    ) -> io::Result<()> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(current_directory.join(&self.user_dictionary_path))?;
+        writeln!(file, "{}", word)?;
+        self.add_word(word);
+        Ok(())
+    }
+}
+
+// words with digits or underscores, acronyms and camelCase words are most likely names and are
+// never flagged, while contractions like "don't" are checked as a single word
+pub fn for_each_misspelling<F>(spell_checker: &SpellChecker, line: &str, mut callback: F)
+where
+    F: FnMut(Range<usize>),
+{
+    fn should_check(word: &str) -> bool {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(c) if c.is_alphabetic() => (),
+            _ => return false,
+        }
+        let rest = chars.as_str();
+        !rest.is_empty()
+            && rest
+                .chars()
+                .all(|c| c.is_alphabetic() && !c.is_uppercase() || c == '\'' || c == '’')
+    }
+
+    let mut index = 0;
+    let mut words = WordIter(line);
+    while let Some(word) = words.next() {
+        let start = index;
+        index += word.text.len();
+        if word.kind != WordKind::Identifier {
+            continue;
+        }
+
+        let mut rest = words.clone();
+        if let (Some(apostrophe), Some(suffix)) = (rest.next(), rest.next()) {
+            if matches!(apostrophe.text, "'" | "’") && suffix.kind == WordKind::Identifier {
+                index += apostrophe.text.len() + suffix.text.len();
+                words = rest;
+            }
+        }
+
+        let word = &line[start..index];
+        if should_check(word) && !spell_checker.contains(word) {
+            callback(start..index);
+        }
+    }
+}
+
+fn push_line_misspellings(
+    spell_checker: &SpellChecker,
+    content: &BufferContent,
+    line_index: usize,
+    ranges: &mut Vec<BufferRange>,
+) {
+    let line = content.lines()[line_index].as_str();
+    for_each_misspelling(spell_checker, line, |range| {
+        ranges.push(BufferRange::between(
+            BufferPosition::line_col(line_index as _, range.start as _),
+            BufferPosition::line_col(line_index as _, range.end as _),
+        ));
+    });
+}
+
+#[derive(Default)]
+pub struct BufferMisspellings {
+    ranges: Vec<BufferRange>,
+    dirty_line_ranges: Vec<(BufferPositionIndex, BufferPositionIndex)>,
+    needs_update: bool,
+    checked_version: u32,
+}
+
+impl BufferMisspellings {
+    pub fn ranges(&self) -> &[BufferRange] {
+        &self.ranges
+    }
+
+    // also makes the next update check the whole buffer again
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+        self.dirty_line_ranges.clear();
+        self.needs_update = true;
+    }
+
+    pub(crate) fn invalidate(&mut self) {
+        self.needs_update = true;
+    }
+
+    // only the edited lines are checked again on the next update
+    pub(crate) fn insert_range(&mut self, range: BufferRange) {
+        for misspelling in &mut self.ranges {
+            misspelling.from = misspelling.from.insert(range);
+            misspelling.to = misspelling.to.insert(range);
+        }
+
+        let insert_line_count = range.to.line_index - range.from.line_index;
+        if insert_line_count > 0 {
+            for (from, to) in &mut self.dirty_line_ranges {
+                if range.from.line_index < *from {
+                    *from += insert_line_count;
+                }
+                if range.from.line_index < *to {
+                    *to += insert_line_count;
+                }
+            }
+        }
+        self.dirty_line_ranges
+            .push((range.from.line_index, range.to.line_index));
+    }
+
+    pub(crate) fn delete_range(&mut self, range: BufferRange) {
+        for misspelling in &mut self.ranges {
+            misspelling.from = misspelling.from.delete(range);
+            misspelling.to = misspelling.to.delete(range);
+        }
+
+        let delete_line_count = range.to.line_index - range.from.line_index;
+        if delete_line_count > 0 {
+            let delete_line_index = |index: &mut BufferPositionIndex| {
+                if range.to.line_index <= *index {
+                    *index -= delete_line_count;
+                } else if range.from.line_index < *index {
+                    *index = range.from.line_index;
+                }
+            };
+            for (from, to) in &mut self.dirty_line_ranges {
+                delete_line_index(from);
+                delete_line_index(to);
+            }
+        }
+        self.dirty_line_ranges
+            .push((range.from.line_index, range.from.line_index));
+    }
+
+    pub fn update(&mut self, spell_checker: &SpellChecker, content: &BufferContent) {
+        if self.needs_update || self.checked_version != spell_checker.version {
+            self.needs_update = false;
+            self.checked_version = spell_checker.version;
+            self.dirty_line_ranges.clear();
+
+            self.ranges.clear();
+            if spell_checker.is_empty() {
+                return;
+            }
+            for line_index in 0..content.lines().len() {
+                push_line_misspellings(spell_checker, content, line_index, &mut self.ranges);
+            }
+            return;
+        }
+
+        if self.dirty_line_ranges.is_empty() {
+            return;
+        }
+
+        self.dirty_line_ranges.sort_unstable();
+        self.dirty_line_ranges.dedup_by(|next, previous| {
+            if next.0 <= previous.1.saturating_add(1) {
+                previous.1 = previous.1.max(next.1);
+                true
+            } else {
+                false
+            }
+        });
+
+        let dirty_line_ranges = &self.dirty_line_ranges;
+        let is_dirty = |line_index| {
+            dirty_line_ranges
+                .binary_search_by(|&(from, to)| {
+                    if to < line_index {
+                        Ordering::Less
+                    } else if line_index < from {
+                        Ordering::Greater
+                    } else {
+                        Ordering::Equal
+                    }
+                })
+                .is_ok()
+        };
+        self.ranges
+            .retain(|r| !is_dirty(r.from.line_index) && !is_dirty(r.to.line_index));
+
+        if !spell_checker.is_empty() {
+            let line_count = content.lines().len();
+            for &(from, to) in dirty_line_ranges {
+                let to = (to as usize + 1).min(line_count);
+                for line_index in from as usize..to {
+                    push_line_misspellings(spell_checker, content, line_index, &mut self.ranges);
+                }
+            }
+            self.ranges.sort_unstable_by_key(|r| r.from);
+        }
+
+        self.dirty_line_ranges.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn misspellings(spell_checker: &SpellChecker, line: &str) -> Vec<String> {
+        let mut words = Vec::new();
+        for_each_misspelling(spell_checker, line, |range| words.push(line[range].into()));
+        words
+    }
+
+    #[test]
+    fn find_misspellings() {
+        let mut spell_checker = SpellChecker::default();
+        for line in ["3", "# comment", "the", "quick/AB", "fox", "don't", ""] {
+            spell_checker.add_dictionary_line(line);
+        }
+        assert!(spell_checker.contains("quick"));
+        assert!(!spell_checker.contains("3"));
+        assert!(!spell_checker.contains("quick/AB"));
+
+        assert_eq!(
+            vec!["quik", "brwn", "doesn't"],
+            misspellings(
+                &spell_checker,
+                "The quik brwn fox, don't; doesn't a HTTP fooBar x86 snake_case",
+            ),
+        );
+
+        spell_checker.add_word("brwn");
+        assert_eq!(vec!["quik"], misspellings(&spell_checker, "quik brwn Brwn"));
+    }
+
+    #[test]
+    fn update_misspellings_of_edited_lines() {
+        fn assert_same_as_full_update(
+            spell_checker: &SpellChecker,
+            content: &BufferContent,
+            misspellings: &mut BufferMisspellings,
+        ) {
+            misspellings.update(spell_checker, content);
+            let mut expected = BufferMisspellings::default();
+            expected.clear();
+            expected.update(spell_checker, content);
+            assert_eq!(expected.ranges(), misspellings.ranges());
+        }
+
+        let mut spell_checker = SpellChecker::default();
+        for word in ["the", "quick", "fox"] {
+            spell_checker.add_word(word);
+        }
+
+        let mut content = BufferContent::new();
+        content.insert_text(BufferPosition::zero(), "the quik\nfox\nbrwn fox\nthe");
+        let mut misspellings = BufferMisspellings::default();
+        misspellings.clear();
+        misspellings.update(&spell_checker, &content);
+        assert_eq!(2, misspellings.ranges().len());
+
+        let range = content.insert_text(BufferPosition::line_col(1, 3), " jmps\nover");
+        misspellings.insert_range(range);
+        let range = content.insert_text(BufferPosition::line_col(0, 0), "lazzy ");
+        misspellings.insert_range(range);
+        assert_same_as_full_update(&spell_checker, &content, &mut misspellings);
+        assert_eq!(5, misspellings.ranges().len());
+
+        let range = BufferRange::between(
+            BufferPosition::line_col(0, 6),
+            BufferPosition::line_col(2, 0),
+        );
+        content.delete_range(range);
+        misspellings.delete_range(range);
+        let range = BufferRange::between(
+            BufferPosition::line_col(1, 0),
+            BufferPosition::line_col(1, 5),
+        );
+        content.delete_range(range);
+        misspellings.delete_range(range);
+        assert_same_as_full_update(&spell_checker, &content, &mut misspellings);
+        assert_eq!(2, misspellings.ranges().len());
+    }
+}
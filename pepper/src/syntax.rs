@@ -1,4 +1,4 @@
-use std::str::FromStr;
+use std::{ops::Range, str::FromStr};
 
 use crate::{
     buffer::BufferContent,
@@ -114,6 +114,27 @@ impl Syntax {
         self.rules[kind as usize].compile(pattern)
     }
 
+    pub fn has_rule(&self, kind: TokenKind) -> bool {
+        !self.rules[kind as usize].is_empty()
+    }
+
+    // calls `f` with the line index and byte range of each comment token in the buffer
+    pub fn for_each_comment<F>(&self, buffer: &BufferContent, mut f: F)
+    where
+        F: FnMut(usize, Range<usize>),
+    {
+        let mut tokens = Vec::new();
+        let mut parse_state = LineParseState::Finished;
+        for (line_index, line) in buffer.lines().iter().enumerate() {
+            parse_state = self.parse_line(line.as_str(), parse_state, &mut tokens);
+            for token in &tokens {
+                if token.kind == TokenKind::Comment {
+                    f(line_index, token.from as usize..token.to as usize);
+                }
+            }
+        }
+    }
+
     fn parse_line(
         &self,
         line: &str,
@@ -302,7 +323,9 @@ impl HighlightedBuffer {
 
     pub fn clear(&mut self) {
         self.highlighted_len = 1;
+        self.lines[0].parse_state = LineParseState::Dirty;
         self.dirty_line_indexes.clear();
+        self.dirty_line_indexes.push(0);
     }
 
     pub fn insert_range(&mut self, range: BufferRange) {
@@ -426,9 +449,8 @@ impl HighlightedBuffer {
                     return HighlightResult::Pending;
                 }
 
-                if previous_state == LineParseState::Finished
-                    && previous_parse_state == LineParseState::Finished
-                {
+                // following lines only depend on the state the previous line ended with
+                if previous_state == previous_parse_state {
                     break;
                 }
             }
@@ -445,6 +467,44 @@ impl HighlightedBuffer {
             &[]
         }
     }
+
+    pub fn for_each_line_span<F>(&self, line_index: usize, line: &str, mut f: F)
+    where
+        F: FnMut(TokenKind, &str),
+    {
+        let mut line_tokens = self.line_tokens(line_index).iter();
+        let mut last_line_token = Token::default();
+        let mut span_kind = TokenKind::Text;
+        let mut span_start = 0;
+
+        for (char_index, c) in line.char_indices() {
+            let token_kind = if c.is_ascii_whitespace() {
+                TokenKind::Whitespace
+            } else {
+                if !last_line_token.contains(char_index as _) {
+                    for token in line_tokens.by_ref() {
+                        if token.contains(char_index as _) {
+                            last_line_token = token.clone();
+                            break;
+                        }
+                    }
+                }
+                last_line_token.kind
+            };
+
+            if token_kind != span_kind {
+                if span_start < char_index {
+                    f(span_kind, &line[span_start..char_index]);
+                }
+                span_kind = token_kind;
+                span_start = char_index;
+            }
+        }
+
+        if span_start < line.len() {
+            f(span_kind, &line[span_start..]);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -534,6 +594,24 @@ mod tests {
         assert_token("  ", TokenKind::Text, line, &tokens[5]);
     }
 
+    #[test]
+    fn comment_ranges() {
+        let mut syntax = Syntax::new();
+        assert!(!syntax.has_rule(TokenKind::Comment));
+        syntax
+            .set_rule(TokenKind::Comment, "//{.}|/*{!(*/).$}")
+            .unwrap();
+        assert!(syntax.has_rule(TokenKind::Comment));
+
+        let mut buffer = BufferContent::new();
+        buffer.insert_text(BufferPosition::zero(), "a // b\n/* c\nd */ e\nf");
+        let mut ranges = Vec::new();
+        syntax.for_each_comment(&buffer, |line_index, range| {
+            ranges.push((line_index, range))
+        });
+        assert_eq!(&[(0, 1..6), (1, 0..4), (2, 0..4)], &ranges[..]);
+    }
+
     #[test]
     fn beginning_anchor_syntax() {
         let mut syntax = Syntax::new();
@@ -616,6 +694,28 @@ mod tests {
         }
     }
 
+    #[test]
+    fn clearing_highlighted_buffer() {
+        let mut syntax = Syntax::new();
+        syntax.set_rule(TokenKind::Keyword, "fn").unwrap();
+
+        let mut buffer = BufferContent::new();
+        let mut highlighted = HighlightedBuffer::new();
+
+        let range = buffer.insert_text(BufferPosition::zero(), "fn");
+        highlighted.insert_range(range);
+        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        assert_eq!(TokenKind::Keyword, highlighted.line_tokens(0)[0].kind);
+
+        highlighted.clear();
+        highlighted.highlight_dirty_lines(&Syntax::new(), &buffer);
+        assert_eq!(TokenKind::Text, highlighted.line_tokens(0)[0].kind);
+
+        highlighted.clear();
+        highlighted.highlight_dirty_lines(&syntax, &buffer);
+        assert_eq!(TokenKind::Keyword, highlighted.line_tokens(0)[0].kind);
+    }
+
     #[test]
     fn highlight_range_after_unfinished_line() {
         let mut syntax = Syntax::new();
@@ -743,4 +843,92 @@ mod tests {
             assert_eq!(None, tokens.next());
         }
     }
+
+    #[test]
+    fn highlighted_line_spans() {
+        let mut syntax = Syntax::new();
+        syntax.set_rule(TokenKind::Keyword, "fn").unwrap();
+        syntax.set_rule(TokenKind::Symbol, "%(|%)").unwrap();
+
+        let mut buffer = BufferContent::new();
+        let mut highlighted = HighlightedBuffer::new();
+
+        let range = buffer.insert_text(BufferPosition::zero(), "fn  main()");
+        highlighted.insert_range(range);
+        highlighted.highlight_dirty_lines(&syntax, &buffer);
+
+        let mut spans = Vec::new();
+        highlighted.for_each_line_span(0, buffer.lines()[0].as_str(), |kind, text| {
+            spans.push((kind, text.to_string()))
+        });
+        assert_eq!(
+            vec![
+                (TokenKind::Keyword, "fn".into()),
+                (TokenKind::Whitespace, "  ".into()),
+                (TokenKind::Text, "main".into()),
+                (TokenKind::Symbol, "()".into()),
+            ],
+            spans,
+        );
+    }
+
+    #[test]
+    fn highlight_cost_scales_with_edit() {
+        fn highlight_all(
+            highlighted: &mut HighlightedBuffer,
+            syntax: &Syntax,
+            buffer: &BufferContent,
+        ) {
+            while let HighlightResult::Pending = highlighted.highlight_dirty_lines(syntax, buffer) {
+            }
+        }
+
+        let mut syntax = Syntax::new();
+        syntax.set_rule(TokenKind::Comment, "/*{!(*/).$}").unwrap();
+        syntax.set_rule(TokenKind::Keyword, "fn").unwrap();
+
+        let mut text = String::new();
+        text.push_str("/*\n");
+        for _ in 0..100_000 {
+            text.push_str("fn a\n");
+        }
+        text.push_str("*/");
+        let mut paste = String::new();
+        for _ in 0..10_000 {
+            paste.push_str("fn b\n");
+        }
+
+        let mut buffer = BufferContent::new();
+        let mut highlighted = HighlightedBuffer::new();
+        let range = buffer.insert_text(BufferPosition::zero(), &text);
+        highlighted.insert_range(range);
+        highlight_all(&mut highlighted, &syntax, &buffer);
+        assert_eq!(buffer.lines().len(), highlighted.highlighted_len);
+
+        // lines that get reparsed will have their tokens restored
+        let sentinel_line_index = 90_000;
+        highlighted.lines[sentinel_line_index].tokens.clear();
+
+        let range = buffer.insert_text(BufferPosition::line_col(50_000, 0), &paste);
+        highlighted.insert_range(range);
+        highlight_all(&mut highlighted, &syntax, &buffer);
+        assert_eq!(buffer.lines().len(), highlighted.highlighted_len);
+
+        let pasted_tokens = highlighted.line_tokens(55_000);
+        assert_eq!(1, pasted_tokens.len());
+        assert_eq!(TokenKind::Comment, pasted_tokens[0].kind);
+        assert!(highlighted
+            .line_tokens(sentinel_line_index + 10_000)
+            .is_empty());
+
+        let range = BufferRange::between(
+            BufferPosition::line_col(50_000, 0),
+            BufferPosition::line_col(60_000, 0),
+        );
+        buffer.delete_range(range);
+        highlighted.delete_range(range);
+        highlight_all(&mut highlighted, &syntax, &buffer);
+        assert_eq!(buffer.lines().len(), highlighted.highlighted_len);
+        assert!(highlighted.line_tokens(sentinel_line_index).is_empty());
+    }
 }
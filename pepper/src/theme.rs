@@ -1,3 +1,5 @@
+use crate::syntax::TokenKind;
+
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Color(pub u8, pub u8, pub u8);
 
@@ -42,12 +44,20 @@ theme_colors! {
     active_background,
     breakpoint_background,
     highlight,
+    matching_bracket,
+    unmatched_bracket,
+    invisible_char,
     normal_cursor,
     select_cursor,
     insert_cursor,
     inactive_cursor,
     statusbar_active_background,
     statusbar_inactive_background,
+    diff_added,
+    diff_modified,
+    diff_removed,
+    minimap_viewport,
+    minimap_lint,
 
     token_whitespace,
     token_text,
@@ -59,6 +69,21 @@ theme_colors! {
     token_literal,
 }
 
+impl Theme {
+    pub fn token_color(&self, kind: TokenKind) -> Color {
+        match kind {
+            TokenKind::Keyword => self.token_keyword,
+            TokenKind::Type => self.token_type,
+            TokenKind::Symbol => self.token_symbol,
+            TokenKind::Literal => self.token_literal,
+            TokenKind::String => self.token_string,
+            TokenKind::Comment => self.token_comment,
+            TokenKind::Text => self.token_text,
+            TokenKind::Whitespace => self.token_whitespace,
+        }
+    }
+}
+
 impl Default for Theme {
     fn default() -> Self {
         gruvbox_theme()
@@ -71,12 +96,20 @@ pub fn gruvbox_theme() -> Theme {
         active_background: Color::from_u32(0x282828),
         breakpoint_background: Color::from_u32(0x3d2021),
         highlight: Color::from_u32(0xfabd2f),
+        matching_bracket: Color::from_u32(0x83a598),
+        unmatched_bracket: Color::from_u32(0xfb4934),
+        invisible_char: Color::from_u32(0xfe8019),
         normal_cursor: Color::from_u32(0xcc241d),
         insert_cursor: Color::from_u32(0xfabd2f),
         select_cursor: Color::from_u32(0x458588),
         inactive_cursor: Color::from_u32(0x504945),
         statusbar_active_background: Color::from_u32(0x504945),
         statusbar_inactive_background: Color::from_u32(0x282828),
+        diff_added: Color::from_u32(0xb8bb26),
+        diff_modified: Color::from_u32(0xfabd2f),
+        diff_removed: Color::from_u32(0xfb4934),
+        minimap_viewport: Color::from_u32(0x3c3836),
+        minimap_lint: Color::from_u32(0xfb4934),
 
         token_whitespace: Color::from_u32(0x504945),
         token_text: Color::from_u32(0xebdbb2),
@@ -1,12 +1,14 @@
-use std::{io, iter};
+use std::{io, iter, ops::Range};
 
 use crate::{
-    buffer::CharDisplayDistances,
+    buffer::{Buffer, CharDisplayDistances, DisplayLen, MatchingBracket},
     buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::{BufferViewHandle, CursorMovementKind},
+    config::{Config, CursorShape},
     cursor::Cursor,
     editor::Editor,
     editor_utils::{LoggerStatusBarDisplay, REGISTER_READLINE_INPUT, REGISTER_READLINE_PROMPT},
+    git::GitHunkKind,
     mode::ModeKind,
     syntax::{Token, TokenKind},
     theme::Color,
@@ -16,6 +18,7 @@ pub static ENTER_ALTERNATE_BUFFER_CODE: &[u8] = b"\x1b[?1049h";
 pub static EXIT_ALTERNATE_BUFFER_CODE: &[u8] = b"\x1b[?1049l";
 pub static HIDE_CURSOR_CODE: &[u8] = b"\x1b[?25l";
 pub static SHOW_CURSOR_CODE: &[u8] = b"\x1b[?25h";
+pub static RESET_CURSOR_SHAPE_CODE: &[u8] = b"\x1b[0 q";
 pub static RESET_STYLE_CODE: &[u8] = b"\x1b[0;49m";
 pub static MODE_256_COLORS_CODE: &[u8] = b"\x1b[=19h";
 pub static BEGIN_TITLE_CODE: &[u8] = b"\x1b]0;";
@@ -23,6 +26,10 @@ pub static END_TITLE_CODE: &[u8] = b"\x07";
 
 static TOO_LONG_PREFIX: &[u8] = b"...";
 
+// each minimap cell is a braille char of 2x4 dots. a dot is set when any of the buffer columns and
+// lines it covers has a non whitespace char
+const MINIMAP_COLUMNS_PER_DOT: usize = 4;
+
 pub fn clear_line(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[2K");
 }
@@ -33,7 +40,16 @@ pub fn clear_until_new_line(buf: &mut Vec<u8>) {
 
 pub fn move_cursor_to(buf: &mut Vec<u8>, x: usize, y: usize) {
     use io::Write;
-    let _ = write!(buf, "\x1b[{};{}H", x, y);
+    let _ = write!(buf, "\x1b[{};{}H", y + 1, x + 1);
+}
+
+pub fn set_cursor_shape(buf: &mut Vec<u8>, shape: CursorShape) {
+    let code: &[u8] = match shape {
+        CursorShape::Block => b"\x1b[2 q",
+        CursorShape::Underline => b"\x1b[4 q",
+        CursorShape::Bar => b"\x1b[6 q",
+    };
+    buf.extend_from_slice(code);
 }
 
 pub fn move_cursor_to_next_line(buf: &mut Vec<u8>) {
@@ -63,6 +79,24 @@ pub fn set_not_underlined(buf: &mut Vec<u8>) {
     buf.extend_from_slice(b"\x1b[24m");
 }
 
+// the minimap is drawn to the right of the buffer view. its first column marks rows with lints or
+// search matches and the others show the whole buffer compressed to fit the screen height
+pub fn minimap_width(config: &Config, viewport_width: u16) -> usize {
+    let width = config.minimap_width as usize;
+    if config.minimap && width >= 2 && width * 2 <= viewport_width as usize {
+        width
+    } else {
+        0
+    }
+}
+
+// how many buffer lines each minimap row covers so that the whole buffer fits in `height` rows.
+// it's always a multiple of 4 as each row is 4 braille dots tall
+pub fn minimap_lines_per_row(line_count: usize, height: usize) -> usize {
+    let dot_rows = height.max(1) * 4;
+    line_count.div_ceil(dot_rows).max(1) * 4
+}
+
 pub struct RenderContext<'a> {
     pub editor: &'a Editor,
     pub status_bar_display: &'a LoggerStatusBarDisplay<'a, 'a>,
@@ -72,9 +106,30 @@ pub struct RenderContext<'a> {
 }
 
 pub fn draw(ctx: &RenderContext, buffer_view_handle: Option<BufferViewHandle>, buf: &mut Vec<u8>) {
-    draw_buffer_view(ctx, buffer_view_handle, buf);
+    buf.extend_from_slice(HIDE_CURSOR_CODE);
+    let main_cursor_screen_position = draw_buffer_view(ctx, buffer_view_handle, buf);
     draw_picker(ctx, buf);
     draw_statusbar(ctx, buffer_view_handle, buf);
+
+    if let Some((x, y)) = main_cursor_screen_position {
+        draw_terminal_cursor(ctx, x, y, buf);
+    }
+}
+
+fn draw_terminal_cursor(ctx: &RenderContext, x: usize, y: usize, buf: &mut Vec<u8>) {
+    if !ctx.editor.config.terminal_cursor {
+        return;
+    }
+
+    let shape = match ctx.editor.mode.kind() {
+        ModeKind::Normal => ctx.editor.config.normal_cursor_shape,
+        ModeKind::Insert => ctx.editor.config.insert_cursor_shape,
+        _ => return,
+    };
+
+    move_cursor_to(buf, x, y);
+    set_cursor_shape(buf, shape);
+    buf.extend_from_slice(SHOW_CURSOR_CODE);
 }
 
 fn draw_empty_view(ctx: &RenderContext, buf: &mut Vec<u8>) {
@@ -142,27 +197,36 @@ fn draw_empty_view(ctx: &RenderContext, buf: &mut Vec<u8>) {
     }
 }
 
+// returns the screen position of the main cursor if it was drawn
 fn draw_buffer_view(
     ctx: &RenderContext,
     buffer_view_handle: Option<BufferViewHandle>,
     buf: &mut Vec<u8>,
-) {
+) -> Option<(usize, usize)> {
     let buffer_view_handle = match buffer_view_handle {
         Some(handle) => handle,
         None => {
             draw_empty_view(ctx, buf);
-            return;
+            return None;
         }
     };
 
     let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
     let buffer = ctx.editor.buffers.get(buffer_view.buffer_handle);
     let cursors = &buffer_view.cursors[..];
-    let active_line_index = buffer_view.cursors.main_cursor().position.line_index as usize;
-
-    let tab_size = ctx.editor.config.tab_size.max(1);
-
-    let draw_width = ctx.viewport_size.0 as usize;
+    let main_cursor_position = buffer_view.cursors.main_cursor().position;
+    let active_line_index = main_cursor_position.line_index as usize;
+
+    let tab_size = buffer
+        .indentation_config(&ctx.editor.config)
+        .tab_size
+        .max(1);
+    let tab_stops = &ctx.editor.config.tab_stops;
+    let wrap_lines = buffer.wraps_lines(ctx.editor.config.wrap_lines);
+
+    let minimap_width = minimap_width(&ctx.editor.config, ctx.viewport_size.0);
+    let draw_width = ctx.viewport_size.0 as usize - minimap_width;
+    let wrap_width = if wrap_lines { draw_width } else { usize::MAX };
     let draw_height = ctx.viewport_size.1.saturating_sub(1);
     let draw_height = if ctx.has_focus {
         let picker_height = ctx
@@ -197,27 +261,34 @@ fn draw_buffer_view(
     let lints = buffer.lints.all();
     let lints_end_index = lints.len().saturating_sub(1);
 
+    let misspellings = buffer.misspellings.ranges();
+    let misspellings_end_index = misspellings.len().saturating_sub(1);
+
     let breakpoints = buffer.breakpoints();
     let breakpoints_end_index = breakpoints.len().saturating_sub(1);
 
+    let gutter_width = buffer.git_diff.gutter_width();
+
     let mut scroll_offset = BufferPosition::zero();
     let mut scroll_padding_top = ctx.scroll as usize;
-    for (line_index, display_len) in buffer_content.line_display_lens().iter().enumerate() {
+    for (line_index, &display_len) in buffer_content.line_display_lens().iter().enumerate() {
         scroll_offset.line_index = line_index as _;
 
         if scroll_padding_top == 0 {
             break;
         }
 
-        let line_height = 1 + display_len.total_len(tab_size) / draw_width;
+        let line = buffer_content.lines()[line_index].as_str();
+        let line_len = tab_stops.line_len(line, display_len, tab_size);
+        let line_height = 1 + (gutter_width + line_len) / wrap_width;
         if line_height <= scroll_padding_top {
             scroll_padding_top -= line_height;
             continue;
         }
 
-        let line = buffer_content.lines()[line_index].as_str();
-        let target_display_len = (scroll_padding_top * draw_width) as _;
-        for d in CharDisplayDistances::new(line, tab_size) {
+        let target_display_len =
+            (scroll_padding_top * wrap_width).saturating_sub(gutter_width) as _;
+        for d in CharDisplayDistances::new(line, tab_size).with_tab_stops(tab_stops) {
             if d.distance >= target_display_len {
                 let index = d.char_index as usize + d.char.len_utf8();
                 scroll_offset.column_byte_index = index as _;
@@ -228,6 +299,40 @@ fn draw_buffer_view(
         break;
     }
 
+    let scroll_x = if wrap_lines {
+        0
+    } else {
+        let position = buffer_view.cursors.main_cursor().position;
+        let line = buffer_content.lines()[position.line_index as usize].as_str();
+        let line = &line[..position.column_byte_index as usize];
+        let mut column = 0;
+        for d in CharDisplayDistances::new(line, tab_size).with_tab_stops(tab_stops) {
+            column = d.distance as usize;
+        }
+        (column + 1 + gutter_width).saturating_sub(draw_width)
+    };
+
+    let mut bracket_position = BufferPosition::line_col(BufferPositionIndex::MAX, 0);
+    let mut matching_bracket_position = bracket_position;
+    let mut bracket_color = ctx.editor.theme.matching_bracket;
+    if ctx.has_focus {
+        let position = buffer_view.cursors.main_cursor().position;
+        let mut matching_bracket = buffer_content.find_matching_bracket(position);
+        bracket_position = position;
+        if matching_bracket.is_none() && position.column_byte_index > 0 {
+            bracket_position = buffer_content.position_before(position);
+            matching_bracket = buffer_content.find_matching_bracket(bracket_position);
+        }
+
+        match matching_bracket {
+            Some(MatchingBracket::Matched(position)) => matching_bracket_position = position,
+            Some(MatchingBracket::Unmatched) => {
+                bracket_color = ctx.editor.theme.unmatched_bracket;
+            }
+            None => bracket_position = matching_bracket_position,
+        }
+    }
+
     let mut current_cursor_index = cursors.len();
     let mut current_cursor_position = BufferPosition::zero();
     let mut current_cursor_range = BufferRange::zero();
@@ -261,6 +366,16 @@ fn draw_buffer_view(
         }
     }
 
+    let mut current_misspelling_index = misspellings.len();
+    let mut current_misspelling_range = BufferRange::zero();
+    for (i, &range) in misspellings.iter().enumerate() {
+        if scroll_offset < range.to {
+            current_misspelling_index = i;
+            current_misspelling_range = range;
+            break;
+        }
+    }
+
     let mut current_breakpoint_index = breakpoints.len();
     let mut current_breakpoint_line_index = BufferPositionIndex::MAX;
     for (i, breakpoint) in breakpoints.iter().enumerate() {
@@ -309,7 +424,10 @@ fn draw_buffer_view(
         .encode_utf8(&mut visual_tab_repeat)
         .as_bytes();
 
+    let mut main_cursor_screen_position = None;
     let mut lines_drawn_count = 0;
+    let first_drawn_line_index = scroll_offset.line_index as usize;
+    let mut last_drawn_line_index = first_drawn_line_index;
     for (line_index, line) in buffer_content
         .lines()
         .iter()
@@ -321,6 +439,8 @@ fn draw_buffer_view(
             Token(TokenKind),
             Selection(TokenKind),
             Highlight,
+            Bracket,
+            InvisibleChar,
             Cursor,
         }
 
@@ -328,11 +448,18 @@ fn draw_buffer_view(
             break;
         }
         lines_drawn_count += 1;
+        last_drawn_line_index = line_index;
 
-        let line = &line.as_str()[scroll_offset.column_byte_index as usize..];
+        let (skipped_line, line) = line
+            .as_str()
+            .split_at(scroll_offset.column_byte_index as usize);
         let mut draw_state = DrawState::Token(TokenKind::Text);
         let mut was_inside_lint_range = false;
         let mut x = 0;
+        let mut column = 0;
+        let mut tab_index = skipped_line.matches('\t').count();
+        let mut tab_column =
+            tab_stops.line_len(skipped_line, DisplayLen::from(skipped_line), tab_size);
         let mut last_line_token = Token::default();
         let mut line_tokens = highlighted_buffer.line_tokens(line_index).iter();
 
@@ -353,12 +480,42 @@ fn draw_buffer_view(
         };
 
         set_background_color(buf, background_color);
+
+        if gutter_width > 0 && skipped_line.is_empty() {
+            match buffer.git_diff.line_sign(line_index) {
+                Some(GitHunkKind::Added) => {
+                    set_foreground_color(buf, ctx.editor.theme.diff_added);
+                    buf.push(b'+');
+                }
+                Some(GitHunkKind::Modified) => {
+                    set_foreground_color(buf, ctx.editor.theme.diff_modified);
+                    buf.push(b'~');
+                }
+                Some(GitHunkKind::Removed) => {
+                    set_foreground_color(buf, ctx.editor.theme.diff_removed);
+                    buf.push(b'-');
+                }
+                None => buf.push(b' '),
+            }
+            x = gutter_width;
+        }
+
         set_foreground_color(buf, ctx.editor.theme.token_text);
 
         for (char_index, c) in line.char_indices().chain(iter::once((line.len(), '\n'))) {
             let char_index = char_index + scroll_offset.column_byte_index as usize;
             let char_position = BufferPosition::line_col(line_index as _, char_index as _);
 
+            let char_len = match c {
+                '\t' => {
+                    let len = tab_stops.tab_len(tab_index, tab_column, tab_size);
+                    tab_index += 1;
+                    len
+                }
+                _ => 1,
+            };
+            tab_column += char_len;
+
             let token_kind = if c.is_ascii_whitespace() {
                 TokenKind::Whitespace
             } else {
@@ -373,16 +530,8 @@ fn draw_buffer_view(
                 last_line_token.kind
             };
 
-            let text_color = match token_kind {
-                TokenKind::Keyword => ctx.editor.theme.token_keyword,
-                TokenKind::Type => ctx.editor.theme.token_type,
-                TokenKind::Symbol => ctx.editor.theme.token_symbol,
-                TokenKind::Literal => ctx.editor.theme.token_literal,
-                TokenKind::String => ctx.editor.theme.token_string,
-                TokenKind::Comment => ctx.editor.theme.token_comment,
-                TokenKind::Text => ctx.editor.theme.token_text,
-                TokenKind::Whitespace => ctx.editor.theme.token_whitespace,
-            };
+            let text_color = ctx.editor.theme.token_color(token_kind);
+            let is_invisible_char = !c.is_ascii() && ctx.editor.config.invisible_chars.contains(c);
 
             if current_cursor_index < cursors_end_index && current_cursor_range.to < char_position {
                 current_cursor_index += 1;
@@ -409,6 +558,16 @@ fn draw_buffer_view(
             let inside_lint_range =
                 current_lint_range.from <= char_position && char_position < current_lint_range.to;
 
+            if current_misspelling_range.to <= char_position
+                && current_misspelling_index < misspellings_end_index
+            {
+                current_misspelling_index += 1;
+                current_misspelling_range = misspellings[current_misspelling_index];
+            }
+            let inside_misspelling_range = current_misspelling_range.from <= char_position
+                && char_position < current_misspelling_range.to;
+            let inside_lint_range = inside_lint_range || inside_misspelling_range;
+
             if inside_lint_range != was_inside_lint_range {
                 was_inside_lint_range = inside_lint_range;
                 if inside_lint_range {
@@ -430,6 +589,20 @@ fn draw_buffer_view(
                     set_background_color(buf, text_color);
                     set_foreground_color(buf, background_color);
                 }
+            } else if is_invisible_char {
+                if draw_state != DrawState::InvisibleChar {
+                    draw_state = DrawState::InvisibleChar;
+                    set_background_color(buf, ctx.editor.theme.invisible_char);
+                    set_foreground_color(buf, background_color);
+                }
+            } else if char_position == bracket_position
+                || char_position == matching_bracket_position
+            {
+                if draw_state != DrawState::Bracket {
+                    draw_state = DrawState::Bracket;
+                    set_background_color(buf, bracket_color);
+                    set_foreground_color(buf, background_color);
+                }
             } else if inside_search_range {
                 if draw_state != DrawState::Highlight {
                     draw_state = DrawState::Highlight;
@@ -442,6 +615,36 @@ fn draw_buffer_view(
                 set_foreground_color(buf, text_color);
             }
 
+            if !wrap_lines {
+                let char_column = column;
+                column += char_len;
+
+                if column <= scroll_x || x + (column - char_column) > draw_width {
+                    continue;
+                }
+                if char_column < scroll_x {
+                    for _ in scroll_x..column {
+                        x += 1;
+                        buf.extend_from_slice(visual_tab_repeat);
+                    }
+                    continue;
+                }
+            }
+
+            // the terminal only wraps at the screen edge so lines are wrapped here when the
+            // minimap takes the columns after the buffer view
+            if wrap_lines && minimap_width > 0 && x + char_len > draw_width {
+                if lines_drawn_count == draw_height {
+                    break;
+                }
+                if x < draw_width {
+                    clear_until_new_line(buf);
+                }
+                move_cursor_to(buf, 0, lines_drawn_count as _);
+                x = 0;
+                lines_drawn_count += 1;
+            }
+
             let previous_x = x;
             let previous_buf_len = buf.len();
 
@@ -450,15 +653,20 @@ fn draw_buffer_view(
                     x += 1;
                     buf.push(b' ');
                 }
+                // drawn as a blank cell as some of them would otherwise take no space at all
+                _ if is_invisible_char => {
+                    x += 1;
+                    buf.push(b' ');
+                }
                 ' ' => {
                     x += 1;
                     buf.extend_from_slice(visual_space);
                 }
                 '\t' => {
-                    x += tab_size as usize;
+                    x += char_len;
 
                     buf.extend_from_slice(visual_tab_first);
-                    for _ in 0..tab_size - 1 {
+                    for _ in 1..char_len {
                         buf.extend_from_slice(visual_tab_repeat);
                     }
                 }
@@ -468,8 +676,9 @@ fn draw_buffer_view(
                 }
             }
 
-            if x > ctx.viewport_size.0 as _ {
-                x -= ctx.viewport_size.0 as usize;
+            let row = lines_drawn_count as usize - 1;
+            if wrap_lines && x > draw_width {
+                x -= draw_width;
                 lines_drawn_count += 1;
                 if lines_drawn_count > draw_height {
                     lines_drawn_count = draw_height;
@@ -478,12 +687,35 @@ fn draw_buffer_view(
                     break;
                 }
             }
+
+            if ctx.has_focus && char_position == main_cursor_position {
+                main_cursor_screen_position = if previous_x < draw_width {
+                    Some((previous_x, row))
+                } else {
+                    Some((previous_x - draw_width, row + 1))
+                };
+            }
         }
 
         scroll_offset.column_byte_index = 0;
         set_background_color(buf, background_color);
 
-        if x < ctx.viewport_size.0 as _ {
+        if line_index == active_line_index {
+            if let Some(annotation) = buffer.git_blame.line_annotation(line_index) {
+                let annotation_len = annotation.chars().count();
+                if x + 1 + annotation_len < draw_width {
+                    set_not_underlined(buf);
+                    set_foreground_color(buf, ctx.editor.theme.token_comment);
+                    for _ in x..draw_width - annotation_len - 1 {
+                        buf.push(b' ');
+                    }
+                    buf.extend_from_slice(annotation.as_bytes());
+                    x = draw_width - 1;
+                }
+            }
+        }
+
+        if x < draw_width {
             clear_until_new_line(buf);
         }
 
@@ -499,6 +731,121 @@ fn draw_buffer_view(
         clear_until_new_line(buf);
         move_cursor_to_next_line(buf);
     }
+
+    if minimap_width > 0 {
+        let visible_lines = first_drawn_line_index..last_drawn_line_index + 1;
+        draw_minimap(
+            ctx,
+            buffer,
+            visible_lines,
+            draw_width,
+            draw_height as _,
+            buf,
+        );
+        move_cursor_to(buf, 0, draw_height as _);
+    }
+
+    main_cursor_screen_position
+}
+
+fn draw_minimap(
+    ctx: &RenderContext,
+    buffer: &Buffer,
+    visible_lines: Range<usize>,
+    x: usize,
+    draw_height: usize,
+    buf: &mut Vec<u8>,
+) {
+    // braille dot bits indexed by [dot_column][dot_row]
+    const DOT_BITS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+
+    let config = &ctx.editor.config;
+    let theme = &ctx.editor.theme;
+    let tab_size = buffer.indentation_config(config).tab_size.max(1);
+
+    let lines = buffer.content().lines();
+    // the height does not shrink with the picker so rows keep matching `goto-minimap-row`
+    let height = ctx.viewport_size.1.saturating_sub(1) as usize;
+    let lines_per_row = minimap_lines_per_row(lines.len(), height);
+    let lines_per_dot = lines_per_row / 4;
+
+    let mut cells = [0u8; u8::MAX as usize];
+    let cells = &mut cells[..minimap_width(config, ctx.viewport_size.0) - 1];
+    let max_column = cells.len() * 2 * MINIMAP_COLUMNS_PER_DOT;
+
+    let lints = buffer.lints.all();
+    let mut lint_index = 0;
+    let search_ranges = buffer.search_ranges();
+    let mut search_range_index = 0;
+
+    let mut char_buf = [0; std::mem::size_of::<char>()];
+    for row in 0..draw_height {
+        let rows_lines =
+            (row * lines_per_row).min(lines.len())..((row + 1) * lines_per_row).min(lines.len());
+
+        while lint_index < lints.len()
+            && (lints[lint_index].range.from.line_index as usize) < rows_lines.start
+        {
+            lint_index += 1;
+        }
+        while search_range_index < search_ranges.len()
+            && (search_ranges[search_range_index].from.line_index as usize) < rows_lines.start
+        {
+            search_range_index += 1;
+        }
+        let has_lint = lint_index < lints.len()
+            && (lints[lint_index].range.from.line_index as usize) < rows_lines.end;
+        let has_search_range = search_range_index < search_ranges.len()
+            && (search_ranges[search_range_index].from.line_index as usize) < rows_lines.end;
+
+        let background_color =
+            if rows_lines.start < visible_lines.end && visible_lines.start < rows_lines.end {
+                theme.minimap_viewport
+            } else {
+                theme.normal_background
+            };
+        let marker_color = if has_lint {
+            theme.minimap_lint
+        } else if has_search_range {
+            theme.highlight
+        } else {
+            background_color
+        };
+
+        move_cursor_to(buf, x, row);
+        set_background_color(buf, marker_color);
+        buf.push(b' ');
+        set_background_color(buf, background_color);
+        set_foreground_color(buf, theme.token_comment);
+
+        cells.fill(0);
+        for line_index in rows_lines.clone() {
+            let dot_row = (line_index - rows_lines.start) / lines_per_dot;
+            let line = lines[line_index].as_str();
+            let mut column = 0;
+            for d in CharDisplayDistances::new(line, tab_size).with_tab_stops(&config.tab_stops) {
+                let char_column = column;
+                column = d.distance as usize;
+                if char_column >= max_column {
+                    break;
+                }
+                if !d.char.is_whitespace() {
+                    let dot_column = char_column / MINIMAP_COLUMNS_PER_DOT;
+                    cells[dot_column / 2] |= DOT_BITS[dot_column % 2][dot_row];
+                }
+            }
+        }
+
+        for &cell in cells.iter() {
+            match cell {
+                0 => buf.push(b' '),
+                _ => {
+                    let c = char::from_u32(0x2800 + cell as u32).unwrap_or(' ');
+                    buf.extend_from_slice(c.encode_utf8(&mut char_buf).as_bytes());
+                }
+            }
+        }
+    }
 }
 
 fn draw_picker(ctx: &RenderContext, buf: &mut Vec<u8>) {
@@ -789,3 +1136,119 @@ fn draw_statusbar(
 
     clear_until_new_line(buf);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimap_layout() {
+        let mut config = Config {
+            minimap_width: 12,
+            ..Default::default()
+        };
+        assert_eq!(0, minimap_width(&config, 80));
+        config.minimap = true;
+        assert_eq!(12, minimap_width(&config, 80));
+        assert_eq!(12, minimap_width(&config, 24));
+        assert_eq!(0, minimap_width(&config, 23));
+        config.minimap_width = 1;
+        assert_eq!(0, minimap_width(&config, 80));
+
+        assert_eq!(4, minimap_lines_per_row(1, 10));
+        assert_eq!(4, minimap_lines_per_row(40, 10));
+        assert_eq!(8, minimap_lines_per_row(41, 10));
+        assert_eq!(12, minimap_lines_per_row(101, 10));
+        assert_eq!(4, minimap_lines_per_row(3, 0));
+    }
+
+    // plays back the drawing codes used by `draw` into a grid of chars
+    fn render_to_screen(buf: &[u8], (width, height): (u16, u16)) -> Vec<String> {
+        let mut screen = vec![vec![' '; width as usize]; height as usize];
+        let (mut x, mut y) = (0, 0);
+        let text = std::str::from_utf8(buf).unwrap();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c != '\x1b' {
+                if let Some(cell) = screen.get_mut(y).and_then(|l| l.get_mut(x)) {
+                    *cell = c;
+                }
+                x += 1;
+                continue;
+            }
+            chars.next();
+            let mut params = String::new();
+            let command = loop {
+                match chars.next() {
+                    Some(c) if c.is_ascii_alphabetic() => break c,
+                    Some(c) => params.push(c),
+                    None => break ' ',
+                }
+            };
+            match command {
+                'H' => {
+                    let mut params = params.split(';').map(|p| p.parse::<usize>().unwrap());
+                    y = params.next().unwrap() - 1;
+                    x = params.next().unwrap() - 1;
+                }
+                'E' => {
+                    x = 0;
+                    y += 1;
+                }
+                'K' if params == "0" => {
+                    if let Some(line) = screen.get_mut(y) {
+                        for cell in line.iter_mut().skip(x) {
+                            *cell = ' ';
+                        }
+                    }
+                }
+                _ => (),
+            }
+        }
+        screen
+            .into_iter()
+            .map(|l| l.into_iter().collect())
+            .collect()
+    }
+
+    #[test]
+    fn minimap_wrapped_line() {
+        use crate::{buffer_position::BufferPosition, client::ClientHandle};
+
+        let mut editor = Editor::new(std::path::PathBuf::new(), String::new());
+        editor.config.minimap = true;
+        editor.config.minimap_width = 6;
+        editor.config.visual_space = ' ';
+        let buffer = editor.buffers.add_new();
+        let buffer_handle = buffer.handle();
+        buffer.insert_text(
+            &mut editor.word_database,
+            BufferPosition::zero(),
+            "0123456789abcdef\nxy",
+            &mut editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(buffer_handle),
+        );
+        let buffer_view_handle = editor.buffer_views.add_new(ClientHandle(0), buffer_handle);
+
+        let viewport_size = (16, 5);
+        let status_bar_display = LoggerStatusBarDisplay::default();
+        let ctx = RenderContext {
+            editor: &editor,
+            status_bar_display: &status_bar_display,
+            viewport_size,
+            scroll: 0,
+            has_focus: false,
+        };
+        let mut buf = Vec::new();
+        draw_buffer_view(&ctx, Some(buffer_view_handle), &mut buf);
+
+        let screen = render_to_screen(&buf, viewport_size);
+        let text_columns: Vec<_> = screen.iter().map(|l| l[..10].trim_end()).collect();
+        assert_eq!(&["0123456789", "abcdef", "xy", "~"], &text_columns[..4]);
+        for line in &screen {
+            assert!(!line[10..].contains(|c: char| c.is_ascii_alphanumeric()));
+        }
+    }
+}
@@ -317,6 +317,17 @@ pub fn client_capabilities(json: &mut Json) -> JsonValue {
         capabilities.set("window".into(), window_capabilities.into(), json);
     }
 
+    {
+        let mut general_capabilities = JsonObject::default();
+
+        let mut position_encodings = JsonArray::default();
+        position_encodings.push("utf-8".into(), json);
+        position_encodings.push("utf-16".into(), json);
+        general_capabilities.set("positionEncodings".into(), position_encodings.into(), json);
+
+        capabilities.set("general".into(), general_capabilities.into(), json);
+    }
+
     {
         // proposed extension for utf-8 offsets
         // https://clangd.llvm.org/extensions.html#utf-8-offsets
@@ -6,8 +6,8 @@ use std::{
 };
 
 use pepper::{
-    buffer::{BufferCollection, BufferHandle, BufferProperties},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer::{BufferCollection, BufferContent, BufferHandle, BufferProperties},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
     buffer_view::BufferViewHandle,
     client,
     cursor::Cursor,
@@ -25,7 +25,8 @@ use crate::{
     mode::readline,
     protocol::{
         self, DocumentCodeAction, DocumentDiagnostic, DocumentPosition, DocumentRange,
-        DocumentSymbolInformation, PendingRequestColection, Protocol, ResponseError, Uri,
+        DocumentSymbolInformation, PendingRequestColection, PositionEncoding, Protocol,
+        ResponseError, Uri,
     },
 };
 
@@ -100,6 +101,7 @@ impl<'json> FromJson<'json> for RenameCapability {
     }
 }
 
+#[derive(Clone, Copy)]
 enum TextDocumentSyncKind {
     None,
     Full,
@@ -253,6 +255,9 @@ pub(crate) struct VersionedBuffer {
     version: usize,
     texts: String,
     pending_edits: Vec<VersionedBufferEdit>,
+    // the text as the server last saw it. only kept for position encodings other than utf-8 so
+    // the byte columns of each pending edit can be converted against the text it was made on
+    synced_lines: Vec<String>,
 }
 impl VersionedBuffer {
     pub fn new() -> Self {
@@ -260,6 +265,7 @@ impl VersionedBuffer {
             version: 2,
             texts: String::new(),
             pending_edits: Vec::new(),
+            synced_lines: Vec::new(),
         }
     }
 
@@ -272,8 +278,112 @@ impl VersionedBuffer {
     pub fn dispose(&mut self) {
         self.flush();
         self.version = 1;
+        self.synced_lines.clear();
+    }
+
+    fn set_synced_lines(&mut self, content: &BufferContent) {
+        self.synced_lines.clear();
+        self.synced_lines
+            .extend(content.lines().iter().map(|l| String::from(l.as_str())));
+    }
+
+    fn synced_position(&self, position: BufferPosition) -> (usize, usize) {
+        let line_index = (position.line_index as usize).min(self.synced_lines.len() - 1);
+        let line = &self.synced_lines[line_index];
+        let mut column = (position.column_byte_index as usize).min(line.len());
+        while !line.is_char_boundary(column) {
+            column -= 1;
+        }
+        (line_index, column)
+    }
+
+    // converts the edit range against the synced text and then applies the edit to it
+    fn sync_edit(
+        &mut self,
+        range: BufferRange,
+        text: &str,
+        encoding: PositionEncoding,
+    ) -> DocumentRange {
+        let from = self.synced_position(range.from);
+        let to = self.synced_position(range.to);
+        let encode = |(line_index, column): (usize, usize)| {
+            let prefix = &self.synced_lines[line_index][..column];
+            DocumentPosition {
+                line: line_index as _,
+                character: match encoding {
+                    PositionEncoding::Utf8 => column,
+                    PositionEncoding::Utf16 => prefix.encode_utf16().count(),
+                    PositionEncoding::Utf32 => prefix.chars().count(),
+                } as _,
+            }
+        };
+        let document_range = DocumentRange {
+            start: encode(from),
+            end: encode(to),
+        };
+
+        let suffix = self.synced_lines[to.0].split_off(to.1);
+        self.synced_lines.drain(from.0 + 1..to.0 + 1);
+        let mut lines = text.split('\n');
+        if let Some(first_line) = lines.next() {
+            let line = &mut self.synced_lines[from.0];
+            line.truncate(from.1);
+            line.push_str(first_line);
+        }
+        let inserted_start = from.0 + 1;
+        self.synced_lines
+            .splice(inserted_start..inserted_start, lines.map(String::from));
+        let last_line_index = from.0 + text.matches('\n').count();
+        self.synced_lines[last_line_index].push_str(&suffix);
+
+        document_range
+    }
+}
+// merges an edit into the previous one when it continues it: an insert right after the previously
+// inserted text, a deletion right before (backspace) or at (delete) the previously deleted range
+fn try_coalesce_edit(
+    last_edit: &mut VersionedBufferEdit,
+    last_text: &str,
+    range: BufferRange,
+    text: &str,
+) -> bool {
+    let last_range = last_edit.buffer_range;
+    let is_last_insert = last_range.from == last_range.to && !last_text.is_empty();
+    let is_last_delete = last_range.from != last_range.to && last_text.is_empty();
+
+    if range.from == range.to {
+        if !is_last_insert || text.is_empty() {
+            return false;
+        }
+
+        let mut inserted_end = last_range.from;
+        match last_text.rfind('\n') {
+            Some(i) => {
+                inserted_end.line_index += last_text.matches('\n').count() as BufferPositionIndex;
+                inserted_end.column_byte_index = (last_text.len() - i - 1) as _;
+            }
+            None => inserted_end.column_byte_index += last_text.len() as BufferPositionIndex,
+        }
+        range.from == inserted_end
+    } else if text.is_empty() && is_last_delete {
+        if range.to == last_range.from {
+            last_edit.buffer_range.from = range.from;
+            true
+        } else if range.from == last_range.from
+            && range.from.line_index == range.to.line_index
+            && last_range.from.line_index == last_range.to.line_index
+        {
+            last_edit.buffer_range.to.column_byte_index +=
+                range.to.column_byte_index - range.from.column_byte_index;
+            true
+        } else {
+            false
+        }
+    } else {
+        false
     }
 }
+
 #[derive(Default)]
 pub(crate) struct VersionedBufferCollection {
     buffers: Vec<VersionedBuffer>,
@@ -285,6 +395,15 @@ impl VersionedBufferCollection {
             self.buffers.resize_with(index + 1, VersionedBuffer::new);
         }
         let buffer = &mut self.buffers[index];
+        if let Some(last_edit) = buffer.pending_edits.last_mut() {
+            let last_text = &buffer.texts[last_edit.text_range.start as usize..];
+            if try_coalesce_edit(last_edit, last_text, range, text) {
+                buffer.texts.push_str(text);
+                last_edit.text_range.end = buffer.texts.len() as _;
+                return;
+            }
+        }
+
         let text_range_start = buffer.texts.len();
         buffer.texts.push_str(text);
         buffer.pending_edits.push(VersionedBufferEdit {
@@ -299,6 +418,14 @@ impl VersionedBufferCollection {
         }
     }
 
+    pub fn set_synced_content(&mut self, buffer_handle: BufferHandle, content: &BufferContent) {
+        let index = buffer_handle.0 as usize;
+        if index >= self.buffers.len() {
+            self.buffers.resize_with(index + 1, VersionedBuffer::new);
+        }
+        self.buffers[index].set_synced_lines(content);
+    }
+
     pub fn iter_pending_mut(
         &mut self,
     ) -> impl Iterator<Item = (BufferHandle, &mut VersionedBuffer)> {
@@ -310,64 +437,121 @@ impl VersionedBufferCollection {
     }
 }
 
-struct BufferDiagnosticDataRange {
-    position: BufferPosition,
-    range: Range<u32>,
+struct BufferDiagnostic {
+    range: BufferRange,
+    message_range: Range<u32>,
+    data_range: Range<u32>,
 }
 
 #[derive(Default)]
-pub(crate) struct BufferDiagnosticDataCollection {
+pub(crate) struct BufferDiagnosticCollection {
+    messages: String,
     data: Vec<u8>,
-    ranges: Vec<BufferDiagnosticDataRange>,
+    diagnostics: Vec<BufferDiagnostic>,
 }
-impl BufferDiagnosticDataCollection {
+impl BufferDiagnosticCollection {
     pub fn clear(&mut self) {
+        self.messages.clear();
         self.data.clear();
-        self.ranges.clear();
+        self.diagnostics.clear();
     }
 
-    pub fn add(&mut self, position: BufferPosition, data: &JsonValue, json: &Json) {
-        let start = self.data.len() as _;
+    pub fn add(&mut self, range: BufferRange, message: &str, data: &JsonValue, json: &Json) {
+        let message_start = self.messages.len() as _;
+        self.messages.push_str(message);
+        let message_end = self.messages.len() as _;
+
+        let data_start = self.data.len() as _;
         let _ = json.write(&mut self.data, data);
-        let end = self.data.len() as _;
+        let data_end = self.data.len() as _;
 
-        self.ranges.push(BufferDiagnosticDataRange {
-            position,
-            range: start..end,
+        self.diagnostics.push(BufferDiagnostic {
+            range,
+            message_range: message_start..message_end,
+            data_range: data_start..data_end,
         });
     }
 
     pub fn sort(&mut self) {
-        self.ranges.sort_unstable_by_key(|d| d.position);
+        self.diagnostics.sort_unstable_by_key(|d| d.range.from);
+    }
+
+    // yields the range, message and raw json data of each diagnostic
+    pub fn iter(&self) -> impl Iterator<Item = (BufferRange, &str, &[u8])> {
+        self.diagnostics.iter().map(|d| {
+            let message =
+                &self.messages[d.message_range.start as usize..d.message_range.end as usize];
+            let data = &self.data[d.data_range.start as usize..d.data_range.end as usize];
+            (d.range, message, data)
+        })
+    }
+
+    pub fn insert_range(&mut self, range: BufferRange) {
+        for diagnostic in &mut self.diagnostics {
+            diagnostic.range.from = diagnostic.range.from.insert(range);
+            diagnostic.range.to = diagnostic.range.to.insert(range);
+        }
     }
 
-    pub fn get_data(&self, index: usize) -> Option<&[u8]> {
-        self.ranges
-            .get(index)
-            .map(|d| &self.data[d.range.start as usize..d.range.end as usize])
+    pub fn delete_range(&mut self, range: BufferRange) {
+        for diagnostic in &mut self.diagnostics {
+            diagnostic.range.from = diagnostic.range.from.delete(range);
+            diagnostic.range.to = diagnostic.range.to.delete(range);
+        }
     }
 }
 
 #[derive(Default)]
 pub(crate) struct DiagnosticCollection {
-    buffer_data_diagnostics: Vec<BufferDiagnosticDataCollection>,
+    buffer_diagnostics: Vec<BufferDiagnosticCollection>,
 }
 impl DiagnosticCollection {
     pub fn get_buffer_diagnostics(
         &mut self,
         buffer_handle: BufferHandle,
-    ) -> &mut BufferDiagnosticDataCollection {
+    ) -> &mut BufferDiagnosticCollection {
         let index = buffer_handle.0 as usize;
-        if index >= self.buffer_data_diagnostics.len() {
-            self.buffer_data_diagnostics
-                .resize_with(index + 1, BufferDiagnosticDataCollection::default);
+        if index >= self.buffer_diagnostics.len() {
+            self.buffer_diagnostics
+                .resize_with(index + 1, BufferDiagnosticCollection::default);
         }
-        &mut self.buffer_data_diagnostics[index]
+        &mut self.buffer_diagnostics[index]
     }
 
-    pub(crate) fn on_close_buffer(&mut self, buffer_handle: BufferHandle) {
+    pub fn buffer_diagnostics(
+        &self,
+        buffer_handle: BufferHandle,
+    ) -> Option<&BufferDiagnosticCollection> {
+        self.buffer_diagnostics.get(buffer_handle.0 as usize)
+    }
+
+    pub(crate) fn clear_buffer(&mut self, buffer_handle: BufferHandle) {
         self.get_buffer_diagnostics(buffer_handle).clear();
     }
+
+    pub(crate) fn clear_all(&mut self) {
+        for diagnostics in &mut self.buffer_diagnostics {
+            diagnostics.clear();
+        }
+    }
+}
+
+// features that a server may or may not provide.
+// they are used to route requests when multiple servers are attached to the same buffer
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ServerFeature {
+    Completion,
+    Hover,
+    SignatureHelp,
+    Declaration,
+    Definition,
+    Implementation,
+    References,
+    DocumentSymbols,
+    CodeAction,
+    Formatting,
+    Rename,
+    WorkspaceSymbols,
 }
 
 pub(crate) enum RequestState {
@@ -401,6 +585,10 @@ pub(crate) enum RequestState {
     FinishDocumentSymbols {
         buffer_view_handle: BufferViewHandle,
     },
+    FunctionMotion {
+        buffer_view_handle: BufferViewHandle,
+        forward: bool,
+    },
     WorkspaceSymbols,
     FinishWorkspaceSymbols,
     Formatting {
@@ -417,6 +605,33 @@ impl RequestState {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogVerbosity {
+    Off,
+    Messages,
+    Verbose,
+}
+impl fmt::Display for LogVerbosity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Off => f.write_str("off"),
+            Self::Messages => f.write_str("messages"),
+            Self::Verbose => f.write_str("verbose"),
+        }
+    }
+}
+impl FromStr for LogVerbosity {
+    type Err = ();
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "off" => Ok(Self::Off),
+            "messages" => Ok(Self::Messages),
+            "verbose" => Ok(Self::Verbose),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 pub struct ClientHandle(pub(crate) u8);
 impl fmt::Display for ClientHandle {
@@ -436,6 +651,7 @@ impl FromStr for ClientHandle {
 
 pub struct Client {
     handle: ClientHandle,
+    pub(crate) command: String,
     pub(crate) protocol: Protocol,
     pub(crate) json: Json,
     pub(crate) root: PathBuf,
@@ -444,7 +660,7 @@ pub struct Client {
     pub(crate) initialized: bool,
     pub(crate) server_capabilities: ServerCapabilities,
 
-    pub(crate) document_selectors: Vec<Glob>,
+    pub(crate) document_selectors: Vec<(String, Glob)>,
     pub(crate) versioned_buffers: VersionedBufferCollection,
     pub(crate) diagnostics: DiagnosticCollection,
 
@@ -452,12 +668,20 @@ pub struct Client {
 
     pub(crate) request_state: RequestState,
     pub(crate) request_raw_json: Vec<u8>,
+    pub(crate) showing_signature_help: bool,
+    pub(crate) position_encoding: PositionEncoding,
+
+    pub(crate) log_verbosity: LogVerbosity,
+    pub(crate) log_buffer_handle: Option<BufferHandle>,
+    pub(crate) log_method_filter: String,
+    log_write_buf: Vec<u8>,
 }
 
 impl Client {
-    pub(crate) fn new(handle: ClientHandle, root: PathBuf) -> Self {
+    pub(crate) fn new(handle: ClientHandle, command: String, root: PathBuf) -> Self {
         Self {
             handle,
+            command,
             protocol: Protocol::new(),
             json: Json::new(),
             root,
@@ -473,6 +697,13 @@ impl Client {
             request_state: RequestState::Idle,
             request_raw_json: Vec::new(),
             temp_edits: Vec::new(),
+            showing_signature_help: false,
+            position_encoding: PositionEncoding::default(),
+
+            log_verbosity: LogVerbosity::Off,
+            log_buffer_handle: None,
+            log_method_filter: String::new(),
+            log_write_buf: Vec::new(),
         }
     }
 
@@ -480,11 +711,29 @@ impl Client {
         self.handle
     }
 
+    pub fn supports(&self, feature: ServerFeature) -> bool {
+        let capabilities = &self.server_capabilities;
+        match feature {
+            ServerFeature::Completion => capabilities.completion_provider.on,
+            ServerFeature::Hover => capabilities.hover_provider.0,
+            ServerFeature::SignatureHelp => capabilities.signature_help_provider.on,
+            ServerFeature::Declaration => capabilities.declaration_provider.0,
+            ServerFeature::Definition => capabilities.definition_provider.0,
+            ServerFeature::Implementation => capabilities.implementation_provider.0,
+            ServerFeature::References => capabilities.references_provider.0,
+            ServerFeature::DocumentSymbols => capabilities.document_symbol_provider.0,
+            ServerFeature::CodeAction => capabilities.code_action_provider.0,
+            ServerFeature::Formatting => capabilities.document_formatting_provider.0,
+            ServerFeature::Rename => capabilities.rename_provider.on,
+            ServerFeature::WorkspaceSymbols => capabilities.workspace_symbol_provider.0,
+        }
+    }
+
     pub fn handles_path(&self, path: &str) -> bool {
         if self.document_selectors.is_empty() {
             true
         } else {
-            self.document_selectors.iter().any(|g| g.matches(path))
+            self.document_selectors.iter().any(|(_, g)| g.matches(path))
         }
     }
 
@@ -521,7 +770,11 @@ impl Client {
 
         let buffer = editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            self.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -549,7 +802,11 @@ impl Client {
 
         let buffer = editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            self.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -653,7 +910,11 @@ impl Client {
 
         let buffer = editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            self.position_encoding,
+        );
 
         let mut context = JsonObject::default();
         context.set("includeDeclaration".into(), true.into(), &mut self.json);
@@ -694,7 +955,11 @@ impl Client {
 
         let buffer = ctx.editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            self.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -742,7 +1007,11 @@ impl Client {
 
         let buffer = editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            self.position_encoding,
+        );
         let readline_input = editor.registers.get(REGISTER_READLINE_INPUT);
         let new_name = self.json.create_string(readline_input);
 
@@ -762,7 +1031,6 @@ impl Client {
         &mut self,
         editor: &mut Editor,
         platform: &mut Platform,
-        plugin_handle: PluginHandle,
         buffer_handle: BufferHandle,
         range: BufferRange,
     ) {
@@ -777,22 +1045,19 @@ impl Client {
 
         let mut diagnostics = JsonArray::default();
 
-        let buffer_diagnostics = self.diagnostics.get_buffer_diagnostics(buffer_handle);
-        for (i, lint) in buffer
-            .lints
-            .all()
-            .iter()
-            .filter(|l| l.plugin_handle == plugin_handle)
-            .enumerate()
-        {
-            if lint.range.from <= range.from && range.from < lint.range.to
-                || lint.range.from <= range.to && range.to < lint.range.to
-            {
-                if let Some(data) = buffer_diagnostics.get_data(i) {
-                    let range = DocumentRange::from_buffer_range(lint.range);
+        if let Some(buffer_diagnostics) = self.diagnostics.buffer_diagnostics(buffer_handle) {
+            for (diagnostic_range, message, data) in buffer_diagnostics.iter() {
+                if diagnostic_range.from <= range.from && range.from < diagnostic_range.to
+                    || diagnostic_range.from <= range.to && range.to < diagnostic_range.to
+                {
+                    let diagnostic_range = DocumentRange::from_buffer_range_encoded(
+                        diagnostic_range,
+                        buffer.content(),
+                        self.position_encoding,
+                    );
                     let diagnostic = DocumentDiagnostic::to_json_value_from_parts(
-                        lint.message(&buffer.lints),
-                        range,
+                        message,
+                        diagnostic_range,
                         data,
                         &mut self.json,
                     );
@@ -808,7 +1073,12 @@ impl Client {
         params.set("textDocument".into(), text_document.into(), &mut self.json);
         params.set(
             "range".into(),
-            DocumentRange::from_buffer_range(range).to_json_value(&mut self.json),
+            DocumentRange::from_buffer_range_encoded(
+                range,
+                buffer.content(),
+                self.position_encoding,
+            )
+            .to_json_value(&mut self.json),
             &mut self.json,
         );
         params.set("context".into(), context.into(), &mut self.json);
@@ -822,7 +1092,13 @@ impl Client {
         );
     }
 
-    pub(crate) fn finish_code_action(&mut self, editor: &mut Editor, index: usize) {
+    // the action edit is applied before its command is sent to be executed by the server
+    pub(crate) fn finish_code_action(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        index: usize,
+    ) {
         match self.request_state {
             RequestState::FinishCodeAction => (),
             _ => return,
@@ -837,14 +1113,34 @@ impl Client {
             Ok(actions) => actions,
             Err(_) => return,
         };
-        if let Some(edit) = code_actions
+        let action = match code_actions
             .elements(&self.json)
             .filter_map(|a| DocumentCodeAction::from_json(a, &self.json).ok())
             .filter(|a| !a.disabled)
-            .map(|a| a.edit)
             .nth(index)
         {
-            edit.apply(editor, &mut self.temp_edits, &self.root, &self.json);
+            Some(action) => action,
+            None => return,
+        };
+
+        action.edit.apply(
+            editor,
+            &mut self.temp_edits,
+            &self.root,
+            &self.json,
+            self.position_encoding,
+        );
+
+        if let Some(command) = action.command {
+            let mut params = JsonObject::default();
+            params.set("command".into(), command.command.into(), &mut self.json);
+            params.set("arguments".into(), command.arguments.into(), &mut self.json);
+            self.request(
+                platform,
+                "workspace/executeCommand",
+                params,
+                &mut editor.logger,
+            );
         }
     }
 
@@ -876,6 +1172,38 @@ impl Client {
         );
     }
 
+    pub fn function_motion(
+        &mut self,
+        editor: &mut Editor,
+        platform: &mut Platform,
+        buffer_view_handle: BufferViewHandle,
+        forward: bool,
+    ) {
+        if !self.server_capabilities.document_symbol_provider.0 || !self.request_state.is_idle() {
+            return;
+        }
+
+        util::send_pending_did_change(self, editor, platform);
+
+        let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+        let buffer_path = &editor.buffers.get(buffer_handle).path;
+        let text_document = util::text_document_with_id(&self.root, buffer_path, &mut self.json);
+
+        let mut params = JsonObject::default();
+        params.set("textDocument".into(), text_document.into(), &mut self.json);
+
+        self.request_state = RequestState::FunctionMotion {
+            buffer_view_handle,
+            forward,
+        };
+        self.request(
+            platform,
+            "textDocument/documentSymbol",
+            params,
+            &mut editor.logger,
+        );
+    }
+
     pub(crate) fn finish_document_symbols(
         &mut self,
         editor: &mut Editor,
@@ -925,8 +1253,12 @@ impl Client {
         if let Ok(position) = find_symbol_position(symbols, &self.json, index) {
             NavigationHistory::save_snapshot(clients.get_mut(client_handle), &editor.buffer_views);
 
+            let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+            let position = position.into_buffer_position_encoded(
+                editor.buffers.get(buffer_handle).content(),
+                self.position_encoding,
+            );
             let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
-            let position = position.into_buffer_position();
             let mut cursors = buffer_view.cursors.mut_guard();
             cursors.clear();
             cursors.add(Cursor {
@@ -988,8 +1320,12 @@ impl Client {
                     let client = clients.get_mut(client_handle);
                     client.set_buffer_view_handle(Some(buffer_view_handle), &editor.buffer_views);
 
+                    let buffer_handle = editor.buffer_views.get(buffer_view_handle).buffer_handle;
+                    let position = symbol.range.start.into_buffer_position_encoded(
+                        editor.buffers.get(buffer_handle).content(),
+                        self.position_encoding,
+                    );
                     let buffer_view = editor.buffer_views.get_mut(buffer_view_handle);
-                    let position = symbol.range.start.into_buffer_position();
                     let mut cursors = buffer_view.cursors.mut_guard();
                     cursors.clear();
                     cursors.add(Cursor {
@@ -1018,17 +1354,18 @@ impl Client {
 
         util::send_pending_did_change(self, editor, platform);
 
-        let buffer_path = &editor.buffers.get(buffer_handle).path;
-        let text_document = util::text_document_with_id(&self.root, buffer_path, &mut self.json);
+        let buffer = editor.buffers.get(buffer_handle);
+        let indentation_config = buffer.indentation_config(&editor.config);
+        let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
         let mut options = JsonObject::default();
         options.set(
             "tabSize".into(),
-            JsonValue::Integer(editor.config.tab_size as _),
+            JsonValue::Integer(indentation_config.tab_size as _),
             &mut self.json,
         );
         options.set(
             "insertSpaces".into(),
-            (!editor.config.indent_with_tabs).into(),
+            (!indentation_config.indent_with_tabs).into(),
             &mut self.json,
         );
         options.set("trimTrailingWhitespace".into(), true.into(), &mut self.json);
@@ -1063,7 +1400,11 @@ impl Client {
 
         let buffer = editor.buffers.get(buffer_handle);
         let text_document = util::text_document_with_id(&self.root, &buffer.path, &mut self.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            self.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set("textDocument".into(), text_document.into(), &mut self.json);
@@ -1086,6 +1427,99 @@ impl Client {
         );
     }
 
+    pub(crate) fn start_logging(
+        &mut self,
+        editor: &mut Editor,
+        verbosity: LogVerbosity,
+    ) -> BufferHandle {
+        self.log_verbosity = verbosity;
+        if let Some(handle) = self.log_buffer_handle {
+            if editor.buffers.try_get(handle).is_some() {
+                return handle;
+            }
+        }
+
+        let buffer = editor.buffers.add_new();
+        buffer.properties = BufferProperties::output();
+        buffer.set_path(Path::new(&format!("lsp-{}.log", self.handle)));
+        let handle = buffer.handle();
+        self.log_buffer_handle = Some(handle);
+        handle
+    }
+
+    pub(crate) fn stop_logging(&mut self) {
+        self.log_verbosity = LogVerbosity::Off;
+        self.log_write_buf.clear();
+    }
+
+    // every message starts with a "<tag> '<method>'" line ('>>' for sent and '<<' for received)
+    // so the log can be grepped by direction, kind or method.
+    // 'body' is only written with 'LogVerbosity::Verbose'
+    pub(crate) fn write_to_log_buffer<F>(
+        &mut self,
+        tag: &str,
+        method: JsonValue,
+        write_details: F,
+        body: &JsonValue,
+    ) where
+        F: FnOnce(&mut Vec<u8>, &Json),
+    {
+        if let LogVerbosity::Off = self.log_verbosity {
+            return;
+        }
+
+        let method = match &method {
+            JsonValue::Str(method) => method,
+            JsonValue::String(method) => method.as_str(&self.json),
+            _ => "",
+        };
+        if !self.log_method_filter.is_empty() && self.log_method_filter != method {
+            return;
+        }
+
+        self.log_write_buf.extend_from_slice(tag.as_bytes());
+        if !method.is_empty() {
+            self.log_write_buf.extend_from_slice(b" '");
+            self.log_write_buf.extend_from_slice(method.as_bytes());
+            self.log_write_buf.push(b'\'');
+        }
+        write_details(&mut self.log_write_buf, &self.json);
+        self.log_write_buf.push(b'\n');
+        if let LogVerbosity::Verbose = self.log_verbosity {
+            let _ = self.json.write(&mut self.log_write_buf, body);
+            self.log_write_buf.extend_from_slice(b"\n\n");
+        }
+    }
+
+    pub(crate) fn flush_log_buffer(&mut self, editor: &mut Editor) {
+        if self.log_write_buf.is_empty() {
+            return;
+        }
+
+        let buffer_handle = match self.log_buffer_handle {
+            Some(handle) if editor.buffers.try_get(handle).is_some() => handle,
+            _ => {
+                self.log_buffer_handle = None;
+                self.stop_logging();
+                return;
+            }
+        };
+
+        let buffer = editor.buffers.get_mut(buffer_handle);
+        let position = buffer.content().end();
+        let text = String::from_utf8_lossy(&self.log_write_buf);
+        buffer.insert_text(
+            &mut editor.word_database,
+            position,
+            &text,
+            &mut editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(buffer_handle),
+        );
+        self.log_write_buf.clear();
+    }
+
     fn request(
         &mut self,
         platform: &mut Platform,
@@ -1111,7 +1545,17 @@ impl Client {
 
         let id = self
             .protocol
-            .request(platform, &mut self.json, method, params);
+            .request(platform, &mut self.json, method, params.clone());
+
+        use io::Write;
+        self.write_to_log_buffer(
+            ">> request",
+            method.into(),
+            |buf, _| {
+                let _ = write!(buf, " id: {}", id.0);
+            },
+            &params,
+        );
 
         self.pending_requests.add(id, method);
     }
@@ -1145,6 +1589,34 @@ impl Client {
             }
         }
 
+        use io::Write;
+        match &result {
+            Ok(result) => self.write_to_log_buffer(
+                ">> response",
+                JsonValue::Null,
+                |buf, json| {
+                    buf.extend_from_slice(b" id: ");
+                    let _ = json.write(buf, &request_id);
+                },
+                result,
+            ),
+            Err(error) => self.write_to_log_buffer(
+                ">> response",
+                JsonValue::Null,
+                |buf, json| {
+                    buf.extend_from_slice(b" id: ");
+                    let _ = json.write(buf, &request_id);
+                    let _ = write!(
+                        buf,
+                        " error: {} '{}'",
+                        error.code,
+                        error.message.as_str(json)
+                    );
+                },
+                &error.data,
+            ),
+        }
+
         self.protocol
             .respond(platform, &mut self.json, request_id, result);
     }
@@ -1168,6 +1640,8 @@ impl Client {
             let _ = self.json.write(&mut log_writer, &params);
         }
 
+        self.write_to_log_buffer(">> notification", method.into(), |_, _| (), &params);
+
         self.protocol
             .notify(platform, &mut self.json, method, params);
     }
@@ -1263,7 +1737,11 @@ pub(crate) mod util {
         let buffer = editor.buffers.get(buffer_handle);
         let text_document =
             util::text_document_with_id(&client.root, &buffer.path, &mut client.json);
-        let position = DocumentPosition::from_buffer_position(buffer_position);
+        let position = DocumentPosition::from_buffer_position_encoded(
+            buffer_position,
+            buffer.content(),
+            client.position_encoding,
+        );
 
         let mut params = JsonObject::default();
         params.set(
@@ -1313,6 +1791,12 @@ pub(crate) mod util {
         );
 
         client.notify(platform, "textDocument/didOpen", params, logger);
+
+        if client.position_encoding != PositionEncoding::Utf8 {
+            client
+                .versioned_buffers
+                .set_synced_content(buffer_handle, buffer.content());
+        }
     }
 
     pub fn send_pending_did_change(
@@ -1346,31 +1830,59 @@ pub(crate) mod util {
                 &mut client.json,
             );
 
+            // pending edit ranges are byte columns into text that may no longer exist. servers
+            // that count columns otherwise get the full text when that text was not kept
+            let needs_synced_lines = client.position_encoding != PositionEncoding::Utf8;
+            let mut sync_kind = client.server_capabilities.text_document_sync.change;
+            if let TextDocumentSyncKind::Incremental = sync_kind {
+                if needs_synced_lines && versioned_buffer.synced_lines.is_empty() {
+                    sync_kind = TextDocumentSyncKind::Full;
+                }
+            }
+
             let mut content_changes = JsonArray::default();
-            match client.server_capabilities.text_document_sync.change {
+            match sync_kind {
                 TextDocumentSyncKind::None => (),
                 TextDocumentSyncKind::Full => {
                     let text = client.json.fmt_string(format_args!("{}", buffer.content()));
                     let mut change_event = JsonObject::default();
                     change_event.set("text".into(), text.into(), &mut client.json);
                     content_changes.push(change_event.into(), &mut client.json);
+
+                    if needs_synced_lines {
+                        versioned_buffer.set_synced_lines(buffer.content());
+                    }
                 }
                 TextDocumentSyncKind::Incremental => {
-                    for edit in &versioned_buffer.pending_edits {
+                    // taken out so the synced lines can be updated while going through the edits
+                    let pending_edits = std::mem::take(&mut versioned_buffer.pending_edits);
+                    let texts = std::mem::take(&mut versioned_buffer.texts);
+                    for edit in &pending_edits {
                         let mut change_event = JsonObject::default();
 
-                        let edit_range = DocumentRange::from_buffer_range(edit.buffer_range)
-                            .to_json_value(&mut client.json);
-                        change_event.set("range".into(), edit_range, &mut client.json);
-
                         let edit_text_range =
                             edit.text_range.start as usize..edit.text_range.end as usize;
-                        let text = &versioned_buffer.texts[edit_text_range];
+                        let text = &texts[edit_text_range];
+
+                        let edit_range = if needs_synced_lines {
+                            versioned_buffer.sync_edit(
+                                edit.buffer_range,
+                                text,
+                                client.position_encoding,
+                            )
+                        } else {
+                            DocumentRange::from_buffer_range(edit.buffer_range)
+                        };
+                        let edit_range = edit_range.to_json_value(&mut client.json);
+                        change_event.set("range".into(), edit_range, &mut client.json);
+
                         let text = client.json.create_string(text);
                         change_event.set("text".into(), text.into(), &mut client.json);
 
                         content_changes.push(change_event.into(), &mut client.json);
                     }
+                    versioned_buffer.pending_edits = pending_edits;
+                    versioned_buffer.texts = texts;
                 }
             }
 
@@ -1427,17 +1939,36 @@ pub(crate) mod util {
         editor: &mut Editor,
         platform: &mut Platform,
         buffer_handle: BufferHandle,
+    ) {
+        let path = &editor.buffers.get(buffer_handle).path;
+        send_did_close_path(
+            client,
+            &editor.buffers,
+            platform,
+            buffer_handle,
+            path,
+            &mut editor.logger,
+        );
+    }
+
+    pub fn send_did_close_path(
+        client: &mut Client,
+        buffers: &BufferCollection,
+        platform: &mut Platform,
+        buffer_handle: BufferHandle,
+        path: &Path,
+        logger: &mut Logger,
     ) {
         if !client.server_capabilities.text_document_sync.open_close {
             return;
         }
 
-        let buffer = editor.buffers.get(buffer_handle);
+        let buffer = buffers.get(buffer_handle);
         if !buffer.properties.saving_enabled {
             return;
         }
 
-        let text_document = text_document_with_id(&client.root, &buffer.path, &mut client.json);
+        let text_document = text_document_with_id(&client.root, path, &mut client.json);
         let mut params = JsonObject::default();
         params.set(
             "textDocument".into(),
@@ -1445,11 +1976,85 @@ pub(crate) mod util {
             &mut client.json,
         );
 
-        client.notify(
-            platform,
-            "textDocument/didClose",
-            params,
-            &mut editor.logger,
+        client.notify(platform, "textDocument/didClose", params, logger);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pending_edits(
+        versioned_buffers: &mut VersionedBufferCollection,
+    ) -> Vec<(BufferRange, &str)> {
+        let (_, buffer) = versioned_buffers.iter_pending_mut().next().unwrap();
+        let buffer = &*buffer;
+        buffer
+            .pending_edits
+            .iter()
+            .map(|e| {
+                let text = &buffer.texts[e.text_range.start as usize..e.text_range.end as usize];
+                (e.buffer_range, text)
+            })
+            .collect()
+    }
+
+    fn point(line_index: usize, column_byte_index: usize) -> BufferRange {
+        let position = BufferPosition::line_col(line_index as _, column_byte_index as _);
+        BufferRange::between(position, position)
+    }
+
+    fn range(from: (usize, usize), to: (usize, usize)) -> BufferRange {
+        BufferRange::between(
+            BufferPosition::line_col(from.0 as _, from.1 as _),
+            BufferPosition::line_col(to.0 as _, to.1 as _),
+        )
+    }
+
+    #[test]
+    fn coalesce_sequential_inserts() {
+        let handle = BufferHandle(0);
+        let mut versioned_buffers = VersionedBufferCollection::default();
+        versioned_buffers.add_edit(handle, point(2, 4), "a");
+        versioned_buffers.add_edit(handle, point(2, 5), "b");
+        versioned_buffers.add_edit(handle, point(2, 6), "c\n");
+        versioned_buffers.add_edit(handle, point(3, 0), "d");
+        assert_eq!(
+            vec![(point(2, 4), "abc\nd")],
+            pending_edits(&mut versioned_buffers)
+        );
+
+        versioned_buffers.add_edit(handle, point(0, 0), "x");
+        versioned_buffers.add_edit(handle, point(3, 2), "y");
+        assert_eq!(
+            vec![
+                (point(2, 4), "abc\nd"),
+                (point(0, 0), "x"),
+                (point(3, 2), "y")
+            ],
+            pending_edits(&mut versioned_buffers)
+        );
+    }
+
+    #[test]
+    fn coalesce_sequential_deletes() {
+        let handle = BufferHandle(0);
+        let mut versioned_buffers = VersionedBufferCollection::default();
+        versioned_buffers.add_edit(handle, range((1, 1), (1, 2)), "");
+        versioned_buffers.add_edit(handle, range((1, 0), (1, 1)), "");
+        versioned_buffers.add_edit(handle, range((0, 5), (1, 0)), "");
+        assert_eq!(
+            vec![(range((0, 5), (1, 2)), "")],
+            pending_edits(&mut versioned_buffers)
+        );
+
+        versioned_buffers.dispose(handle);
+        versioned_buffers.add_edit(handle, range((1, 3), (1, 4)), "");
+        versioned_buffers.add_edit(handle, range((1, 3), (1, 5)), "");
+        versioned_buffers.add_edit(handle, range((1, 3), (1, 4)), "z");
+        assert_eq!(
+            vec![(range((1, 3), (1, 6)), ""), (range((1, 3), (1, 4)), "z")],
+            pending_edits(&mut versioned_buffers)
         );
     }
 }
@@ -1,8 +1,9 @@
-use std::{cmp::Ord, fmt, fs::File, io, path::Path};
+use std::{cmp::Ord, fs::File, io};
 
 use pepper::{
     buffer::{BufferContent, BufferProperties},
-    buffer_position::{BufferPosition, BufferRange},
+    buffer_position::{BufferPosition, BufferPositionIndex, BufferRange},
+    buffer_view::{BufferViewHandle, CursorMovementKind},
     client,
     cursor::Cursor,
     editor::EditorContext,
@@ -11,6 +12,7 @@ use pepper::{
     mode::ModeKind,
     picker::Picker,
     plugin::PluginHandle,
+    quickfix,
     word_database::WordKind,
 };
 
@@ -22,9 +24,11 @@ use crate::{
     mode::{picker, readline},
     protocol::{
         DocumentCodeAction, DocumentCompletionItem, DocumentDiagnostic, DocumentLocation,
-        DocumentPosition, DocumentRange, DocumentSymbolInformation, ProtocolError,
-        ServerNotification, ServerRequest, ServerResponse, TextEdit, Uri, WorkspaceEdit,
+        DocumentPosition, DocumentRange, DocumentSymbolInformation, PositionEncoding,
+        ProtocolError, ServerNotification, ServerRequest, ServerResponse, TextEdit, Uri,
+        WorkspaceEdit,
     },
+    LspPlugin,
 };
 
 pub(crate) fn on_request(
@@ -44,6 +48,16 @@ pub(crate) fn on_request(
         let _ = client.json.write(&mut log_writer, &request.params);
     }
 
+    client.write_to_log_buffer(
+        "<< request",
+        request.method.clone().into(),
+        |buf, json| {
+            buf.extend_from_slice(b" id: ");
+            let _ = json.write(buf, &request.id);
+        },
+        &request.params,
+    );
+
     match request.method.as_str(&client.json) {
         "client/registerCapability" => {
             for registration in request
@@ -106,7 +120,7 @@ pub(crate) fn on_request(
                             };
                             let mut glob = Glob::default();
                             glob.compile(pattern)?;
-                            client.document_selectors.push(glob);
+                            client.document_selectors.push((pattern.into(), glob));
                         }
                     }
                     _ => (),
@@ -199,12 +213,21 @@ pub(crate) fn on_request(
                             );
                         }
                         if let Some(range) = params.selection {
+                            let buffer_handle = ctx
+                                .editor
+                                .buffer_views
+                                .get(buffer_view_handle)
+                                .buffer_handle;
+                            let range = range.into_buffer_range_encoded(
+                                ctx.editor.buffers.get(buffer_handle).content(),
+                                client.position_encoding,
+                            );
                             let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
                             let mut cursors = buffer_view.cursors.mut_guard();
                             cursors.clear();
                             cursors.add(Cursor {
-                                anchor: range.start.into_buffer_position(),
-                                position: range.end.into_buffer_position(),
+                                anchor: range.from,
+                                position: range.to,
                             });
                         }
                         true
@@ -225,6 +248,21 @@ pub(crate) fn on_request(
             result.set("success".into(), success.into(), &mut client.json);
             Ok(result.into())
         }
+        "workspace/applyEdit" => {
+            let edit = request.params.get("edit", &client.json);
+            let edit = WorkspaceEdit::from_json(edit, &client.json)?;
+            edit.apply(
+                &mut ctx.editor,
+                &mut client.temp_edits,
+                &client.root,
+                &client.json,
+                client.position_encoding,
+            );
+
+            let mut result = JsonObject::default();
+            result.set("applied".into(), true.into(), &mut client.json);
+            Ok(result.into())
+        }
         _ => Err(ProtocolError::MethodNotFound),
     }
 }
@@ -245,6 +283,13 @@ pub(crate) fn on_notification(
         let _ = client.json.write(&mut log_writer, &notification.params);
     }
 
+    client.write_to_log_buffer(
+        "<< notification",
+        notification.method.clone().into(),
+        |_, _| (),
+        &notification.params,
+    );
+
     match notification.method.as_str(&client.json) {
         "window/showMessage" => {
             let mut message_type: JsonInteger = 0;
@@ -314,26 +359,35 @@ pub(crate) fn on_notification(
                 }
             }
             if let Some(buffer_handle) = buffer_handle {
-                let mut lints = ctx
-                    .editor
-                    .buffers
-                    .get_mut(buffer_handle)
-                    .lints
-                    .mut_guard(plugin_handle);
-                lints.clear();
-
-                let diagnostics = client.diagnostics.get_buffer_diagnostics(buffer_handle);
-                diagnostics.clear();
-
+                let min_severity = ctx.editor.config.min_diagnostic_severity;
+                let content = ctx.editor.buffers.get(buffer_handle).content();
+                let mut buffer_diagnostics = Vec::new();
                 for diagnostic in params.diagnostics.elements(&client.json) {
                     let diagnostic = DocumentDiagnostic::from_json(diagnostic, &client.json)?;
-                    let range = diagnostic.range.into_buffer_range();
-
-                    lints.add(diagnostic.message.as_str(&client.json), range);
-                    diagnostics.add(range.from, &diagnostic.data, &client.json);
+                    if diagnostic
+                        .severity
+                        .map(|s| s > min_severity)
+                        .unwrap_or(false)
+                    {
+                        continue;
+                    }
+                    let range = diagnostic
+                        .range
+                        .into_buffer_range_encoded(content, client.position_encoding);
+                    buffer_diagnostics.push((range, diagnostic));
                 }
 
+                let diagnostics = client.diagnostics.get_buffer_diagnostics(buffer_handle);
+                diagnostics.clear();
+                for (range, diagnostic) in &buffer_diagnostics {
+                    let message = diagnostic.message.as_str(&client.json);
+                    diagnostics.add(*range, message, &diagnostic.data, &client.json);
+                }
                 diagnostics.sort();
+
+                // diagnostics from all servers attached to this buffer are merged
+                let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+                lsp.rebuild_lints(Some(client), &mut ctx.editor, plugin_handle, buffer_handle);
             }
 
             Ok(())
@@ -376,6 +430,32 @@ pub(crate) fn on_response(
         }
     }
 
+    use io::Write;
+    match &response.result {
+        Ok(result) => client.write_to_log_buffer(
+            "<< response",
+            method.into(),
+            |buf, _| {
+                let _ = write!(buf, " id: {}", response.id.0);
+            },
+            result,
+        ),
+        Err(error) => client.write_to_log_buffer(
+            "<< response",
+            method.into(),
+            |buf, json| {
+                let _ = write!(
+                    buf,
+                    " id: {} error: {} '{}'",
+                    response.id.0,
+                    error.code,
+                    error.message.as_str(json)
+                );
+            },
+            &error.data,
+        ),
+    }
+
     let result = match response.result {
         Ok(result) => result,
         Err(error) => {
@@ -391,11 +471,24 @@ pub(crate) fn on_response(
     match method {
         "initialize" => {
             let mut server_name = "";
+            client.position_encoding = PositionEncoding::default();
             for (key, value) in result.members(&client.json) {
                 match key {
                     "capabilities" => {
                         client.server_capabilities =
-                            ServerCapabilities::from_json(value, &client.json)?
+                            ServerCapabilities::from_json(value.clone(), &client.json)?;
+                        if let Ok(encoding) = PositionEncoding::from_json(
+                            value.get("positionEncoding", &client.json),
+                            &client.json,
+                        ) {
+                            client.position_encoding = encoding;
+                        }
+                    }
+                    // clangd's extension
+                    "offsetEncoding" => {
+                        if let Ok(encoding) = PositionEncoding::from_json(value, &client.json) {
+                            client.position_encoding = encoding;
+                        }
                     }
                     "serverInfo" => {
                         if let JsonValue::String(name) = value.get("name", &client.json) {
@@ -516,6 +609,7 @@ pub(crate) fn on_response(
                     .write(LogKind::Status)
                     .fmt(format_args!("{}\n{}", signature.documentation, label));
             }
+            client.showing_signature_help = true;
 
             Ok(())
         }
@@ -554,7 +648,7 @@ pub(crate) fn on_response(
                 _ => return Ok(()),
             };
 
-            let mut buffer_name = ctx.editor.string_pool.acquire();
+            let mut name = ctx.editor.string_pool.acquire();
             for location in locations.clone().elements(&client.json) {
                 let location = DocumentLocation::from_json(location, &client.json)?;
                 let Uri::Path(path) = Uri::parse(&client.root, location.uri.as_str(&client.json))?;
@@ -565,59 +659,24 @@ pub(crate) fn on_response(
                     .find_with_path(&ctx.editor.current_directory, path)
                     .map(|h| ctx.editor.buffers.get(h))
                 {
-                    let range = location.range.into_buffer_range();
+                    let range = location
+                        .range
+                        .into_buffer_range_encoded(buffer.content(), client.position_encoding);
                     for text in buffer.content().text_range(range) {
-                        buffer_name.push_str(text);
+                        name.push_str(text);
                     }
                     break;
                 }
             }
-            if buffer_name.is_empty() {
-                buffer_name.push_str("lsp");
+            if name.is_empty() {
+                name.push_str("lsp");
             }
-            buffer_name.push_str(".refs");
-
-            let buffer_view_handle = ctx.editor.buffer_view_handle_from_path(
-                client_handle,
-                Path::new(&buffer_name),
-                BufferProperties::scratch(),
-                true,
-            );
-            ctx.editor.string_pool.release(buffer_name);
-            let buffer_view_handle = match buffer_view_handle {
-                Ok(handle) => handle,
-                Err(error) => {
-                    ctx.editor
-                        .logger
-                        .write(LogKind::Error)
-                        .fmt(format_args!("{}", error));
-                    return Ok(());
-                }
-            };
+            ctx.editor.quickfix.clear(&name);
+            ctx.editor.string_pool.release(name);
 
             let mut count = 0;
             let mut context_buffer = BufferContent::new();
-
-            let buffer_view = ctx.editor.buffer_views.get(buffer_view_handle);
-            let buffer = ctx.editor.buffers.get_mut(buffer_view.buffer_handle);
-
-            let range = BufferRange::between(BufferPosition::zero(), buffer.content().end());
-            let mut events = ctx
-                .editor
-                .events
-                .writer()
-                .buffer_range_deletes_mut_guard(buffer.handle());
-            buffer.delete_range(&mut ctx.editor.word_database, range, &mut events);
-            drop(events);
-
-            buffer.properties = BufferProperties::scratch();
-
-            let mut events = ctx
-                .editor
-                .events
-                .writer()
-                .buffer_text_inserts_mut_guard(buffer.handle());
-            let mut text = ctx.editor.string_pool.acquire();
+            let mut message = ctx.editor.string_pool.acquire();
             let mut last_path = "";
             for location in locations.elements(&client.json) {
                 let location = match DocumentLocation::from_json(location, &client.json) {
@@ -633,10 +692,7 @@ pub(crate) fn on_response(
                     None => continue,
                 };
 
-                use fmt::Write;
-                let position = location.range.start.into_buffer_position();
-                let _ = writeln!(text, "{}:{}", path, position,);
-
+                message.clear();
                 if context_len > 0 {
                     if last_path != path {
                         context_buffer.clear();
@@ -650,58 +706,56 @@ pub(crate) fn on_response(
                     let start = line_count
                         .min((location.range.start.line as usize).saturating_sub(context_len - 1));
                     let end = line_count.min(location.range.end.line as usize + context_len);
-
-                    for line in &context_buffer.lines()[start..end] {
-                        let line = line.as_str();
+                    let context_range = BufferRange::between(
+                        BufferPosition::line_col(start as _, 0),
+                        BufferPosition::line_col(
+                            end.saturating_sub(1) as _,
+                            BufferPositionIndex::MAX,
+                        ),
+                    );
+
+                    for (line, line_range) in context_buffer
+                        .lines_in_range(context_range)
+                        .take(end - start)
+                    {
+                        let line = &line.as_str()[line_range];
                         if line.is_empty() {
-                            text.push('~');
+                            message.push('~');
                         } else {
-                            text.push_str(line);
+                            message.push_str(line);
                         }
-                        text.push('\n');
+                        message.push('\n');
                     }
-                    text.push('\n');
                 }
 
-                let position = buffer.content().end();
-                buffer.insert_text(&mut ctx.editor.word_database, position, &text, &mut events);
-                text.clear();
+                let position = location.range.start.into_buffer_position();
+                ctx.editor.quickfix.add(path, position, &message);
 
                 count += 1;
                 last_path = path;
             }
+            ctx.editor.string_pool.release(message);
+
+            if let Err(error) = quickfix::show(ctx, client_handle) {
+                ctx.editor
+                    .logger
+                    .write(LogKind::Error)
+                    .fmt(format_args!("{}", error));
+                return Ok(());
+            }
 
             if count == 1 {
-                text.push_str("1 reference found\n");
+                ctx.editor
+                    .logger
+                    .write(LogKind::Status)
+                    .str("1 reference found");
             } else {
-                use fmt::Write;
-                let _ = writeln!(text, "{} references found\n", count);
+                ctx.editor
+                    .logger
+                    .write(LogKind::Status)
+                    .fmt(format_args!("{} references found", count));
             }
 
-            buffer.insert_text(
-                &mut ctx.editor.word_database,
-                BufferPosition::zero(),
-                &text,
-                &mut events,
-            );
-            ctx.editor.string_pool.release(text);
-            drop(events);
-
-            let client = ctx.clients.get_mut(client_handle);
-            client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
-
-            let mut cursors = ctx
-                .editor
-                .buffer_views
-                .get_mut(buffer_view_handle)
-                .cursors
-                .mut_guard();
-            cursors.clear();
-            cursors.add(Cursor {
-                anchor: BufferPosition::zero(),
-                position: BufferPosition::zero(),
-            });
-
             Ok(())
         }
         "textDocument/prepareRename" => {
@@ -742,7 +796,8 @@ pub(crate) fn on_response(
 
             let buffer = ctx.editor.buffers.get(buffer_handle);
 
-            let mut range = range.into_buffer_range();
+            let mut range =
+                range.into_buffer_range_encoded(buffer.content(), client.position_encoding);
             if let Some(true) = default_behaviour {
                 let word = buffer.content().word_at(buffer_position);
                 range = BufferRange::between(word.position, word.end_position());
@@ -783,6 +838,7 @@ pub(crate) fn on_response(
                 &mut client.temp_edits,
                 &client.root,
                 &client.json,
+                client.position_encoding,
             );
             Ok(())
         }
@@ -804,9 +860,15 @@ pub(crate) fn on_response(
                 .filter_map(|a| DocumentCodeAction::from_json(a, &client.json).ok())
                 .filter(|a| !a.disabled)
             {
-                ctx.editor
-                    .picker
-                    .add_custom_entry(action.title.as_str(&client.json));
+                let title = action.title.as_str(&client.json);
+                match &action.kind {
+                    Some(kind) => ctx.editor.picker.add_custom_entry_fmt(format_args!(
+                        "[{}] {}",
+                        kind.as_str(&client.json),
+                        title
+                    )),
+                    None => ctx.editor.picker.add_custom_entry(title),
+                }
             }
 
             picker::enter_code_action_mode(ctx, plugin_handle, client);
@@ -822,6 +884,23 @@ pub(crate) fn on_response(
         "textDocument/documentSymbol" => {
             let buffer_view_handle = match client.request_state {
                 RequestState::DocumentSymbols { buffer_view_handle } => buffer_view_handle,
+                RequestState::FunctionMotion {
+                    buffer_view_handle,
+                    forward,
+                } => {
+                    client.request_state = RequestState::Idle;
+                    if let JsonValue::Array(symbols) = result {
+                        move_to_function(
+                            ctx,
+                            &client.json,
+                            symbols,
+                            buffer_view_handle,
+                            forward,
+                            client.position_encoding,
+                        );
+                    }
+                    return Ok(());
+                }
                 _ => return Ok(()),
             };
             client.request_state = RequestState::Idle;
@@ -926,6 +1005,7 @@ pub(crate) fn on_response(
                 &mut client.temp_edits,
                 edits,
                 &client.json,
+                client.position_encoding,
             );
 
             for buffer_view in ctx.editor.buffer_views.iter() {
@@ -1052,12 +1132,21 @@ fn goto_definition(
                 false,
             ) {
                 Ok(buffer_view_handle) => {
+                    let buffer_handle = ctx
+                        .editor
+                        .buffer_views
+                        .get(buffer_view_handle)
+                        .buffer_handle;
+                    let position = location.range.start.into_buffer_position_encoded(
+                        ctx.editor.buffers.get(buffer_handle).content(),
+                        client.position_encoding,
+                    );
+
                     let client = ctx.clients.get_mut(client_handle);
                     client
                         .set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
 
                     let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
-                    let position = location.range.start.into_buffer_position();
                     let mut cursors = buffer_view.cursors.mut_guard();
                     cursors.clear();
                     cursors.add(Cursor {
@@ -1101,3 +1190,62 @@ fn goto_definition(
         DefinitionLocation::Invalid => Ok(()),
     }
 }
+
+fn move_to_function(
+    ctx: &mut EditorContext,
+    json: &Json,
+    symbols: JsonArray,
+    buffer_view_handle: BufferViewHandle,
+    forward: bool,
+    encoding: PositionEncoding,
+) {
+    fn add_function_positions(
+        symbols: JsonArray,
+        json: &Json,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+        positions: &mut Vec<BufferPosition>,
+    ) {
+        for symbol in symbols
+            .elements(json)
+            .filter_map(|s| DocumentSymbolInformation::from_json(s, json).ok())
+        {
+            // method, constructor or function
+            if matches!(symbol.kind, 6 | 9 | 12) {
+                positions.push(
+                    symbol
+                        .range
+                        .start
+                        .into_buffer_position_encoded(content, encoding),
+                );
+            }
+            add_function_positions(symbol.children, json, content, encoding, positions);
+        }
+    }
+
+    let buffer_handle = ctx
+        .editor
+        .buffer_views
+        .get(buffer_view_handle)
+        .buffer_handle;
+    let content = ctx.editor.buffers.get(buffer_handle).content();
+    let mut positions = Vec::new();
+    add_function_positions(symbols, json, content, encoding, &mut positions);
+    positions.sort_unstable();
+
+    let movement_kind = ctx.editor.mode.normal_state.movement_kind;
+    let buffer_view = ctx.editor.buffer_views.get_mut(buffer_view_handle);
+    for cursor in &mut buffer_view.cursors.mut_guard()[..] {
+        let position = if forward {
+            positions.iter().find(|&&p| p > cursor.position)
+        } else {
+            positions.iter().rev().find(|&&p| p < cursor.position)
+        };
+        if let Some(&position) = position {
+            cursor.position = position;
+            if let CursorMovementKind::PositionAndAnchor = movement_kind {
+                cursor.anchor = position;
+            }
+        }
+    }
+}
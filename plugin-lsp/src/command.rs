@@ -1,15 +1,18 @@
 use pepper::{
-    buffer::BufferHandle,
-    command::{CommandError, CommandIO, CommandManager},
+    buffer::{BufferHandle, BufferLint, BufferLintCollection},
+    buffer_position::BufferPosition,
+    command::{CommandError, CommandIO, CommandManager, CompletionSource},
     cursor::Cursor,
     editor::{Editor, EditorContext},
-    editor_utils::parse_process_command,
+    editor_utils::{parse_process_command, LogKind},
+    navigation_history::NavigationHistory,
     plugin::PluginHandle,
 };
 
 use crate::{
-    client::Client,
-    {ClientGuard, LspPlugin},
+    client::{Client, ClientHandle, LogVerbosity, ServerFeature},
+    mode::picker,
+    {ClientGuard, LspPlugin, PendingAccess},
 };
 
 pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHandle) {
@@ -51,14 +54,23 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
 
         let buffer_handle = io.current_buffer_handle(ctx).ok();
         let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
-        let any_stopped = match find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle) {
-            Some(client) => {
-                let handle = client.handle();
-                lsp.release(client);
-                lsp.stop(&mut ctx.platform, handle, &mut ctx.editor.logger)
+        let buffer_path = buffer_handle.and_then(|h| ctx.editor.buffers.get(h).path.to_str());
+
+        // stops every server attached to the current buffer or all of them if there is none
+        let mut any_stopped = false;
+        if let Some(path) = buffer_path {
+            let handles: Vec<_> = lsp
+                .clients()
+                .filter(|c| lsp.is_attached(c, path))
+                .map(Client::handle)
+                .collect();
+            for handle in handles {
+                any_stopped |= lsp.stop(&mut ctx.platform, handle, &mut ctx.editor.logger);
             }
-            None => lsp.stop_all(&mut ctx.platform, &mut ctx.editor.logger),
-        };
+        }
+        if !any_stopped {
+            any_stopped = lsp.stop_all(&mut ctx.platform, &mut ctx.editor.logger);
+        }
         if any_stopped {
             Ok(())
         } else {
@@ -77,19 +89,172 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         }
     });
 
+    r("lsp-status", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        if lsp.clients().next().is_none() {
+            return Err(CommandError::OtherStatic("no lsp server running"));
+        }
+
+        let mut write = ctx.editor.logger.write(LogKind::Info);
+        for (i, client) in lsp.clients().enumerate() {
+            if i > 0 {
+                write.str("\n");
+            }
+
+            write.fmt(format_args!(
+                "lsp server {}: {}\n root: {}\n initialized: {}\n pending requests: {}\n log: {}",
+                client.handle().0,
+                client.command,
+                client.root.display(),
+                client.initialized,
+                client.pending_requests.len(),
+                client.log_verbosity,
+            ));
+            if !client.log_method_filter.is_empty() {
+                write.fmt(format_args!(" (only '{}')", client.log_method_filter));
+            }
+            write.str("\n document selectors:");
+            if client.document_selectors.is_empty() {
+                write.str(" <all>");
+            }
+            for (pattern, _) in &client.document_selectors {
+                write.str(" ");
+                write.str(pattern);
+            }
+        }
+
+        Ok(())
+    });
+
+    static LOG_VERBOSITY_COMPLETIONS: &[CompletionSource] =
+        &[CompletionSource::Custom(&["messages", "verbose", "off"])];
+    r("lsp-log-toggle", LOG_VERBOSITY_COMPLETIONS, |ctx, io| {
+        let verbosity = match io.args.try_next() {
+            Some(arg) => match arg.parse::<LogVerbosity>() {
+                Ok(verbosity) => Some(verbosity),
+                Err(()) => {
+                    return Err(CommandError::OtherOwned(format!(
+                        "invalid log verbosity '{}'",
+                        arg
+                    )))
+                }
+            },
+            None => None,
+        };
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx).ok();
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        let mut client = match find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle) {
+            Some(client) => client,
+            None => return Err(CommandError::OtherStatic("no lsp server running")),
+        };
+
+        let verbosity = match verbosity {
+            Some(verbosity) if verbosity != client.log_verbosity => verbosity,
+            Some(_) => LogVerbosity::Off,
+            None if client.log_verbosity == LogVerbosity::Off => LogVerbosity::Messages,
+            None => LogVerbosity::Off,
+        };
+        let log_buffer_handle = match verbosity {
+            LogVerbosity::Off => {
+                client.stop_logging();
+                None
+            }
+            _ => Some(client.start_logging(&mut ctx.editor, verbosity)),
+        };
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        lsp.release(client);
+
+        if let (Some(buffer_handle), Ok(client_handle)) = (log_buffer_handle, io.client_handle()) {
+            let buffer_view_handle = ctx
+                .editor
+                .buffer_views
+                .buffer_view_handle_from_buffer_handle(client_handle, buffer_handle);
+            let client = ctx.clients.get_mut(client_handle);
+            client.set_buffer_view_handle(Some(buffer_view_handle), &ctx.editor.buffer_views);
+        }
+
+        Ok(())
+    });
+
+    r("lsp-log-filter", &[], |ctx, io| {
+        let method = io.args.try_next().unwrap_or("");
+        io.args.assert_empty()?;
+
+        let buffer_handle = io.current_buffer_handle(ctx).ok();
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        let mut client = match find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle) {
+            Some(client) => client,
+            None => return Err(CommandError::OtherStatic("no lsp server running")),
+        };
+
+        client.log_method_filter.clear();
+        client.log_method_filter.push_str(method);
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        lsp.release(client);
+        Ok(())
+    });
+
+    r("lsp-clear-diagnostics", &[], |ctx, io| {
+        io.args.assert_empty()?;
+
+        let plugin_handle = io.plugin_handle();
+        let buffer_handle = if io.bang {
+            None
+        } else {
+            Some(io.current_buffer_handle(ctx)?)
+        };
+
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        for client in lsp.clients_mut() {
+            match buffer_handle {
+                Some(handle) => client.diagnostics.clear_buffer(handle),
+                None => client.diagnostics.clear_all(),
+            }
+        }
+
+        match buffer_handle {
+            Some(handle) => ctx
+                .editor
+                .buffers
+                .get_mut(handle)
+                .lints
+                .mut_guard(plugin_handle)
+                .clear(),
+            None => {
+                for buffer in ctx.editor.buffers.iter_mut() {
+                    buffer.lints.mut_guard(plugin_handle).clear();
+                }
+            }
+        }
+
+        Ok(())
+    });
+
     r("lsp-hover", &[], |ctx, io| {
         io.args.assert_empty()?;
 
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.hover(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::Hover,
+            move |ctx, client| {
+                let op = client.hover(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-definition", &[], |ctx, io| {
@@ -98,16 +263,22 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.definition(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::Definition,
+            move |ctx, client| {
+                let op = client.definition(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-declaration", &[], |ctx, io| {
@@ -116,32 +287,44 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.declaration(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::Declaration,
+            move |ctx, client| {
+                let op = client.declaration(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-implementation", &[], |ctx, io| {
         io.args.assert_empty()?;
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.implementation(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::Implementation,
+            move |ctx, client| {
+                let op = client.implementation(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-references", &[], |ctx, io| {
@@ -162,17 +345,23 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let client_handle = io.client_handle()?;
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.references(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                buffer_handle,
-                cursor.position,
-                context_len,
-                client_handle,
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::References,
+            move |ctx, client| {
+                let op = client.references(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.position,
+                    context_len,
+                    client_handle,
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-rename", &[], |ctx, io| {
@@ -181,28 +370,38 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
         let plugin_handle = io.plugin_handle();
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.rename(ctx, plugin_handle, buffer_handle, cursor.position);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::Rename,
+            move |ctx, client| {
+                let op = client.rename(ctx, plugin_handle, buffer_handle, cursor.position);
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-code-action", &[], |ctx, io| {
         io.args.assert_empty()?;
 
         let (buffer_handle, cursor) = current_buffer_and_main_cursor(ctx, io)?;
-        let plugin_handle = io.plugin_handle();
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.code_action(
-                &mut ctx.editor,
-                &mut ctx.platform,
-                plugin_handle,
-                buffer_handle,
-                cursor.to_range(),
-            );
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::CodeAction,
+            move |ctx, client| {
+                let op = client.code_action(
+                    &mut ctx.editor,
+                    &mut ctx.platform,
+                    buffer_handle,
+                    cursor.to_range(),
+                );
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-document-symbols", &[], |ctx, io| {
@@ -211,34 +410,162 @@ pub fn register_commands(commands: &mut CommandManager, plugin_handle: PluginHan
         let view_handle = io.current_buffer_view_handle(ctx)?;
         let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
 
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.document_symbols(&mut ctx.editor, &mut ctx.platform, view_handle);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::DocumentSymbols,
+            move |ctx, client| {
+                let op = client.document_symbols(&mut ctx.editor, &mut ctx.platform, view_handle);
+                Ok(op)
+            },
+        )
+    });
+
+    fn function_motion(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        forward: bool,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_handle = ctx.editor.buffer_views.get(view_handle).buffer_handle;
+
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::DocumentSymbols,
+            move |ctx, client| {
+                client.function_motion(&mut ctx.editor, &mut ctx.platform, view_handle, forward);
+                Ok(())
+            },
+        )
+    }
+
+    r("lsp-next-function", &[], |ctx, io| {
+        function_motion(ctx, io, true)
+    });
+    r("lsp-prev-function", &[], |ctx, io| {
+        function_motion(ctx, io, false)
+    });
+
+    fn goto_diagnostic(
+        ctx: &mut EditorContext,
+        io: &mut CommandIO,
+        forward: bool,
+    ) -> Result<(), CommandError> {
+        io.args.assert_empty()?;
+
+        let client_handle = io.client_handle()?;
+        let view_handle = io.current_buffer_view_handle(ctx)?;
+        let buffer_view = ctx.editor.buffer_views.get(view_handle);
+        let main_position = buffer_view.cursors.main_cursor().position;
+        let lints = &ctx.editor.buffers.get(buffer_view.buffer_handle).lints;
+
+        let diagnostic = match find_diagnostic(lints, io.plugin_handle(), main_position, forward) {
+            Some(diagnostic) => diagnostic,
+            None => {
+                ctx.editor
+                    .logger
+                    .write(LogKind::Status)
+                    .str("no diagnostics");
+                return Ok(());
+            }
+        };
+        let position = diagnostic.range.from;
+        ctx.editor
+            .logger
+            .write(LogKind::Status)
+            .str(diagnostic.message(lints));
+
+        NavigationHistory::save_snapshot(
+            ctx.clients.get_mut(client_handle),
+            &ctx.editor.buffer_views,
+        );
+        let mut cursors = ctx
+            .editor
+            .buffer_views
+            .get_mut(view_handle)
+            .cursors
+            .mut_guard();
+        cursors.clear();
+        cursors.add(Cursor {
+            anchor: position,
+            position,
+        });
+        Ok(())
+    }
+
+    r("goto-next-diagnostic", &[], |ctx, io| {
+        goto_diagnostic(ctx, io, true)
+    });
+    r("goto-prev-diagnostic", &[], |ctx, io| {
+        goto_diagnostic(ctx, io, false)
     });
 
     r("lsp-workspace-symbols", &[], |ctx, io| {
-        let query = io.args.try_next().unwrap_or("");
+        // owned since the request may only be sent after picking which server handles it
+        let query = String::from(io.args.try_next().unwrap_or(""));
         io.args.assert_empty()?;
 
         let buffer_handle = io.current_buffer_handle(ctx).ok();
-        access(ctx, io, buffer_handle, |ctx, client| {
-            let op = client.workspace_symbols(&mut ctx.editor, &mut ctx.platform, query);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            buffer_handle,
+            ServerFeature::WorkspaceSymbols,
+            move |ctx, client| {
+                let op = client.workspace_symbols(&mut ctx.editor, &mut ctx.platform, &query);
+                Ok(op)
+            },
+        )
     });
 
     r("lsp-format", &[], |ctx, io| {
         io.args.assert_empty()?;
 
         let buffer_handle = io.current_buffer_handle(ctx)?;
-        access(ctx, io, Some(buffer_handle), |ctx, client| {
-            let op = client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
-            Ok(op)
-        })
+        access(
+            ctx,
+            io,
+            Some(buffer_handle),
+            ServerFeature::Formatting,
+            move |ctx, client| {
+                let op = client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
+                Ok(op)
+            },
+        )
     });
 }
 
+// diagnostics are kept as this plugin's lints, sorted and already converted to byte positions.
+// the search wraps around the buffer ends
+pub(crate) fn find_diagnostic(
+    lints: &BufferLintCollection,
+    plugin_handle: PluginHandle,
+    position: BufferPosition,
+    forward: bool,
+) -> Option<&BufferLint> {
+    let diagnostics = lints
+        .all()
+        .iter()
+        .filter(|l| l.plugin_handle == plugin_handle);
+    if forward {
+        diagnostics
+            .clone()
+            .find(|l| l.range.from > position)
+            .or_else(|| diagnostics.clone().next())
+    } else {
+        diagnostics
+            .clone()
+            .rev()
+            .find(|l| l.range.from < position)
+            .or_else(|| diagnostics.clone().next_back())
+    }
+}
+
 fn current_buffer_and_main_cursor(
     ctx: &EditorContext,
     io: &CommandIO,
@@ -259,25 +586,79 @@ fn find_lsp_client_for_buffer(
     match buffer_handle {
         Some(buffer_handle) => {
             let buffer_path = editor.buffers.get(buffer_handle).path.to_str()?;
-            lsp.find_client(|c| c.handles_path(buffer_path))
+            lsp.find_client(Some(buffer_path))
         }
-        None => lsp.find_client(|_| true),
+        None => lsp.find_client(None),
     }
 }
 
+// when more than one attached client supports the feature, the user picks which one handles
+// the request and that choice is remembered for the rest of the session
 fn access<A>(
     ctx: &mut EditorContext,
     io: &mut CommandIO,
     buffer_handle: Option<BufferHandle>,
+    feature: ServerFeature,
+    accessor: A,
+) -> Result<(), CommandError>
+where
+    A: 'static + FnOnce(&mut EditorContext, &mut Client) -> Result<(), CommandError>,
+{
+    let plugin_handle = io.plugin_handle();
+    let buffer_path = buffer_handle.and_then(|h| ctx.editor.buffers.get(h).path.to_str());
+    let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+
+    let client_handles: Vec<_> = lsp
+        .clients()
+        .filter(|c| match buffer_path {
+            Some(path) => lsp.is_attached(c, path),
+            None => true,
+        })
+        .filter(|c| c.supports(feature))
+        .map(Client::handle)
+        .collect();
+    let client_handle = match client_handles[..] {
+        [] => return Ok(()),
+        [handle] => handle,
+        _ => {
+            let preferred = lsp.preferred_client(feature);
+            let preferred_handle = lsp
+                .clients()
+                .filter(|c| client_handles.contains(&c.handle()))
+                .find(|c| Some(c.command.as_str()) == preferred)
+                .map(Client::handle);
+            match preferred_handle {
+                Some(handle) => handle,
+                None => {
+                    lsp.pending_access = Some(PendingAccess {
+                        feature,
+                        client_handles,
+                        accessor: Box::new(accessor),
+                    });
+                    picker::enter_client_mode(ctx, plugin_handle);
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    access_client(ctx, plugin_handle, client_handle, accessor)
+}
+
+pub(crate) fn access_client<A>(
+    ctx: &mut EditorContext,
+    plugin_handle: PluginHandle,
+    client_handle: ClientHandle,
     accessor: A,
 ) -> Result<(), CommandError>
 where
     A: FnOnce(&mut EditorContext, &mut Client) -> Result<(), CommandError>,
 {
-    let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
-    if let Some(mut client) = find_lsp_client_for_buffer(lsp, &ctx.editor, buffer_handle) {
+    let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+    if let Some(mut client) = lsp.acquire(client_handle) {
         let result = accessor(ctx, &mut client);
-        let lsp = ctx.plugins.get_as::<LspPlugin>(io.plugin_handle());
+        client.flush_log_buffer(&mut ctx.editor);
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
         lsp.release(client);
         let _ = result?;
     }
@@ -411,8 +411,7 @@ impl Json {
                         b'r' => json.strings.push('\r'),
                         b't' => json.strings.push('\t'),
                         b'u' => {
-                            let mut buf = [0; 4];
-                            if reader.read(&mut buf)? == buf.len() {
+                            fn read_code_unit(reader: &mut dyn io::BufRead) -> io::Result<u32> {
                                 fn from_hex_digit(d: u8) -> io::Result<u32> {
                                     match d {
                                         b'0'..=b'9' => Ok((d - b'0') as _),
@@ -422,24 +421,53 @@ impl Json {
                                     }
                                 }
 
+                                let mut buf = [0; 4];
+                                if reader.read(&mut buf)? != buf.len() {
+                                    return Err(invalid_data_error());
+                                }
+
                                 let mut c: u32 = 0;
                                 c += from_hex_digit(buf[0])? << 12;
                                 c += from_hex_digit(buf[1])? << 8;
                                 c += from_hex_digit(buf[2])? << 4;
                                 c += from_hex_digit(buf[3])?;
-                                c = u32::from_le(c);
+                                Ok(c)
+                            }
 
-                                match std::char::from_u32(c) {
-                                    Some(c) => json.strings.push(c),
-                                    None => return Err(invalid_data_error()),
+                            let mut c = read_code_unit(reader)?;
+                            // characters outside the basic multilingual plane are escaped
+                            // as an utf-16 surrogate pair
+                            if (0xd800..0xdc00).contains(&c) {
+                                consume_bytes(reader, b"\\u")?;
+                                let low = read_code_unit(reader)?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(invalid_data_error());
                                 }
-                            } else {
-                                return Err(invalid_data_error());
+                                c = 0x10000 + ((c - 0xd800) << 10) + (low - 0xdc00);
+                            }
+
+                            match std::char::from_u32(c) {
+                                Some(c) => json.strings.push(c),
+                                None => return Err(invalid_data_error()),
                             }
                         }
                         _ => return Err(invalid_data_error()),
                     },
-                    c => json.strings.push(c as _),
+                    c if c.is_ascii() => json.strings.push(c as _),
+                    c => {
+                        let len = match c {
+                            0xc0..=0xdf => 2,
+                            0xe0..=0xef => 3,
+                            0xf0..=0xf7 => 4,
+                            _ => return Err(invalid_data_error()),
+                        };
+                        let mut buf = [c, 0, 0, 0];
+                        reader.read_exact(&mut buf[1..len])?;
+                        match std::str::from_utf8(&buf[..len]) {
+                            Ok(c) => json.strings.push_str(c),
+                            Err(_) => return Err(invalid_data_error()),
+                        }
+                    }
                 }
             }
         }
@@ -535,22 +563,46 @@ impl Json {
                         }
                     }
 
-                    if match_byte(reader, b'.')? {
+                    let has_fraction = match_byte(reader, b'.')?;
+                    let mut fraction: JsonNumber = 0.0;
+                    if has_fraction {
                         let mut fraction_base: JsonNumber = 1.0;
-                        let mut fraction: JsonNumber = 0.0;
-
                         while let Some(d) = next_digit(reader)? {
                             fraction_base *= 0.1;
                             fraction += (d as JsonNumber) * fraction_base;
                         }
+                    }
+
+                    let has_exponent = match_byte(reader, b'e')? || match_byte(reader, b'E')?;
+                    let mut exponent: i32 = 0;
+                    if has_exponent {
+                        let is_exponent_negative = match_byte(reader, b'-')?;
+                        if !is_exponent_negative {
+                            match_byte(reader, b'+')?;
+                        }
+
+                        let mut has_digits = false;
+                        while let Some(d) = next_digit(reader)? {
+                            has_digits = true;
+                            exponent = exponent.saturating_mul(10).saturating_add(d as _);
+                        }
+                        if !has_digits {
+                            return Err(invalid_data_error());
+                        }
+                        if is_exponent_negative {
+                            exponent = -exponent;
+                        }
+                    }
 
-                        fraction += integer as JsonNumber;
+                    if has_fraction || has_exponent {
+                        let mut number = (integer as JsonNumber + fraction)
+                            * (10.0 as JsonNumber).powi(exponent);
                         if is_negative {
-                            fraction = -fraction;
+                            number = -number;
                         }
 
                         skip_whitespace(reader)?;
-                        Ok(JsonValue::Number(fraction))
+                        Ok(JsonValue::Number(number))
                     } else {
                         if is_negative {
                             integer = -integer;
@@ -566,7 +618,24 @@ impl Json {
         read_value(self, reader)
     }
 
+    // object members are written in insertion order
     pub fn write(&self, buf: &mut dyn io::Write, value: &JsonValue) -> io::Result<()> {
+        self.write_value(buf, value, false)
+    }
+
+    // object members are written sorted by key so that tests do not depend on the
+    // order in which they were set
+    #[cfg(test)]
+    pub fn write_sorted(&self, buf: &mut dyn io::Write, value: &JsonValue) -> io::Result<()> {
+        self.write_value(buf, value, true)
+    }
+
+    fn write_value(
+        &self,
+        buf: &mut dyn io::Write,
+        value: &JsonValue,
+        sort_members: bool,
+    ) -> io::Result<()> {
         fn append_str(buf: &mut dyn io::Write, s: &str) -> io::Result<()> {
             buf.write_all(b"\"")?;
             for c in s.chars() {
@@ -626,7 +695,7 @@ impl Json {
                 if next != 0 {
                     loop {
                         let element = &self.elements[next];
-                        self.write(buf, &element.value)?;
+                        self.write_value(buf, &element.value, sort_members)?;
                         next = element.next as _;
                         if next == 0 {
                             break;
@@ -638,20 +707,34 @@ impl Json {
                 Ok(())
             }
             JsonValue::Object(o) => {
-                buf.write_all(b"{")?;
-                let mut next = o.first as usize;
-                if next != 0 {
-                    loop {
-                        let member = &self.members[next];
-                        append_str(buf, member.key.as_str(self))?;
-                        buf.write_all(b":")?;
-                        self.write(buf, &member.value)?;
-                        next = member.next as _;
-                        if next == 0 {
-                            break;
-                        }
+                let write_member = |buf: &mut dyn io::Write, index: usize, first: bool| {
+                    if !first {
                         buf.write_all(b",")?;
                     }
+                    let member = &self.members[index];
+                    append_str(buf, member.key.as_str(self))?;
+                    buf.write_all(b":")?;
+                    self.write_value(buf, &member.value, sort_members)
+                };
+
+                buf.write_all(b"{")?;
+                if sort_members {
+                    let mut member_indices = Vec::new();
+                    let mut next = o.first as usize;
+                    while next != 0 {
+                        member_indices.push(next);
+                        next = self.members[next].next as _;
+                    }
+                    member_indices.sort_by_key(|&i| self.members[i].key.as_str(self));
+                    for (i, &index) in member_indices.iter().enumerate() {
+                        write_member(buf, index, i == 0)?;
+                    }
+                } else {
+                    let mut next = o.first as usize;
+                    while next != 0 {
+                        write_member(buf, next, next == o.first as usize)?;
+                        next = self.members[next].next as _;
+                    }
                 }
                 buf.write_all(b"}")?;
                 Ok(())
@@ -742,6 +825,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn write_sorted() {
+        let mut json = Json::new();
+
+        let mut inner = JsonObject::default();
+        inner.set("z".into(), JsonValue::Integer(1), &mut json);
+        inner.set("a".into(), JsonValue::Integer(2), &mut json);
+
+        let mut object = JsonObject::default();
+        object.set("second".into(), inner.into(), &mut json);
+        object.set("first".into(), JsonValue::Null, &mut json);
+        let object = object.into();
+
+        let mut buf = Vec::new();
+        json.write(&mut buf, &object).unwrap();
+        assert_eq!(
+            "{\"second\":{\"z\":1,\"a\":2},\"first\":null}",
+            String::from_utf8(buf).unwrap()
+        );
+
+        let mut buf = Vec::new();
+        json.write_sorted(&mut buf, &object).unwrap();
+        assert_eq!(
+            "{\"first\":null,\"second\":{\"a\":2,\"z\":1}}",
+            String::from_utf8(buf).unwrap()
+        );
+    }
+
     #[test]
     fn read_value() {
         let mut json = Json::new();
@@ -771,9 +882,13 @@ mod tests {
         assert_json!(JsonValue::Integer(0), "000");
         assert_json!(JsonValue::Integer(-1), "-001");
         assert_json!(JsonValue::Number(n), "0.5" => assert_eq!(0.5, n));
+        assert_json!(JsonValue::Number(n), "1e5" => assert_eq!(100000.0, n));
+        assert_json!(JsonValue::Number(n), "-2.5E-1" => assert_eq!(-0.25, n));
         assert_json!(JsonValue::String(s), "\"string\"" => assert_eq!("string", s.as_str(&json)));
         assert_json!(JsonValue::String(s), "\"\\u00e1\"" => assert_eq!("\u{00e1}", s.as_str(&json)));
         assert_json!(JsonValue::String(s), "\"\\ufa09\"" => assert_eq!("\u{fa09}", s.as_str(&json)));
+        assert_json!(JsonValue::String(s), "\"\\ud83d\\ude00\"" => assert_eq!("\u{1f600}", s.as_str(&json)));
+        assert_json!(JsonValue::String(s), "\"\u{00e1}\u{1f600}\"" => assert_eq!("\u{00e1}\u{1f600}", s.as_str(&json)));
         assert_json!(JsonValue::String(s), "\"\\\"\\\\\\/\\b\\f\\n\\r\\t\"" => assert_eq!("\"\\/\x08\x0c\n\r\t", s.as_str(&json)));
         assert_json!(
             JsonValue::String(s),
@@ -1,12 +1,15 @@
 use std::{
     ops::{Deref, DerefMut},
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
 use pepper::{
+    buffer::BufferHandle,
     buffer_position::BufferRange,
-    editor::EditorContext,
+    command::CommandError,
+    config::CompletionTrigger,
+    editor::{Editor, EditorContext},
     editor_utils::{hash_bytes, parse_process_command, LogKind, Logger},
     events::{EditorEvent, EditorEventIter},
     glob::{Glob, InvalidGlobError},
@@ -22,8 +25,10 @@ mod command;
 mod json;
 mod mode;
 mod protocol;
+#[cfg(test)]
+mod test_platform;
 
-use client::{util, Client, ClientHandle};
+use client::{util, Client, ClientHandle, ServerFeature};
 use json::{JsonObject, JsonValue};
 use protocol::{ProtocolError, ResponseError, ServerEvent};
 
@@ -47,6 +52,7 @@ pub static DEFINITION: PluginDefinition = PluginDefinition {
             on_process_exit,
 
             on_completion,
+            on_format,
 
             ..Default::default()
         })
@@ -104,11 +110,23 @@ impl Drop for ClientGuard {
     }
 }
 
+pub(crate) type ClientAccessor =
+    Box<dyn FnOnce(&mut EditorContext, &mut Client) -> Result<(), CommandError>>;
+
+// a request waiting for the user to pick which of the candidate clients should handle it
+pub(crate) struct PendingAccess {
+    pub feature: ServerFeature,
+    pub client_handles: Vec<ClientHandle>,
+    pub accessor: ClientAccessor,
+}
+
 #[derive(Default)]
 pub(crate) struct LspPlugin {
     entries: Vec<ClientEntry>,
     recipes: Vec<ClientRecipe>,
     current_client_handle: Option<ClientHandle>,
+    preferred_clients: Vec<(ServerFeature, String)>,
+    pending_access: Option<PendingAccess>,
 }
 
 impl LspPlugin {
@@ -164,6 +182,12 @@ impl LspPlugin {
 
         let handle = find_vacant_entry(self);
 
+        let mut command_text = command.get_program().to_string_lossy().into_owned();
+        for arg in command.get_args() {
+            command_text.push(' ');
+            command_text.push_str(&arg.to_string_lossy());
+        }
+
         command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
@@ -178,7 +202,7 @@ impl LspPlugin {
             buf_len: SERVER_PROCESS_BUFFER_LEN,
         });
 
-        let client = Client::new(handle, root);
+        let client = Client::new(handle, command_text, root);
         self.entries[handle.0 as usize] = ClientEntry::Occupied(Box::new(client));
         handle
     }
@@ -220,6 +244,20 @@ impl LspPlugin {
         any_stopped
     }
 
+    pub(crate) fn clients(&self) -> impl Iterator<Item = &Client> {
+        self.entries.iter().filter_map(|e| match e {
+            ClientEntry::Occupied(client) => Some(client.deref()),
+            _ => None,
+        })
+    }
+
+    pub(crate) fn clients_mut(&mut self) -> impl Iterator<Item = &mut Client> {
+        self.entries.iter_mut().filter_map(|e| match e {
+            ClientEntry::Occupied(client) => Some(client.deref_mut()),
+            _ => None,
+        })
+    }
+
     pub(crate) fn get_mut(&mut self, handle: ClientHandle) -> Option<&mut Client> {
         match &mut self.entries[handle.0 as usize] {
             ClientEntry::Occupied(client) => Some(client.deref_mut()),
@@ -241,13 +279,20 @@ impl LspPlugin {
         self.entries[index] = ClientEntry::Occupied(client);
     }
 
-    pub(crate) fn find_client<P>(&mut self, mut predicate: P) -> Option<ClientGuard>
-    where
-        P: FnMut(&Client) -> bool,
-    {
+    // a client is attached to a path when it handles it and all recipes that started it match it.
+    // clients started manually (with no recipe) are attached to every path they handle
+    pub(crate) fn is_attached(&self, client: &Client, path: &str) -> bool {
+        is_client_attached(&self.recipes, client, path)
+    }
+
+    pub(crate) fn find_client(&mut self, path: Option<&str>) -> Option<ClientGuard> {
         for entry in &mut self.entries {
             if let ClientEntry::Occupied(c) = entry {
-                if predicate(c) {
+                let attached = match path {
+                    Some(path) => is_client_attached(&self.recipes, c, path),
+                    None => true,
+                };
+                if attached {
                     let client = entry.reserve_and_take().unwrap();
                     return Some(ClientGuard(client));
                 }
@@ -256,6 +301,68 @@ impl LspPlugin {
 
         None
     }
+
+    // the server command picked the last time several clients could handle a feature
+    pub(crate) fn preferred_client(&self, feature: ServerFeature) -> Option<&str> {
+        self.preferred_clients
+            .iter()
+            .find(|(f, _)| *f == feature)
+            .map(|(_, command)| &command[..])
+    }
+
+    pub(crate) fn set_preferred_client(&mut self, feature: ServerFeature, command: &str) {
+        match self
+            .preferred_clients
+            .iter_mut()
+            .find(|(f, _)| *f == feature)
+        {
+            Some((_, preferred)) => {
+                preferred.clear();
+                preferred.push_str(command);
+            }
+            None => self.preferred_clients.push((feature, command.into())),
+        }
+    }
+
+    // rebuilds the buffer lints from the diagnostics of every client.
+    // `client` is for when a client is currently acquired and thus missing from the entries
+    pub(crate) fn rebuild_lints(
+        &self,
+        client: Option<&Client>,
+        editor: &mut Editor,
+        plugin_handle: PluginHandle,
+        buffer_handle: BufferHandle,
+    ) {
+        let mut lints = editor
+            .buffers
+            .get_mut(buffer_handle)
+            .lints
+            .mut_guard(plugin_handle);
+        lints.clear();
+
+        // keeps lints ordered by client handle even when one of them is acquired
+        let clients = self.entries.iter().filter_map(|e| match e {
+            ClientEntry::Occupied(client) => Some(client.deref()),
+            ClientEntry::Reserved => client,
+            ClientEntry::Vacant => None,
+        });
+        for client in clients {
+            if let Some(diagnostics) = client.diagnostics.buffer_diagnostics(buffer_handle) {
+                for (range, message, _) in diagnostics.iter() {
+                    lints.add(message, range);
+                }
+            }
+        }
+    }
+}
+
+fn is_client_attached(recipes: &[ClientRecipe], client: &Client, path: &str) -> bool {
+    let handle = client.handle();
+    client.handles_path(path)
+        && recipes
+            .iter()
+            .filter(|r| r.running_client == Some(handle))
+            .all(|r| r.glob.matches(path))
 }
 
 fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
@@ -268,37 +375,33 @@ fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
                 Some(path) => path,
                 None => continue,
             };
-            let (index, recipe) = match lsp
-                .recipes
-                .iter_mut()
-                .enumerate()
-                .find(|(_, r)| r.glob.matches(buffer_path))
-            {
-                Some(recipe) => recipe,
-                None => continue,
-            };
-            if recipe.running_client.is_some() {
-                continue;
-            }
-            let command = match parse_process_command(&recipe.command) {
-                Some(command) => command,
-                None => {
-                    ctx.editor
-                        .logger
-                        .write(LogKind::Error)
-                        .fmt(format_args!("invalid lsp command '{}'", &recipe.command));
+
+            // every matching server is started so a buffer may end up with several clients
+            for index in 0..lsp.recipes.len() {
+                let recipe = &lsp.recipes[index];
+                if recipe.running_client.is_some() || !recipe.glob.matches(buffer_path) {
                     continue;
                 }
-            };
+                let command = match parse_process_command(&recipe.command) {
+                    Some(command) => command,
+                    None => {
+                        ctx.editor
+                            .logger
+                            .write(LogKind::Error)
+                            .fmt(format_args!("invalid lsp command '{}'", &recipe.command));
+                        continue;
+                    }
+                };
 
-            let root = if recipe.root.as_os_str().is_empty() {
-                ctx.editor.current_directory.clone()
-            } else {
-                recipe.root.clone()
-            };
+                let root = if recipe.root.as_os_str().is_empty() {
+                    ctx.editor.current_directory.clone()
+                } else {
+                    recipe.root.clone()
+                };
 
-            let client_handle = lsp.start(&mut ctx.platform, plugin_handle, command, root);
-            lsp.recipes[index].running_client = Some(client_handle);
+                let client_handle = lsp.start(&mut ctx.platform, plugin_handle, command, root);
+                lsp.recipes[index].running_client = Some(client_handle);
+            }
         }
     }
 
@@ -326,6 +429,10 @@ fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
                             let text = insert.text(ctx.editor.events.reader());
                             let range = BufferRange::between(insert.range.from, insert.range.from);
                             client.versioned_buffers.add_edit(handle, range, text);
+                            client
+                                .diagnostics
+                                .get_buffer_diagnostics(handle)
+                                .insert_range(insert.range);
                         }
                     }
                 }
@@ -334,6 +441,10 @@ fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
                     if buffer.path.to_str() != ctx.editor.logger.log_file_path() {
                         for &range in deletes.as_slice(ctx.editor.events.reader()) {
                             client.versioned_buffers.add_edit(handle, range, "");
+                            client
+                                .diagnostics
+                                .get_buffer_diagnostics(handle)
+                                .delete_range(range);
                         }
                     }
                 }
@@ -361,15 +472,40 @@ fn on_editor_events(plugin_handle: PluginHandle, ctx: &mut EditorContext) {
                     let buffer = ctx.editor.buffers.get(handle);
                     if buffer.path.to_str() != ctx.editor.logger.log_file_path() {
                         client.versioned_buffers.dispose(handle);
-                        client.diagnostics.on_close_buffer(handle);
+                        client.diagnostics.clear_buffer(handle);
                         util::send_pending_did_change(client, &mut ctx.editor, &mut ctx.platform);
                         util::send_did_close(client, &mut ctx.editor, &mut ctx.platform, handle);
                     }
                 }
+                EditorEvent::BufferRename { handle, old_path } => {
+                    let buffer = ctx.editor.buffers.get(handle);
+                    if buffer.path.to_str() != ctx.editor.logger.log_file_path() {
+                        let old_path = Path::new(old_path.as_str(ctx.editor.events.reader()));
+                        client.versioned_buffers.dispose(handle);
+                        client.diagnostics.clear_buffer(handle);
+                        util::send_did_close_path(
+                            client,
+                            &ctx.editor.buffers,
+                            &mut ctx.platform,
+                            handle,
+                            old_path,
+                            &mut ctx.editor.logger,
+                        );
+                        util::send_did_open(
+                            client,
+                            &ctx.editor.buffers,
+                            &mut ctx.platform,
+                            handle,
+                            &mut ctx.editor.logger,
+                        );
+                    }
+                }
                 EditorEvent::FixCursors { .. } => (),
                 EditorEvent::BufferBreakpointsChanged { .. } => (),
             }
         }
+
+        client.flush_log_buffer(&mut ctx.editor);
     }
 }
 
@@ -470,17 +606,13 @@ fn on_process_output(
         }
     }
     events.finish(&mut client.protocol);
+    client.flush_log_buffer(&mut ctx.editor);
 
     let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
     lsp.release(client_guard);
 }
 
 fn on_process_exit(plugin_handle: PluginHandle, ctx: &mut EditorContext, client_index: u32) {
-    for buffer in ctx.editor.buffers.iter_mut() {
-        let mut lints = buffer.lints.mut_guard(plugin_handle);
-        lints.clear();
-    }
-
     let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
     if let ClientEntry::Occupied(client) = &mut lsp.entries[client_index as usize] {
         {
@@ -489,6 +621,7 @@ fn on_process_exit(plugin_handle: PluginHandle, ctx: &mut EditorContext, client_
             log_writer.str("lsp server stopped");
         }
 
+        client.diagnostics.clear_all();
         let client_handle = client.handle();
         for recipe in &mut lsp.recipes {
             if recipe.running_client == Some(client_handle) {
@@ -496,6 +629,12 @@ fn on_process_exit(plugin_handle: PluginHandle, ctx: &mut EditorContext, client_
             }
         }
     }
+
+    // lints from the other servers still running are kept
+    let buffer_handles: Vec<_> = ctx.editor.buffers.iter().map(|b| b.handle()).collect();
+    for buffer_handle in buffer_handles {
+        lsp.rebuild_lints(None, &mut ctx.editor, plugin_handle, buffer_handle);
+    }
 }
 
 fn on_completion(
@@ -509,6 +648,11 @@ fn on_completion(
             ClientEntry::Occupied(client) => client,
             _ => continue,
         };
+        let buffer = ctx.editor.buffers.get(completion_ctx.buffer_handle);
+        let buffer_path = buffer.path.to_str().unwrap_or("");
+        if !is_client_attached(&lsp.recipes, client, buffer_path) {
+            continue;
+        }
         client.json.clear();
 
         let mut should_complete = completion_ctx.completion_requested;
@@ -523,21 +667,37 @@ fn on_completion(
                 .next()
                 .and_then(|s| s.chars().next_back())
             {
-                if client.signature_help_triggers().contains(c) {
+                if ctx.editor.logger.is_status_bar_message_empty() {
+                    client.showing_signature_help = false;
+                }
+                if c == ')' && client.showing_signature_help {
+                    client.showing_signature_help = false;
+                    ctx.editor.logger.clear_status_bar_message();
+                    return false;
+                }
+
+                if ctx.editor.config.signature_help_trigger
+                    && client.supports(ServerFeature::SignatureHelp)
+                    && client.signature_help_triggers().contains(c)
+                {
                     client.signature_help(
                         &mut ctx.editor,
                         &mut ctx.platform,
                         completion_ctx.buffer_handle,
                         completion_ctx.cursor_position,
                     );
+                    client.flush_log_buffer(&mut ctx.editor);
                     return false;
                 }
 
-                should_complete = client.completion_triggers().contains(c);
+                // with a manual trigger, only the popup is suppressed and signature help still shows
+                should_complete = ctx.editor.config.completion_trigger != CompletionTrigger::Manual
+                    && client.completion_triggers().contains(c);
             }
         }
 
-        if should_complete {
+        // a server without completion must not prevent the next attached one from completing
+        if should_complete && client.supports(ServerFeature::Completion) {
             client.completion(
                 &mut ctx.editor,
                 &mut ctx.platform,
@@ -545,9 +705,759 @@ fn on_completion(
                 completion_ctx.buffer_handle,
                 completion_ctx.cursor_position,
             );
+            client.flush_log_buffer(&mut ctx.editor);
             return true;
         }
     }
 
     false
 }
+
+// when several attached servers can format, the one last picked for 'lsp-format' is used
+fn on_format(handle: PluginHandle, ctx: &mut EditorContext, buffer_handle: BufferHandle) -> bool {
+    let buffer_path = match ctx.editor.buffers.get(buffer_handle).path.to_str() {
+        Some(path) => path,
+        None => return false,
+    };
+    let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
+    let preferred = lsp.preferred_client(ServerFeature::Formatting);
+    let mut client_handle = None;
+    for client in lsp.clients() {
+        if !lsp.is_attached(client, buffer_path) || !client.supports(ServerFeature::Formatting) {
+            continue;
+        }
+        if client_handle.is_none() || Some(client.command.as_str()) == preferred {
+            client_handle = Some(client.handle());
+        }
+    }
+    let client_handle = match client_handle {
+        Some(client_handle) => client_handle,
+        None => return false,
+    };
+
+    let _ = command::access_client(ctx, handle, client_handle, |ctx, client| {
+        client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
+        ctx.editor.logger.write(LogKind::Status).fmt(format_args!(
+            "formatting with lsp server '{}'",
+            client.command
+        ));
+        Ok(())
+    });
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use pepper::{
+        buffer_position::BufferPosition,
+        command::CommandManager,
+        editor::KeysIterator,
+        editor_utils::{ReadLinePoll, REGISTER_READLINE_INPUT},
+        mode::ModeKind,
+    };
+
+    use client::LogVerbosity;
+    use protocol::PositionEncoding;
+    use test_platform::{location_json, text_edit_json, TestPlatform};
+
+    #[test]
+    fn initialize_handshake() {
+        let mut test = TestPlatform::new();
+        test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true}}");
+
+        let messages = test.sent_messages();
+        let methods: Vec<_> = messages.iter().map(|m| m.method()).collect();
+        assert_eq!(&["initialized", "textDocument/didOpen"], &methods[..]);
+
+        test.client(|_, client| assert!(client.initialized));
+    }
+
+    #[test]
+    fn initialize_client_info() {
+        let mut test = TestPlatform::new();
+        let messages = test.sent_messages();
+        let initialize = messages
+            .iter()
+            .find(|m| m.method() == "initialize")
+            .unwrap();
+        let client_info = initialize.params().get("clientInfo", &initialize.json);
+        let field = |key| match client_info.clone().get(key, &initialize.json) {
+            JsonValue::String(value) => value.as_str(&initialize.json).to_string(),
+            _ => panic!("clientInfo without {}", key),
+        };
+        assert_eq!(env!("CARGO_PKG_NAME"), field("name"));
+        assert_eq!(env!("CARGO_PKG_VERSION"), field("version"));
+        assert_ne!(field("name"), field("version"));
+    }
+
+    #[test]
+    fn did_open_message() {
+        let mut test = TestPlatform::new();
+        test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true}}");
+
+        let messages = test.sent_messages();
+        let did_open = messages
+            .iter()
+            .find(|m| m.method() == "textDocument/didOpen")
+            .unwrap();
+        let uri = did_open.text_document_uri().unwrap();
+        assert_eq!(
+            format!(
+                "{{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/didOpen\",\"params\":{{\"textDocument\":{{\"languageId\":\"rust\",\"text\":\"fn main() {{}}\",\"uri\":\"{}\",\"version\":1}}}}}}",
+                uri
+            ),
+            did_open.to_sorted_string()
+        );
+    }
+
+    fn lint_messages(test: &TestPlatform, buffer_handle: BufferHandle) -> Vec<String> {
+        let lints = &test.ctx.editor.buffers.get(buffer_handle).lints;
+        lints
+            .all()
+            .iter()
+            .map(|l| l.message(lints).to_string())
+            .collect()
+    }
+
+    #[test]
+    fn diagnostics_from_multiple_servers() {
+        fn did_open_uri(test: &mut TestPlatform) -> Option<String> {
+            let messages = test.sent_messages();
+            messages
+                .iter()
+                .find(|m| m.method() == "textDocument/didOpen")?
+                .text_document_uri()
+        }
+
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true}}");
+        let uri = did_open_uri(&mut test).unwrap();
+
+        let other_client_handle = test.start_client("{\"textDocumentSync\":{\"openClose\":true}}");
+        assert_eq!(Some(&uri), did_open_uri(&mut test).as_ref());
+
+        let client_handle = test.client_handle;
+        test.publish_diagnostics(client_handle, &uri, &[((0, 3), (0, 7), "first")]);
+        test.publish_diagnostics(other_client_handle, &uri, &[((0, 3), (0, 7), "second")]);
+        assert_eq!(
+            &["first", "second"],
+            &lint_messages(&test, buffer_handle)[..]
+        );
+
+        test.insert_text(buffer_handle, BufferPosition::zero(), "\n");
+        let lsp = test.ctx.plugins.get_as::<LspPlugin>(test.plugin_handle);
+        let client = lsp.get_mut(other_client_handle).unwrap();
+        let (range, _, _) = client
+            .diagnostics
+            .get_buffer_diagnostics(buffer_handle)
+            .iter()
+            .next()
+            .unwrap();
+        assert_eq!(BufferPosition::line_col(1, 3), range.from);
+
+        on_process_exit(
+            test.plugin_handle,
+            &mut test.ctx,
+            other_client_handle.0 as _,
+        );
+        assert_eq!(&["first"], &lint_messages(&test, buffer_handle)[..]);
+    }
+
+    #[test]
+    fn goto_diagnostics() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "let s = \"\u{1f600}\"; x\ny");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true}}");
+        let messages = test.sent_messages();
+        let did_open = messages
+            .iter()
+            .find(|m| m.method() == "textDocument/didOpen")
+            .unwrap();
+        let uri = did_open.text_document_uri().unwrap();
+
+        let client_handle = test.client_handle;
+        test.publish_diagnostics(
+            client_handle,
+            &uri,
+            &[((1, 0), (1, 1), "y"), ((0, 13), (0, 14), "x")],
+        );
+
+        let lints = &test.ctx.editor.buffers.get(buffer_handle).lints;
+        let find = |position, forward| {
+            command::find_diagnostic(lints, test.plugin_handle, position, forward)
+                .map(|l| (l.range.from, l.message(lints)))
+        };
+        let x = (BufferPosition::line_col(0, 15), "x");
+        let y = (BufferPosition::line_col(1, 0), "y");
+        assert_eq!(Some(x), find(BufferPosition::zero(), true));
+        assert_eq!(Some(y), find(x.0, true));
+        assert_eq!(Some(x), find(y.0, true));
+        assert_eq!(Some(x), find(y.0, false));
+        assert_eq!(Some(y), find(x.0, false));
+        assert_eq!(Some(y), find(BufferPosition::zero(), false));
+    }
+
+    #[test]
+    fn pick_client_for_request() {
+        let mut test = TestPlatform::new();
+        test.initialize("{\"workspaceSymbolProvider\":true}");
+        test.start_client("{\"workspaceSymbolProvider\":true}");
+        test.sent_messages();
+
+        let command = "lsp-workspace-symbols main";
+        assert!(CommandManager::eval(&mut test.ctx, None, "test", command).is_ok());
+        assert_eq!(ModeKind::Picker, test.ctx.editor.mode.kind());
+        assert_eq!(2, test.ctx.editor.picker.len());
+        assert!(test.sent_messages().is_empty());
+
+        test.ctx.editor.picker.move_cursor(1);
+        let (_, entry) = test
+            .ctx
+            .editor
+            .picker
+            .current_entry(&test.ctx.editor.word_database)
+            .unwrap();
+        let picked = entry.to_string();
+        let on_client_keys = test.ctx.editor.mode.picker_state.on_client_keys;
+        on_client_keys(
+            &mut test.ctx,
+            pepper::client::ClientHandle(0),
+            &mut KeysIterator { index: 0 },
+            ReadLinePoll::Submitted,
+        );
+        assert_eq!(ModeKind::default(), test.ctx.editor.mode.kind());
+        assert_eq!(1, test.sent_messages().len());
+        let lsp = test.ctx.plugins.get_as::<LspPlugin>(test.plugin_handle);
+        assert_eq!(
+            Some(picked.as_str()),
+            lsp.preferred_client(ServerFeature::WorkspaceSymbols)
+        );
+
+        let lsp = test.ctx.plugins.get_as::<LspPlugin>(test.plugin_handle);
+        for client in lsp.clients_mut() {
+            client.cancel_current_request();
+        }
+        assert!(CommandManager::eval(&mut test.ctx, None, "test", command).is_ok());
+        assert_eq!(ModeKind::default(), test.ctx.editor.mode.kind());
+        assert_eq!(1, test.sent_messages().len());
+    }
+
+    fn did_change_content_changes(test: &mut TestPlatform) -> String {
+        test.client(|ctx, client| {
+            util::send_pending_did_change(client, &mut ctx.editor, &mut ctx.platform);
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        assert_eq!("textDocument/didChange", messages[0].method());
+
+        let mut changes = Vec::new();
+        let content_changes = messages[0]
+            .params()
+            .get("contentChanges", &messages[0].json);
+        messages[0]
+            .json
+            .write_sorted(&mut changes, &content_changes)
+            .unwrap();
+        String::from_utf8(changes).unwrap()
+    }
+
+    #[test]
+    fn incremental_sync_multiline_insert() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true,\"change\":2}}");
+        test.sent_messages();
+
+        test.insert_text(
+            buffer_handle,
+            BufferPosition::line_col(0, 11),
+            "\n    let a = 1;\n",
+        );
+        assert_eq!(
+            "[{\"range\":{\"end\":{\"character\":11,\"line\":0},\"start\":{\"character\":11,\"line\":0}},\"text\":\"\\n    let a = 1;\\n\"}]",
+            did_change_content_changes(&mut test)
+        );
+
+        let buffer = test.ctx.editor.buffers.get(buffer_handle).content();
+        let lines: Vec<_> = buffer.lines().iter().map(|l| l.as_str()).collect();
+        assert_eq!(&["fn main() {", "    let a = 1;", "}"], &lines[..]);
+    }
+
+    #[test]
+    fn incremental_sync_multiline_delete() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn main() {\n    let a = 1;\n}");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true,\"change\":2}}");
+        test.sent_messages();
+
+        test.delete_range(
+            buffer_handle,
+            BufferRange::between(
+                BufferPosition::line_col(0, 11),
+                BufferPosition::line_col(2, 0),
+            ),
+        );
+        assert_eq!(
+            "[{\"range\":{\"end\":{\"character\":0,\"line\":2},\"start\":{\"character\":11,\"line\":0}},\"text\":\"\"}]",
+            did_change_content_changes(&mut test)
+        );
+
+        let buffer = test.ctx.editor.buffers.get(buffer_handle).content();
+        assert_eq!("fn main() {}", buffer.lines()[0].as_str());
+    }
+
+    #[test]
+    fn hover() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"hoverProvider\":true}");
+        test.sent_messages();
+
+        test.client(|ctx, client| {
+            client.hover(
+                &mut ctx.editor,
+                &mut ctx.platform,
+                buffer_handle,
+                BufferPosition::line_col(0, 3),
+            );
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        assert_eq!("textDocument/hover", messages[0].method());
+        let position = messages[0].params().get("position", &messages[0].json);
+        assert!(matches!(
+            position.clone().get("line", &messages[0].json),
+            JsonValue::Integer(0)
+        ));
+        assert!(matches!(
+            position.get("character", &messages[0].json),
+            JsonValue::Integer(3)
+        ));
+
+        let id = messages[0].id().unwrap();
+        test.respond(id, "{\"contents\":\"fn main()\"}");
+        assert_eq!("fn main()", test.ctx.editor.logger.status_bar_message());
+    }
+
+    #[test]
+    fn definition_locations() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn a() {}\nfn a() {}\na();");
+        test.initialize("{\"definitionProvider\":true}");
+        test.sent_messages();
+
+        test.client(|ctx, client| {
+            client.definition(
+                &mut ctx.editor,
+                &mut ctx.platform,
+                buffer_handle,
+                BufferPosition::line_col(2, 0),
+                pepper::client::ClientHandle(0),
+            );
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        assert_eq!("textDocument/definition", messages[0].method());
+        let uri = messages[0].text_document_uri().unwrap();
+
+        let id = messages[0].id().unwrap();
+        test.respond(
+            id,
+            &format!(
+                "[{},{}]",
+                location_json(&uri, (0, 3), (0, 4)),
+                location_json(&uri, (1, 3), (1, 4)),
+            ),
+        );
+        assert_eq!(ModeKind::Picker, test.ctx.editor.mode.kind());
+        let mut entries = test.ctx.editor.picker.custom_entries().collect::<Vec<_>>();
+        entries.sort_unstable();
+        assert_eq!(vec!["main.rs:1:4", "main.rs:2:4"], entries);
+    }
+
+    #[test]
+    fn formatting() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn  main() {}");
+        test.initialize("{\"documentFormattingProvider\":true}");
+        test.sent_messages();
+
+        test.client(|ctx, client| {
+            client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        assert_eq!("textDocument/formatting", messages[0].method());
+
+        let id = messages[0].id().unwrap();
+        test.respond(id, &format!("[{}]", text_edit_json((0, 2), (0, 4), " ")));
+
+        let buffer = test.ctx.editor.buffers.get(buffer_handle).content();
+        assert_eq!("fn main() {}", buffer.lines()[0].as_str());
+    }
+
+    #[test]
+    fn rename_across_buffers() {
+        let mut test = TestPlatform::new();
+        let main_handle = test.open_buffer("main.rs", "let a = 1;\na + a");
+        let lib_handle = test.open_buffer("lib.rs", "use a;");
+        test.initialize("{\"renameProvider\":true}");
+        test.sent_messages();
+
+        let plugin_handle = test.plugin_handle;
+        test.client(|ctx, client| {
+            client.rename(
+                ctx,
+                plugin_handle,
+                main_handle,
+                BufferPosition::line_col(0, 4),
+            );
+            ctx.editor.registers.set(REGISTER_READLINE_INPUT, "bb");
+            client.finish_rename(&mut ctx.editor, &mut ctx.platform);
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        assert_eq!("textDocument/rename", messages[0].method());
+        let params = messages[0].params();
+        match params.clone().get("newName", &messages[0].json) {
+            JsonValue::String(name) => assert_eq!("bb", name.as_str(&messages[0].json)),
+            _ => panic!("rename without newName"),
+        }
+        let main_uri = messages[0].text_document_uri().unwrap();
+        let lib_uri = main_uri.replace("main.rs", "lib.rs");
+
+        fn edit(line: u32, from: u32, to: u32) -> String {
+            text_edit_json((line, from), (line, to), "bb")
+        }
+        let id = messages[0].id().unwrap();
+        test.respond(
+            id,
+            &format!(
+                "{{\"changes\":{{\"{}\":[{},{},{}],\"{}\":[{}]}}}}",
+                main_uri,
+                edit(0, 4, 5),
+                edit(1, 0, 1),
+                edit(1, 4, 5),
+                lib_uri,
+                edit(0, 4, 5),
+            ),
+        );
+
+        let main = test.ctx.editor.buffers.get(main_handle).content();
+        assert_eq!("let bb = 1;", main.lines()[0].as_str());
+        assert_eq!("bb + bb", main.lines()[1].as_str());
+        let lib = test.ctx.editor.buffers.get(lib_handle).content();
+        assert_eq!("use bb;", lib.lines()[0].as_str());
+
+        let editor = &mut test.ctx.editor;
+        let main = editor.buffers.get_mut(main_handle);
+        main.undo(&mut editor.word_database, editor.events.writer())
+            .for_each(drop);
+        assert_eq!("let a = 1;", main.content().lines()[0].as_str());
+        assert_eq!("a + a", main.content().lines()[1].as_str());
+    }
+
+    #[test]
+    fn code_action() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "let x = 1;");
+        test.initialize("{\"codeActionProvider\":true}");
+        test.sent_messages();
+
+        let range = BufferRange::between(
+            BufferPosition::line_col(0, 4),
+            BufferPosition::line_col(0, 5),
+        );
+        test.client(|ctx, client| {
+            client.code_action(&mut ctx.editor, &mut ctx.platform, buffer_handle, range);
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        assert_eq!("textDocument/codeAction", messages[0].method());
+        let uri = messages[0].text_document_uri().unwrap();
+
+        let id = messages[0].id().unwrap();
+        test.respond(
+            id,
+            &format!(
+                "[{{\"title\":\"rename to y\",\"kind\":\"quickfix\",\"edit\":{{\"changes\":{{\"{}\":[{}]}}}},\"command\":{{\"title\":\"log\",\"command\":\"log.rename\",\"arguments\":[1]}}}},{{\"title\":\"organize imports\",\"command\":\"organize\"}}]",
+                uri,
+                text_edit_json((0, 4), (0, 5), "y"),
+            ),
+        );
+        assert_eq!(ModeKind::Picker, test.ctx.editor.mode.kind());
+        assert_eq!(
+            vec!["[quickfix] rename to y", "organize imports"],
+            test.ctx.editor.picker.custom_entries().collect::<Vec<_>>()
+        );
+
+        let picker = &mut test.ctx.editor.picker;
+        while !picker
+            .current_entry(&test.ctx.editor.word_database)
+            .unwrap()
+            .1
+            .starts_with("[quickfix]")
+        {
+            picker.move_cursor(1);
+        }
+        let on_client_keys = test.ctx.editor.mode.picker_state.on_client_keys;
+        on_client_keys(
+            &mut test.ctx,
+            pepper::client::ClientHandle(0),
+            &mut KeysIterator { index: 0 },
+            ReadLinePoll::Submitted,
+        );
+        let buffer = test.ctx.editor.buffers.get(buffer_handle).content();
+        assert_eq!("let y = 1;", buffer.lines()[0].as_str());
+
+        let messages = test.sent_messages();
+        let execute_command = messages
+            .iter()
+            .find(|m| m.method() == "workspace/executeCommand")
+            .unwrap();
+        match execute_command
+            .params()
+            .get("command", &execute_command.json)
+        {
+            JsonValue::String(command) => {
+                assert_eq!("log.rename", command.as_str(&execute_command.json))
+            }
+            _ => panic!("executeCommand without command"),
+        }
+    }
+
+    #[test]
+    fn completion_trigger_characters() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "a.b");
+        test.initialize("{\"completionProvider\":{\"triggerCharacters\":[\".\"]}}");
+        test.sent_messages();
+
+        let complete = |test: &mut TestPlatform, column| {
+            let completion_ctx = CompletionContext {
+                client_handle: pepper::client::ClientHandle(0),
+                buffer_handle,
+                word_range: BufferRange::between(
+                    BufferPosition::line_col(0, column - 1),
+                    BufferPosition::line_col(0, column),
+                ),
+                cursor_position: BufferPosition::line_col(0, column),
+                completion_requested: false,
+            };
+            let completing = on_completion(test.plugin_handle, &mut test.ctx, &completion_ctx);
+            let messages = test.sent_messages();
+            let requested = messages
+                .iter()
+                .any(|m| m.method() == "textDocument/completion");
+            assert_eq!(completing, requested);
+            test.client(|_, client| client.cancel_current_request());
+            requested
+        };
+        assert!(!complete(&mut test, 1));
+        assert!(complete(&mut test, 2));
+        assert!(!complete(&mut test, 3));
+    }
+
+    #[test]
+    fn manual_completion_trigger_keeps_signature_help() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "f(a.");
+        test.initialize(concat!(
+            "{\"completionProvider\":{\"triggerCharacters\":[\".\"]},",
+            "\"signatureHelpProvider\":{\"triggerCharacters\":[\"(\"]}}",
+        ));
+        test.sent_messages();
+        test.ctx.editor.config.completion_trigger = CompletionTrigger::Manual;
+
+        let sent_method = |test: &mut TestPlatform, column| {
+            let completion_ctx = CompletionContext {
+                client_handle: pepper::client::ClientHandle(0),
+                buffer_handle,
+                word_range: BufferRange::between(
+                    BufferPosition::line_col(0, column - 1),
+                    BufferPosition::line_col(0, column),
+                ),
+                cursor_position: BufferPosition::line_col(0, column),
+                completion_requested: false,
+            };
+            assert!(!on_completion(
+                test.plugin_handle,
+                &mut test.ctx,
+                &completion_ctx
+            ));
+            let messages = test.sent_messages();
+            test.client(|_, client| client.cancel_current_request());
+            messages.first().map(|m| String::from(m.method()))
+        };
+        assert_eq!(
+            Some("textDocument/signatureHelp"),
+            sent_method(&mut test, 2).as_deref()
+        );
+        assert_eq!(None, sent_method(&mut test, 4));
+    }
+
+    fn hover_position_character(test: &mut TestPlatform, position: BufferPosition) -> i64 {
+        let buffer_handle = test.ctx.editor.buffers.iter().next().unwrap().handle();
+        test.client(|ctx, client| {
+            client.hover(&mut ctx.editor, &mut ctx.platform, buffer_handle, position);
+        });
+        let messages = test.sent_messages();
+        assert_eq!(1, messages.len());
+        let position = messages[0].params().get("position", &messages[0].json);
+        match position.get("character", &messages[0].json) {
+            JsonValue::Integer(character) => character,
+            _ => panic!("hover without character"),
+        }
+    }
+
+    #[test]
+    fn position_encoding_utf16() {
+        let mut test = TestPlatform::new();
+        test.open_buffer("main.rs", "let s = \"\u{e9}\u{1f600}\"; s");
+        test.initialize("{\"hoverProvider\":true}");
+        test.sent_messages();
+
+        test.client(|_, client| assert_eq!(PositionEncoding::Utf16, client.position_encoding));
+        let column = "let s = \"\u{e9}\u{1f600}\"; ".len();
+        let character =
+            hover_position_character(&mut test, BufferPosition::line_col(0, column as _));
+        assert_eq!(15, character);
+    }
+
+    #[test]
+    fn position_encoding_utf8() {
+        let mut test = TestPlatform::new();
+        test.open_buffer("main.rs", "let s = \"\u{e9}\u{1f600}\"; s");
+        test.initialize("{\"positionEncoding\":\"utf-8\",\"hoverProvider\":true}");
+        test.sent_messages();
+
+        let column = "let s = \"\u{e9}\u{1f600}\"; ".len();
+        let character =
+            hover_position_character(&mut test, BufferPosition::line_col(0, column as _));
+        assert_eq!(column as i64, character);
+    }
+
+    #[test]
+    fn incremental_sync_utf16() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "let s = \"\u{e9}\u{1f600}\";\n}");
+        test.initialize("{\"textDocumentSync\":{\"openClose\":true,\"change\":2}}");
+        test.sent_messages();
+
+        let column = "let s = \"\u{e9}\u{1f600}".len();
+        test.insert_text(
+            buffer_handle,
+            BufferPosition::line_col(0, column as _),
+            "\u{e9}\n",
+        );
+        test.delete_range(
+            buffer_handle,
+            BufferRange::between(
+                BufferPosition::line_col(0, column as _),
+                BufferPosition::line_col(0, column as u32 + 2),
+            ),
+        );
+        test.insert_text(buffer_handle, BufferPosition::line_col(1, 2), "x");
+        assert_eq!(
+            concat!(
+                "[{\"range\":{\"end\":{\"character\":12,\"line\":0},\"start\":{\"character\":12,\"line\":0}},\"text\":\"\\u00e9\\n\"},",
+                "{\"range\":{\"end\":{\"character\":13,\"line\":0},\"start\":{\"character\":12,\"line\":0}},\"text\":\"\"},",
+                "{\"range\":{\"end\":{\"character\":2,\"line\":1},\"start\":{\"character\":2,\"line\":1}},\"text\":\"x\"}]",
+            ),
+            did_change_content_changes(&mut test)
+        );
+
+        let buffer = test.ctx.editor.buffers.get(buffer_handle).content();
+        let lines: Vec<_> = buffer.lines().iter().map(|l| l.as_str()).collect();
+        assert_eq!(&["let s = \"\u{e9}\u{1f600}", "\";x", "}"], &lines[..]);
+    }
+
+    #[test]
+    fn log_buffer() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"hoverProvider\":true}");
+        test.sent_messages();
+
+        let mut log_buffer_handle = None;
+        test.client(|ctx, client| {
+            log_buffer_handle = Some(client.start_logging(&mut ctx.editor, LogVerbosity::Messages));
+            client.hover(
+                &mut ctx.editor,
+                &mut ctx.platform,
+                buffer_handle,
+                BufferPosition::zero(),
+            );
+            client.flush_log_buffer(&mut ctx.editor);
+        });
+        let id = test.sent_messages()[0].id().unwrap();
+        test.respond(id, "null");
+
+        let log_buffer = test.ctx.editor.buffers.get(log_buffer_handle.unwrap());
+        let lines: Vec<_> = log_buffer
+            .content()
+            .lines()
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(
+            &[
+                &format!(">> request 'textDocument/hover' id: {}", id)[..],
+                &format!("<< response 'textDocument/hover' id: {}", id)[..],
+                "",
+            ],
+            &lines[..]
+        );
+
+        test.client(|_, client| client.stop_logging());
+        test.client(|ctx, client| {
+            client.hover(
+                &mut ctx.editor,
+                &mut ctx.platform,
+                buffer_handle,
+                BufferPosition::zero(),
+            );
+            client.flush_log_buffer(&mut ctx.editor);
+        });
+        let log_buffer = test.ctx.editor.buffers.get(log_buffer_handle.unwrap());
+        assert_eq!(3, log_buffer.content().lines().len());
+    }
+
+    #[test]
+    fn log_buffer_method_filter() {
+        let mut test = TestPlatform::new();
+        let buffer_handle = test.open_buffer("main.rs", "fn main() {}");
+        test.initialize("{\"hoverProvider\":true,\"documentFormattingProvider\":true}");
+        test.sent_messages();
+
+        let mut log_buffer_handle = None;
+        test.client(|ctx, client| {
+            log_buffer_handle = Some(client.start_logging(&mut ctx.editor, LogVerbosity::Verbose));
+            client.log_method_filter.push_str("textDocument/formatting");
+            client.hover(
+                &mut ctx.editor,
+                &mut ctx.platform,
+                buffer_handle,
+                BufferPosition::zero(),
+            );
+            client.formatting(&mut ctx.editor, &mut ctx.platform, buffer_handle);
+            client.flush_log_buffer(&mut ctx.editor);
+        });
+
+        let log_buffer = test.ctx.editor.buffers.get(log_buffer_handle.unwrap());
+        let lines: Vec<_> = log_buffer
+            .content()
+            .lines()
+            .iter()
+            .map(|l| l.as_str())
+            .collect();
+        assert_eq!(4, lines.len());
+        assert!(lines[0].starts_with(">> request 'textDocument/formatting' id: "));
+        assert!(lines[1].starts_with("{\"textDocument\":"));
+    }
+}
@@ -12,7 +12,7 @@ use pepper::{
     word_database::WordIndicesIter,
 };
 
-use crate::{client::Client, LspPlugin};
+use crate::{client::Client, command, LspPlugin};
 
 pub fn enter_definition_mode(
     ctx: &mut EditorContext,
@@ -117,7 +117,7 @@ pub fn enter_code_action_mode(
                             Some((EntrySource::Custom(i), _)) => i,
                             _ => 0,
                         };
-                        client.finish_code_action(&mut ctx.editor, index);
+                        client.finish_code_action(&mut ctx.editor, &mut ctx.platform, index);
                     }
                 }
 
@@ -307,3 +307,90 @@ pub fn enter_workspace_symbol_mode(
         client.cancel_current_request();
     }
 }
+
+pub fn enter_client_mode(ctx: &mut EditorContext, plugin_handle: PluginHandle) {
+    fn on_client_keys(
+        ctx: &mut EditorContext,
+        _: ClientHandle,
+        _: &mut KeysIterator,
+        poll: ReadLinePoll,
+    ) -> Option<EditorFlow> {
+        match poll {
+            ReadLinePoll::Pending => Some(EditorFlow::Continue),
+            ReadLinePoll::Submitted => {
+                let index = match ctx.editor.picker.current_entry(&ctx.editor.word_database) {
+                    Some((EntrySource::Custom(i), _)) => Some(i),
+                    _ => None,
+                };
+                // leaves the picker first as the request may enter a mode of its own
+                let plugin_handle = ctx.editor.mode.plugin_handle;
+                ctx.editor.enter_mode(ModeKind::default());
+
+                if let Some(handle) = plugin_handle {
+                    let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
+                    let pending_access = lsp.pending_access.take();
+                    if let (Some(pending_access), Some(index)) = (pending_access, index) {
+                        let client_handle = pending_access.client_handles[index];
+                        if let Some(client) = lsp.get_mut(client_handle) {
+                            let command = ctx.editor.string_pool.acquire_with(&client.command);
+                            lsp.set_preferred_client(pending_access.feature, &command);
+                            ctx.editor.string_pool.release(command);
+                        }
+
+                        let result = command::access_client(
+                            ctx,
+                            handle,
+                            client_handle,
+                            pending_access.accessor,
+                        );
+                        if let Err(error) = result {
+                            ctx.editor
+                                .logger
+                                .write(LogKind::Error)
+                                .fmt(format_args!("{}", error));
+                        }
+                    }
+                }
+
+                Some(EditorFlow::Continue)
+            }
+            ReadLinePoll::Canceled => {
+                if let Some(handle) = ctx.editor.mode.plugin_handle {
+                    let lsp = ctx.plugins.get_as::<LspPlugin>(handle);
+                    lsp.pending_access = None;
+                }
+
+                ctx.editor.enter_mode(ModeKind::default());
+                Some(EditorFlow::Continue)
+            }
+        }
+    }
+
+    let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+    let mut pending_access = match lsp.pending_access.take() {
+        Some(pending_access) => pending_access,
+        None => return,
+    };
+
+    // entry indices must map to client handles
+    pending_access
+        .client_handles
+        .retain(|&h| lsp.get_mut(h).is_some());
+    ctx.editor.picker.clear();
+    for &client_handle in &pending_access.client_handles {
+        if let Some(client) = lsp.get_mut(client_handle) {
+            ctx.editor.picker.add_custom_entry(&client.command);
+        }
+    }
+    lsp.pending_access = Some(pending_access);
+
+    ctx.editor
+        .registers
+        .set(REGISTER_READLINE_PROMPT, "lsp server:");
+    ctx.editor.picker.filter(WordIndicesIter::empty(), "");
+    ctx.editor.picker.move_cursor(0);
+
+    ctx.editor.mode.plugin_handle = Some(plugin_handle);
+    ctx.editor.mode.picker_state.on_client_keys = on_client_keys;
+    ctx.editor.enter_mode(ModeKind::Picker);
+}
@@ -6,8 +6,9 @@ use std::{
 };
 
 use pepper::{
-    buffer::{BufferHandle, BufferProperties},
+    buffer::{BufferContent, BufferHandle, BufferProperties},
     buffer_position::{BufferPosition, BufferRange},
+    config::DiagnosticSeverity,
     editor::Editor,
     editor_utils::LogKind,
     glob::InvalidGlobError,
@@ -276,6 +277,53 @@ impl DocumentPosition {
         BufferPosition::line_col(self.line as _, self.character as _)
     }
 
+    pub fn from_buffer_position_encoded(
+        position: BufferPosition,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+    ) -> Self {
+        let mut this = Self::from_buffer_position(position);
+        if let Some(line) = content.lines().get(position.line_index as usize) {
+            let line = line.as_str();
+            let column = (position.column_byte_index as usize).min(line.len());
+            let prefix = line.get(..column).unwrap_or(line);
+            this.character = match encoding {
+                PositionEncoding::Utf8 => this.character,
+                PositionEncoding::Utf16 => prefix.encode_utf16().count() as _,
+                PositionEncoding::Utf32 => prefix.chars().count() as _,
+            };
+        }
+        this
+    }
+
+    pub fn into_buffer_position_encoded(
+        self,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+    ) -> BufferPosition {
+        let mut position = self.into_buffer_position();
+        if encoding == PositionEncoding::Utf8 {
+            return position;
+        }
+        if let Some(line) = content.lines().get(self.line as usize) {
+            let line = line.as_str();
+            let mut units = 0;
+            let mut column = line.len();
+            for (i, c) in line.char_indices() {
+                if units >= self.character as usize {
+                    column = i;
+                    break;
+                }
+                units += match encoding {
+                    PositionEncoding::Utf16 => c.len_utf16(),
+                    _ => 1,
+                };
+            }
+            position.column_byte_index = column as _;
+        }
+        position
+    }
+
     pub fn to_json_value(self, json: &mut Json) -> JsonValue {
         let mut value = JsonObject::default();
         value.set("line".into(), JsonValue::Integer(self.line as _), json);
@@ -305,6 +353,27 @@ impl<'json> FromJson<'json> for DocumentPosition {
     }
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+impl<'json> FromJson<'json> for PositionEncoding {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        match value {
+            JsonValue::String(s) => match s.as_str(json) {
+                "utf-8" => Ok(Self::Utf8),
+                "utf-16" => Ok(Self::Utf16),
+                "utf-32" => Ok(Self::Utf32),
+                _ => Err(JsonConvertError),
+            },
+            _ => Err(JsonConvertError),
+        }
+    }
+}
+
 #[derive(Default, Clone, Copy)]
 pub struct DocumentRange {
     pub start: DocumentPosition,
@@ -318,10 +387,25 @@ impl DocumentRange {
         }
     }
 
-    pub fn into_buffer_range(self) -> BufferRange {
+    pub fn from_buffer_range_encoded(
+        range: BufferRange,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+    ) -> Self {
+        Self {
+            start: DocumentPosition::from_buffer_position_encoded(range.from, content, encoding),
+            end: DocumentPosition::from_buffer_position_encoded(range.to, content, encoding),
+        }
+    }
+
+    pub fn into_buffer_range_encoded(
+        self,
+        content: &BufferContent,
+        encoding: PositionEncoding,
+    ) -> BufferRange {
         BufferRange::between(
-            self.start.into_buffer_position(),
-            self.end.into_buffer_position(),
+            self.start.into_buffer_position_encoded(content, encoding),
+            self.end.into_buffer_position_encoded(content, encoding),
         )
     }
 
@@ -385,20 +469,26 @@ impl TextEdit {
         temp_edits: &mut Vec<(BufferRange, BufferRange)>,
         edits: JsonArray,
         json: &Json,
+        encoding: PositionEncoding,
     ) {
         let buffer = editor.buffers.get_mut(buffer_handle);
 
         buffer.commit_edits();
         temp_edits.clear();
 
+        // edit ranges refer to the content before any edit is applied
+        let mut converted_edits = Vec::new();
         for edit in edits.elements(json) {
-            let edit = match TextEdit::from_json(edit, json) {
-                Ok(edit) => edit,
-                Err(_) => continue,
-            };
+            if let Ok(edit) = TextEdit::from_json(edit, json) {
+                let range = edit
+                    .range
+                    .into_buffer_range_encoded(buffer.content(), encoding);
+                converted_edits.push((range, edit.new_text));
+            }
+        }
 
-            let mut delete_range: BufferRange = edit.range.into_buffer_range();
-            let text = edit.new_text.as_str(&json);
+        for (mut delete_range, text) in converted_edits {
+            let text = text.as_str(json);
 
             for (d, i) in temp_edits.iter() {
                 delete_range.from = delete_range.from.delete(*d);
@@ -619,6 +709,7 @@ impl WorkspaceEdit {
         temp_edits: &mut Vec<(BufferRange, BufferRange)>,
         root: &Path,
         json: &Json,
+        encoding: PositionEncoding,
     ) {
         for (uri, text_edits) in self.changes.clone().members(json) {
             let path = match Uri::parse(&root, uri) {
@@ -634,7 +725,14 @@ impl WorkspaceEdit {
             buffer_properties.saving_enabled = true;
             let result = editor.buffer_handle_from_path(path, buffer_properties);
 
-            TextEdit::apply_edits(editor, result.buffer_handle, temp_edits, text_edits, json);
+            TextEdit::apply_edits(
+                editor,
+                result.buffer_handle,
+                temp_edits,
+                text_edits,
+                json,
+                encoding,
+            );
 
             if result.is_new {
                 let _ = editor
@@ -670,6 +768,7 @@ impl WorkspaceEdit {
                         temp_edits,
                         edit.edits,
                         json,
+                        encoding,
                     );
 
                     if result.is_new {
@@ -770,6 +869,7 @@ impl<'json> FromJson<'json> for WorkspaceEdit {
 pub struct DocumentDiagnostic {
     pub message: JsonString,
     pub range: DocumentRange,
+    pub severity: Option<DiagnosticSeverity>,
     pub data: JsonValue,
 }
 impl DocumentDiagnostic {
@@ -806,6 +906,15 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
             match key {
                 "message" => this.message = JsonString::from_json(value, json)?,
                 "range" => this.range = DocumentRange::from_json(value, json)?,
+                "severity" => {
+                    this.severity = match value {
+                        JsonValue::Integer(1) => Some(DiagnosticSeverity::Error),
+                        JsonValue::Integer(2) => Some(DiagnosticSeverity::Warning),
+                        JsonValue::Integer(3) => Some(DiagnosticSeverity::Info),
+                        JsonValue::Integer(4) => Some(DiagnosticSeverity::Hint),
+                        _ => None,
+                    }
+                }
                 "data" => this.data = value,
                 _ => (),
             }
@@ -814,10 +923,35 @@ impl<'json> FromJson<'json> for DocumentDiagnostic {
     }
 }
 
+#[derive(Default)]
+pub struct DocumentCommand {
+    pub command: JsonString,
+    pub arguments: JsonArray,
+}
+impl<'json> FromJson<'json> for DocumentCommand {
+    fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
+        let value = match value {
+            JsonValue::Object(value) => value,
+            _ => return Err(JsonConvertError),
+        };
+        let mut this = Self::default();
+        for (key, value) in value.members(json) {
+            match key {
+                "command" => this.command = JsonString::from_json(value, json)?,
+                "arguments" => this.arguments = JsonArray::from_json(value, json)?,
+                _ => (),
+            }
+        }
+        Ok(this)
+    }
+}
+
 #[derive(Default)]
 pub struct DocumentCodeAction {
     pub title: JsonString,
+    pub kind: Option<JsonString>,
     pub edit: WorkspaceEdit,
+    pub command: Option<DocumentCommand>,
     pub disabled: bool,
 }
 impl<'json> FromJson<'json> for DocumentCodeAction {
@@ -827,10 +961,19 @@ impl<'json> FromJson<'json> for DocumentCodeAction {
             _ => return Err(JsonConvertError),
         };
         let mut this = Self::default();
-        for (key, value) in value.members(json) {
+        for (key, member) in value.clone().members(json) {
             match key {
-                "title" => this.title = JsonString::from_json(value, json)?,
-                "edit" => this.edit = WorkspaceEdit::from_json(value, json)?,
+                "title" => this.title = JsonString::from_json(member, json)?,
+                "kind" => this.kind = FromJson::from_json(member, json)?,
+                "edit" => this.edit = WorkspaceEdit::from_json(member, json)?,
+                // servers may also respond with bare commands instead of code actions
+                "command" => {
+                    let command = match member {
+                        JsonValue::String(_) => value.clone().into(),
+                        _ => member,
+                    };
+                    this.command = Some(DocumentCommand::from_json(command, json)?);
+                }
                 "disabled" => this.disabled = true,
                 _ => (),
             }
@@ -842,6 +985,7 @@ impl<'json> FromJson<'json> for DocumentCodeAction {
 #[derive(Default)]
 pub struct DocumentSymbolInformation {
     pub name: JsonString,
+    pub kind: u32,
     pub uri: JsonString,
     pub range: DocumentRange,
     pub container_name: Option<JsonString>,
@@ -857,6 +1001,7 @@ impl<'json> FromJson<'json> for DocumentSymbolInformation {
         for (key, value) in value.members(json) {
             match key {
                 "name" => this.name = JsonString::from_json(value, json)?,
+                "kind" => this.kind = u32::from_json(value, json)?,
                 "location" => {
                     let location = DocumentLocation::from_json(value, json)?;
                     this.uri = location.uri;
@@ -877,23 +1022,28 @@ pub struct DocumentCompletionItem {
     pub text: JsonString,
 }
 impl<'json> FromJson<'json> for DocumentCompletionItem {
+    // the inserted text is taken from 'textEdit', then 'insertText' and then 'label'
     fn from_json(value: JsonValue, json: &'json Json) -> Result<Self, JsonConvertError> {
         let value = match value {
             JsonValue::Object(value) => value,
             _ => return Err(JsonConvertError),
         };
-        let mut this = Self::default();
+        let mut label = None;
+        let mut insert_text = None;
+        let mut text_edit_text = None;
         for (key, value) in value.members(json) {
             match key {
-                "label" => this.text = JsonString::from_json(value, json)?,
-                "insertText" => {
-                    this.text = JsonString::from_json(value, json)?;
-                    break;
+                "label" => label = Some(JsonString::from_json(value, json)?),
+                "insertText" => insert_text = Some(JsonString::from_json(value, json)?),
+                "textEdit" => {
+                    let new_text = value.get("newText", json);
+                    text_edit_text = Some(JsonString::from_json(new_text, json)?);
                 }
                 _ => (),
             }
         }
-        Ok(this)
+        let text = text_edit_text.or(insert_text).or(label).unwrap_or_default();
+        Ok(Self { text })
     }
 }
 
@@ -1149,6 +1299,10 @@ impl PendingRequestColection {
         self.pending_requests.push(PendingRequest { id, method });
     }
 
+    pub fn len(&self) -> usize {
+        self.pending_requests.len()
+    }
+
     pub fn take(&mut self, id: RequestId) -> Option<&'static str> {
         for i in 0..self.pending_requests.len() {
             let request = &self.pending_requests[i];
@@ -1183,4 +1337,27 @@ mod tests {
             assert_uri("c:/file.rs", "file:///c:/file.rs");
         }
     }
+
+    #[test]
+    fn completion_item_text() {
+        fn text(item: &str) -> String {
+            let mut json = Json::new();
+            let value = json.read(&mut io::Cursor::new(item)).unwrap();
+            let item = match DocumentCompletionItem::from_json(value, &json) {
+                Ok(item) => item,
+                Err(_) => panic!("invalid completion item"),
+            };
+            item.text.as_str(&json).into()
+        }
+
+        assert_eq!("len", text("{\"label\":\"len\",\"kind\":2}"));
+        assert_eq!(
+            "len()",
+            text("{\"insertText\":\"len()\",\"label\":\"len\"}")
+        );
+        assert_eq!(
+            "len(&self)",
+            text("{\"label\":\"len\",\"textEdit\":{\"range\":{\"start\":{\"line\":0,\"character\":0},\"end\":{\"line\":0,\"character\":1}},\"newText\":\"len(&self)\"},\"insertText\":\"len()\"}")
+        );
+    }
 }
@@ -0,0 +1,333 @@
+use std::{env, io, process::Command};
+
+use pepper::{
+    buffer::{BufferHandle, BufferProperties},
+    buffer_position::{BufferPosition, BufferRange},
+    client::ClientManager,
+    editor::{Editor, EditorContext},
+    platform::{drop_request, Platform, PlatformProcessHandle, PlatformRequest},
+    plugin::{PluginCollection, PluginHandle},
+};
+
+use crate::{
+    client::{Client, ClientHandle},
+    json::{Json, JsonValue},
+    LspPlugin, DEFINITION,
+};
+
+pub(crate) struct SentMessage {
+    pub json: Json,
+    pub body: JsonValue,
+}
+impl SentMessage {
+    pub fn method(&self) -> &str {
+        match self.body.clone().get("method", &self.json) {
+            JsonValue::String(method) => method.as_str(&self.json),
+            _ => "",
+        }
+    }
+
+    pub fn id(&self) -> Option<i64> {
+        match self.body.clone().get("id", &self.json) {
+            JsonValue::Integer(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn params(&self) -> JsonValue {
+        self.body.clone().get("params", &self.json)
+    }
+
+    pub fn text_document_uri(&self) -> Option<String> {
+        match self
+            .params()
+            .get("textDocument", &self.json)
+            .get("uri", &self.json)
+        {
+            JsonValue::String(uri) => Some(uri.as_str(&self.json).into()),
+            _ => None,
+        }
+    }
+
+    // the message json with object members sorted by key
+    pub fn to_sorted_string(&self) -> String {
+        let mut buf = Vec::new();
+        self.json.write_sorted(&mut buf, &self.body).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+// an lsp position as '(line, character)'
+pub(crate) type Position = (u32, u32);
+
+pub(crate) fn range_json(start: Position, end: Position) -> String {
+    format!(
+        "{{\"start\":{{\"line\":{},\"character\":{}}},\"end\":{{\"line\":{},\"character\":{}}}}}",
+        start.0, start.1, end.0, end.1
+    )
+}
+
+pub(crate) fn location_json(uri: &str, start: Position, end: Position) -> String {
+    format!(
+        "{{\"uri\":\"{}\",\"range\":{}}}",
+        uri,
+        range_json(start, end)
+    )
+}
+
+pub(crate) fn text_edit_json(start: Position, end: Position, new_text: &str) -> String {
+    format!(
+        "{{\"range\":{},\"newText\":\"{}\"}}",
+        range_json(start, end),
+        new_text
+    )
+}
+
+// runs the lsp plugin against an in-memory server: everything the client writes to the
+// server process is captured from the platform requests and server messages are injected
+// as if they were read from the process output
+pub(crate) struct TestPlatform {
+    pub ctx: EditorContext,
+    pub plugin_handle: PluginHandle,
+    pub client_handle: ClientHandle,
+}
+impl TestPlatform {
+    pub fn new() -> Self {
+        let current_directory = env::current_dir().unwrap_or_default();
+        let mut ctx = EditorContext {
+            editor: Editor::new(current_directory, String::new()),
+            platform: Platform::default(),
+            clients: ClientManager::default(),
+            plugins: PluginCollection::default(),
+        };
+
+        let plugin_handle = PluginCollection::add(&mut ctx, DEFINITION).unwrap();
+        let root = ctx.editor.current_directory.clone();
+        let lsp = ctx.plugins.get_as::<LspPlugin>(plugin_handle);
+        let client_handle = lsp.start(
+            &mut ctx.platform,
+            plugin_handle,
+            Command::new("test-server"),
+            root,
+        );
+
+        let mut this = Self {
+            ctx,
+            plugin_handle,
+            client_handle,
+        };
+        let spawned = this
+            .ctx
+            .platform
+            .requests
+            .drain()
+            .any(|r| matches!(r, PlatformRequest::SpawnProcess { .. }));
+        assert!(spawned);
+
+        crate::on_process_spawned(
+            plugin_handle,
+            &mut this.ctx,
+            client_handle.0 as _,
+            PlatformProcessHandle(0),
+        );
+        this
+    }
+
+    pub fn initialize(&mut self, capabilities: &str) {
+        let id = self
+            .sent_messages()
+            .iter()
+            .find(|m| m.method() == "initialize")
+            .and_then(SentMessage::id)
+            .expect("client did not send 'initialize'");
+        self.respond(id, &format!("{{\"capabilities\":{}}}", capabilities));
+    }
+
+    // starts and initializes another server. since it drains the sent messages,
+    // it should be called after the first server got its 'initialize' response
+    pub fn start_client(&mut self, capabilities: &str) -> ClientHandle {
+        let root = self.ctx.editor.current_directory.clone();
+        let lsp = self.ctx.plugins.get_as::<LspPlugin>(self.plugin_handle);
+        let client_handle = lsp.start(
+            &mut self.ctx.platform,
+            self.plugin_handle,
+            Command::new("other-test-server"),
+            root,
+        );
+        crate::on_process_spawned(
+            self.plugin_handle,
+            &mut self.ctx,
+            client_handle.0 as _,
+            PlatformProcessHandle(client_handle.0 as _),
+        );
+
+        let id = self
+            .sent_messages()
+            .iter()
+            .find(|m| m.method() == "initialize")
+            .and_then(SentMessage::id)
+            .expect("client did not send 'initialize'");
+        self.respond_from(
+            client_handle,
+            id,
+            &format!("{{\"capabilities\":{}}}", capabilities),
+        );
+        client_handle
+    }
+
+    // buffers opened before 'initialize' gets a response are sent to the server with 'didOpen'
+    pub fn open_buffer(&mut self, path: &str, text: &str) -> BufferHandle {
+        let buffer = self.ctx.editor.buffers.add_new();
+        buffer.properties = BufferProperties::text();
+        buffer.set_path(path.as_ref());
+        let handle = buffer.handle();
+        buffer.insert_text(
+            &mut self.ctx.editor.word_database,
+            BufferPosition::zero(),
+            text,
+            &mut self
+                .ctx
+                .editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(handle),
+        );
+        self.ctx.trigger_event_handlers();
+        handle
+    }
+
+    pub fn insert_text(
+        &mut self,
+        buffer_handle: BufferHandle,
+        position: BufferPosition,
+        text: &str,
+    ) {
+        self.ctx.editor.buffers.get_mut(buffer_handle).insert_text(
+            &mut self.ctx.editor.word_database,
+            position,
+            text,
+            &mut self
+                .ctx
+                .editor
+                .events
+                .writer()
+                .buffer_text_inserts_mut_guard(buffer_handle),
+        );
+        self.ctx.trigger_event_handlers();
+    }
+
+    pub fn delete_range(&mut self, buffer_handle: BufferHandle, range: BufferRange) {
+        self.ctx.editor.buffers.get_mut(buffer_handle).delete_range(
+            &mut self.ctx.editor.word_database,
+            range,
+            &mut self
+                .ctx
+                .editor
+                .events
+                .writer()
+                .buffer_range_deletes_mut_guard(buffer_handle),
+        );
+        self.ctx.trigger_event_handlers();
+    }
+
+    pub fn client<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut EditorContext, &mut Client),
+    {
+        let lsp = self.ctx.plugins.get_as::<LspPlugin>(self.plugin_handle);
+        let mut client = lsp.acquire(self.client_handle).unwrap();
+        f(&mut self.ctx, &mut client);
+        let lsp = self.ctx.plugins.get_as::<LspPlugin>(self.plugin_handle);
+        lsp.release(client);
+    }
+
+    pub fn sent_messages(&mut self) -> Vec<SentMessage> {
+        let platform = &mut self.ctx.platform;
+        let mut messages = Vec::new();
+        for request in platform.requests.drain() {
+            match request {
+                PlatformRequest::WriteToProcess { buf, .. } => {
+                    let bytes = buf.as_bytes();
+                    let body_start = bytes
+                        .windows(4)
+                        .position(|w| w == b"\r\n\r\n")
+                        .map(|i| i + 4)
+                        .unwrap_or(0);
+                    let mut json = Json::new();
+                    let mut reader = io::Cursor::new(&bytes[body_start..]);
+                    let body = json.read(&mut reader).unwrap_or(JsonValue::Null);
+                    messages.push(SentMessage { json, body });
+                    platform.buf_pool.release(buf);
+                }
+                request => drop_request(&mut platform.buf_pool, request),
+            }
+        }
+        messages
+    }
+
+    pub fn receive(&mut self, body: &str) {
+        self.receive_from(self.client_handle, body);
+    }
+
+    pub fn receive_from(&mut self, client_handle: ClientHandle, body: &str) {
+        let message = format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+        crate::on_process_output(
+            self.plugin_handle,
+            &mut self.ctx,
+            client_handle.0 as _,
+            message.as_bytes(),
+        );
+        self.ctx.trigger_event_handlers();
+    }
+
+    pub fn respond(&mut self, id: i64, result: &str) {
+        self.receive(&format!(
+            "{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":{}}}",
+            id, result
+        ));
+    }
+
+    pub fn publish_diagnostics(
+        &mut self,
+        client_handle: ClientHandle,
+        uri: &str,
+        diagnostics: &[(Position, Position, &str)],
+    ) {
+        let mut diagnostics_json = String::new();
+        for (i, &(start, end, message)) in diagnostics.iter().enumerate() {
+            if i > 0 {
+                diagnostics_json.push(',');
+            }
+            diagnostics_json.push_str(&format!(
+                "{{\"range\":{},\"message\":\"{}\"}}",
+                range_json(start, end),
+                message
+            ));
+        }
+        self.receive_from(
+            client_handle,
+            &format!(
+                "{{\"jsonrpc\":\"2.0\",\"method\":\"textDocument/publishDiagnostics\",\"params\":{{\"uri\":\"{}\",\"diagnostics\":[{}]}}}}",
+                uri, diagnostics_json
+            ),
+        );
+    }
+
+    pub fn respond_from(&mut self, client_handle: ClientHandle, id: i64, result: &str) {
+        self.receive_from(
+            client_handle,
+            &format!(
+                "{{\"jsonrpc\":\"2.0\",\"id\":{},\"result\":{}}}",
+                id, result
+            ),
+        );
+    }
+}
+impl Drop for TestPlatform {
+    fn drop(&mut self) {
+        let platform = &mut self.ctx.platform;
+        for request in platform.requests.drain() {
+            drop_request(&mut platform.buf_pool, request);
+        }
+    }
+}